@@ -50,7 +50,17 @@ fn build_map_from(arg: &Value) -> Result<Value, RuntimeError> {
 }
 
 impl Interpreter {
-    pub fn call_builtin(&mut self, name: &str, args: Vec<Value>) -> Result<Value, RuntimeError> {
+    pub fn call_builtin(
+        &mut self,
+        name: &str,
+        mut args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        if let Some(Value::Range(r)) = args.first() {
+            if super::range::COLLECTING_BUILTINS.contains(&name) {
+                let items = super::range::collect(r).map_err(|e| RuntimeError::new(&e))?;
+                args[0] = Value::Array(items.into_iter().map(Value::Int).collect());
+            }
+        }
         match name {
             "print" => {
                 let text: Vec<String> = args.iter().map(|v| format!("{}", v)).collect();
@@ -71,6 +81,7 @@ impl Interpreter {
                 }
                 Some(Value::Object(o)) => Ok(Value::Int(o.len() as i64)),
                 Some(Value::Map(m)) => Ok(Value::Int(m.len() as i64)),
+                Some(Value::Range(r)) => Ok(Value::Int(r.int_len())),
                 _ => Err(RuntimeError::new(
                     "len() requires string, array, tuple, set, map, or object",
                 )),
@@ -152,6 +163,9 @@ impl Interpreter {
                 (Some(Value::Map(pairs)), Some(key)) => Ok(Value::Bool(
                     pairs.iter().any(|(k, _)| Value::container_eq(k, key)),
                 )),
+                (Some(Value::Range(r)), Some(val)) => {
+                    Ok(Value::Bool(matches!(val, Value::Int(n) if r.contains(*n))))
+                }
                 _ => Err(RuntimeError::new(
                     "contains() requires (string, substring), (array, value), (object, key), or (map, key)",
                 )),
@@ -495,6 +509,8 @@ impl Interpreter {
                 None => Err(RuntimeError::new("json() requires an argument")),
             },
             "uuid" => Ok(Value::String(uuid::Uuid::new_v4().to_string())),
+            "response" => crate::stdlib::http::build_response(&args).map_err(|e| RuntimeError::new(&e)),
+            "redirect" => crate::stdlib::http::build_redirect(&args).map_err(|e| RuntimeError::new(&e)),
             "say" => {
                 let text: Vec<String> = args.iter().map(|v| format!("{}", v)).collect();
                 self.write_output(&text.join(" "), true);
@@ -1607,12 +1623,26 @@ impl Interpreter {
             _ if name.starts_with("toml.") => {
                 crate::stdlib::toml_module::call(name, args).map_err(|e| RuntimeError::new(&e))
             }
+            "ws.join" | "ws.leave" | "ws.rooms" | "ws.broadcast" | "ws.binary" => {
+                crate::runtime::sockets::call(name, self.ws_connection, args)
+                    .map_err(|e| RuntimeError::new(&e))
+            }
+            "ws.close" if args.is_empty() => {
+                crate::runtime::sockets::call(name, self.ws_connection, args)
+                    .map_err(|e| RuntimeError::new(&e))
+            }
             _ if name.starts_with("ws.") => {
                 crate::stdlib::ws::call(name, args).map_err(|e| RuntimeError::new(&e))
             }
             _ if name.starts_with("jwt.") => {
                 crate::stdlib::jwt::call(name, args).map_err(|e| RuntimeError::new(&e))
             }
+            _ if name.starts_with("metrics.") => {
+                crate::stdlib::metrics::call(name, args).map_err(|e| RuntimeError::new(&e))
+            }
+            _ if name.starts_with("testing.") => {
+                crate::stdlib::testing::call(name, args).map_err(|e| RuntimeError::new(&e))
+            }
             _ if name.starts_with("os.") => {
                 crate::stdlib::os_module::call(name, args).map_err(|e| RuntimeError::new(&e))
             }
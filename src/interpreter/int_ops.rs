@@ -0,0 +1,50 @@
+//! Integer semantics of `**`, `~/`, `<<` and `>>`, shared with the VM so
+//! both engines agree on overflow and range errors.
+
+/// The result of an integer operation that may leave the `Int` range.
+pub(crate) enum IntOrFloat {
+    Int(i64),
+    Float(f64),
+}
+
+/// `base ** exp`. Negative exponents and results that overflow `Int`
+/// produce a `Float`, the same way `*` promotes on overflow.
+pub(crate) fn pow(base: i64, exp: i64) -> IntOrFloat {
+    u32::try_from(exp)
+        .ok()
+        .and_then(|exp| base.checked_pow(exp))
+        .map(IntOrFloat::Int)
+        .unwrap_or_else(|| IntOrFloat::Float((base as f64).powf(exp as f64)))
+}
+
+/// `a ~/ b`, rounding toward negative infinity. The caller rejects a zero
+/// divisor; `Int.min ~/ -1` overflows into a `Float`.
+pub(crate) fn floor_div(a: i64, b: i64) -> IntOrFloat {
+    match a.checked_div(b) {
+        Some(q) if a % b != 0 && (a < 0) != (b < 0) => IntOrFloat::Int(q - 1),
+        Some(q) => IntOrFloat::Int(q),
+        None => IntOrFloat::Float((a as f64 / b as f64).floor()),
+    }
+}
+
+/// `a << b` or `a >> b`. Bits shifted past either end are dropped and `>>`
+/// keeps the sign; shifting by a negative amount or by 64 or more is an
+/// error rather than a silent wrap.
+pub(crate) fn shift(a: i64, b: i64, left: bool) -> Result<i64, String> {
+    if !(0..64).contains(&b) {
+        return Err(format!(
+            "shift amount {} is out of range\n  hint: shift by 0 to 63 bits",
+            b
+        ));
+    }
+    Ok(if left { a << b } else { a >> b })
+}
+
+impl From<IntOrFloat> for super::Value {
+    fn from(n: IntOrFloat) -> Self {
+        match n {
+            IntOrFloat::Int(n) => super::Value::Int(n),
+            IntOrFloat::Float(n) => super::Value::Float(n),
+        }
+    }
+}
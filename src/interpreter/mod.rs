@@ -1,9 +1,13 @@
 mod builtins; // call_builtin — extracted for readability
+pub(crate) mod int_ops;
+pub(crate) mod modules;
+pub(crate) mod range;
 use crate::parser::ast::*;
 /// Forge Tree-Walk Interpreter
 /// Walks the AST and executes it directly.
 /// Phase 1 only — replaced by bytecode VM in Phase 3.
 use indexmap::IndexMap;
+pub use range::IntRange;
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::{Arc, Mutex};
@@ -71,6 +75,10 @@ pub enum StreamKind {
         chars: Vec<char>,
         idx: usize,
     },
+    RangeIter {
+        range: IntRange,
+        idx: usize,
+    },
     Filter {
         upstream: Arc<Mutex<StreamCell>>,
         pred: Value,
@@ -121,6 +129,7 @@ impl StreamKind {
             StreamKind::SetIter { .. } => "SetIter",
             StreamKind::MapIter { .. } => "MapIter",
             StreamKind::StringIter { .. } => "StringIter",
+            StreamKind::RangeIter { .. } => "RangeIter",
             StreamKind::Filter { .. } => "Filter",
             StreamKind::Map { .. } => "Map",
             StreamKind::Take { .. } => "Take",
@@ -146,6 +155,9 @@ pub enum Value {
     /// order; keys compared via `container_eq` so NaN self-matches and
     /// Int/Float promote (e.g. `1` and `1.0` collide).
     Map(Vec<(Value, Value)>),
+    /// `a..b` or `a..=b`. Holds only its bounds; iterating, indexing and
+    /// `len()` compute elements on demand.
+    Range(IntRange),
     /// Lazy iterator. Single-use; terminal ops drain it. Rc-shared so
     /// combinators can wrap upstreams without cloning cursor state.
     Stream(Arc<Mutex<StreamCell>>),
@@ -205,6 +217,7 @@ impl PartialEq for Value {
                             .any(|(bk, bv)| Value::container_eq(ak, bk) && av == bv)
                     })
             }
+            (Value::Range(a), Value::Range(b)) => a == b,
             (Value::Object(a), Value::Object(b)) => a == b,
             (Value::ResultOk(a), Value::ResultOk(b)) => a == b,
             (Value::ResultErr(a), Value::ResultErr(b)) => a == b,
@@ -290,6 +303,7 @@ impl Value {
             Value::Tuple(_) => "Tuple",
             Value::Set(_) => "Set",
             Value::Map(_) => "Map",
+            Value::Range(_) => "Range",
             Value::Object(_) => "Object",
             Value::Stream(_) => "Stream",
             Value::Function { .. } => "Function",
@@ -313,6 +327,7 @@ impl Value {
             Value::Null => false,
             Value::Array(a) | Value::Tuple(a) | Value::Set(a) => !a.is_empty(),
             Value::Map(m) => !m.is_empty(),
+            Value::Range(r) => !r.is_empty(),
             Value::Object(o) => !o.is_empty(),
             Value::ResultOk(_) => true,
             Value::ResultErr(_) => false,
@@ -397,6 +412,7 @@ impl fmt::Display for Value {
                     .collect();
                 write!(f, "Map({})", strs.join(", "))
             }
+            Value::Range(r) => write!(f, "{}", r),
             Value::Stream(s) => {
                 let name = s.lock().map(|cell| cell.kind.short_name()).unwrap_or("?");
                 write!(f, "Stream({})", name)
@@ -509,6 +525,40 @@ impl Environment {
         Err(RuntimeError::new(&format!("undefined variable: {}", name)))
     }
 
+    /// Whether both environments share the same outermost scope.
+    fn shares_globals(&self, other: &Environment) -> bool {
+        match (self.scopes.first(), other.scopes.first()) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Whether this is the global environment of an imported module.
+    fn is_module(&self) -> bool {
+        self.scopes.first().is_some_and(|scope| {
+            scope
+                .lock()
+                .unwrap_or_else(|p| p.into_inner())
+                .contains_key(MODULE_MARKER)
+        })
+    }
+
+    /// This environment with `module`'s globals layered on top, so lookups
+    /// find the module's names before the caller's.
+    fn with_module_globals(&self, module: &Environment) -> Environment {
+        let mut env = self.clone();
+        if let (Some(scope), Some(muts)) = (module.scopes.first(), module.mutability.first()) {
+            env.scopes.push(Arc::clone(scope));
+            env.mutability.push(Arc::clone(muts));
+        }
+        env
+    }
+
+    /// Whether definitions land in the outermost (module) scope.
+    pub fn is_top_level(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
     /// Collect all defined variable names across all scopes (for REPL tab completion).
     pub fn all_names(&self) -> Vec<String> {
         let mut names = Vec::new();
@@ -766,6 +816,25 @@ enum Signal {
 
 const MAX_CALL_DEPTH: usize = 512;
 
+/// Defined in the global scope of every imported module's interpreter, so
+/// calls into its functions can tell module globals from the caller's.
+const MODULE_MARKER: &str = "__module__";
+
+/// Every name a fresh interpreter defines. Imports may shadow these;
+/// anything else already bound is a collision.
+fn builtin_names() -> &'static std::collections::HashSet<String> {
+    static NAMES: std::sync::OnceLock<std::collections::HashSet<String>> =
+        std::sync::OnceLock::new();
+    NAMES.get_or_init(|| Interpreter::new().env.all_names().into_iter().collect())
+}
+
+/// Module names `import` treats as the always-available stdlib rather
+/// than files.
+pub(crate) const BUILTIN_MODULES: &[&str] = &[
+    "math", "fs", "io", "crypto", "db", "pg", "env", "json", "regex", "log", "term", "http", "csv",
+    "exec", "time", "url", "toml", "npc", "ws", "jwt", "mysql", "metrics", "testing",
+];
+
 /// Debug action requested by the DAP client
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DebugAction {
@@ -802,6 +871,10 @@ pub struct DebugState {
     pub paused_depth: Mutex<usize>,
 }
 
+/// Receives values from `yield` / `emit` statements. Installed by the HTTP
+/// server for `@sse` handlers; an `Err` (client gone) aborts the handler.
+pub type EventSink = Arc<dyn Fn(Value) -> Result<(), String> + Send + Sync>;
+
 /// The interpreter
 pub struct Interpreter {
     pub env: Environment,
@@ -821,6 +894,9 @@ pub struct Interpreter {
     pub embedded_fields: HashMap<String, Vec<(String, String)>>,
     /// Struct defaults: type_name -> { field_name -> default_value }
     pub struct_defaults: HashMap<String, IndexMap<String, Value>>,
+    /// Struct field annotations: type_name -> [(field_name, type)], used
+    /// by the HTTP server to validate typed request bodies.
+    pub struct_fields: HashMap<String, Vec<(String, TypeAnn)>>,
     /// Current source line number (set during run())
     pub current_line: usize,
     /// Source code (for error display)
@@ -837,6 +913,24 @@ pub struct Interpreter {
     pub call_stack: Vec<DebugFrame>,
     /// Squad handle collector: when Some, spawn_task pushes handles here
     squad_handles: Option<Vec<Value>>,
+    /// Destination for `yield` / `emit`; they are no-ops when unset.
+    pub event_sink: Option<EventSink>,
+    /// The `@ws` connection this interpreter serves, which `ws.join`,
+    /// `ws.leave`, `ws.rooms` and `ws.close()` act on.
+    pub ws_connection: Option<u64>,
+    /// Whether this request has a `session` global.
+    pub(crate) in_session: bool,
+    /// Names bound by file imports, with the resolved file and the path
+    /// as written, so a second binding of the same name is an error
+    /// rather than silent shadowing.
+    imports: HashMap<String, (std::path::PathBuf, String)>,
+    /// Modules this file imported whole or mounted, with their canonical
+    /// path and the path as written. Whoever imports this file whole
+    /// gets their exports too, so their route handlers resolve there.
+    nested_modules: Vec<modules::NestedModule>,
+    /// Canonical paths of the files whose imports are still running,
+    /// outermost first, ending with this interpreter's own file.
+    import_chain: Vec<std::path::PathBuf>,
 }
 
 impl Interpreter {
@@ -850,6 +944,7 @@ impl Interpreter {
             static_methods: HashMap::new(),
             embedded_fields: HashMap::new(),
             struct_defaults: HashMap::new(),
+            struct_fields: HashMap::new(),
             current_line: 0,
             source: None,
             source_file: None,
@@ -858,6 +953,12 @@ impl Interpreter {
             output_sink: None,
             call_stack: Vec::new(),
             squad_handles: None,
+            event_sink: None,
+            ws_connection: None,
+            in_session: false,
+            imports: HashMap::new(),
+            nested_modules: Vec::new(),
+            import_chain: Vec::new(),
         };
         interp.register_builtins();
         interp
@@ -959,6 +1060,7 @@ impl Interpreter {
         interp.static_methods = self.static_methods.clone();
         interp.embedded_fields = self.embedded_fields.clone();
         interp.struct_defaults = self.struct_defaults.clone();
+        interp.struct_fields = self.struct_fields.clone();
         interp.source = self.source.clone();
         interp.source_file = self.source_file.clone();
         // Per-request fresh state. The cancel flag is created per-request
@@ -990,6 +1092,7 @@ impl Interpreter {
         interp.static_methods = self.static_methods.clone();
         interp.embedded_fields = self.embedded_fields.clone();
         interp.struct_defaults = self.struct_defaults.clone();
+        interp.struct_fields = self.struct_fields.clone();
         interp.current_line = self.current_line;
         interp.source = self.source.clone();
         interp.source_file = self.source_file.clone();
@@ -1046,6 +1149,14 @@ impl Interpreter {
             .define("ws".to_string(), crate::stdlib::create_ws_module());
         self.env
             .define("jwt".to_string(), crate::stdlib::create_jwt_module());
+        self.env.define(
+            "metrics".to_string(),
+            crate::stdlib::create_metrics_module(),
+        );
+        self.env.define(
+            "testing".to_string(),
+            crate::stdlib::create_testing_module(),
+        );
         self.env
             .define("os".to_string(), crate::stdlib::create_os_module());
         self.env
@@ -1112,6 +1223,8 @@ impl Interpreter {
             "unwrap_or",
             "unwrap_err",
             "fetch",
+            "response",
+            "redirect",
             "uuid",
             "say",
             "yell",
@@ -1276,6 +1389,91 @@ impl Interpreter {
         Ok(last)
     }
 
+    /// Bind the exports of the modules `module` imported whole or mounted,
+    /// and of the modules those pulled in, as if this file had imported
+    /// each of them itself.
+    fn adopt_nested_exports(&mut self, module: &modules::LoadedModule) -> Result<(), RuntimeError> {
+        for (key, path, nested) in &module.nested_modules {
+            for name in nested.program.export_names() {
+                self.adopt_struct_metadata(nested, &name);
+                if let Some(val) = nested.env.get(&name) {
+                    self.bind_import(&name, val, key, path)?;
+                }
+            }
+            for spanned in &nested.program.statements {
+                if let Stmt::TypeDef { name, .. } = &spanned.stmt {
+                    if nested.program.exports(&spanned.stmt) {
+                        let meta_key = format!("__type_{}__", name);
+                        if let Some(val) = nested.env.get(&meta_key) {
+                            self.env.define(meta_key, val);
+                        }
+                    }
+                }
+            }
+            self.adopt_nested_exports(nested)?;
+        }
+        Ok(())
+    }
+
+    /// Copy the defaults, embeds and field types of struct `name` from
+    /// the interpreter that ran an imported file.
+    fn adopt_struct_metadata(&mut self, import_interp: &modules::LoadedModule, name: &str) {
+        if let Some(defaults) = import_interp.struct_defaults.get(name) {
+            self.struct_defaults
+                .insert(name.to_string(), defaults.clone());
+        }
+        if let Some(embeds) = import_interp.embedded_fields.get(name) {
+            self.embedded_fields
+                .insert(name.to_string(), embeds.clone());
+        }
+        if let Some(fields) = import_interp.struct_fields.get(name) {
+            self.struct_fields.insert(name.to_string(), fields.clone());
+        }
+    }
+
+    /// Bind an imported name. Importing it again from the same file is a
+    /// no-op; a name that is already defined here, or imported from a
+    /// different file, is an error.
+    fn bind_import(
+        &mut self,
+        name: &str,
+        value: Value,
+        file: &std::path::Path,
+        path: &str,
+    ) -> Result<(), RuntimeError> {
+        match self.imports.get(name) {
+            Some((prev_file, _)) if prev_file == file => {}
+            Some((_, prev_path)) => {
+                return Err(RuntimeError::new(&modules::collision_error(
+                    path,
+                    name,
+                    Some(prev_path),
+                )));
+            }
+            None if self.env.get(name).is_some() && !builtin_names().contains(name) => {
+                return Err(RuntimeError::new(&modules::collision_error(
+                    path, name, None,
+                )));
+            }
+            None => {}
+        }
+        self.imports
+            .insert(name.to_string(), (file.to_path_buf(), path.to_string()));
+        self.env.define(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Top-level declarations may not redefine a name an import bound.
+    fn check_not_imported(&self, name: &str) -> Result<(), RuntimeError> {
+        if !self.env.is_top_level() {
+            return Ok(());
+        }
+        match self.imports.get(name) {
+            Some((_, path)) => Err(RuntimeError::new(&modules::redefinition_error(name, path))),
+            None => Ok(()),
+        }
+    }
+
     fn exec_stmt(&mut self, stmt: &Stmt) -> Result<Signal, RuntimeError> {
         // Cooperative cancellation check (used by timeout blocks)
         if self.cancelled.load(std::sync::atomic::Ordering::Acquire) {
@@ -1288,6 +1486,7 @@ impl Interpreter {
                 value,
                 ..
             } => {
+                self.check_not_imported(name)?;
                 let val = self.eval_expr(value)?;
                 self.env.define_with_mutability(name.clone(), val, *mutable);
                 Ok(Signal::None)
@@ -1367,6 +1566,7 @@ impl Interpreter {
                 decorators,
                 ..
             } => {
+                self.check_not_imported(name)?;
                 let func = Value::Function {
                     name: name.clone(),
                     params: params.clone(),
@@ -1396,6 +1596,7 @@ impl Interpreter {
             }
 
             Stmt::StructDef { name, fields, .. } => {
+                self.check_not_imported(name)?;
                 self.env
                     .define(name.clone(), Value::BuiltIn(format!("struct:{}", name)));
 
@@ -1419,11 +1620,21 @@ impl Interpreter {
                 if !defaults.is_empty() {
                     self.struct_defaults.insert(name.clone(), defaults);
                 }
+                self.struct_fields.insert(
+                    name.clone(),
+                    fields
+                        .iter()
+                        .map(|field| (field.name.clone(), field.type_ann.clone()))
+                        .collect(),
+                );
 
                 Ok(Signal::None)
             }
 
-            Stmt::TypeDef { name, variants } => {
+            Stmt::TypeDef { name, variants, .. } => {
+                for variant in variants {
+                    self.check_not_imported(&variant.name)?;
+                }
                 let mut variant_names = Vec::new();
                 for variant in variants {
                     variant_names.push(variant.name.clone());
@@ -1596,8 +1807,15 @@ impl Interpreter {
                         let type_key = format!("__type_{}__", type_name);
                         if let Some(Value::Object(type_meta)) = self.env.get(&type_key) {
                             if let Some(Value::Array(variant_list)) = type_meta.get("variants") {
+                                // Guarded arms may fall through, so only
+                                // unguarded alternatives count as coverage.
+                                let covering: Vec<&Pattern> = arms
+                                    .iter()
+                                    .filter(|a| a.guard.is_none())
+                                    .flat_map(|a| a.pattern.alternatives())
+                                    .collect();
                                 let has_wildcard =
-                                    arms.iter().any(|a| matches!(a.pattern, Pattern::Wildcard));
+                                    covering.iter().any(|p| matches!(p, Pattern::Wildcard));
                                 let variant_names: Vec<&str> = variant_list
                                     .iter()
                                     .filter_map(|v| {
@@ -1608,8 +1826,8 @@ impl Interpreter {
                                         }
                                     })
                                     .collect();
-                                let has_true_catchall = arms.iter().any(|a| {
-                                    if let Pattern::Binding(bname) = &a.pattern {
+                                let has_true_catchall = covering.iter().any(|p| {
+                                    if let Pattern::Binding(bname) = p {
                                         !variant_names.contains(&bname.as_str())
                                     } else {
                                         false
@@ -1617,7 +1835,7 @@ impl Interpreter {
                                 });
                                 if !has_wildcard && !has_true_catchall {
                                     for vname in &variant_names {
-                                        let covered = arms.iter().any(|a| match &a.pattern {
+                                        let covered = covering.iter().any(|p| match p {
                                             Pattern::Constructor { name, .. } => name == vname,
                                             Pattern::Binding(bname) => bname == vname,
                                             _ => false,
@@ -1639,6 +1857,19 @@ impl Interpreter {
                     if self.match_pattern(&arm.pattern, &val) {
                         self.env.push_scope();
                         self.bind_pattern(&arm.pattern, &val);
+                        if let Some(guard) = &arm.guard {
+                            match self.eval_expr(guard) {
+                                Ok(cond) if cond.is_truthy() => {}
+                                Ok(_) => {
+                                    self.env.pop_scope();
+                                    continue;
+                                }
+                                Err(e) => {
+                                    self.env.pop_scope();
+                                    return Err(e);
+                                }
+                            }
+                        }
                         let result = self.exec_block(&arm.body);
                         self.env.pop_scope();
                         return result;
@@ -1655,6 +1886,29 @@ impl Interpreter {
             } => {
                 let iter_val = self.eval_expr(iterable)?;
                 match iter_val {
+                    Value::Range(range) => {
+                        for n in range.iter() {
+                            self.env.push_scope();
+                            self.env.define(var.clone(), Value::Int(n));
+                            match self.exec_block(body)? {
+                                Signal::Break => {
+                                    self.env.pop_scope();
+                                    break;
+                                }
+                                Signal::Continue => {
+                                    self.env.pop_scope();
+                                    continue;
+                                }
+                                Signal::Return(v) => {
+                                    self.env.pop_scope();
+                                    return Ok(Signal::Return(v));
+                                }
+                                Signal::None | Signal::ImplicitReturn(_) => {
+                                    self.env.pop_scope();
+                                }
+                            }
+                        }
+                    }
                     Value::Array(items) | Value::Tuple(items) | Value::Set(items) => {
                         for item in items {
                             self.env.push_scope();
@@ -1854,13 +2108,8 @@ impl Interpreter {
                 }
             },
 
-            Stmt::Import { path, names } => {
-                let builtin_modules = [
-                    "math", "fs", "io", "crypto", "db", "pg", "env", "json", "regex", "log",
-                    "term", "http", "csv", "exec", "time", "url", "toml", "npc", "ws", "jwt",
-                    "mysql",
-                ];
-                if builtin_modules.contains(&path.as_str()) {
+            Stmt::Import { path, names, alias } => {
+                if BUILTIN_MODULES.contains(&path.as_str()) {
                     if self.env.get(path).is_some() {
                         return Ok(Signal::None);
                     }
@@ -1884,85 +2133,115 @@ impl Interpreter {
                         )));
                     }
                 };
-                let source = std::fs::read_to_string(&file_path)
-                    .map_err(|e| RuntimeError::new(&format!("cannot import '{}': {}", path, e)))?;
-                let mut lexer = crate::lexer::Lexer::new(&source);
-                let tokens = lexer.tokenize().map_err(|e| {
-                    RuntimeError::new(&format!("import '{}' lex error: {}", path, e.message))
-                })?;
-                let mut parser = crate::parser::Parser::new(tokens);
-                let program = parser.parse_program().map_err(|e| {
-                    RuntimeError::new(&format!("import '{}' parse error: {}", path, e.message))
-                })?;
+                // Each file runs once per process; later imports bind names
+                // from the same instance, so they share its state.
+                let key = modules::canonical(&file_path);
+                let module = match modules::cached(&key) {
+                    Some(module) => module,
+                    None => {
+                        let chain = modules::enter_import(
+                            &self.import_chain,
+                            self.source_file.as_deref(),
+                            &key,
+                        )
+                        .map_err(|e| RuntimeError::new(&e))?;
+                        let program = modules::parse_module(path, &file_path)
+                            .map_err(|e| RuntimeError::new(&e))?;
+                        let mut import_interp = Interpreter::new();
+                        import_interp.source_file = Some(file_path.clone());
+                        import_interp.import_chain = chain;
+                        import_interp.env.define(
+                            MODULE_MARKER.to_string(),
+                            Value::String(file_path.display().to_string()),
+                        );
+                        import_interp.run(&program)?;
+                        modules::store(key.clone(), program, import_interp)
+                    }
+                };
+                let program = &module.program;
+
+                // Impl blocks attach to their type wherever it is used, so
+                // methods come along whatever the import names.
+                for spanned in &program.statements {
+                    if let Stmt::ImplBlock { type_name, .. } = &spanned.stmt {
+                        if let Some(methods) = module.method_tables.get(type_name) {
+                            let entry = self.method_tables.entry(type_name.clone()).or_default();
+                            for (k, v) in methods {
+                                entry.insert(k.clone(), v.clone());
+                            }
+                        }
+                        if let Some(statics) = module.static_methods.get(type_name) {
+                            let entry = self.static_methods.entry(type_name.clone()).or_default();
+                            for (k, v) in statics {
+                                entry.insert(k.clone(), v.clone());
+                            }
+                        }
+                    }
+                }
 
-                let mut import_interp = Interpreter::new();
-                import_interp.source_file = Some(file_path.clone());
-                import_interp.run(&program)?;
+                let exports = program.export_names();
+                for name in &exports {
+                    self.adopt_struct_metadata(&module, name);
+                }
+                for spanned in &program.statements {
+                    if let Stmt::TypeDef { name, .. } = &spanned.stmt {
+                        if program.exports(&spanned.stmt) {
+                            let meta_key = format!("__type_{}__", name);
+                            if let Some(val) = module.env.get(&meta_key) {
+                                self.env.define(meta_key, val);
+                            }
+                        }
+                    }
+                }
 
                 if let Some(name_list) = names {
                     for name in name_list {
-                        if let Some(val) = import_interp.env.get(name) {
-                            self.env.define(name.to_string(), val);
+                        if !exports.contains(name) {
+                            return Err(RuntimeError::new(&format!(
+                                "import '{}' does not export '{}'",
+                                path, name
+                            )));
+                        }
+                        if let Some(val) = module.env.get(name) {
+                            self.bind_import(name, val, &key, path)?;
                         }
                     }
+                } else if let Some(alias) = alias {
+                    let mut namespace = IndexMap::new();
+                    for name in &exports {
+                        if let Some(val) = module.env.get(name) {
+                            namespace.insert(name.clone(), val);
+                        }
+                    }
+                    self.bind_import(alias, Value::Object(namespace), &key, path)?;
                 } else {
-                    // Import all top-level definitions
-                    for spanned in &program.statements {
-                        match &spanned.stmt {
-                            Stmt::FnDef { name, .. } | Stmt::Let { name, .. } => {
-                                if let Some(val) = import_interp.env.get(name) {
-                                    self.env.define(name.clone(), val);
-                                }
-                            }
-                            Stmt::StructDef { name, .. } => {
-                                if let Some(val) = import_interp.env.get(name) {
-                                    self.env.define(name.clone(), val);
-                                }
-                                // Copy struct defaults and embedded fields
-                                if let Some(defaults) = import_interp.struct_defaults.get(name) {
-                                    self.struct_defaults.insert(name.clone(), defaults.clone());
-                                }
-                                if let Some(embeds) = import_interp.embedded_fields.get(name) {
-                                    self.embedded_fields.insert(name.clone(), embeds.clone());
-                                }
-                            }
-                            Stmt::TypeDef { name, variants } => {
-                                // Import each variant individually
-                                for variant in variants {
-                                    if let Some(val) = import_interp.env.get(&variant.name) {
-                                        self.env.define(variant.name.clone(), val);
-                                    }
-                                }
-                                // Import type metadata
-                                let meta_key = format!("__type_{}__", name);
-                                if let Some(val) = import_interp.env.get(&meta_key) {
-                                    self.env.define(meta_key, val);
-                                }
-                            }
-                            Stmt::ImplBlock { type_name, .. } => {
-                                // Copy method tables and static methods
-                                if let Some(methods) = import_interp.method_tables.get(type_name) {
-                                    let entry = self
-                                        .method_tables
-                                        .entry(type_name.clone())
-                                        .or_insert_with(IndexMap::new);
-                                    for (k, v) in methods {
-                                        entry.insert(k.clone(), v.clone());
-                                    }
-                                }
-                                if let Some(statics) = import_interp.static_methods.get(type_name) {
-                                    let entry = self
-                                        .static_methods
-                                        .entry(type_name.clone())
-                                        .or_insert_with(IndexMap::new);
-                                    for (k, v) in statics {
-                                        entry.insert(k.clone(), v.clone());
-                                    }
-                                }
-                            }
-                            _ => {}
+                    for name in &exports {
+                        if let Some(val) = module.env.get(name) {
+                            self.bind_import(name, val, &key, path)?;
                         }
                     }
+                    // Route modules the imported file pulled in with its own
+                    // whole-file imports or `@mount`s must be callable from
+                    // here too, since the server resolves handlers by name.
+                    self.adopt_nested_exports(&module)?;
+                    if !self.nested_modules.iter().any(|(k, _, _)| *k == key) {
+                        self.nested_modules
+                            .push((key.clone(), path.clone(), Arc::clone(&module)));
+                    }
+                }
+                Ok(Signal::None)
+            }
+
+            // `@mount("/admin", "admin_routes")` loads the module like a
+            // whole-file import so its handlers are defined; the runtime
+            // plan registers its routes under the prefix.
+            Stmt::DecoratorStmt(dec) if dec.name == "mount" => {
+                if let Some(DecoratorArg::Positional(Expr::StringLit(path))) = dec.args.get(1) {
+                    self.exec_stmt(&Stmt::Import {
+                        path: path.clone(),
+                        names: None,
+                        alias: None,
+                    })?;
                 }
                 Ok(Signal::None)
             }
@@ -2031,7 +2310,13 @@ impl Interpreter {
                 Ok(Signal::None)
             }
 
-            Stmt::YieldStmt(_expr) => Ok(Signal::None),
+            Stmt::YieldStmt(expr) => {
+                if let Some(sink) = self.event_sink.clone() {
+                    let value = self.eval_expr(expr)?;
+                    sink(value).map_err(|e| RuntimeError::new(&e))?;
+                }
+                Ok(Signal::None)
+            }
 
             Stmt::When { subject, arms } => {
                 let val = self.eval_expr(subject)?;
@@ -2390,6 +2675,32 @@ impl Interpreter {
             Expr::Bool(b) => Ok(Value::Bool(*b)),
             Expr::StringLit(s) => Ok(Value::String(s.clone())),
 
+            Expr::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = start.as_ref().map(|e| self.eval_expr(e)).transpose()?;
+                let end = end.as_ref().map(|e| self.eval_expr(e)).transpose()?;
+                match (&start, &end) {
+                    (None | Some(Value::Int(_)), None | Some(Value::Int(_))) => {
+                        let bound = |v: Option<Value>| match v {
+                            Some(Value::Int(n)) => Some(n),
+                            _ => None,
+                        };
+                        Ok(Value::Range(IntRange::open(
+                            bound(start),
+                            bound(end),
+                            *inclusive,
+                        )))
+                    }
+                    _ => Err(RuntimeError::new(&range::bounds_error(
+                        start.as_ref().map_or("Int", Value::type_name),
+                        end.as_ref().map_or("Int", Value::type_name),
+                    ))),
+                }
+            }
+
             Expr::StringInterp(parts) => {
                 let mut result = String::new();
                 for part in parts {
@@ -2580,6 +2891,13 @@ impl Interpreter {
                         "len" => Ok(Value::Int(items.len() as i64)),
                         _ => Err(RuntimeError::new(&format!("no method '{}' on Set", field))),
                     },
+                    Value::Range(range) => match field.as_str() {
+                        "start" => Ok(Value::Int(range.start)),
+                        "end" => Ok(Value::Int(range.end)),
+                        "inclusive" => Ok(Value::Bool(range.inclusive)),
+                        "len" => Ok(Value::Int(range.int_len())),
+                        _ => Err(RuntimeError::new(&format!("no field '{}' on Range", field))),
+                    },
                     _ => Err(RuntimeError::new(&format!(
                         "cannot access field '{}' on {}",
                         field,
@@ -2597,6 +2915,23 @@ impl Interpreter {
                     other => other,
                 };
                 match (inner, &idx) {
+                    (Value::Array(items), Value::Range(range)) => {
+                        let (from, to) = range.slice_bounds(items.len());
+                        Ok(Value::Array(items[from..to].to_vec()))
+                    }
+                    (Value::Tuple(items), Value::Range(range)) => {
+                        let (from, to) = range.slice_bounds(items.len());
+                        Ok(Value::Tuple(items[from..to].to_vec()))
+                    }
+                    (Value::String(s), Value::Range(range)) => {
+                        let (from, to) = range.slice_bounds(s.chars().count());
+                        Ok(Value::String(
+                            s.chars().skip(from).take(to - from).collect(),
+                        ))
+                    }
+                    (Value::Range(range), Value::Int(i)) => range::index(range, *i)
+                        .map(Value::Int)
+                        .map_err(|e| RuntimeError::new(&e)),
                     (Value::Array(items) | Value::Tuple(items), Value::Int(i)) => {
                         // Support negative indices (Python-style: -1 = last)
                         let len = items.len() as i64;
@@ -3238,6 +3573,10 @@ impl Interpreter {
                             }
                             return Ok(Value::Null);
                         }
+                        Value::Range(range) if method_name == "to_array" && args.is_empty() => {
+                            let items = range::collect(range).map_err(|e| RuntimeError::new(&e))?;
+                            return Ok(Value::Array(items.into_iter().map(Value::Int).collect()));
+                        }
                         // `.stream()` intercept — turn a source collection
                         // into a Value::Stream. Combinators are chained
                         // on Stream values by the block below.
@@ -3250,6 +3589,7 @@ impl Interpreter {
                                 Value::Tuple(items) => StreamKind::TupleIter { items, idx: 0 },
                                 Value::Set(items) => StreamKind::SetIter { items, idx: 0 },
                                 Value::Map(pairs) => StreamKind::MapIter { pairs, idx: 0 },
+                                Value::Range(range) => StreamKind::RangeIter { range, idx: 0 },
                                 Value::String(s) => StreamKind::StringIter {
                                     chars: s.chars().collect(),
                                     idx: 0,
@@ -3727,6 +4067,18 @@ impl Interpreter {
     }
 
     fn eval_binop(&self, left: &Value, op: &BinOp, right: &Value) -> Result<Value, RuntimeError> {
+        if matches!(
+            op,
+            BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::Shl | BinOp::Shr
+        ) && !matches!((left, right), (Value::Int(_), Value::Int(_)))
+        {
+            return Err(RuntimeError::new(&format!(
+                "'{}' requires Int operands, got {} and {}",
+                op.symbol(),
+                left.type_name(),
+                right.type_name()
+            )));
+        }
         match (left, right) {
             (Value::Int(a), Value::Int(b)) => match op {
                 BinOp::Add => match a.checked_add(*b) {
@@ -3753,6 +4105,19 @@ impl Interpreter {
                     }
                     Ok(Value::Int(a % b))
                 }
+                BinOp::FloorDiv => {
+                    if *b == 0 {
+                        return Err(RuntimeError::new("division by zero\n  hint: check that the divisor is not zero before dividing"));
+                    }
+                    Ok(int_ops::floor_div(*a, *b).into())
+                }
+                BinOp::Pow => Ok(int_ops::pow(*a, *b).into()),
+                BinOp::BitAnd => Ok(Value::Int(a & b)),
+                BinOp::BitOr => Ok(Value::Int(a | b)),
+                BinOp::BitXor => Ok(Value::Int(a ^ b)),
+                BinOp::Shl | BinOp::Shr => int_ops::shift(*a, *b, *op == BinOp::Shl)
+                    .map(Value::Int)
+                    .map_err(|e| RuntimeError::new(&e)),
                 BinOp::Eq => Ok(Value::Bool(a == b)),
                 BinOp::NotEq => Ok(Value::Bool(a != b)),
                 BinOp::Lt => Ok(Value::Bool(a < b)),
@@ -3768,6 +4133,8 @@ impl Interpreter {
                 BinOp::Mul => Ok(Value::Float(a * b)),
                 BinOp::Div => Ok(Value::Float(a / b)),
                 BinOp::Mod => Ok(Value::Float(a % b)),
+                BinOp::FloorDiv => Ok(Value::Float((a / b).floor())),
+                BinOp::Pow => Ok(Value::Float(a.powf(*b))),
                 BinOp::Eq => Ok(Value::Bool(a == b)),
                 BinOp::NotEq => Ok(Value::Bool(a != b)),
                 BinOp::Lt => Ok(Value::Bool(a < b)),
@@ -3858,6 +4225,12 @@ impl Interpreter {
                 _ => Err(RuntimeError::new("maps only support == and != operators")),
             },
 
+            (Value::Range(a), Value::Range(b)) => match op {
+                BinOp::Eq => Ok(Value::Bool(a == b)),
+                BinOp::NotEq => Ok(Value::Bool(a != b)),
+                _ => Err(RuntimeError::new("ranges only support == and != operators")),
+            },
+
             _ => Err(RuntimeError::new(&format!(
                 "cannot apply {:?} to {} and {}",
                 op,
@@ -3963,6 +4336,13 @@ impl Interpreter {
                             Step::Done
                         }
                     }
+                    StreamKind::RangeIter { range, idx } => match range.get(*idx) {
+                        Some(n) => {
+                            *idx += 1;
+                            Step::Yield(Value::Int(n))
+                        }
+                        None => Step::Done,
+                    },
                     StreamKind::Filter { upstream, pred } => Step::PullFilter {
                         upstream: upstream.clone(),
                         pred: pred.clone(),
@@ -4476,6 +4856,16 @@ impl Interpreter {
                 ..
             } => {
                 let is_global_fn = !name.is_empty() && closure.scopes.len() == 1;
+                // Top-level functions of an imported module resolve names in
+                // their module first, so they can reach private helpers the
+                // import did not bind here.
+                let saved_module_env =
+                    if is_global_fn && !self.env.shares_globals(&closure) && closure.is_module() {
+                        let layered = self.env.with_module_globals(&closure);
+                        Some(std::mem::replace(&mut self.env, layered))
+                    } else {
+                        None
+                    };
 
                 let result = if is_global_fn {
                     self.env.push_scope();
@@ -4489,6 +4879,9 @@ impl Interpreter {
                     }
                     let result = self.exec_stmts(&body);
                     self.env.pop_scope();
+                    if let Some(saved) = saved_module_env {
+                        self.env = saved;
+                    }
                     result
                 } else {
                     let saved_env = self.env.clone();
@@ -4739,6 +5132,56 @@ impl Interpreter {
                 (Expr::Bool(a), Value::Bool(b)) => a == b,
                 _ => false,
             },
+            Pattern::Or(alternatives) => alternatives.iter().any(|p| self.match_pattern(p, value)),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let ordering = |bound: &Expr| match (value, bound) {
+                    (Value::Int(v), Expr::Int(b)) => Some(v.cmp(b)),
+                    (Value::Int(v), Expr::Float(b)) => (*v as f64).partial_cmp(b),
+                    (Value::Float(v), Expr::Int(b)) => v.partial_cmp(&(*b as f64)),
+                    (Value::Float(v), Expr::Float(b)) => v.partial_cmp(b),
+                    (Value::String(v), Expr::StringLit(b)) => Some(v.as_str().cmp(b.as_str())),
+                    _ => None,
+                };
+                let above_start = start
+                    .as_ref()
+                    .is_none_or(|start| ordering(start).is_some_and(|o| o.is_ge()));
+                let below_end = end.as_ref().is_none_or(|end| {
+                    ordering(end).is_some_and(|o| if *inclusive { o.is_le() } else { o.is_lt() })
+                });
+                above_start && below_end
+            }
+            Pattern::Object { type_name, fields } => {
+                let Value::Object(map) = value else {
+                    return false;
+                };
+                if let Some(type_name) = type_name {
+                    if !matches!(map.get("__type__"), Some(Value::String(t)) if t == type_name) {
+                        return false;
+                    }
+                }
+                fields.iter().all(|(key, pat)| {
+                    map.get(key)
+                        .is_some_and(|field_val| self.match_pattern(pat, field_val))
+                })
+            }
+            Pattern::Array { items, rest } => {
+                let Value::Array(values) = value else {
+                    return false;
+                };
+                let len_ok = match rest {
+                    Some(_) => values.len() >= items.len(),
+                    None => values.len() == items.len(),
+                };
+                len_ok
+                    && items
+                        .iter()
+                        .zip(values)
+                        .all(|(pat, item)| self.match_pattern(pat, item))
+            }
             Pattern::Constructor { name, fields } => {
                 match (name.as_str(), value) {
                     ("Ok", Value::ResultOk(inner)) => {
@@ -4822,7 +5265,32 @@ impl Interpreter {
                     }
                 }
             }
-            _ => {}
+            Pattern::Or(alternatives) => {
+                if let Some(matched) = alternatives.iter().find(|p| self.match_pattern(p, value)) {
+                    self.bind_pattern(matched, value);
+                }
+            }
+            Pattern::Object { fields, .. } => {
+                if let Value::Object(map) = value {
+                    for (key, field_pat) in fields {
+                        if let Some(val) = map.get(key) {
+                            self.bind_pattern(field_pat, val);
+                        }
+                    }
+                }
+            }
+            Pattern::Array { items, rest } => {
+                if let Value::Array(values) = value {
+                    for (item_pat, item) in items.iter().zip(values) {
+                        self.bind_pattern(item_pat, item);
+                    }
+                    if let Some(rest) = rest {
+                        let remainder = values.get(items.len()..).unwrap_or_default().to_vec();
+                        self.bind_pattern(rest, &Value::Array(remainder));
+                    }
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => {}
         }
     }
 }
@@ -0,0 +1,179 @@
+//! Per-process cache of imported modules.
+//!
+//! A file is lexed, parsed and run once no matter how many files import
+//! it, so diamond imports share one instance and its module-level state.
+//! Entries are keyed by canonical path and reloaded when the file's size
+//! or modification time changes.
+
+use super::{Environment, Interpreter, Value};
+use crate::parser::ast::Program;
+use crate::parser::ast::TypeAnn;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+type Stamp = (Option<SystemTime>, u64);
+type ParseCache = Mutex<HashMap<PathBuf, (Stamp, Arc<Program>)>>;
+
+/// A module imported whole or mounted: its canonical path, the path as
+/// written, and the running instance.
+pub(crate) type NestedModule = (PathBuf, String, Arc<LoadedModule>);
+
+/// A module that has finished running: its program and the state its
+/// interpreter was left in.
+pub(crate) struct LoadedModule {
+    pub program: Arc<Program>,
+    pub env: Environment,
+    pub method_tables: HashMap<String, IndexMap<String, Value>>,
+    pub static_methods: HashMap<String, IndexMap<String, Value>>,
+    pub embedded_fields: HashMap<String, Vec<(String, String)>>,
+    pub struct_defaults: HashMap<String, IndexMap<String, Value>>,
+    pub struct_fields: HashMap<String, Vec<(String, TypeAnn)>>,
+    pub nested_modules: Vec<NestedModule>,
+    stamp: Stamp,
+}
+
+impl LoadedModule {
+    fn new(program: Arc<Program>, interp: Interpreter, stamp: Stamp) -> Self {
+        Self {
+            program,
+            env: interp.env,
+            method_tables: interp.method_tables,
+            static_methods: interp.static_methods,
+            embedded_fields: interp.embedded_fields,
+            struct_defaults: interp.struct_defaults,
+            struct_fields: interp.struct_fields,
+            nested_modules: interp.nested_modules,
+            stamp,
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    match std::fs::metadata(path) {
+        Ok(meta) => (meta.modified().ok(), meta.len()),
+        Err(_) => (None, 0),
+    }
+}
+
+/// The key a module is cached and cycle-checked under.
+pub(crate) fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Parse `file`, reusing the previous parse while the file is unchanged.
+/// `name` is the import path as written, for error messages.
+pub(crate) fn parse_module(name: &str, file: &Path) -> Result<Arc<Program>, String> {
+    static PROGRAMS: OnceLock<ParseCache> = OnceLock::new();
+    let key = canonical(file);
+    let current = stamp(&key);
+    let programs = PROGRAMS.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some((cached, program)) = programs.lock().unwrap_or_else(|p| p.into_inner()).get(&key) {
+        if *cached == current {
+            return Ok(Arc::clone(program));
+        }
+    }
+
+    let source =
+        std::fs::read_to_string(file).map_err(|e| format!("cannot import '{}': {}", name, e))?;
+    let tokens = crate::lexer::Lexer::new(&source)
+        .tokenize()
+        .map_err(|e| format!("import '{}' lex error: {}", name, e.message))?;
+    let program = crate::parser::Parser::new(tokens)
+        .parse_program()
+        .map_err(|e| format!("import '{}' parse error: {}", name, e.message))?;
+    let program = Arc::new(program);
+    programs
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .insert(key, (current, Arc::clone(&program)));
+    Ok(program)
+}
+
+fn instances() -> &'static Mutex<HashMap<PathBuf, Arc<LoadedModule>>> {
+    static INSTANCES: OnceLock<Mutex<HashMap<PathBuf, Arc<LoadedModule>>>> = OnceLock::new();
+    INSTANCES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The running instance of the module at canonical path `key`, if it has
+/// been loaded and the file has not changed since.
+pub(crate) fn cached(key: &Path) -> Option<Arc<LoadedModule>> {
+    let module = instances()
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .get(key)
+        .cloned()?;
+    (module.stamp == stamp(key)).then_some(module)
+}
+
+/// Record the instance `interp` left after running `program` from `key`.
+pub(crate) fn store(key: PathBuf, program: Arc<Program>, interp: Interpreter) -> Arc<LoadedModule> {
+    let module = Arc::new(LoadedModule::new(program, interp, stamp(&key)));
+    instances()
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .insert(key, Arc::clone(&module));
+    module
+}
+
+/// The files still loading once `key` starts to: `loading`, or just
+/// `entry` for an import made by the entry file, followed by `key`. Both
+/// engines keep this chain, so they report the same cycles the same way.
+pub(crate) fn enter_import(
+    loading: &[PathBuf],
+    entry: Option<&Path>,
+    key: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let mut chain = loading.to_vec();
+    if chain.is_empty() {
+        chain.extend(entry.map(canonical));
+    }
+    if chain.iter().any(|file| file == key) {
+        return Err(cycle_error(&chain, key));
+    }
+    chain.push(key.to_path_buf());
+    Ok(chain)
+}
+
+/// Why `import '{path}'` cannot bind `name`: the file already imported it
+/// from `previous`, or defines it itself. Imports are named by the path as
+/// written in the `import` statement.
+pub(crate) fn collision_error(path: &str, name: &str, previous: Option<&str>) -> String {
+    match previous {
+        Some(previous) => format!(
+            "import '{}' collides with '{}' already imported from '{}'",
+            path, name, previous
+        ),
+        None => format!(
+            "import '{}' collides with '{}' already defined in this file",
+            path, name
+        ),
+    }
+}
+
+/// Why a top-level declaration cannot redefine `name`, which
+/// `import '{path}'` bound.
+pub(crate) fn redefinition_error(name: &str, path: &str) -> String {
+    format!(
+        "'{}' is already imported from '{}'; rename it or import the module with 'as'",
+        name, path
+    )
+}
+
+/// "import cycle: a.fg -> b.fg -> a.fg", naming each file in the chain
+/// of imports that are still loading.
+fn cycle_error(chain: &[PathBuf], repeated: &Path) -> String {
+    let cwd = std::env::current_dir().ok();
+    let shown = |path: &Path| {
+        cwd.as_deref()
+            .and_then(|cwd| path.strip_prefix(cwd).ok())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    let mut names: Vec<String> = chain.iter().map(|p| shown(p)).collect();
+    names.push(shown(repeated));
+    format!("import cycle: {}", names.join(" -> "))
+}
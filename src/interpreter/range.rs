@@ -0,0 +1,174 @@
+//! Integer ranges: the value of `a..b` and `a..=b`, and the bounds of a
+//! `xs[a..b]` slice. Both engines store this struct directly, so a range
+//! never materializes its elements unless it is collected.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy)]
+pub struct IntRange {
+    pub start: i64,
+    pub end: i64,
+    pub inclusive: bool,
+}
+
+impl IntRange {
+    pub fn new(start: i64, end: i64, inclusive: bool) -> Self {
+        Self {
+            start,
+            end,
+            inclusive,
+        }
+    }
+
+    /// The bounds of a slice with either side left open: `xs[..n]` starts
+    /// at 0 and `xs[n..]` runs to the end of the sequence.
+    pub fn open(start: Option<i64>, end: Option<i64>, inclusive: bool) -> Self {
+        match end {
+            Some(end) => Self::new(start.unwrap_or(0), end, inclusive),
+            None => Self::new(start.unwrap_or(0), i64::MAX, true),
+        }
+    }
+
+    /// One past the last element, widened so `..=i64::MAX` cannot overflow.
+    fn stop(&self) -> i128 {
+        self.end as i128 + self.inclusive as i128
+    }
+
+    pub fn len(&self) -> usize {
+        (self.stop() - self.start as i128).clamp(0, usize::MAX as i128) as usize
+    }
+
+    /// [`len`](Self::len) as an Int. `0..=Int.max` has one element too
+    /// many to count, so lengths saturate at `Int.max`.
+    pub fn int_len(&self) -> i64 {
+        i64::try_from(self.len()).unwrap_or(i64::MAX)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, idx: usize) -> Option<i64> {
+        let n = self.start as i128 + idx as i128;
+        (n < self.stop()).then_some(n as i64)
+    }
+
+    pub fn contains(&self, n: i64) -> bool {
+        n >= self.start && (n as i128) < self.stop()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i64> {
+        let start = self.start;
+        (0..self.len()).map(move |i| start.wrapping_add(i as i64))
+    }
+
+    /// Resolve against a sequence of `len` items the way `slice()` does:
+    /// negative bounds count from the end and out-of-range bounds clamp, so
+    /// the result is always a valid (possibly empty) `start..end`.
+    pub fn slice_bounds(&self, len: usize) -> (usize, usize) {
+        let len = len as i128;
+        let resolve = |bound: i64| {
+            let bound = bound as i128;
+            if bound < 0 {
+                len + bound
+            } else {
+                bound
+            }
+        };
+        let start = resolve(self.start).clamp(0, len);
+        let end = (resolve(self.end) + self.inclusive as i128).clamp(start, len);
+        (start as usize, end as usize)
+    }
+}
+
+/// Ranges are equal when they produce the same elements from the same
+/// start, so `0..=4 == 0..5`.
+impl PartialEq for IntRange {
+    fn eq(&self, other: &Self) -> bool {
+        (self.is_empty() && other.is_empty())
+            || (self.start == other.start && self.stop() == other.stop())
+    }
+}
+
+impl fmt::Display for IntRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = if self.inclusive { "..=" } else { ".." };
+        write!(f, "{}{}{}", self.start, op, self.end)
+    }
+}
+
+/// `range[i]`, counting from the end when `i` is negative like arrays do.
+pub(crate) fn index(range: &IntRange, i: i64) -> Result<i64, String> {
+    let len = range.len();
+    let actual = if i < 0 {
+        len as i128 + i as i128
+    } else {
+        i as i128
+    };
+    usize::try_from(actual)
+        .ok()
+        .and_then(|actual| range.get(actual))
+        .ok_or_else(|| {
+            format!(
+                "index out of bounds: index {} on range of length {}",
+                i, len
+            )
+        })
+}
+
+pub(crate) fn bounds_error(start: &str, end: &str) -> String {
+    format!("range bounds must be Int, got {} and {}", start, end)
+}
+
+/// The most elements a range collects into an array. Collecting allocates
+/// every element up front, so anything bigger is an error instead of an
+/// allocation failure that would take the whole process down.
+pub(crate) const MAX_COLLECTED: usize = 1 << 24;
+
+/// The elements of `range`, or an error pointing at the lazy alternatives
+/// when there are more than [`MAX_COLLECTED`].
+pub(crate) fn collect(range: &IntRange) -> Result<Vec<i64>, String> {
+    if range.len() > MAX_COLLECTED {
+        return Err(format!(
+            "range {} is too large to collect into an array ({} elements, limit {}); iterate it with `for` or .stream() instead",
+            range,
+            range.len(),
+            MAX_COLLECTED
+        ));
+    }
+    Ok(range.iter().collect())
+}
+
+/// Builtins whose first argument is an array and which accept a range in
+/// its place by collecting it. `len` and `contains` answer without
+/// collecting, so they are not listed.
+pub(crate) const COLLECTING_BUILTINS: &[&str] = &[
+    "map",
+    "filter",
+    "reduce",
+    "for_each",
+    "find",
+    "flat_map",
+    "any",
+    "all",
+    "count",
+    "sum",
+    "min_of",
+    "max_of",
+    "sort",
+    "sort_by",
+    "reverse",
+    "enumerate",
+    "unique",
+    "zip",
+    "chunk",
+    "partition",
+    "group_by",
+    "frequencies",
+    "first",
+    "last",
+    "take_n",
+    "skip",
+    "join",
+    "slice",
+];
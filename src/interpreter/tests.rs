@@ -1377,6 +1377,182 @@ fn import_file() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn import_respects_pub_and_binds_namespaces() {
+    let dir = unique_temp_path("pub_import");
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = dir.join("shapes.fg");
+    std::fs::write(
+        &module,
+        r#"
+        fn side(n) { return n * 2 }
+        pub fn perimeter(n) { return side(n) * 2 }
+        pub let sides = 4
+        "#,
+    )
+    .unwrap();
+    let path = forge_string_literal_path(&module);
+
+    let result = try_run_forge(&format!(
+        r#"
+        import "{path}" as shapes
+        import {{ perimeter }} from "{path}"
+        assert_eq(shapes.perimeter(3), 12)
+        assert_eq(perimeter(1), 4)
+        assert_eq(shapes.sides, 4)
+        "#
+    ));
+    assert!(result.is_ok(), "{:?}", result);
+
+    let private = try_run_forge(&format!(r#"import {{ side }} from "{path}""#)).unwrap_err();
+    assert!(
+        private.message.contains("does not export 'side'"),
+        "{}",
+        private.message
+    );
+
+    let hidden = try_run_forge(&format!("import \"{path}\"\nside(1)")).unwrap_err();
+    assert!(
+        hidden.message.contains("undefined variable"),
+        "{}",
+        hidden.message
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn import_name_collisions_are_errors() {
+    let dir = unique_temp_path("import_clash");
+    std::fs::create_dir_all(&dir).unwrap();
+    let a = dir.join("a.fg");
+    let b = dir.join("b.fg");
+    std::fs::write(&a, "fn greet() { return 1 }").unwrap();
+    std::fs::write(&b, "fn greet() { return 2 }").unwrap();
+    let (a, b) = (forge_string_literal_path(&a), forge_string_literal_path(&b));
+
+    let twice = try_run_forge(&format!("import \"{a}\"\nimport \"{b}\"")).unwrap_err();
+    assert!(
+        twice.message.contains("collides with 'greet'"),
+        "{}",
+        twice.message
+    );
+
+    let redefined =
+        try_run_forge(&format!("import \"{a}\"\nfn greet() {{ return 3 }}")).unwrap_err();
+    assert!(
+        redefined.message.contains("already imported"),
+        "{}",
+        redefined.message
+    );
+
+    let shadowed = try_run_forge(&format!("let greet = 0\nimport \"{a}\"")).unwrap_err();
+    assert!(
+        shadowed.message.contains("already defined in this file"),
+        "{}",
+        shadowed.message
+    );
+
+    // Re-importing the same file, or aliasing both modules, is fine.
+    assert!(try_run_forge(&format!("import \"{a}\"\nimport \"{a}\"")).is_ok());
+    assert!(try_run_forge(&format!("import \"{a}\" as a\nimport \"{b}\" as b")).is_ok());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn nested_whole_file_imports_pass_on_only_exports() {
+    let dir = unique_temp_path("nested_import");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("inner.fg"),
+        "fn inner_helper() { return 0 }\npub fn inner_open() { return 1 }",
+    )
+    .unwrap();
+    let inner = forge_string_literal_path(&dir.join("inner.fg"));
+    std::fs::write(
+        dir.join("lib.fg"),
+        format!("import \"{inner}\"\nfn zz_private() {{ return 2 }}\npub fn open() {{ return inner_open() }}"),
+    )
+    .unwrap();
+    let lib = forge_string_literal_path(&dir.join("lib.fg"));
+
+    let result = try_run_forge(&format!(
+        "import \"{lib}\"\nassert_eq(open(), 1)\nassert_eq(inner_open(), 1)"
+    ));
+    assert!(result.is_ok(), "{:?}", result);
+
+    for name in ["zz_private", "inner_helper"] {
+        let hidden = try_run_forge(&format!("import \"{lib}\"\n{name}()")).unwrap_err();
+        assert!(
+            hidden.message.contains("undefined variable"),
+            "{name}: {}",
+            hidden.message
+        );
+    }
+
+    let shadowed =
+        try_run_forge(&format!("fn inner_open() {{ return 3 }}\nimport \"{lib}\"")).unwrap_err();
+    assert!(
+        shadowed.message.contains("already defined in this file"),
+        "{}",
+        shadowed.message
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn diamond_imports_share_one_module_instance() {
+    let dir = unique_temp_path("diamond_import");
+    std::fs::create_dir_all(&dir).unwrap();
+    let counter = forge_string_literal_path(&dir.join("counter.fg"));
+    std::fs::write(
+        dir.join("counter.fg"),
+        "let mut hits = 0\npub fn bump() {\n    hits = hits + 1\n    return hits\n}",
+    )
+    .unwrap();
+    for side in ["left", "right"] {
+        std::fs::write(
+            dir.join(format!("{side}.fg")),
+            format!("import \"{counter}\" as c\npub fn {side}() {{ return c.bump() }}"),
+        )
+        .unwrap();
+    }
+    let left = forge_string_literal_path(&dir.join("left.fg"));
+    let right = forge_string_literal_path(&dir.join("right.fg"));
+
+    let result = try_run_forge(&format!(
+        r#"
+        import "{left}"
+        import "{right}"
+        assert_eq(left(), 1)
+        assert_eq(right(), 2)
+        assert_eq(left(), 3)
+        "#
+    ));
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn import_cycles_report_the_chain() {
+    let dir = unique_temp_path("import_cycle");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.fg"), "import \"b\"").unwrap();
+    std::fs::write(dir.join("b.fg"), "import \"a\"").unwrap();
+    let a = forge_string_literal_path(&dir.join("a.fg"));
+
+    let err = try_run_forge(&format!("import \"{a}\"")).unwrap_err();
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(err.message.contains("import cycle:"), "{}", err.message);
+    let files: Vec<&str> = err.message["import cycle: ".len()..]
+        .split(" -> ")
+        .map(|p| p.rsplit('/').next().unwrap())
+        .collect();
+    assert_eq!(files, vec!["a.fg", "b.fg", "a.fg"], "{}", err.message);
+}
+
 #[test]
 fn try_catch_error_binding() {
     let result = try_run_forge(
@@ -3599,6 +3775,72 @@ fn match_none_branch() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn match_guard_falls_through_to_next_arm() {
+    let value = run_forge(
+        r#"
+        fn sign(n) {
+            match n {
+                x if x > 0 => { return "positive" }
+                x if x < 0 => { return "negative" }
+                _ => { return "zero" }
+            }
+        }
+        [sign(4), sign(-4), sign(0)]
+        "#,
+    );
+    assert_eq!(format!("{}", value), "[positive, negative, zero]");
+}
+
+#[test]
+fn match_exhaustiveness_ignores_guarded_arms() {
+    let err = try_run_forge(
+        r#"
+        type Light = Red | Green
+        let light = Green
+        match light {
+            Red => say "stop"
+            Green if false => say "go"
+        }
+        "#,
+    )
+    .unwrap_err();
+    assert_eq!(err.message, "non-exhaustive match: missing variant 'Green'");
+
+    let ok = try_run_forge(
+        r#"
+        type Light = Red | Amber | Green
+        let light = Amber
+        match light {
+            Red | Amber => say "stop"
+            Green => say "go"
+        }
+        "#,
+    );
+    assert!(ok.is_ok());
+}
+
+#[test]
+fn match_range_patterns_require_same_kind() {
+    let value = run_forge(
+        r#"
+        fn kind(v) {
+            match v {
+                0..10 => { return "small" }
+                10.0..=20.0 => { return "medium" }
+                "a"..="m" => { return "early" }
+                _ => { return "other" }
+            }
+        }
+        [kind(9), kind(10), kind(20), kind("kiwi"), kind("zebra"), kind(true)]
+        "#,
+    );
+    assert_eq!(
+        format!("{}", value),
+        "[small, medium, medium, early, other, other]"
+    );
+}
+
 #[test]
 fn option_equality() {
     let result = try_run_forge(
@@ -7180,3 +7422,82 @@ fn fork_for_serving_panics_if_template_env_holds_a_stream() {
 
     let _ = template.fork_for_serving();
 }
+
+#[test]
+fn yield_and_emit_feed_the_event_sink() {
+    let tokens = Lexer::new("let x = 2\nemit x\nyield x * 10\n")
+        .tokenize()
+        .expect("lexing should succeed");
+    let program = Parser::new(tokens)
+        .parse_program()
+        .expect("parsing should succeed");
+
+    // Without a sink, yield/emit stay no-ops.
+    Interpreter::new().run(&program).expect("run without sink");
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink_seen = seen.clone();
+    let mut interpreter = Interpreter::new();
+    interpreter.event_sink = Some(Arc::new(move |value| {
+        sink_seen.lock().unwrap().push(value);
+        Ok(())
+    }));
+    interpreter.run(&program).expect("run with sink");
+    let seen = seen.lock().unwrap();
+    assert!(matches!(seen.as_slice(), [Value::Int(2), Value::Int(20)]));
+
+    let mut closed = Interpreter::new();
+    closed.event_sink = Some(Arc::new(|_| Err("client disconnected".to_string())));
+    let err = closed.run(&program).expect_err("closed sink aborts");
+    assert!(err.message.contains("client disconnected"));
+}
+
+#[test]
+fn bitwise_and_integer_operators() {
+    let result = run_forge(
+        r#"
+        let mut mask = 0
+        mask |= 1 << 4
+        mask |= 1 << 1
+        mask ^= 3
+        let parts = [mask, mask & 16, 255 >> 4, 3 ** 4, 9 ~/ 4, -9 ~/ 4, 9 % 4]
+        parts
+        "#,
+    );
+    assert_eq!(result.to_string(), "[17, 16, 15, 81, 2, -3, 1]");
+
+    // Overflow promotes to Float like `*`; shifts drop the high bits.
+    assert!(matches!(run_forge("10 ** 19"), Value::Float(_)));
+    assert!(matches!(run_forge("(1 << 62) << 1"), Value::Int(n) if n == i64::MIN));
+
+    let err = try_run_forge("1.5 | 1").unwrap_err();
+    assert!(err
+        .message
+        .contains("'|' requires Int operands, got Float and Int"));
+}
+
+#[test]
+fn ranges_are_lazy() {
+    // Neither of these would fit in memory if the range were collected.
+    let result = run_forge(
+        r#"
+        let big = 0..=9000000000000000000
+        let mut firsts = []
+        for n in big {
+            if n == 3 { break }
+            firsts = push(firsts, n)
+        }
+        [len(big), contains(big, 42), big[-1], firsts, big.stream().skip(5).take(2).collect()]
+        "#,
+    );
+    assert_eq!(
+        result.to_string(),
+        "[9000000000000000001, true, 9000000000000000000, [0, 1, 2], [5, 6]]"
+    );
+
+    assert!(matches!(run_forge("0..3 == 0..=2"), Value::Bool(true)));
+    assert!(matches!(run_forge("5..1 == 9..0"), Value::Bool(true)));
+
+    let err = try_run_forge("(0..3) < (1..4)").unwrap_err();
+    assert!(err.message.contains("ranges only support == and !="));
+}
@@ -87,7 +87,15 @@ impl Lexer {
                 }
                 '*' => {
                     self.advance();
-                    if self.current_matches('=') {
+                    if self.current_matches('*') {
+                        self.advance();
+                        if self.current_matches('=') {
+                            self.advance();
+                            Token::StarStarEq
+                        } else {
+                            Token::StarStar
+                        }
+                    } else if self.current_matches('=') {
                         self.advance();
                         Token::StarEq
                     } else {
@@ -112,6 +120,30 @@ impl Lexer {
                         Token::Percent
                     }
                 }
+                '~' => {
+                    self.advance();
+                    if !self.current_matches('/') {
+                        return Err(
+                            self.error("unexpected character: '~' (floor division is '~/')")
+                        );
+                    }
+                    self.advance();
+                    if self.current_matches('=') {
+                        self.advance();
+                        Token::TildeSlashEq
+                    } else {
+                        Token::TildeSlash
+                    }
+                }
+                '^' => {
+                    self.advance();
+                    if self.current_matches('=') {
+                        self.advance();
+                        Token::CaretEq
+                    } else {
+                        Token::Caret
+                    }
+                }
                 '=' => {
                     self.advance();
                     if self.current_matches('=') {
@@ -138,6 +170,14 @@ impl Lexer {
                     if self.current_matches('=') {
                         self.advance();
                         Token::LtEq
+                    } else if self.current_matches('<') {
+                        self.advance();
+                        if self.current_matches('=') {
+                            self.advance();
+                            Token::ShlEq
+                        } else {
+                            Token::Shl
+                        }
                     } else {
                         Token::Lt
                     }
@@ -149,7 +189,12 @@ impl Lexer {
                         Token::GtEq
                     } else if self.current_matches('>') {
                         self.advance();
-                        Token::PipeRight
+                        if self.current_matches('=') {
+                            self.advance();
+                            Token::ShrEq
+                        } else {
+                            Token::PipeRight
+                        }
                     } else {
                         Token::Gt
                     }
@@ -159,6 +204,9 @@ impl Lexer {
                     if self.current_matches('&') {
                         self.advance();
                         Token::And
+                    } else if self.current_matches('=') {
+                        self.advance();
+                        Token::AmpersandEq
                     } else {
                         Token::Ampersand
                     }
@@ -171,6 +219,9 @@ impl Lexer {
                     } else if self.current_matches('>') {
                         self.advance();
                         Token::Pipe
+                    } else if self.current_matches('=') {
+                        self.advance();
+                        Token::BarEq
                     } else {
                         Token::Bar
                     }
@@ -186,6 +237,9 @@ impl Lexer {
                         if self.current_matches('.') {
                             self.advance();
                             Token::DotDotDot
+                        } else if self.current_matches('=') {
+                            self.advance();
+                            Token::DotDotEq
                         } else {
                             Token::DotDot
                         }
@@ -551,6 +605,30 @@ mod tests {
         assert_eq!(lex("*="), vec![Token::StarEq]);
         assert_eq!(lex("/="), vec![Token::SlashEq]);
         assert_eq!(lex("%="), vec![Token::PercentEq]);
+        assert_eq!(lex("**="), vec![Token::StarStarEq]);
+        assert_eq!(lex("~/="), vec![Token::TildeSlashEq]);
+        assert_eq!(lex("&="), vec![Token::AmpersandEq]);
+        assert_eq!(lex("|="), vec![Token::BarEq]);
+        assert_eq!(lex("^="), vec![Token::CaretEq]);
+        assert_eq!(lex("<<="), vec![Token::ShlEq]);
+        assert_eq!(lex(">>="), vec![Token::ShrEq]);
+    }
+
+    #[test]
+    fn test_bitwise_and_integer_operators() {
+        assert_eq!(
+            lex("& | ^ << >> ** ~/"),
+            vec![
+                Token::Ampersand,
+                Token::Bar,
+                Token::Caret,
+                Token::Shl,
+                Token::PipeRight,
+                Token::StarStar,
+                Token::TildeSlash,
+            ]
+        );
+        assert!(Lexer::new("a ~ b").tokenize().is_err());
     }
 
     #[test]
@@ -558,6 +636,18 @@ mod tests {
         assert_eq!(lex("..."), vec![Token::DotDotDot]);
     }
 
+    #[test]
+    fn test_range_operators() {
+        assert_eq!(
+            lex("1..9"),
+            vec![Token::Int(1), Token::DotDot, Token::Int(9)]
+        );
+        assert_eq!(
+            lex("1..=9"),
+            vec![Token::Int(1), Token::DotDotEq, Token::Int(9)]
+        );
+    }
+
     #[test]
     fn test_pipe_right() {
         assert_eq!(lex(">>"), vec![Token::PipeRight]);
@@ -86,39 +86,46 @@ pub enum Token {
     DotDotDot, // ... (spread)
 
     // Innovation tokens
-    When,      // when guards
-    Unless,    // postfix unless
-    Until,     // postfix until
-    Must,      // must expr (crash on error)
-    Check,     // check validation
-    Safe,      // safe { } blocks
-    Where,     // collection where filter
-    Timeout,   // timeout N seconds { }
-    Retry,     // retry N times { }
-    Schedule,  // schedule every N { }
-    Every,     // every x in items
-    Any,       // any x in items
-    Ask,       // ask "prompt"
-    Prompt,    // prompt name() { }
-    Agent,     // agent name() { }
-    Transform, // transform data { }
-    Table,     // table [...]
-    Select,    // from X select Y
-    Order,     // order by
-    By,        // sort by / order by
-    Limit,     // limit N
-    Keep,      // keep where
-    Take,      // take N
-    Freeze,    // freeze expr
-    Watch,     // watch "file" { }
-    PipeRight, // >> pipe operator
-    Download,  // download url to path
-    Crawl,     // crawl url
-    PlusEq,    // +=
-    MinusEq,   // -=
-    StarEq,    // *=
-    SlashEq,   // /=
-    PercentEq, // %=
+    When,         // when guards
+    Unless,       // postfix unless
+    Until,        // postfix until
+    Must,         // must expr (crash on error)
+    Check,        // check validation
+    Safe,         // safe { } blocks
+    Where,        // collection where filter
+    Timeout,      // timeout N seconds { }
+    Retry,        // retry N times { }
+    Schedule,     // schedule every N { }
+    Every,        // every x in items
+    Any,          // any x in items
+    Ask,          // ask "prompt"
+    Prompt,       // prompt name() { }
+    Agent,        // agent name() { }
+    Transform,    // transform data { }
+    Table,        // table [...]
+    Select,       // from X select Y
+    Order,        // order by
+    By,           // sort by / order by
+    Limit,        // limit N
+    Keep,         // keep where
+    Take,         // take N
+    Freeze,       // freeze expr
+    Watch,        // watch "file" { }
+    PipeRight,    // >> pipe chain / shift right
+    Download,     // download url to path
+    Crawl,        // crawl url
+    PlusEq,       // +=
+    MinusEq,      // -=
+    StarEq,       // *=
+    SlashEq,      // /=
+    PercentEq,    // %=
+    StarStarEq,   // **=
+    TildeSlashEq, // ~/=
+    AmpersandEq,  // &=
+    BarEq,        // |=
+    CaretEq,      // ^=
+    ShlEq,        // <<=
+    ShrEq,        // >>=
 
     // Built-in type names
     IntType,    // Int
@@ -128,29 +135,34 @@ pub enum Token {
     JsonType,   // Json
 
     // === Operators ===
-    Plus,      // +
-    Minus,     // -
-    Star,      // *
-    Slash,     // /
-    Percent,   // %
-    Eq,        // =
-    EqEq,      // ==
-    NotEq,     // !=
-    Lt,        // <
-    Gt,        // >
-    LtEq,      // <=
-    GtEq,      // >=
-    And,       // &&
-    Or,        // ||
-    Not,       // !
-    Pipe,      // |>
-    Bar,       // | (single bar, for ADT variants)
-    Question,  // ?
-    Arrow,     // ->
-    FatArrow,  // =>
-    Dot,       // .
-    DotDot,    // ..
-    Ampersand, // &
+    Plus,       // +
+    Minus,      // -
+    Star,       // *
+    Slash,      // /
+    Percent,    // %
+    StarStar,   // **
+    TildeSlash, // ~/ (floor division)
+    Caret,      // ^
+    Shl,        // <<
+    Eq,         // =
+    EqEq,       // ==
+    NotEq,      // !=
+    Lt,         // <
+    Gt,         // >
+    LtEq,       // <=
+    GtEq,       // >=
+    And,        // &&
+    Or,         // ||
+    Not,        // !
+    Pipe,       // |>
+    Bar,        // | (single bar, for ADT variants)
+    Question,   // ?
+    Arrow,      // ->
+    FatArrow,   // =>
+    Dot,        // .
+    DotDot,     // ..
+    DotDotEq,   // ..=
+    Ampersand,  // &
 
     // === Delimiters ===
     LParen,    // (
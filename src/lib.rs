@@ -43,6 +43,24 @@ pub extern "C" fn forge_execute_bytecode(bytecode_ptr: *const u8, bytecode_len:
             }
         };
 
+        if !chunk.declarations.is_empty() {
+            let served = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("failed to create Tokio runtime: {e}"))
+                .and_then(|rt| {
+                    rt.block_on(runtime::host::serve_compiled(&chunk))
+                        .map_err(|e| e.message)
+                });
+            return match served {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("forge: {}", e);
+                    1
+                }
+            };
+        }
+
         let mut machine = vm::machine::VM::new();
         match machine.execute(&chunk) {
             Ok(_) => 0,
@@ -107,7 +107,8 @@ fn handle_message(body: &str) -> Option<String> {
 
             store_document(uri, text);
 
-            let diagnostics = get_diagnostics(text);
+            let mut diagnostics = get_diagnostics(text);
+            diagnostics.extend(get_import_diagnostics(uri, text));
             let notification = serde_json::json!({
                 "jsonrpc": "2.0",
                 "method": "textDocument/publishDiagnostics",
@@ -275,6 +276,98 @@ fn get_diagnostics(source: &str) -> Vec<serde_json::Value> {
     }
 }
 
+/// Errors the runtime would raise for this file's imports: named imports
+/// of names the module does not export, and imported names that collide
+/// with another import or with a top-level declaration.
+fn get_import_diagnostics(uri: &str, source: &str) -> Vec<serde_json::Value> {
+    let mut lexer = crate::lexer::Lexer::new(source);
+    let Ok(tokens) = lexer.tokenize() else {
+        return Vec::new();
+    };
+    let mut parser = crate::parser::Parser::new(tokens);
+    let Ok(program) = parser.parse_program() else {
+        return Vec::new();
+    };
+
+    let mut declared = std::collections::HashSet::new();
+    for spanned in &program.statements {
+        match &spanned.stmt {
+            Stmt::FnDef { name, .. } | Stmt::Let { name, .. } | Stmt::StructDef { name, .. } => {
+                declared.insert(name.clone());
+            }
+            Stmt::TypeDef { variants, .. } => {
+                declared.extend(variants.iter().map(|v| v.name.clone()));
+            }
+            _ => {}
+        }
+    }
+
+    let source_lines: Vec<&str> = source.lines().collect();
+    let mut diagnostics = Vec::new();
+    let mut bound: std::collections::HashMap<String, (std::path::PathBuf, String)> =
+        std::collections::HashMap::new();
+    for spanned in &program.statements {
+        let Stmt::Import { path, names, alias } = &spanned.stmt else {
+            continue;
+        };
+        let Some(resolved) = resolve_import_for_lsp(path, uri) else {
+            continue;
+        };
+        let Some(module) = std::fs::read_to_string(&resolved).ok().and_then(|text| {
+            let tokens = crate::lexer::Lexer::new(&text).tokenize().ok()?;
+            crate::parser::Parser::new(tokens).parse_program().ok()
+        }) else {
+            continue;
+        };
+        let exports = module.export_names();
+        let mut messages = Vec::new();
+        let names_bound: Vec<String> = match (names, alias) {
+            (Some(name_list), _) => {
+                for name in name_list.iter().filter(|n| !exports.contains(n)) {
+                    messages.push(format!("import '{}' does not export '{}'", path, name));
+                }
+                name_list
+                    .iter()
+                    .filter(|n| exports.contains(n))
+                    .cloned()
+                    .collect()
+            }
+            (None, Some(alias)) => vec![alias.clone()],
+            (None, None) => exports,
+        };
+        for name in names_bound {
+            match bound.get(&name) {
+                Some((prev, _)) if *prev == resolved => {}
+                Some((_, prev_path)) => messages.push(format!(
+                    "import '{}' collides with '{}' already imported from '{}'",
+                    path, name, prev_path
+                )),
+                None if declared.contains(&name) => messages.push(format!(
+                    "import '{}' collides with '{}' defined in this file",
+                    path, name
+                )),
+                None => {
+                    bound.insert(name, (resolved.clone(), path.clone()));
+                }
+            }
+        }
+        let line = spanned.line.saturating_sub(1);
+        let end_char = source_lines.get(line).map(|l| l.len()).unwrap_or(0);
+        for message in messages {
+            diagnostics.push(serde_json::json!({
+                "range": {
+                    "start": {"line": line, "character": 0},
+                    "end": {"line": line, "character": end_char}
+                },
+                "severity": 1,
+                "source": "forge",
+                "message": message
+            }));
+        }
+    }
+    diagnostics
+}
+
 fn get_completions() -> Vec<serde_json::Value> {
     let keywords = [
         "let",
@@ -363,6 +456,8 @@ fn get_completions() -> Vec<serde_json::Value> {
         "assert",
         "assert_eq",
         "fetch",
+        "response",
+        "redirect",
         "time",
         "uuid",
         "wait",
@@ -371,7 +466,7 @@ fn get_completions() -> Vec<serde_json::Value> {
     ];
     let modules = [
         "math", "fs", "io", "crypto", "db", "pg", "mysql", "env", "json", "regex", "log", "http",
-        "csv", "term", "time", "jwt", "npc", "exec",
+        "csv", "term", "time", "jwt", "npc", "exec", "metrics", "testing",
     ];
 
     let mut items = Vec::new();
@@ -475,6 +570,16 @@ fn get_module_completions(params: &serde_json::Value) -> Vec<serde_json::Value>
             vec!["test", "find", "find_all", "replace", "split"],
         ),
         ("log", vec!["info", "warn", "error", "debug"]),
+        (
+            "metrics",
+            vec!["counter", "gauge", "histogram", "describe", "render"],
+        ),
+        (
+            "testing",
+            vec![
+                "client", "request", "get", "post", "put", "patch", "delete", "head",
+            ],
+        ),
         (
             "http",
             vec![
@@ -720,7 +825,7 @@ fn get_definition(uri: &str, line: usize, character: usize) -> serde_json::Value
 
     // Cross-file: check imported files for the symbol
     let imports = collect_imports(&text);
-    for (import_path, names) in &imports {
+    for (import_path, names, _) in &imports {
         // If named import, only follow if the word is in the name list
         if let Some(name_list) = names {
             if !name_list.iter().any(|n| n == &word) {
@@ -791,7 +896,7 @@ fn get_references(uri: &str, line: usize, character: usize) -> Vec<serde_json::V
     let mut searched = std::collections::HashSet::new();
     searched.insert(uri.to_string());
 
-    for (import_path, _) in &imports {
+    for (import_path, _, _) in &imports {
         if let Some(resolved) = resolve_import_for_lsp(import_path, uri) {
             let imported_uri = path_to_uri(&resolved);
             if searched.contains(&imported_uri) {
@@ -985,8 +1090,9 @@ fn collect_symbols_from_stmt(stmt: &Stmt, line: usize, symbols: &mut Vec<Documen
                 collect_symbols_from_stmt(&s.stmt, s.line.saturating_sub(1), symbols);
             }
         }
-        Stmt::Import { path, names } => {
-            // Show named imports as symbols; for wildcard imports, show the module path
+        Stmt::Import { path, names, alias } => {
+            // Show named imports and namespace aliases as symbols; for
+            // wildcard imports, show the module path
             if let Some(name_list) = names {
                 for n in name_list {
                     symbols.push(DocumentSymbolInfo {
@@ -997,7 +1103,7 @@ fn collect_symbols_from_stmt(stmt: &Stmt, line: usize, symbols: &mut Vec<Documen
                 }
             } else {
                 symbols.push(DocumentSymbolInfo {
-                    name: path.clone(),
+                    name: alias.clone().unwrap_or_else(|| path.clone()),
                     kind: 2,
                     line,
                 });
@@ -1038,6 +1144,8 @@ fn get_hover(uri: &str, line: usize, character: usize) -> serde_json::Value {
         ("starts_with", "fn starts_with(string, prefix) -> Bool — Check string prefix"),
         ("ends_with", "fn ends_with(string, suffix) -> Bool — Check string suffix"),
         ("fetch", "fn fetch(url) -> Object — HTTP GET request, returns {status, body, headers}"),
+        ("response", "fn response(status, body?, headers?) -> Response — Build a server handler response"),
+        ("redirect", "fn redirect(url, status?) -> Response — Build a 3xx redirect response"),
         ("uuid", "fn uuid() -> String — Generate a random UUID v4"),
         ("assert", "fn assert(condition) — Panic if condition is false"),
         ("assert_eq", "fn assert_eq(a, b) — Panic if a != b"),
@@ -1086,6 +1194,8 @@ fn get_hover(uri: &str, line: usize, character: usize) -> serde_json::Value {
         ("json", "module json — JSON: parse, stringify, pretty"),
         ("regex", "module regex — Regular expressions: test, find, find_all, replace, split"),
         ("log", "module log — Logging: info, warn, error, debug"),
+        ("metrics", "module metrics — Prometheus metrics: counter, gauge, histogram, describe, render"),
+        ("testing", "module testing — In-process server client: client, request, get, post, put, patch, delete, head"),
         ("http", "module http — HTTP client: get, post, put, delete, patch, head, download, crawl"),
         ("csv", "module csv — CSV: parse, stringify, read, write"),
         ("term", "module term — Terminal: red, green, blue, bold, table, hr, sparkline, bar, banner, box"),
@@ -1234,6 +1344,7 @@ fn hover_from_stmt(stmt: &Stmt, name: &str) -> Option<String> {
         Stmt::TypeDef {
             name: type_name,
             variants,
+            ..
         } => {
             if type_name == name {
                 let variants_str = variants
@@ -1414,8 +1525,9 @@ fn path_to_uri(path: &std::path::Path) -> String {
     format!("file://{}", path.display())
 }
 
-/// Extract import statements from source code.
-fn collect_imports(source: &str) -> Vec<(String, Option<Vec<String>>)> {
+/// Extract import statements from source code as (path, names, alias).
+#[allow(clippy::type_complexity)]
+fn collect_imports(source: &str) -> Vec<(String, Option<Vec<String>>, Option<String>)> {
     let mut lexer = crate::lexer::Lexer::new(source);
     let tokens = match lexer.tokenize() {
         Ok(t) => t,
@@ -1428,8 +1540,8 @@ fn collect_imports(source: &str) -> Vec<(String, Option<Vec<String>>)> {
     };
     let mut imports = Vec::new();
     for spanned in &program.statements {
-        if let Stmt::Import { path, names } = &spanned.stmt {
-            imports.push((path.clone(), names.clone()));
+        if let Stmt::Import { path, names, alias } = &spanned.stmt {
+            imports.push((path.clone(), names.clone(), alias.clone()));
         }
     }
     imports
@@ -1443,7 +1555,8 @@ fn resolve_import_for_lsp(import_path: &str, current_uri: &str) -> Option<std::p
 }
 
 /// Collect top-level (exported) symbols from a file — only FnDef, Let, StructDef,
-/// TypeDef, InterfaceDef at the top level (not nested inside functions).
+/// TypeDef, InterfaceDef at the top level (not nested inside functions), and
+/// only the `pub` ones once the file marks anything `pub`.
 fn collect_exported_symbols(source: &str) -> Vec<DocumentSymbolInfo> {
     let mut lexer = crate::lexer::Lexer::new(source);
     let tokens = match lexer.tokenize() {
@@ -1457,6 +1570,9 @@ fn collect_exported_symbols(source: &str) -> Vec<DocumentSymbolInfo> {
     };
     let mut symbols = Vec::new();
     for spanned in &program.statements {
+        if !program.exports(&spanned.stmt) {
+            continue;
+        }
         let line = spanned.line.saturating_sub(1);
         match &spanned.stmt {
             Stmt::FnDef { name, .. } => symbols.push(DocumentSymbolInfo {
@@ -1775,6 +1891,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn collect_imports_keeps_namespace_alias() {
+        let imports = collect_imports("import \"utils\" as u");
+        assert_eq!(imports[0].0, "utils");
+        assert_eq!(imports[0].2.as_deref(), Some("u"));
+    }
+
+    #[test]
+    fn collect_exported_symbols_respects_pub() {
+        let symbols = collect_exported_symbols("fn hidden() {}\npub fn shown() {}\n");
+        let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["shown"]);
+    }
+
+    #[test]
+    fn import_diagnostics_report_private_names_and_collisions() {
+        let dir = std::env::temp_dir().join("forge-lsp-test-visibility");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("lib.fg"), "fn secret() {}\npub fn open() {}\n").unwrap();
+        std::fs::write(dir.join("other.fg"), "fn open() {}\n").unwrap();
+        let uri = format!("file://{}", dir.join("main.fg").display());
+
+        let source = "import { secret } from \"lib\"\nimport \"lib\"\nimport \"other\"\n";
+        let diags = get_import_diagnostics(&uri, source);
+        let messages: Vec<&str> = diags
+            .iter()
+            .filter_map(|d| d.get("message").and_then(|m| m.as_str()))
+            .collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(messages[0].contains("does not export 'secret'"));
+        assert!(messages[1].contains("collides with 'open'"));
+        assert_eq!(
+            diags[1]
+                .pointer("/range/start/line")
+                .and_then(|v| v.as_u64()),
+            Some(2)
+        );
+
+        let aliased = "import \"lib\" as lib\nimport \"other\" as other\n";
+        assert!(get_import_diagnostics(&uri, aliased).is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn collect_exported_symbols_finds_top_level_only() {
         let symbols = collect_exported_symbols(
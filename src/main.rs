@@ -76,9 +76,10 @@ struct Cli {
     #[arg(long = "vm")]
     use_vm: bool,
 
-    /// Use the tree-walking interpreter instead of the VM. Required for
-    /// decorator-driven HTTP servers (@server, @get, etc.). The VM
-    /// auto-falls back to the interpreter when decorators are detected.
+    /// Use the tree-walking interpreter instead of the VM. The VM serves
+    /// decorator-driven HTTP servers itself; it auto-falls back to the
+    /// interpreter for constructs it does not support yet, such as
+    /// routes from imported modules.
     #[arg(long = "interp")]
     use_interp: bool,
 
@@ -201,6 +202,14 @@ enum Command {
         /// Files or directories (defaults to current directory)
         paths: Vec<PathBuf>,
     },
+    /// Print the OpenAPI 3.1 document for a decorated server
+    Openapi {
+        /// Path to the .fg file declaring @server and its routes
+        file: PathBuf,
+        /// Write the document to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -267,7 +276,7 @@ async fn main() {
                 }
             };
             if file.extension().map(|e| e == "fgc").unwrap_or(false) {
-                run_bytecode_file(&file, profile);
+                run_bytecode_file(&file, profile).await;
                 return;
             }
             let path_str = file.display().to_string();
@@ -441,6 +450,9 @@ async fn main() {
         Some(Command::Doc { paths }) => {
             doc::generate_docs(&paths);
         }
+        Some(Command::Openapi { file, output }) => {
+            write_openapi(&file, output.as_deref());
+        }
         None => {
             repl::run_repl();
         }
@@ -483,6 +495,48 @@ fn prepare_program(
     Ok((program, warnings))
 }
 
+fn write_openapi(file: &std::path::Path, output: Option<&std::path::Path>) {
+    let path_str = file.display().to_string();
+    let source = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                errors::format_simple_error(&format!("could not read '{}': {}", path_str, e))
+            );
+            process::exit(1);
+        }
+    };
+    let program = match prepare_program(&source, false) {
+        Ok((program, _)) => program,
+        Err(err) => print_frontend_error(&source, &path_str, err),
+    };
+    let Some(document) = runtime::metadata::openapi_document(&program, file.parent()) else {
+        eprintln!(
+            "{}",
+            errors::format_simple_error(&format!("'{}' has no @server declaration", path_str))
+        );
+        process::exit(1);
+    };
+    let rendered = serde_json::to_string_pretty(&document).unwrap_or_default();
+    match output {
+        Some(out) => {
+            if let Err(e) = fs::write(out, rendered + "\n") {
+                eprintln!(
+                    "{}",
+                    errors::format_simple_error(&format!(
+                        "could not write '{}': {}",
+                        out.display(),
+                        e
+                    ))
+                );
+                process::exit(1);
+            }
+        }
+        None => println!("{}", rendered),
+    }
+}
+
 fn print_frontend_error(source: &str, filename: &str, err: FrontendError) -> ! {
     match err {
         FrontendError::Lex { line, col, message } | FrontendError::Parse { line, col, message } => {
@@ -609,6 +663,9 @@ fn collect_vm_incompatible_stmt(stmt: &Stmt, issues: &mut BTreeSet<&'static str>
         }
         Stmt::Match { arms, .. } => {
             for arm in arms {
+                if let Some(guard) = &arm.guard {
+                    collect_vm_incompatible_expr(guard, issues);
+                }
                 for s in &arm.body {
                     collect_vm_incompatible_stmt(&s.stmt, issues);
                 }
@@ -664,6 +721,11 @@ fn collect_vm_incompatible_expr(expr: &Expr, issues: &mut BTreeSet<&'static str>
             collect_vm_incompatible_expr(object, issues);
             collect_vm_incompatible_expr(index, issues);
         }
+        Expr::Range { start, end, .. } => {
+            for bound in [start, end].into_iter().flatten() {
+                collect_vm_incompatible_expr(bound, issues);
+            }
+        }
         Expr::Call { function, args } => {
             collect_vm_incompatible_expr(function, issues);
             for arg in args {
@@ -739,8 +801,34 @@ fn collect_vm_incompatible_expr(expr: &Expr, issues: &mut BTreeSet<&'static str>
 
 fn vm_incompatibilities(program: &Program) -> Vec<&'static str> {
     let mut issues = BTreeSet::new();
+    let serves = program
+        .statements
+        .iter()
+        .any(|s| matches!(&s.stmt, Stmt::DecoratorStmt(dec) if dec.name == "server"));
     for stmt in &program.statements {
-        collect_vm_incompatible_stmt(&stmt.stmt, &mut issues);
+        // Top-level decorators compile into the chunk's declarations, which
+        // the host runtime serves from; route modules it would load from
+        // other files still need the interpreter. Helpers imported whole
+        // are compiled in like any other import.
+        match &stmt.stmt {
+            Stmt::DecoratorStmt(dec) if dec.name == "mount" => {
+                issues.insert("routes from imported modules");
+            }
+            Stmt::DecoratorStmt(_) => {}
+            Stmt::Import {
+                path,
+                names: None,
+                alias: None,
+            } if serves && runtime::metadata::import_declares_routes(path, None) => {
+                issues.insert("routes from imported modules");
+            }
+            Stmt::FnDef { body, .. } => {
+                for s in body {
+                    collect_vm_incompatible_stmt(&s.stmt, &mut issues);
+                }
+            }
+            other => collect_vm_incompatible_stmt(other, &mut issues),
+        }
     }
     issues.into_iter().collect()
 }
@@ -766,7 +854,7 @@ async fn run_source(source: &str, filename: &str, use_vm: bool, profile: bool, s
     };
     emit_type_warnings(&warnings);
 
-    // Auto-fallback: if VM is requested but program uses decorators, fall back to interpreter
+    // Auto-fallback: if VM is requested but the program uses constructs it does not support, fall back to interpreter
     let effective_vm = if use_vm {
         match ensure_vm_compatible(&program, "VM") {
             Ok(()) => true,
@@ -780,23 +868,14 @@ async fn run_source(source: &str, filename: &str, use_vm: bool, profile: bool, s
     };
 
     if effective_vm {
-        if profile {
-            match vm::run_with_profiling(&program) {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!("{}", errors::format_simple_error(&e.to_string()));
-                    process::exit(1);
-                }
-            }
-        } else {
-            match vm::run(&program) {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!("{}", errors::format_simple_error(&e.to_string()));
-                    process::exit(1);
-                }
+        let chunk = match vm::compiler::compile(&program) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                eprintln!("{}", errors::format_simple_error(&e.message));
+                process::exit(1);
             }
-        }
+        };
+        run_chunk(&chunk, Some(std::path::Path::new(filename)), profile).await;
     } else {
         let mut interpreter = Interpreter::new();
         interpreter.source = Some(source.to_string());
@@ -829,7 +908,12 @@ async fn run_source(source: &str, filename: &str, use_vm: bool, profile: bool, s
             }
         }
 
-        let runtime_plan = runtime::metadata::extract_runtime_plan(&program);
+        let base_dir = interpreter
+            .source_file
+            .as_ref()
+            .and_then(|file| file.parent().map(|dir| dir.to_path_buf()));
+        let runtime_plan =
+            runtime::metadata::extract_runtime_plan_in(&program, base_dir.as_deref());
         if let Err(e) = runtime::host::launch(interpreter, &runtime_plan).await {
             eprintln!("{}", errors::format_simple_error(&e.message));
             process::exit(1);
@@ -856,6 +940,15 @@ fn run_jit(source: &str, filename: &str, strict: bool) {
             process::exit(1);
         }
     };
+    if !chunk.declarations.is_empty() {
+        eprintln!(
+            "{}",
+            errors::format_simple_error(
+                "--jit does not serve decorator-driven programs\n  hint: use --vm to serve them on the bytecode VM"
+            )
+        );
+        process::exit(1);
+    }
 
     let mut jit = match vm::jit::jit_module::JitCompiler::new() {
         Ok(j) => j,
@@ -1038,9 +1131,9 @@ fn compile_to_native_aot(source: &str, filename: &str, file_path: &PathBuf, stri
     emit_type_warnings(&warnings);
 
     if let Err(message) = ensure_vm_compatible(&program, "AOT build") {
-        let message = if message.contains("decorator-driven runtime features") {
+        let message = if message.contains("routes from imported modules") {
             format!(
-                "{message}\n  hint: decorator-driven servers are not bytecode AOT yet; use `forge build --native` for a standalone source-runtime server binary"
+                "{message}\n  hint: use `forge build --native` for a standalone source-runtime server binary"
             )
         } else {
             message
@@ -1088,7 +1181,7 @@ fn compile_to_native_aot(source: &str, filename: &str, file_path: &PathBuf, stri
     }
 }
 
-fn run_bytecode_file(file_path: &PathBuf, profile: bool) {
+async fn run_bytecode_file(file_path: &PathBuf, profile: bool) {
     let bytes = match fs::read(file_path) {
         Ok(b) => b,
         Err(e) => {
@@ -1119,12 +1212,33 @@ fn run_bytecode_file(file_path: &PathBuf, profile: bool) {
         }
     };
 
+    run_chunk(&chunk, Some(file_path), profile).await;
+}
+
+/// Execute a compiled program, loaded from `source_file`, serving it when
+/// it declares a server or lifecycle hooks.
+async fn run_chunk(
+    chunk: &vm::bytecode::Chunk,
+    source_file: Option<&std::path::Path>,
+    profile: bool,
+) {
+    if !chunk.declarations.is_empty() {
+        if let Err(e) = runtime::host::serve_compiled(chunk).await {
+            eprintln!("{}", errors::format_simple_error(&e.message));
+            process::exit(1);
+        }
+        return;
+    }
+
     let mut vm = if profile {
         vm::machine::VM::with_profiling()
     } else {
         vm::machine::VM::new()
     };
-    match vm.execute(&chunk) {
+    vm.source_file = source_file
+        .filter(|path| path.exists())
+        .map(std::path::Path::to_path_buf);
+    match vm.execute(chunk) {
         Ok(_) => {}
         Err(e) => {
             // Use the full Display impl so the stack trace (function +
@@ -1159,6 +1273,13 @@ mod tests {
         assert!(Cli::try_parse_from(["forge", "build", "--aot", "--allow-run", "app.fg"]).is_err());
     }
 
+    #[test]
+    fn openapi_takes_a_file_and_optional_output() {
+        assert!(Cli::try_parse_from(["forge", "openapi", "app.fg"]).is_ok());
+        assert!(Cli::try_parse_from(["forge", "openapi", "app.fg", "-o", "api.json"]).is_ok());
+        assert!(Cli::try_parse_from(["forge", "openapi"]).is_err());
+    }
+
     #[test]
     fn parity_corpus_supported_cases() {
         let cases = crate::testing::parity::load_supported_cases();
@@ -1426,7 +1547,26 @@ mod tests {
     }
 
     #[test]
-    fn vm_incompatibilities_reject_server_route_decorators() {
+    fn vm_incompatibilities_allow_servers_importing_helpers() {
+        let source = r#"
+        @server(port: 8080)
+        import "tests/parity/modules/import_helper.fg"
+        @get("/answer")
+        fn show_answer() { return helper() }
+        "#;
+        let (program, _) = prepare_program(source, false).expect("program should parse");
+        assert!(vm_incompatibilities(&program).is_empty());
+
+        let source = source.replace("import_helper", "route_helper");
+        let (program, _) = prepare_program(&source, false).expect("program should parse");
+        assert_eq!(
+            vm_incompatibilities(&program),
+            vec!["routes from imported modules"]
+        );
+    }
+
+    #[test]
+    fn vm_incompatibilities_allow_top_level_server_decorators() {
         let source = r#"
         @server(port: 8080)
         @get("/hello")
@@ -1434,6 +1574,25 @@ mod tests {
         "#;
 
         let (program, _) = prepare_program(source, false).expect("program should parse");
-        assert!(vm_incompatibilities(&program).contains(&"decorator-driven runtime features"));
+        assert!(vm_incompatibilities(&program).is_empty());
+
+        let source = r#"
+        @server(port: 8080)
+        @mount("/admin", "admin_routes")
+        @sse("/events")
+        fn events() { emit 1 }
+        fn outer() {
+            @get("/nested")
+            fn nested() { return 1 }
+        }
+        "#;
+        let (program, _) = prepare_program(source, false).expect("program should parse");
+        assert_eq!(
+            vm_incompatibilities(&program),
+            vec![
+                "decorator-driven runtime features",
+                "routes from imported modules"
+            ]
+        );
     }
 }
@@ -29,6 +29,46 @@ pub struct Program {
     pub statements: Vec<SpannedStmt>,
 }
 
+impl Program {
+    /// Whether any top-level declaration is marked `pub`. A file without
+    /// `pub` exports every top-level declaration, as before visibility
+    /// existed; once one is marked, only `pub` ones are exported.
+    pub fn declares_visibility(&self) -> bool {
+        self.statements.iter().any(|s| s.stmt.is_pub())
+    }
+
+    /// Whether importing this file brings `stmt` into the importer.
+    /// Decorated functions stay visible so servers and schedules that
+    /// import a file can still find its handlers.
+    pub fn exports(&self, stmt: &Stmt) -> bool {
+        if !self.declares_visibility() || stmt.is_pub() {
+            return true;
+        }
+        matches!(stmt, Stmt::FnDef { decorators, .. } if !decorators.is_empty())
+    }
+
+    /// Names a file exports: its exported functions, bindings, structs and
+    /// the variants of its exported types.
+    pub fn export_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for spanned in &self.statements {
+            if !self.exports(&spanned.stmt) {
+                continue;
+            }
+            match &spanned.stmt {
+                Stmt::FnDef { name, .. }
+                | Stmt::Let { name, .. }
+                | Stmt::StructDef { name, .. } => names.push(name.clone()),
+                Stmt::TypeDef { variants, .. } => {
+                    names.extend(variants.iter().map(|v| v.name.clone()))
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+}
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub enum Stmt {
@@ -37,6 +77,7 @@ pub enum Stmt {
         mutable: bool,
         type_ann: Option<TypeAnn>,
         value: Expr,
+        is_pub: bool,
     },
     Assign {
         target: Expr,
@@ -50,6 +91,7 @@ pub enum Stmt {
         body: Vec<SpannedStmt>,
         decorators: Vec<Decorator>,
         is_async: bool,
+        is_pub: bool,
     },
     Destructure {
         pattern: DestructurePattern,
@@ -59,6 +101,7 @@ pub enum Stmt {
         name: String,
         type_params: Vec<String>,
         fields: Vec<FieldDef>,
+        is_pub: bool,
     },
     Return(Option<Expr>),
     If {
@@ -95,6 +138,7 @@ pub enum Stmt {
     TypeDef {
         name: String,
         variants: Vec<Variant>,
+        is_pub: bool,
     },
     InterfaceDef {
         name: String,
@@ -111,9 +155,11 @@ pub enum Stmt {
         catch_var: String,
         catch_body: Vec<SpannedStmt>,
     },
+    /// `import "m"`, `import { a, b } from "m"` or `import "m" as m`.
     Import {
         path: String,
         names: Option<Vec<String>>,
+        alias: Option<String>,
     },
     YieldStmt(Expr),
     /// when subject { < val -> expr, else -> expr }
@@ -170,6 +216,19 @@ pub enum Stmt {
     Expression(Expr),
 }
 
+impl Stmt {
+    /// Whether this declaration is marked `pub`.
+    pub fn is_pub(&self) -> bool {
+        matches!(
+            self,
+            Stmt::Let { is_pub: true, .. }
+                | Stmt::FnDef { is_pub: true, .. }
+                | Stmt::StructDef { is_pub: true, .. }
+                | Stmt::TypeDef { is_pub: true, .. }
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WhenArm {
     pub op: Option<BinOp>,
@@ -256,6 +315,12 @@ pub enum Expr {
         fields: Vec<(String, Expr)>,
     },
     Block(Vec<SpannedStmt>),
+    /// `a..b` or `a..=b`. A bound is only left open inside `xs[...]`.
+    Range {
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+        inclusive: bool,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -271,6 +336,15 @@ pub enum BinOp {
     Mul,
     Div,
     Mod,
+    /// `**`: exponentiation, right-associative.
+    Pow,
+    /// `~/`: division rounded toward negative infinity.
+    FloorDiv,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     Eq,
     NotEq,
     Lt,
@@ -281,6 +355,34 @@ pub enum BinOp {
     Or,
 }
 
+impl BinOp {
+    /// The operator as written in source.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Pow => "**",
+            BinOp::FloorDiv => "~/",
+            BinOp::BitAnd => "&",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "^",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::Eq => "==",
+            BinOp::NotEq => "!=",
+            BinOp::Lt => "<",
+            BinOp::Gt => ">",
+            BinOp::LtEq => "<=",
+            BinOp::GtEq => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOp {
     Neg,
@@ -330,6 +432,9 @@ pub enum DecoratorArg {
 #[derive(Debug, Clone)]
 pub struct MatchArm {
     pub pattern: Pattern,
+    /// `pattern if cond =>` — the arm only runs when `cond` is truthy with
+    /// the pattern's bindings in scope.
+    pub guard: Option<Expr>,
     pub body: Vec<SpannedStmt>,
 }
 
@@ -371,5 +476,88 @@ pub enum Pattern {
     Wildcard,
     Literal(Expr),
     Binding(String),
-    Constructor { name: String, fields: Vec<Pattern> },
+    Constructor {
+        name: String,
+        fields: Vec<Pattern>,
+    },
+    /// `A | B | C` — matches when any alternative does.
+    Or(Vec<Pattern>),
+    /// `1..=9` or `"a".."n"`; the end is exclusive unless `inclusive`.
+    /// Either bound may be left open, as in `..0` or `100..`.
+    Range {
+        start: Option<Expr>,
+        end: Option<Expr>,
+        inclusive: bool,
+    },
+    /// `{ x, y: 0 }` or `Point { x, y }`. Fields not listed are ignored;
+    /// `type_name` also requires the object's `__type__` to match.
+    Object {
+        type_name: Option<String>,
+        fields: Vec<(String, Pattern)>,
+    },
+    /// `[first, second]` matches exactly two items; `[first, ...rest]`
+    /// matches one or more and binds the remainder (`...` alone skips it).
+    Array {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+    },
+}
+
+impl Pattern {
+    /// The alternatives of an or-pattern, or the pattern itself.
+    pub fn alternatives(&self) -> Vec<&Pattern> {
+        match self {
+            Pattern::Or(alternatives) => {
+                alternatives.iter().flat_map(|p| p.alternatives()).collect()
+            }
+            pattern => vec![pattern],
+        }
+    }
+
+    /// Whether the pattern matches every value: `_`, a plain binding, or an
+    /// or-pattern with such an alternative.
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Binding(_) => true,
+            Pattern::Or(alternatives) => alternatives.iter().any(Pattern::is_irrefutable),
+            _ => false,
+        }
+    }
+
+    /// Names the pattern binds, in first-seen order. For an or-pattern these
+    /// are the union over its alternatives.
+    pub fn bindings(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.collect_bindings(&mut names);
+        names
+    }
+
+    fn collect_bindings(&self, names: &mut Vec<String>) {
+        match self {
+            Pattern::Binding(name) => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            Pattern::Constructor { fields: items, .. } | Pattern::Or(items) => {
+                for item in items {
+                    item.collect_bindings(names);
+                }
+            }
+            Pattern::Object { fields, .. } => {
+                for (_, field) in fields {
+                    field.collect_bindings(names);
+                }
+            }
+            Pattern::Array { items, rest } => {
+                for item in items {
+                    item.collect_bindings(names);
+                }
+                if let Some(rest) = rest {
+                    rest.collect_bindings(names);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => {}
+        }
+    }
 }
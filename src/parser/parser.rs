@@ -8,11 +8,18 @@ use crate::lexer::Lexer;
 pub struct Parser {
     tokens: Vec<Spanned>,
     pos: usize,
+    /// Set while parsing the steps of a `>>` pipe chain, where `>>`
+    /// separates steps instead of shifting.
+    in_pipe_chain: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Spanned>) -> Self {
-        Self { tokens, pos: 0 }
+        Self {
+            tokens,
+            pos: 0,
+            in_pipe_chain: false,
+        }
     }
 
     /// Return (line, col) of the current token position.
@@ -81,6 +88,7 @@ impl Parser {
             Token::Wait => self.parse_wait(),
             Token::TryKw => self.parse_try_catch(),
             Token::Import => self.parse_import(),
+            Token::Pub => self.parse_pub(),
             Token::Async | Token::ForgeKw => self.parse_fn_def(Vec::new()),
             Token::Yield | Token::Emit => self.parse_yield(),
             Token::Unpack => self.parse_unpack(),
@@ -145,6 +153,7 @@ impl Parser {
             mutable,
             type_ann,
             value,
+            is_pub: false,
         })
     }
 
@@ -168,6 +177,7 @@ impl Parser {
             mutable,
             type_ann: None,
             value,
+            is_pub: false,
         })
     }
 
@@ -181,6 +191,39 @@ impl Parser {
         Ok(Stmt::Assign { target, value })
     }
 
+    /// Parses: pub fn / pub let / pub struct / pub type, marking the
+    /// declaration as exported from its module.
+    fn parse_pub(&mut self) -> Result<Stmt, ParseError> {
+        self.expect(Token::Pub)?;
+        let mut stmt = match self.current_token() {
+            Token::Let => self.parse_let()?,
+            Token::Set => self.parse_set()?,
+            Token::Fn | Token::Define | Token::Async | Token::ForgeKw => {
+                self.parse_fn_def(Vec::new())?
+            }
+            Token::Type => self.parse_type_def()?,
+            Token::Struct | Token::Thing => self.parse_struct_def()?,
+            Token::At => self.parse_decorator_or_fn()?,
+            _ => {
+                return Err(
+                    self.error("'pub' must be followed by a fn, let, struct or type declaration")
+                )
+            }
+        };
+        match &mut stmt {
+            Stmt::Let { is_pub, .. }
+            | Stmt::FnDef { is_pub, .. }
+            | Stmt::StructDef { is_pub, .. }
+            | Stmt::TypeDef { is_pub, .. } => *is_pub = true,
+            _ => {
+                return Err(
+                    self.error("'pub' must be followed by a fn, let, struct or type declaration")
+                )
+            }
+        }
+        Ok(stmt)
+    }
+
     /// Parses: type Name = Variant(fields) | Variant(fields) | ...
     fn parse_type_def(&mut self) -> Result<Stmt, ParseError> {
         self.expect(Token::Type)?;
@@ -223,7 +266,11 @@ impl Parser {
             }
         }
 
-        Ok(Stmt::TypeDef { name, variants })
+        Ok(Stmt::TypeDef {
+            name,
+            variants,
+            is_pub: false,
+        })
     }
 
     /// Parses: interface Name { fn method(params) -> Type, ... }
@@ -379,6 +426,7 @@ impl Parser {
             mutable: false,
             type_ann: None,
             value: fetch_call,
+            is_pub: false,
         })
     }
 
@@ -411,7 +459,7 @@ impl Parser {
         })
     }
 
-    /// Parses: import "path" / import { name, name } from "path"
+    /// Parses: import "path" / import "path" as name / import { name, name } from "path"
     fn parse_import(&mut self) -> Result<Stmt, ParseError> {
         self.expect(Token::Import)?;
 
@@ -437,6 +485,7 @@ impl Parser {
             Ok(Stmt::Import {
                 path,
                 names: Some(names),
+                alias: None,
             })
         } else {
             // import "path" or import name
@@ -455,7 +504,18 @@ impl Parser {
                     ));
                 }
             };
-            Ok(Stmt::Import { path, names: None })
+            // Contextual "as" — namespace import: import "utils" as u
+            let alias = if matches!(self.current_token(), Token::Ident(ref id) if id == "as") {
+                self.advance();
+                Some(self.expect_ident()?)
+            } else {
+                None
+            };
+            Ok(Stmt::Import {
+                path,
+                names: None,
+                alias,
+            })
         }
     }
 
@@ -917,6 +977,7 @@ impl Parser {
             body,
             decorators,
             is_async,
+            is_pub: false,
         })
     }
 
@@ -979,6 +1040,7 @@ impl Parser {
             name,
             type_params,
             fields,
+            is_pub: false,
         })
     }
 
@@ -1038,6 +1100,12 @@ impl Parser {
         let mut arms = Vec::new();
         while !self.check(&Token::RBrace) {
             let pattern = self.parse_pattern()?;
+            let guard = if self.check(&Token::If) {
+                self.advance();
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
             self.expect(Token::FatArrow)?;
 
             let body = if self.check(&Token::LBrace) {
@@ -1052,7 +1120,11 @@ impl Parser {
                 vec![SpannedStmt::new(stmt, line, col)]
             };
 
-            arms.push(MatchArm { pattern, body });
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
             self.skip_newlines();
             if self.check(&Token::Comma) {
                 self.advance();
@@ -1065,6 +1137,20 @@ impl Parser {
     }
 
     fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let first = self.parse_pattern_atom()?;
+        if !self.check(&Token::Bar) {
+            return Ok(first);
+        }
+        let mut alternatives = vec![first];
+        while self.check(&Token::Bar) {
+            self.advance();
+            self.skip_newlines();
+            alternatives.push(self.parse_pattern_atom()?);
+        }
+        Ok(Pattern::Or(alternatives))
+    }
+
+    fn parse_pattern_atom(&mut self) -> Result<Pattern, ParseError> {
         match self.current_token() {
             Token::Ident(ref name) if name == "_" => {
                 self.advance();
@@ -1086,25 +1172,22 @@ impl Parser {
                     }
                     self.expect(Token::RParen)?;
                     Ok(Pattern::Constructor { name, fields })
+                } else if self.check(&Token::LBrace) {
+                    self.parse_object_pattern(Some(name))
                 } else {
                     Ok(Pattern::Binding(name))
                 }
             }
-            Token::Int(n) => {
-                let n = n;
-                self.advance();
-                Ok(Pattern::Literal(Expr::Int(n)))
-            }
-            Token::Float(n) => {
-                let n = n;
-                self.advance();
-                Ok(Pattern::Literal(Expr::Float(n)))
-            }
-            Token::StringLit(ref s) => {
-                let s = s.clone();
-                self.advance();
-                Ok(Pattern::Literal(Expr::StringLit(s)))
+            Token::LBrace => self.parse_object_pattern(None),
+            Token::LBracket => self.parse_array_pattern(),
+            Token::Int(_) | Token::Float(_) | Token::Minus | Token::StringLit(_) => {
+                let start = self.parse_pattern_literal()?;
+                if self.range_op().is_none() {
+                    return Ok(Pattern::Literal(start));
+                }
+                self.parse_range_pattern(Some(start))
             }
+            Token::DotDot | Token::DotDotEq => self.parse_range_pattern(None),
             Token::True => {
                 self.advance();
                 Ok(Pattern::Literal(Expr::Bool(true)))
@@ -1117,6 +1200,110 @@ impl Parser {
         }
     }
 
+    /// The rest of a range pattern from its `..` or `..=`. The end may only
+    /// be left off an exclusive range with a start, as in `100..`.
+    fn parse_range_pattern(&mut self, start: Option<Expr>) -> Result<Pattern, ParseError> {
+        let inclusive = self.range_op() == Some(true);
+        self.advance();
+        let end = match self.current_token() {
+            Token::Int(_) | Token::Float(_) | Token::Minus | Token::StringLit(_) => {
+                Some(self.parse_pattern_literal()?)
+            }
+            _ if inclusive || start.is_none() => {
+                return Err(self.error("expected the end of the range pattern"))
+            }
+            _ => None,
+        };
+        Ok(Pattern::Range {
+            start,
+            end,
+            inclusive,
+        })
+    }
+
+    /// A number (optionally negated) or string in a literal or range pattern.
+    fn parse_pattern_literal(&mut self) -> Result<Expr, ParseError> {
+        let negate = self.check(&Token::Minus);
+        if negate {
+            self.advance();
+        }
+        let expr = match self.current_token() {
+            Token::Int(n) => Expr::Int(if negate { -n } else { n }),
+            Token::Float(n) => Expr::Float(if negate { -n } else { n }),
+            Token::StringLit(ref s) if !negate => Expr::StringLit(s.clone()),
+            _ => return Err(self.error("expected a number or string in pattern")),
+        };
+        self.advance();
+        Ok(expr)
+    }
+
+    fn parse_object_pattern(&mut self, type_name: Option<String>) -> Result<Pattern, ParseError> {
+        self.expect(Token::LBrace)?;
+        self.skip_newlines();
+        let mut fields = Vec::new();
+        while !self.check(&Token::RBrace) {
+            let key = match self.current_token() {
+                Token::StringLit(ref s) => {
+                    let s = s.clone();
+                    self.advance();
+                    s
+                }
+                _ => self.expect_ident()?,
+            };
+            let pattern = if self.check(&Token::Colon) {
+                self.advance();
+                self.parse_pattern()?
+            } else {
+                Pattern::Binding(key.clone())
+            };
+            fields.push((key, pattern));
+            self.skip_newlines();
+            if self.check(&Token::Comma) {
+                self.advance();
+            }
+            self.skip_newlines();
+        }
+        self.expect(Token::RBrace)?;
+        Ok(Pattern::Object { type_name, fields })
+    }
+
+    fn parse_array_pattern(&mut self) -> Result<Pattern, ParseError> {
+        self.expect(Token::LBracket)?;
+        self.skip_newlines();
+        let mut items = Vec::new();
+        let mut rest = None;
+        while !self.check(&Token::RBracket) {
+            if rest.is_some() {
+                return Err(self.error("'...' must be the last item in an array pattern"));
+            }
+            if self.check(&Token::DotDotDot) {
+                self.advance();
+                let remainder = match self.current_token() {
+                    Token::Ident(ref name) => {
+                        let name = name.clone();
+                        self.advance();
+                        if name == "_" {
+                            Pattern::Wildcard
+                        } else {
+                            Pattern::Binding(name)
+                        }
+                    }
+                    _ => Pattern::Wildcard,
+                };
+                rest = Some(Box::new(remainder));
+            } else {
+                items.push(self.parse_pattern()?);
+            }
+            self.skip_newlines();
+            if self.check(&Token::Comma) {
+                self.advance();
+            }
+            self.skip_newlines();
+        }
+        self.expect(Token::RBracket)?;
+        Ok(Pattern::Array { items, rest })
+    }
+
     fn parse_for(&mut self) -> Result<Stmt, ParseError> {
         self.expect(Token::For)?;
         if self.check(&Token::Each) {
@@ -1172,21 +1359,25 @@ impl Parser {
         let decorator = self.parse_decorator()?;
         self.skip_newlines();
 
-        // @server is always a standalone config decorator
-        if decorator.name == "server" {
+        // @server, @static, @group and @mount are always standalone
+        // config decorators
+        if matches!(
+            decorator.name.as_str(),
+            "server" | "static" | "group" | "mount"
+        ) {
             return Ok(Stmt::DecoratorStmt(decorator));
         }
 
-        if self.check(&Token::Fn) || self.check(&Token::Define) {
-            self.parse_fn_def(vec![decorator])
+        if self.check(&Token::Fn) || self.check(&Token::Define) || self.check(&Token::Pub) {
+            self.parse_decorated_fn(vec![decorator])
         } else if self.check(&Token::At) {
             let mut decorators = vec![decorator];
             while self.check(&Token::At) {
                 decorators.push(self.parse_decorator()?);
                 self.skip_newlines();
             }
-            if self.check(&Token::Fn) || self.check(&Token::Define) {
-                self.parse_fn_def(decorators)
+            if self.check(&Token::Fn) || self.check(&Token::Define) || self.check(&Token::Pub) {
+                self.parse_decorated_fn(decorators)
             } else {
                 Ok(Stmt::DecoratorStmt(decorators.pop().ok_or_else(|| {
                     self.error("internal: empty decorator list")
@@ -1198,6 +1389,19 @@ impl Parser {
         }
     }
 
+    /// Parses the function after its decorators, allowing `pub` between them.
+    fn parse_decorated_fn(&mut self, decorators: Vec<Decorator>) -> Result<Stmt, ParseError> {
+        let exported = self.check(&Token::Pub);
+        if exported {
+            self.advance();
+        }
+        let mut stmt = self.parse_fn_def(decorators)?;
+        if let Stmt::FnDef { is_pub, .. } = &mut stmt {
+            *is_pub = exported;
+        }
+        Ok(stmt)
+    }
+
     fn parse_decorator(&mut self) -> Result<Decorator, ParseError> {
         self.expect(Token::At)?;
         let name = self.expect_ident()?;
@@ -1249,20 +1453,7 @@ impl Parser {
                 target: expr,
                 value,
             })
-        } else if self.check(&Token::PlusEq)
-            || self.check(&Token::MinusEq)
-            || self.check(&Token::StarEq)
-            || self.check(&Token::SlashEq)
-            || self.check(&Token::PercentEq)
-        {
-            let op = match self.current_token() {
-                Token::PlusEq => BinOp::Add,
-                Token::MinusEq => BinOp::Sub,
-                Token::StarEq => BinOp::Mul,
-                Token::SlashEq => BinOp::Div,
-                Token::PercentEq => BinOp::Mod,
-                _ => unreachable!(),
-            };
+        } else if let Some(op) = self.compound_assign_op() {
             self.advance();
             let rhs = self.parse_expr()?;
             Ok(Stmt::Assign {
@@ -1278,6 +1469,26 @@ impl Parser {
         }
     }
 
+    /// The operator a compound assignment token such as `+=` applies.
+    fn compound_assign_op(&self) -> Option<BinOp> {
+        let op = match self.current_token() {
+            Token::PlusEq => BinOp::Add,
+            Token::MinusEq => BinOp::Sub,
+            Token::StarEq => BinOp::Mul,
+            Token::SlashEq => BinOp::Div,
+            Token::PercentEq => BinOp::Mod,
+            Token::StarStarEq => BinOp::Pow,
+            Token::TildeSlashEq => BinOp::FloorDiv,
+            Token::AmpersandEq => BinOp::BitAnd,
+            Token::BarEq => BinOp::BitOr,
+            Token::CaretEq => BinOp::BitXor,
+            Token::ShlEq => BinOp::Shl,
+            Token::ShrEq => BinOp::Shr,
+            _ => return None,
+        };
+        Some(op)
+    }
+
     // ========== Expression Parsing (Pratt) ==========
 
     fn parse_expr(&mut self) -> Result<Expr, ParseError> {
@@ -1293,10 +1504,18 @@ impl Parser {
 
         if self.check(&Token::PipeRight) {
             let mut steps = Vec::new();
+            let outer = std::mem::replace(&mut self.in_pipe_chain, true);
             while self.check(&Token::PipeRight) {
                 self.advance();
-                steps.push(self.parse_pipe_step()?);
+                match self.parse_pipe_step() {
+                    Ok(step) => steps.push(step),
+                    Err(e) => {
+                        self.in_pipe_chain = outer;
+                        return Err(e);
+                    }
+                }
             }
+            self.in_pipe_chain = outer;
             expr = Expr::PipeChain {
                 source: Box::new(expr),
                 steps,
@@ -1459,7 +1678,7 @@ impl Parser {
     }
 
     fn parse_comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut left = self.parse_addition()?;
+        let mut left = self.parse_range()?;
         loop {
             let op = match self.current_token() {
                 Token::Lt => BinOp::Lt,
@@ -1469,6 +1688,132 @@ impl Parser {
                 _ => break,
             };
             self.advance();
+            let right = self.parse_range()?;
+            left = Expr::BinOp {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    /// `a..b` or `a..=b`. Ranges do not chain, so `a..b..c` is an error.
+    fn parse_range(&mut self) -> Result<Expr, ParseError> {
+        let start = self.parse_bit_or()?;
+        let Some(inclusive) = self.range_op() else {
+            return Ok(start);
+        };
+        if matches!(self.peek_token(1), Token::RBracket) {
+            // `xs[a..]`: the open end is handled by parse_index.
+            return Ok(start);
+        }
+        self.advance();
+        let end = self.parse_bit_or()?;
+        if self.range_op().is_some() {
+            return Err(self.error("ranges cannot be chained; add parentheses"));
+        }
+        Ok(Expr::Range {
+            start: Some(Box::new(start)),
+            end: Some(Box::new(end)),
+            inclusive,
+        })
+    }
+
+    /// Whether the current token is `..` (false) or `..=` (true).
+    fn range_op(&self) -> Option<bool> {
+        match self.current_token() {
+            Token::DotDot => Some(false),
+            Token::DotDotEq => Some(true),
+            _ => None,
+        }
+    }
+
+    /// The inside of `xs[...]`: an index, or a slice whose bounds may be
+    /// left open as in `xs[..5]`, `xs[2..]` and `xs[..]`.
+    fn parse_index(&mut self) -> Result<Expr, ParseError> {
+        let start = match self.range_op() {
+            Some(_) => None,
+            None => {
+                let index = self.parse_expr()?;
+                if self.range_op().is_some() {
+                    Some(Box::new(index))
+                } else {
+                    return Ok(index);
+                }
+            }
+        };
+        let inclusive = self.range_op().unwrap_or(false);
+        self.advance();
+        let end = if self.check(&Token::RBracket) {
+            if inclusive {
+                return Err(self.error("'..=' needs an end bound"));
+            }
+            None
+        } else {
+            Some(Box::new(self.parse_bit_or()?))
+        };
+        Ok(Expr::Range {
+            start,
+            end,
+            inclusive,
+        })
+    }
+
+    fn parse_bit_or(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_bit_xor()?;
+        while self.check(&Token::Bar) {
+            self.advance();
+            let right = self.parse_bit_xor()?;
+            left = Expr::BinOp {
+                left: Box::new(left),
+                op: BinOp::BitOr,
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_bit_xor(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_bit_and()?;
+        while self.check(&Token::Caret) {
+            self.advance();
+            let right = self.parse_bit_and()?;
+            left = Expr::BinOp {
+                left: Box::new(left),
+                op: BinOp::BitXor,
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_bit_and(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_shift()?;
+        while self.check(&Token::Ampersand) {
+            self.advance();
+            let right = self.parse_shift()?;
+            left = Expr::BinOp {
+                left: Box::new(left),
+                op: BinOp::BitAnd,
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    /// `<<` and `>>`. A `>>` followed by `keep`, `sort` or `take`, or any
+    /// `>>` between the steps of a pipe chain, is left for
+    /// `parse_query_chain`.
+    fn parse_shift(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_addition()?;
+        loop {
+            let op = match self.current_token() {
+                Token::Shl => BinOp::Shl,
+                Token::PipeRight if !self.in_pipe_chain && !self.pipe_step_follows() => BinOp::Shr,
+                _ => break,
+            };
+            self.advance();
             let right = self.parse_addition()?;
             left = Expr::BinOp {
                 left: Box::new(left),
@@ -1479,6 +1824,15 @@ impl Parser {
         Ok(left)
     }
 
+    /// Whether the token after the current `>>` starts a pipe chain step.
+    fn pipe_step_follows(&self) -> bool {
+        match self.tokens.get(self.pos + 1).map(|s| &s.token) {
+            Some(Token::Keep | Token::Take) => true,
+            Some(Token::Ident(name)) => name == "sort",
+            _ => false,
+        }
+    }
+
     fn parse_addition(&mut self) -> Result<Expr, ParseError> {
         let mut left = self.parse_multiplication()?;
         loop {
@@ -1505,6 +1859,7 @@ impl Parser {
                 Token::Star => BinOp::Mul,
                 Token::Slash => BinOp::Div,
                 Token::Percent => BinOp::Mod,
+                Token::TildeSlash => BinOp::FloorDiv,
                 _ => break,
             };
             self.advance();
@@ -1561,8 +1916,24 @@ impl Parser {
                     operand: Box::new(operand),
                 })
             }
-            _ => self.parse_postfix(),
+            _ => self.parse_power(),
+        }
+    }
+
+    /// `**` binds tighter than unary minus on its left and is
+    /// right-associative, so `-2 ** 2` is `-(2 ** 2)` and `2 ** -1` parses.
+    fn parse_power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.parse_postfix()?;
+        if !self.check(&Token::StarStar) {
+            return Ok(base);
         }
+        self.advance();
+        let exponent = self.parse_unary()?;
+        Ok(Expr::BinOp {
+            left: Box::new(base),
+            op: BinOp::Pow,
+            right: Box::new(exponent),
+        })
     }
 
     /// Postfix: calls, field access, indexing, try (?)
@@ -1590,7 +1961,7 @@ impl Parser {
                 }
                 Token::LBracket => {
                     self.advance();
-                    let index = self.parse_expr()?;
+                    let index = self.parse_index()?;
                     self.expect(Token::RBracket)?;
                     expr = Expr::Index {
                         object: Box::new(expr),
@@ -1728,7 +2099,10 @@ impl Parser {
 
             Token::LParen => {
                 self.advance();
-                let first = self.parse_expr()?;
+                let outer = std::mem::replace(&mut self.in_pipe_chain, false);
+                let first = self.parse_expr();
+                self.in_pipe_chain = outer;
+                let first = first?;
                 if self.check(&Token::Comma) {
                     // Tuple: (e1, e2, ...) or (e1,)
                     self.advance();
@@ -2429,7 +2803,7 @@ mod tests {
         let program = parse_program("type Color = Red | Blue");
 
         match &program.statements[0].stmt {
-            Stmt::TypeDef { name, variants } => {
+            Stmt::TypeDef { name, variants, .. } => {
                 assert_eq!(name, "Color");
                 assert_eq!(variants.len(), 2);
             }
@@ -2587,4 +2961,242 @@ mod tests {
             other => panic!("expected Destructure, got {:?}", other),
         }
     }
+
+    #[test]
+    fn parses_match_guards_and_structured_patterns() {
+        let program = parse_program(
+            r#"match v {
+                n if n > 10 => "big"
+                1 | 2 => "small"
+                -5..=0 => "low"
+                Point { x: 0, y } => "axis"
+                [first, ...rest] => "list"
+                [a, ...] => "open"
+            }"#,
+        );
+        let Stmt::Match { arms, .. } = &program.statements[0].stmt else {
+            panic!("expected match statement");
+        };
+        assert_eq!(arms.len(), 6);
+        assert!(matches!(&arms[0].pattern, Pattern::Binding(n) if n == "n"));
+        assert!(matches!(
+            &arms[0].guard,
+            Some(Expr::BinOp { op: BinOp::Gt, .. })
+        ));
+        assert!(matches!(&arms[1].pattern, Pattern::Or(alts) if alts.len() == 2));
+        assert!(arms[1].guard.is_none());
+        assert!(matches!(
+            &arms[2].pattern,
+            Pattern::Range {
+                start: Some(Expr::Int(-5)),
+                end: Some(Expr::Int(0)),
+                inclusive: true
+            }
+        ));
+        match &arms[3].pattern {
+            Pattern::Object { type_name, fields } => {
+                assert_eq!(type_name.as_deref(), Some("Point"));
+                assert!(matches!(&fields[0], (k, Pattern::Literal(Expr::Int(0))) if k == "x"));
+                assert!(matches!(&fields[1], (k, Pattern::Binding(b)) if k == "y" && b == "y"));
+            }
+            other => panic!("expected object pattern, got {:?}", other),
+        }
+        assert_eq!(arms[4].pattern.bindings(), vec!["first", "rest"]);
+        assert!(matches!(
+            &arms[5].pattern,
+            Pattern::Array { items, rest: Some(rest) }
+                if items.len() == 1 && matches!(**rest, Pattern::Wildcard)
+        ));
+    }
+
+    #[test]
+    fn rejects_items_after_array_rest() {
+        let tokens = Lexer::new("match v { [...rest, last] => 1 }")
+            .tokenize()
+            .unwrap();
+        let err = Parser::new(tokens).parse_program().unwrap_err();
+        assert!(err.message.contains("must be the last item"));
+    }
+
+    #[test]
+    fn parses_pub_declarations_and_namespace_imports() {
+        let program = parse_program(
+            r#"import "utils" as u
+pub fn shout(s) { return s }
+@get("/")
+pub fn index() { return 1 }
+pub let limit_ = 3
+pub type Shape = Circle(Int) | Dot
+fn private() { return 0 }"#,
+        );
+        assert!(matches!(
+            &program.statements[0].stmt,
+            Stmt::Import { path, names: None, alias: Some(a) } if path == "utils" && a == "u"
+        ));
+        let visible: Vec<bool> = program.statements[1..]
+            .iter()
+            .map(|s| s.stmt.is_pub())
+            .collect();
+        assert_eq!(visible, vec![true, true, true, true, false]);
+        assert!(matches!(
+            &program.statements[2].stmt,
+            Stmt::FnDef { decorators, .. } if decorators.len() == 1
+        ));
+        assert_eq!(
+            program.export_names(),
+            vec!["shout", "index", "limit_", "Circle", "Dot"]
+        );
+    }
+
+    #[test]
+    fn rejects_pub_on_non_declarations() {
+        let tokens = Lexer::new("pub println(1)").tokenize().unwrap();
+        let err = Parser::new(tokens).parse_program().unwrap_err();
+        assert!(err.message.contains("'pub' must be followed by"));
+    }
+
+    fn first_expr(source: &str) -> Expr {
+        match parse_program(source).statements.remove(0).stmt {
+            Stmt::Expression(expr) | Stmt::Let { value: expr, .. } => expr,
+            other => panic!("expected an expression, got {:?}", other),
+        }
+    }
+
+    /// Parenthesized rendering of an operator tree, for precedence checks.
+    fn shape(expr: &Expr) -> String {
+        match expr {
+            Expr::BinOp { left, op, right } => {
+                format!("({} {} {})", shape(left), op.symbol(), shape(right))
+            }
+            Expr::UnaryOp {
+                op: UnaryOp::Neg,
+                operand,
+            } => format!("-{}", shape(operand)),
+            Expr::Int(n) => n.to_string(),
+            Expr::Ident(name) => name.clone(),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_bitwise_and_integer_operator_precedence() {
+        assert_eq!(
+            shape(&first_expr("a | b ^ c & d << 1 + 2")),
+            "(a | (b ^ (c & (d << (1 + 2)))))"
+        );
+        assert_eq!(
+            shape(&first_expr("x >> 4 & 15 == 3")),
+            "(((x >> 4) & 15) == 3)"
+        );
+        assert_eq!(shape(&first_expr("7 ~/ 2 * 3")), "((7 ~/ 2) * 3)");
+        assert_eq!(shape(&first_expr("-2 ** 2")), "-(2 ** 2)");
+        assert_eq!(shape(&first_expr("2 ** 3 ** 2")), "(2 ** (3 ** 2))");
+        assert_eq!(shape(&first_expr("2 ** -1")), "(2 ** -1)");
+    }
+
+    #[test]
+    fn parses_bitwise_compound_assignments() {
+        let program = parse_program("flags |= 4\nx <<= 2\nn **= 3\nq ~/= 2");
+        let ops: Vec<&str> = program
+            .statements
+            .iter()
+            .map(|s| match &s.stmt {
+                Stmt::Assign {
+                    value: Expr::BinOp { op, .. },
+                    ..
+                } => op.symbol(),
+                other => panic!("expected compound assignment, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(ops, vec!["|", "<<", "**", "~/"]);
+    }
+
+    #[test]
+    fn shift_right_leaves_pipe_chains_alone() {
+        match first_expr("xs >> keep where n > 1 >> take (8 >> 2)") {
+            Expr::PipeChain { steps, .. } => {
+                assert_eq!(steps.len(), 2);
+                match &steps[1] {
+                    PipeStep::Take(count) => assert_eq!(shape(count), "(8 >> 2)"),
+                    other => panic!("expected take step, got {:?}", other),
+                }
+            }
+            other => panic!("expected pipe chain, got {:?}", other),
+        }
+        assert_eq!(shape(&first_expr("x >> 2")), "(x >> 2)");
+    }
+
+    #[test]
+    fn ranges_bind_looser_than_arithmetic_and_tighter_than_comparison() {
+        match first_expr("a + 1..=b * 2 == r") {
+            Expr::BinOp { left, op, .. } => {
+                assert_eq!(op, BinOp::Eq);
+                match *left {
+                    Expr::Range {
+                        start: Some(start),
+                        end: Some(end),
+                        inclusive: true,
+                    } => {
+                        assert_eq!(shape(&start), "(a + 1)");
+                        assert_eq!(shape(&end), "(b * 2)");
+                    }
+                    other => panic!("expected a range, got {:?}", other),
+                }
+            }
+            other => panic!("expected a comparison, got {:?}", other),
+        }
+        let tokens = Lexer::new("let r = 0..1..2").tokenize().unwrap();
+        let err = Parser::new(tokens).parse_program().unwrap_err();
+        assert!(err.message.contains("ranges cannot be chained"));
+    }
+
+    #[test]
+    fn parses_open_slice_bounds() {
+        let bounds = |source: &str| match first_expr(source) {
+            Expr::Index { index, .. } => match *index {
+                Expr::Range {
+                    start,
+                    end,
+                    inclusive,
+                } => (start.is_some(), end.is_some(), inclusive),
+                other => panic!("expected a range index, got {:?}", other),
+            },
+            other => panic!("expected an index, got {:?}", other),
+        };
+        assert_eq!(bounds("xs[1..3]"), (true, true, false));
+        assert_eq!(bounds("xs[..=2]"), (false, true, true));
+        assert_eq!(bounds("xs[2..]"), (true, false, false));
+        assert_eq!(bounds("xs[..]"), (false, false, false));
+        let tokens = Lexer::new("xs[1..=]").tokenize().unwrap();
+        let err = Parser::new(tokens).parse_program().unwrap_err();
+        assert!(err.message.contains("'..=' needs an end bound"));
+    }
+
+    #[test]
+    fn parses_open_range_patterns() {
+        let program = parse_program("match n { ..0 => 1, 0..=9 => 2, 10.. => 3, _ => 4 }");
+        let arms = match &program.statements[0].stmt {
+            Stmt::Match { arms, .. } => arms,
+            other => panic!("expected a match, got {:?}", other),
+        };
+        let bounds: Vec<_> = arms
+            .iter()
+            .filter_map(|arm| match &arm.pattern {
+                Pattern::Range {
+                    start,
+                    end,
+                    inclusive,
+                } => Some((start.is_some(), end.is_some(), *inclusive)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            bounds,
+            vec![
+                (false, true, false),
+                (true, true, true),
+                (true, false, false)
+            ]
+        );
+    }
 }
@@ -96,6 +96,8 @@ const BUILTINS: &[&str] = &[
     "is_none",
     "json",
     "fetch",
+    "response",
+    "redirect",
     "time",
     "uuid",
     "say",
@@ -169,7 +171,7 @@ const KEYWORDS: &[&str] = &[
 
 const MODULES: &[&str] = &[
     "math", "fs", "io", "crypto", "db", "pg", "mysql", "env", "json", "regex", "log", "http",
-    "csv", "term", "time", "jwt", "npc", "exec",
+    "csv", "term", "time", "jwt", "npc", "exec", "metrics", "testing",
 ];
 
 #[derive(Helper, Validator, Hinter)]
@@ -52,7 +52,11 @@ pub async fn execute_source_on_current_runtime(
         .run(&program)
         .map_err(|err| format_runtime_error(source, &config.source_label, &err))?;
 
-    let runtime_plan = super::metadata::extract_runtime_plan(&program);
+    let base_dir = interpreter
+        .source_file
+        .as_ref()
+        .and_then(|file| file.parent().map(|dir| dir.to_path_buf()));
+    let runtime_plan = super::metadata::extract_runtime_plan_in(&program, base_dir.as_deref());
     super::host::launch(interpreter, &runtime_plan)
         .await
         .map_err(|err| err.message)
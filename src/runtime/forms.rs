@@ -0,0 +1,275 @@
+//! Form and multipart request bodies.
+//!
+//! `application/x-www-form-urlencoded` bodies are buffered like any other
+//! body and decoded in place. `multipart/form-data` bodies are streamed:
+//! text fields are collected in memory (each capped at `max_body`), while
+//! file fields are written to a per-request temp directory as they arrive
+//! and described to handlers as `{ filename, content_type, path, size }`.
+//! The directory is removed once the request finishes, so handlers that
+//! keep an upload must move or copy it.
+//!
+//! In both encodings a repeated field name collects its values into an
+//! array, in the order they were sent.
+
+use std::path::PathBuf;
+
+use axum::body::Body;
+use axum::http::StatusCode;
+use serde_json::{json, Map, Value as JsonValue};
+use tokio::io::AsyncWriteExt;
+
+/// Decode an urlencoded form into an object of strings.
+pub(crate) fn parse_urlencoded(raw: &[u8]) -> JsonValue {
+    let mut fields = Map::new();
+    for (name, value) in url::form_urlencoded::parse(raw) {
+        insert_field(
+            &mut fields,
+            name.into_owned(),
+            JsonValue::String(value.into_owned()),
+        );
+    }
+    JsonValue::Object(fields)
+}
+
+fn insert_field(fields: &mut Map<String, JsonValue>, name: String, value: JsonValue) {
+    match fields.get_mut(&name) {
+        Some(JsonValue::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = JsonValue::Array(vec![first, value]);
+        }
+        None => {
+            fields.insert(name, value);
+        }
+    }
+}
+
+/// Temp directory holding one request's uploaded files, deleted on drop.
+#[derive(Debug)]
+pub(crate) struct UploadDir {
+    path: PathBuf,
+}
+
+impl UploadDir {
+    fn create() -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("forge-upload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&path)?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for UploadDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A decoded `multipart/form-data` body.
+pub(crate) struct MultipartForm {
+    pub fields: JsonValue,
+    /// Where uploaded files were written; `None` when the form had none.
+    pub uploads: Option<UploadDir>,
+}
+
+/// Stream a multipart body into a [`MultipartForm`]. Errors carry the
+/// status to answer with: `413` past `max_upload` (whole request) or
+/// `max_field` (one text field), `400` for a malformed body.
+pub(crate) async fn read_multipart(
+    body: Body,
+    content_type: &str,
+    max_upload: usize,
+    max_field: usize,
+) -> Result<MultipartForm, (StatusCode, String)> {
+    let boundary = multer::parse_boundary(content_type)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let constraints = multer::Constraints::new()
+        .size_limit(multer::SizeLimit::new().whole_stream(max_upload as u64));
+    let mut multipart =
+        multer::Multipart::with_constraints(body.into_data_stream(), boundary, constraints);
+
+    let mut fields = Map::new();
+    let mut dir: Option<UploadDir> = None;
+    let mut stored = 0usize;
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+        let value = match field.file_name().map(str::to_string) {
+            // Browsers send an empty, nameless part for an unset file input.
+            Some(filename) if filename.is_empty() => JsonValue::Null,
+            Some(filename) => {
+                let content_type = field
+                    .content_type()
+                    .map(|mime| mime.to_string())
+                    .unwrap_or_else(|| "application/octet-stream".to_string());
+                let dir = match dir {
+                    Some(ref dir) => dir,
+                    None => dir.insert(UploadDir::create().map_err(store_error)?),
+                };
+                stored += 1;
+                let path = dir
+                    .path
+                    .join(format!("{}-{}", stored, safe_file_name(&filename)));
+                let mut file = tokio::fs::File::create(&path).await.map_err(store_error)?;
+                let mut size = 0u64;
+                while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+                    size += chunk.len() as u64;
+                    file.write_all(&chunk).await.map_err(store_error)?;
+                }
+                file.flush().await.map_err(store_error)?;
+                json!({
+                    "filename": filename,
+                    "content_type": content_type,
+                    "path": path.to_string_lossy(),
+                    "size": size,
+                })
+            }
+            None => {
+                let mut text = Vec::new();
+                while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+                    if text.len() + chunk.len() > max_field {
+                        return Err((
+                            StatusCode::PAYLOAD_TOO_LARGE,
+                            format!("form field '{}' is larger than {} bytes", name, max_field),
+                        ));
+                    }
+                    text.extend_from_slice(&chunk);
+                }
+                JsonValue::String(String::from_utf8_lossy(&text).into_owned())
+            }
+        };
+        insert_field(&mut fields, name, value);
+    }
+    Ok(MultipartForm {
+        fields: JsonValue::Object(fields),
+        uploads: dir,
+    })
+}
+
+fn multipart_error(e: multer::Error) -> (StatusCode, String) {
+    let status = match e {
+        multer::Error::StreamSizeExceeded { .. } | multer::Error::FieldSizeExceeded { .. } => {
+            StatusCode::PAYLOAD_TOO_LARGE
+        }
+        _ => StatusCode::BAD_REQUEST,
+    };
+    (status, e.to_string())
+}
+
+fn store_error(e: std::io::Error) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("failed to store upload: {}", e),
+    )
+}
+
+/// The client's file name reduced to a safe final path component; the
+/// original is still reported as `filename`.
+fn safe_file_name(filename: &str) -> String {
+    let base = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let safe: String = base
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(100)
+        .collect();
+    match safe.trim_start_matches('.') {
+        "" => "upload".to_string(),
+        safe => safe.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "XyZ";
+
+    fn multipart_body(parts: &[(&str, Option<&str>, &str)]) -> Body {
+        let mut body = String::new();
+        for (name, filename, content) in parts {
+            body.push_str(&format!("--{}\r\n", BOUNDARY));
+            match filename {
+                Some(filename) => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: text/plain\r\n\r\n",
+                    name, filename
+                )),
+                None => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                    name
+                )),
+            }
+            body.push_str(content);
+            body.push_str("\r\n");
+        }
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+        Body::from(body)
+    }
+
+    fn read(body: Body, max_upload: usize) -> Result<MultipartForm, (StatusCode, String)> {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(read_multipart(
+                body,
+                &format!("multipart/form-data; boundary={}", BOUNDARY),
+                max_upload,
+                16,
+            ))
+    }
+
+    #[test]
+    fn urlencoded_forms_decode_and_collect_repeats() {
+        assert_eq!(
+            parse_urlencoded(b"name=Ada+L&tag=a&tag=b&tag=c&note=100%25"),
+            json!({ "name": "Ada L", "tag": ["a", "b", "c"], "note": "100%" })
+        );
+    }
+
+    #[test]
+    fn multipart_files_go_to_disk_until_the_form_is_dropped() {
+        let form = read(
+            multipart_body(&[
+                ("title", None, "holiday"),
+                ("photo", Some("../../etc/passwd"), "file contents"),
+                ("empty", Some(""), ""),
+            ]),
+            1024,
+        )
+        .expect("form");
+        assert_eq!(form.fields["title"], "holiday");
+        assert!(form.fields["empty"].is_null());
+
+        let photo = &form.fields["photo"];
+        assert_eq!(photo["filename"], "../../etc/passwd");
+        assert_eq!(photo["content_type"], "text/plain");
+        assert_eq!(photo["size"], 13);
+        let path = PathBuf::from(photo["path"].as_str().unwrap());
+        assert_eq!(path.file_name().unwrap(), "1-passwd");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "file contents");
+
+        drop(form);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn multipart_limits_answer_413() {
+        let too_big = read(
+            multipart_body(&[("f", Some("a.txt"), &"x".repeat(200))]),
+            100,
+        );
+        assert_eq!(
+            too_big.err().map(|e| e.0),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+
+        let long_field = read(multipart_body(&[("note", None, &"x".repeat(17))]), 1024);
+        assert_eq!(
+            long_field.err().map(|e| e.0),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+    }
+}
@@ -1,25 +1,152 @@
+use std::future::Future;
+
 use crate::interpreter::{Interpreter, RuntimeError, Value};
+use crate::parser::ast::Program;
+use crate::vm::bytecode::Chunk;
+use crate::vm::machine::VM;
+use crate::vm::template::VmTemplate;
+
+use super::metadata::{extract_runtime_plan, RuntimePlan, SchedulePlan, WatchPlan};
+use super::server::Shutdown;
+use super::worker::{Compiled, Served};
+
+/// Start everything `plan` describes and serve until Ctrl-C or SIGTERM.
+/// If the drain window closes with handlers still running, the process
+/// exits with status 1 instead of waiting for them.
+pub async fn launch(program: impl Into<Served>, plan: &RuntimePlan) -> Result<(), RuntimeError> {
+    match launch_until(program, plan, super::server::shutdown_signal()).await? {
+        Shutdown::Drained => Ok(()),
+        Shutdown::TimedOut { in_flight } => {
+            eprintln!(
+                "forge: {} handler(s) still running after the drain timeout; exiting",
+                in_flight
+            );
+            std::process::exit(1);
+        }
+    }
+}
 
-use super::metadata::{RuntimePlan, SchedulePlan, WatchPlan};
+/// Run a compiled program's main chunk on the VM and capture the state
+/// it leaves behind for serving.
+///
+/// Routes and hooks are planned from the chunk's declarations exactly as
+/// for source, and every request, hook and WebSocket connection then runs
+/// on a VM started from that state. `schedule` and `watch` blocks were
+/// already started by the VM and are not part of the plan.
+pub fn load_compiled(chunk: &Chunk) -> Result<(Served, RuntimePlan), RuntimeError> {
+    let mut vm = VM::new();
+    vm.execute(chunk)
+        .map_err(|e| RuntimeError::new(&e.to_string()))?;
+    let template = VmTemplate::capture(&vm).map_err(|e| RuntimeError::new(&e.message))?;
+
+    let program = Program {
+        statements: chunk.declarations.clone(),
+    };
+    let compiled = Compiled::new(template, &chunk.declarations)?;
+    Ok((compiled.into(), extract_runtime_plan(&program)))
+}
 
-pub async fn launch(interpreter: Interpreter, plan: &RuntimePlan) -> Result<(), RuntimeError> {
-    for schedule in &plan.schedules {
-        spawn_schedule(&interpreter, schedule)?;
-    }
+/// Serve a compiled program that declares a server or lifecycle hooks.
+/// This is how `forge run app.fgc` and AOT binaries run bytecode with
+/// [`Chunk::declarations`].
+pub async fn serve_compiled(chunk: &Chunk) -> Result<(), RuntimeError> {
+    let (program, plan) = load_compiled(chunk)?;
+    launch(program, &plan).await
+}
+
+/// [`launch`] with the server stopping when `shutdown` completes, and no
+/// forced exit.
+///
+/// `@on_start` functions run first, on the program state that every
+/// request forks from, so globals they set are visible to handlers. A
+/// failing `@on_start` aborts the launch. `@on_shutdown` functions run
+/// after the server has stopped and drained (or failed after starting),
+/// each on a fork taken after `@on_start`; their failures are logged.
+pub async fn launch_until(
+    program: impl Into<Served>,
+    plan: &RuntimePlan,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<Shutdown, RuntimeError> {
+    check_server(plan)?;
+    let program = run_on_start(program.into(), plan.on_start.clone()).await?;
+
+    // A compiled program's VM started its own schedules and watches.
+    if let Served::Interpreted(interpreter) = &program {
+        for schedule in &plan.schedules {
+            spawn_schedule(interpreter, schedule)?;
+        }
 
-    for watch in &plan.watches {
-        spawn_watch(&interpreter, watch)?;
+        for watch in &plan.watches {
+            spawn_watch(interpreter, watch)?;
+        }
     }
 
+    let Some(server) = &plan.server else {
+        return Ok(Shutdown::Drained);
+    };
+    let shutdown_program = program.fork_for_background();
+    let outcome = super::server::serve_until(program, server, shutdown).await;
+    run_on_shutdown(shutdown_program, plan.on_shutdown.clone()).await;
+    outcome
+}
+
+/// Reject a `@server` whose decorators failed to resolve or that has no
+/// routes to serve.
+pub(super) fn check_server(plan: &RuntimePlan) -> Result<(), RuntimeError> {
     if let Some(server) = &plan.server {
+        if !server.errors.is_empty() {
+            return Err(RuntimeError::new(&server.errors.join("\n")));
+        }
         if server.routes.is_empty() {
             return Err(RuntimeError::new(
                 "@server defined but no route handlers found. Add @get/@post functions.",
             ));
         }
-        super::server::start_server(interpreter, server).await
-    } else {
-        Ok(())
+    }
+    Ok(())
+}
+
+/// Call each `@on_start` function on the blocking pool, handing the
+/// program back for the server.
+pub(super) async fn run_on_start(
+    mut program: Served,
+    hooks: Vec<String>,
+) -> Result<Served, RuntimeError> {
+    if hooks.is_empty() {
+        return Ok(program);
+    }
+    tokio::task::spawn_blocking(move || {
+        for name in &hooks {
+            program.call_hook(name).map_err(|e| {
+                RuntimeError::new(&format!("@on_start '{}' failed: {}", name, e.message))
+            })?;
+        }
+        program.settle()?;
+        Ok(program)
+    })
+    .await
+    .map_err(|e| RuntimeError::new(&format!("@on_start panicked: {}", e)))?
+}
+
+async fn run_on_shutdown(mut program: Served, hooks: Vec<String>) {
+    if hooks.is_empty() {
+        return;
+    }
+    let ran = tokio::task::spawn_blocking(move || {
+        for name in &hooks {
+            if let Err(e) = program.call_hook(name) {
+                tracing::warn!(
+                    target: "forge.server",
+                    hook = %name,
+                    error = %e.message,
+                    "@on_shutdown failed"
+                );
+            }
+        }
+    })
+    .await;
+    if let Err(e) = ran {
+        tracing::warn!(target: "forge.server", error = %e, "@on_shutdown panicked");
     }
 }
 
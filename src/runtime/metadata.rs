@@ -1,3 +1,8 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::interpreter::BUILTIN_MODULES;
 use crate::parser::ast::*;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -18,12 +23,225 @@ pub struct ServerConfig {
     pub port: u16,
     pub host: String,
     pub cors: CorsMode,
+    /// Directory served at `/` for requests no route matches
+    /// (`@server(static: "./public")`).
+    pub static_dir: Option<String>,
+    /// File inside `static_dir` served for unknown paths so client-side
+    /// routers can handle them (`spa_fallback: "index.html"`).
+    pub spa_fallback: Option<String>,
+    /// PEM certificate chain and private key. When both are set (here or
+    /// via `FORGE_TLS_CERT` / `FORGE_TLS_KEY`) the server speaks HTTPS.
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    /// Concurrent handler invocations before new requests get `503`.
+    pub max_inflight: Option<usize>,
+    /// Largest accepted request body in bytes; larger bodies get `413`.
+    pub max_body: Option<usize>,
+    /// Largest accepted `multipart/form-data` request in bytes, files
+    /// included; larger uploads get `413`.
+    pub max_upload: Option<usize>,
+    /// Handlers running longer than this are cancelled with a `504`.
+    pub request_timeout: Option<Duration>,
+    /// Idle time before TCP keep-alive probes start on client sockets.
+    pub keepalive: Option<Duration>,
+    /// How long shutdown waits for in-flight requests before cancelling
+    /// them and exiting.
+    pub drain_timeout: Option<Duration>,
+    /// SQLite database holding `@rate_limit` buckets, so several server
+    /// processes on one host share limits. In memory when unset.
+    pub rate_limit_store: Option<String>,
+    /// Path serving the generated OpenAPI document (`docs: "/openapi.json"`).
+    pub docs: Option<String>,
+    /// Path serving Prometheus metrics (`metrics: "/metrics"`).
+    pub metrics: Option<String>,
+    /// Path answering health checks (`health: "/healthz"`); `503` while
+    /// the server drains.
+    pub health: Option<String>,
+    /// Compress responses with gzip or deflate when the client accepts it
+    /// (`compress: true`).
+    pub compress: bool,
+    /// Key signing session cookies. Sessions are off without one (here or
+    /// in `FORGE_SESSION_SECRET`).
+    pub session_secret: Option<String>,
+    /// `"memory"` or a SQLite path to keep session data on the server;
+    /// unset keeps it in the cookie.
+    pub session_store: Option<String>,
+    /// How long a session lasts after it last changed.
+    pub session_max_age: Option<Duration>,
+}
+
+/// What a bare `@server` gets: port 8080 on localhost, restrictive CORS
+/// and no optional features.
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            host: "127.0.0.1".to_string(),
+            cors: CorsMode::Restrictive,
+            static_dir: None,
+            spa_fallback: None,
+            tls_cert: None,
+            tls_key: None,
+            max_inflight: None,
+            max_body: None,
+            max_upload: None,
+            request_timeout: None,
+            keepalive: None,
+            drain_timeout: None,
+            rate_limit_store: None,
+            docs: None,
+            metrics: None,
+            health: None,
+            compress: false,
+            session_secret: None,
+            session_store: None,
+            session_max_age: None,
+        }
+    }
+}
+
+/// A directory served under a URL prefix (`@static("/assets", dir: "./dist")`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaticMount {
+    pub prefix: String,
+    pub dir: String,
+}
+
+/// Which requests a middleware hook applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HookScope {
+    /// Every route (`@before` / `@after` / `@middleware` without arguments).
+    All,
+    /// Routes whose pattern is `prefix` or lives under it
+    /// (`@middleware("/admin")`).
+    PathPrefix(String),
+    /// Routes served by the named handler (`@before("auth")` on a route).
+    Handler(String),
+}
+
+/// A middleware hook: a Forge function run before or after route handlers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hook {
+    pub function: String,
+    pub scope: HookScope,
+}
+
+impl Hook {
+    pub fn applies_to(&self, route: &Route) -> bool {
+        match &self.scope {
+            HookScope::All => true,
+            HookScope::PathPrefix(prefix) => path_has_prefix(&route.pattern, prefix),
+            HookScope::Handler(name) => route.handler_name == *name,
+        }
+    }
+}
+
+/// Segment-aware prefix match: `/admin` covers `/admin` and `/admin/users`
+/// but not `/administrator`.
+fn path_has_prefix(pattern: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        return true;
+    }
+    match pattern.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// How `@rate_limit` tells clients apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RateKey {
+    /// The client's IP address (`key: "ip"`, the default).
+    Ip,
+    /// A request header, such as an API key (`key: "header:X-Api-Key"`).
+    Header(String),
+    /// A Forge function called with the request and the `ctx` the
+    /// before-hooks built (`key: "tenant_of"`).
+    Function(String),
+}
+
+/// `@rate_limit(100, per: "minute")` on a handler: a token bucket holding
+/// `limit` requests per client, refilled evenly over `per`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub handler: String,
+    pub limit: u32,
+    pub per: Duration,
+    pub key: RateKey,
+}
+
+/// Lifecycle callbacks named on a `@ws` route
+/// (`@ws("/chat", on_open: "greet", on_close: "bye")`); the decorated
+/// function handles messages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketHooks {
+    pub handler: String,
+    pub on_open: Option<String>,
+    pub on_close: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ServerPlan {
     pub config: ServerConfig,
     pub routes: Vec<Route>,
+    /// Hooks run before the handler, in declaration order. See
+    /// [`ServerPlan::hooks_for`] for the order they run in.
+    pub before: Vec<Hook>,
+    /// Hooks run after the handler, in declaration order.
+    pub after: Vec<Hook>,
+    pub statics: Vec<StaticMount>,
+    pub rate_limits: Vec<RateLimit>,
+    pub sockets: Vec<SocketHooks>,
+    /// Handlers marked `@csrf`.
+    pub csrf: Vec<String>,
+    /// The OpenAPI document, generated up front when `docs:` is set.
+    pub openapi: Option<serde_json::Value>,
+    /// Problems found while planning the server, such as conflicting
+    /// routes or decorator arguments it cannot use. The server refuses to
+    /// start while any remain.
+    pub errors: Vec<String>,
+}
+
+impl ServerPlan {
+    /// The `@rate_limit` on the handler serving `route`, if any.
+    pub fn rate_limit_for(&self, route: &Route) -> Option<&RateLimit> {
+        self.rate_limits
+            .iter()
+            .find(|limit| limit.handler == route.handler_name)
+    }
+
+    /// Whether the handler serving `route` is marked `@csrf`.
+    pub fn csrf_for(&self, route: &Route) -> bool {
+        self.csrf.contains(&route.handler_name)
+    }
+
+    /// The `on_open` / `on_close` callbacks of the `@ws` handler serving
+    /// `route`, if it names any.
+    pub fn socket_hooks_for(&self, route: &Route) -> Option<&SocketHooks> {
+        self.sockets
+            .iter()
+            .find(|hooks| hooks.handler == route.handler_name)
+    }
+
+    /// Before and after hook function names that apply to `route`, in the
+    /// order they run: global hooks, then prefix hooks, then the route's
+    /// own, each group in declaration order.
+    pub fn hooks_for(&self, route: &Route) -> (Vec<String>, Vec<String>) {
+        let pick = |hooks: &[Hook]| {
+            let mut hooks: Vec<&Hook> = hooks.iter().filter(|h| h.applies_to(route)).collect();
+            hooks.sort_by_key(|hook| match hook.scope {
+                HookScope::All => 0,
+                HookScope::PathPrefix(_) => 1,
+                HookScope::Handler(_) => 2,
+            });
+            hooks
+                .into_iter()
+                .map(|hook| hook.function.clone())
+                .collect()
+        };
+        (pick(&self.before), pick(&self.after))
+    }
 }
 
 #[allow(dead_code)]
@@ -49,23 +267,36 @@ pub struct RuntimePlan {
     pub server: Option<ServerPlan>,
     pub schedules: Vec<SchedulePlan>,
     pub watches: Vec<WatchPlan>,
+    /// `@on_start` functions, run in order before anything else starts.
+    pub on_start: Vec<String>,
+    /// `@on_shutdown` functions, run in order once the server has stopped.
+    pub on_shutdown: Vec<String>,
 }
 
+#[allow(dead_code)]
 pub fn extract_runtime_plan(program: &Program) -> RuntimePlan {
-    let mut server_config = None;
-    let mut routes = Vec::new();
+    extract_runtime_plan_in(program, None)
+}
+
+/// [`extract_runtime_plan`] for a program whose imports and `@mount`s
+/// resolve relative to `base_dir`, the entry file's directory.
+pub fn extract_runtime_plan_in(program: &Program, base_dir: Option<&Path>) -> RuntimePlan {
     let mut schedules = Vec::new();
     let mut watches = Vec::new();
-
+    let mut on_start = Vec::new();
+    let mut on_shutdown = Vec::new();
     for spanned in &program.statements {
         match &spanned.stmt {
             Stmt::FnDef {
                 name, decorators, ..
             } => {
-                routes.extend(extract_routes(name, decorators));
-            }
-            Stmt::DecoratorStmt(dec) if dec.name == "server" && server_config.is_none() => {
-                server_config = Some(extract_server_config(dec));
+                for dec in decorators {
+                    match dec.name.as_str() {
+                        "on_start" => on_start.push(name.clone()),
+                        "on_shutdown" => on_shutdown.push(name.clone()),
+                        _ => {}
+                    }
+                }
             }
             Stmt::ScheduleBlock {
                 interval,
@@ -90,22 +321,420 @@ pub fn extract_runtime_plan(program: &Program) -> RuntimePlan {
         }
     }
 
+    let server = server_plan(program, base_dir).map(|(mut server, modules)| {
+        if server.config.docs.is_some() {
+            let programs: Vec<&Program> = std::iter::once(program).chain(&modules).collect();
+            server.openapi = Some(super::openapi::document(&programs, &server));
+        }
+        server
+    });
+
     RuntimePlan {
-        server: server_config.map(|config| ServerPlan { config, routes }),
+        server,
         schedules,
         watches,
+        on_start,
+        on_shutdown,
     }
 }
 
+/// The OpenAPI document for the program's `@server`, including routes
+/// from imported and mounted files. `None` without a `@server`.
+pub fn openapi_document(program: &Program, base_dir: Option<&Path>) -> Option<serde_json::Value> {
+    let (server, modules) = server_plan(program, base_dir)?;
+    let programs: Vec<&Program> = std::iter::once(program).chain(&modules).collect();
+    Some(super::openapi::document(&programs, &server))
+}
+
+/// Whether importing `module` whole, from a file in `base_dir`, adds to a
+/// server: routes, hooks, rate limits, `@group` or `@mount`, in the file
+/// or in the files it imports whole in turn. A file that cannot be found
+/// or parsed adds nothing.
+pub fn import_declares_routes(module: &str, base_dir: Option<&Path>) -> bool {
+    let mut loader = ModuleLoader::default();
+    let mut set = RouteSet::default();
+    loader.load(module, "", base_dir, false, &mut set);
+    let grouped = loader.modules.iter().any(|program| {
+        program.statements.iter().any(|spanned| {
+            matches!(&spanned.stmt, Stmt::DecoratorStmt(dec) if dec.name == "group" || dec.name == "mount")
+        })
+    });
+    grouped
+        || !set.routes.is_empty()
+        || !set.before.is_empty()
+        || !set.after.is_empty()
+        || !set.rate_limits.is_empty()
+        || !set.sockets.is_empty()
+        || !set.csrf.is_empty()
+}
+
+/// The server plan (without its OpenAPI document) and the parsed route
+/// modules it drew from.
+fn server_plan(program: &Program, base_dir: Option<&Path>) -> Option<(ServerPlan, Vec<Program>)> {
+    let mut loader = ModuleLoader::default();
+    let mut config = None;
+    let mut statics = Vec::new();
+    for spanned in &program.statements {
+        match &spanned.stmt {
+            Stmt::DecoratorStmt(dec) if dec.name == "server" && config.is_none() => {
+                config = Some(extract_server_config(dec, &mut loader.errors));
+            }
+            Stmt::DecoratorStmt(dec) if dec.name == "static" => {
+                statics.extend(extract_static_mount(dec));
+            }
+            _ => {}
+        }
+    }
+    let config = config?;
+
+    let mut set = RouteSet::default();
+    loader.collect(
+        &program.statements,
+        "",
+        base_dir,
+        "the main program",
+        &mut set,
+    );
+    let server = ServerPlan {
+        config,
+        routes: set.routes,
+        before: set.before,
+        after: set.after,
+        statics,
+        rate_limits: set.rate_limits,
+        sockets: set.sockets,
+        csrf: set.csrf,
+        openapi: None,
+        errors: loader.errors,
+    };
+    Some((server, loader.modules))
+}
+
+/// Routes, hooks and rate limits gathered from a file and the modules it
+/// pulls in, in declaration order.
+#[derive(Default)]
+struct RouteSet {
+    routes: Vec<Route>,
+    before: Vec<Hook>,
+    after: Vec<Hook>,
+    rate_limits: Vec<RateLimit>,
+    sockets: Vec<SocketHooks>,
+    csrf: Vec<String>,
+}
+
+/// Walks the entry program and the files it imports whole or `@mount`s.
+///
+/// Each file's routes live under its prefix: the prefix it was mounted at
+/// (inherited by the files it imports) extended by its own `@group`.
+/// Unscoped hooks in a file apply under that prefix, and
+/// `@middleware("/x")` paths are relative to it, so a mounted module's
+/// middleware stays within the module.
+#[derive(Default)]
+struct ModuleLoader {
+    /// Files being walked, to stop at import cycles.
+    stack: Vec<PathBuf>,
+    /// (file, prefix) pairs already walked, so a module imported from
+    /// several files contributes its routes once.
+    seen: HashSet<(PathBuf, String)>,
+    /// The file defining each route handler. Imported functions share one
+    /// namespace, so the same name in two files is an error.
+    handlers: HashMap<String, String>,
+    /// The handler serving each (method, path shape), since the router
+    /// cannot hold two for the same pair.
+    endpoints: HashMap<(String, String), String>,
+    /// The first pattern seen for each path shape. The router matches
+    /// `/users/:id` and `/users/:uid` as one path, so their parameters
+    /// must have the same names.
+    shapes: HashMap<String, String>,
+    modules: Vec<Program>,
+    errors: Vec<String>,
+}
+
+impl ModuleLoader {
+    fn collect(
+        &mut self,
+        statements: &[SpannedStmt],
+        prefix: &str,
+        base_dir: Option<&Path>,
+        file: &str,
+        set: &mut RouteSet,
+    ) {
+        let group = statements.iter().find_map(|spanned| match &spanned.stmt {
+            Stmt::DecoratorStmt(dec) if dec.name == "group" => Some(dec),
+            _ => None,
+        });
+        let prefix = match group.and_then(|dec| positional_strings(dec).into_iter().next()) {
+            Some(group_prefix) => join_path(prefix, &group_prefix),
+            None => prefix.to_string(),
+        };
+        let scope = || match prefix.as_str() {
+            "" => HookScope::All,
+            prefix => HookScope::PathPrefix(prefix.to_string()),
+        };
+
+        for spanned in statements {
+            match &spanned.stmt {
+                Stmt::FnDef {
+                    name, decorators, ..
+                } => {
+                    let routes = extract_routes(name, decorators);
+                    if !routes.is_empty() {
+                        self.claim_handler(name, file);
+                    }
+                    for route in routes {
+                        let route = Route {
+                            pattern: join_path(&prefix, &route.pattern),
+                            ..route
+                        };
+                        self.claim_endpoint(&route);
+                        set.routes.push(route);
+                    }
+                    let (mut before, mut after) = (Vec::new(), Vec::new());
+                    extract_hooks(name, decorators, &mut before, &mut after);
+                    for hook in before.iter_mut().chain(after.iter_mut()) {
+                        hook.scope = match std::mem::replace(&mut hook.scope, HookScope::All) {
+                            HookScope::All => scope(),
+                            HookScope::PathPrefix(path) => {
+                                HookScope::PathPrefix(join_path(&prefix, &path))
+                            }
+                            handler => handler,
+                        };
+                    }
+                    set.before.extend(before);
+                    set.after.extend(after);
+                    set.rate_limits
+                        .extend(extract_rate_limit(name, decorators, &mut self.errors));
+                    set.sockets.extend(extract_socket_hooks(name, decorators));
+                    if decorators.iter().any(|dec| dec.name == "csrf") {
+                        set.csrf.push(name.clone());
+                    }
+                }
+                Stmt::DecoratorStmt(dec) if dec.name == "group" => {
+                    for function in named_strings(dec, "before") {
+                        set.before.push(Hook {
+                            function,
+                            scope: scope(),
+                        });
+                    }
+                    for function in named_strings(dec, "after") {
+                        set.after.push(Hook {
+                            function,
+                            scope: scope(),
+                        });
+                    }
+                }
+                Stmt::DecoratorStmt(dec) if dec.name == "mount" => {
+                    match positional_strings(dec).as_slice() {
+                        [mount_prefix, module] => {
+                            let mount_prefix = join_path(&prefix, mount_prefix);
+                            self.load(module, &mount_prefix, base_dir, true, set);
+                        }
+                        _ => self.errors.push(format!(
+                            "@mount in {} needs a path prefix and a module, e.g. @mount(\"/admin\", \"admin_routes\")",
+                            file
+                        )),
+                    }
+                }
+                // Namespaced imports keep their handlers off the global
+                // names the server resolves routes by.
+                Stmt::Import {
+                    path,
+                    names: None,
+                    alias: None,
+                } => {
+                    self.load(path, &prefix, base_dir, false, set);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Walk the file `module` resolves to. Failures only matter for
+    /// `@mount`: a broken plain import is already reported when the
+    /// program runs.
+    fn load(
+        &mut self,
+        module: &str,
+        prefix: &str,
+        base_dir: Option<&Path>,
+        mounted: bool,
+        set: &mut RouteSet,
+    ) {
+        if BUILTIN_MODULES.contains(&module) {
+            return;
+        }
+        let Some(path) = crate::package::resolve_import_from(module, base_dir) else {
+            if mounted {
+                self.errors
+                    .push(format!("cannot mount '{}': file not found", module));
+            }
+            return;
+        };
+        let path = std::fs::canonicalize(&path).unwrap_or(path);
+        if self.stack.contains(&path) || !self.seen.insert((path.clone(), prefix.to_string())) {
+            return;
+        }
+        let program = match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| parse_module(&source))
+        {
+            Ok(program) => program,
+            Err(e) => {
+                if mounted {
+                    self.errors
+                        .push(format!("cannot mount '{}': {}", module, e));
+                }
+                return;
+            }
+        };
+        self.stack.push(path.clone());
+        let file = path.display().to_string();
+        self.collect(&program.statements, prefix, path.parent(), &file, set);
+        self.stack.pop();
+        self.modules.push(program);
+    }
+
+    fn claim_endpoint(&mut self, route: &Route) {
+        // WebSocket upgrades and event streams are GET requests.
+        let method = match route.method.as_str() {
+            "WS" | "SSE" => "GET",
+            method => method,
+        };
+        let shape = path_shape(&route.pattern);
+        match self.shapes.get(&shape) {
+            Some(first) if *first != route.pattern => {
+                self.errors.push(format!(
+                    "routes {} and {} name the same path parameters differently; use the same names on both",
+                    first, route.pattern
+                ));
+                return;
+            }
+            Some(_) => {}
+            None => {
+                self.shapes.insert(shape.clone(), route.pattern.clone());
+            }
+        }
+        let key = (method.to_string(), shape);
+        match self.endpoints.get(&key) {
+            Some(owner) if *owner == route.handler_name => self.errors.push(format!(
+                "route {} {} is declared twice on '{}'",
+                method, route.pattern, owner
+            )),
+            Some(owner) => self.errors.push(format!(
+                "route {} {} is handled by both '{}' and '{}'; a method and path can only have one handler",
+                method, route.pattern, owner, route.handler_name
+            )),
+            None => {
+                self.endpoints.insert(key, route.handler_name.clone());
+            }
+        }
+    }
+
+    fn claim_handler(&mut self, name: &str, file: &str) {
+        match self.handlers.get(name) {
+            Some(owner) if owner != file => self.errors.push(format!(
+                "route handler '{}' is defined in both {} and {}; handlers share one namespace, so rename one",
+                name, owner, file
+            )),
+            Some(_) => {}
+            None => {
+                self.handlers.insert(name.to_string(), file.to_string());
+            }
+        }
+    }
+}
+
+fn parse_module(source: &str) -> Result<Program, String> {
+    let tokens = crate::lexer::Lexer::new(source)
+        .tokenize()
+        .map_err(|e| e.message)?;
+    crate::parser::Parser::new(tokens)
+        .parse_program()
+        .map_err(|e| e.message)
+}
+
+/// `pattern` with each `:param` segment reduced to `:`, so patterns the
+/// router treats as the same path compare equal.
+fn path_shape(pattern: &str) -> String {
+    pattern
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with(':') {
+                ":"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `prefix` + `path` with exactly one `/` between them: `/api/v1` and
+/// `/users` give `/api/v1/users`, and a `/` route is the prefix itself.
+fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        return path.to_string();
+    }
+    match path.trim_start_matches('/') {
+        "" => format!("/{}", prefix),
+        rest => format!("/{}/{}", prefix, rest),
+    }
+}
+
+fn positional_strings(dec: &Decorator) -> Vec<String> {
+    dec.args
+        .iter()
+        .filter_map(|arg| match arg {
+            DecoratorArg::Positional(Expr::StringLit(s)) => Some(s.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A named decorator argument given as a string or an array of strings.
+fn named_strings(dec: &Decorator, key: &str) -> Vec<String> {
+    dec.args
+        .iter()
+        .filter_map(|arg| match arg {
+            DecoratorArg::Named(k, value) if k == key => Some(value),
+            _ => None,
+        })
+        .flat_map(|value| match value {
+            Expr::StringLit(s) => vec![s.clone()],
+            Expr::Array(items) => items
+                .iter()
+                .filter_map(|item| match item {
+                    Expr::StringLit(s) => Some(s.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Methods accepted by `@route(method: ...)`. `ANY` (or `"*"`) registers
+/// a catch-all handler that answers every method on the path not claimed
+/// by a more specific decorator.
+const ROUTE_METHODS: &[&str] = &[
+    "GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS", "ANY",
+];
+
 fn extract_routes(name: &str, decorators: &[Decorator]) -> Vec<Route> {
     let mut routes = Vec::new();
     for dec in decorators {
-        let method = match dec.name.as_str() {
-            "get" => "GET",
-            "post" => "POST",
-            "put" => "PUT",
-            "delete" => "DELETE",
-            "ws" => "WS",
+        let methods = match dec.name.as_str() {
+            "get" => vec!["GET".to_string()],
+            "post" => vec!["POST".to_string()],
+            "put" => vec!["PUT".to_string()],
+            "patch" => vec!["PATCH".to_string()],
+            "delete" => vec!["DELETE".to_string()],
+            "head" => vec!["HEAD".to_string()],
+            "options" => vec!["OPTIONS".to_string()],
+            "ws" => vec!["WS".to_string()],
+            "sse" => vec!["SSE".to_string()],
+            "route" => route_decorator_methods(dec),
             _ => continue,
         };
         let path = dec
@@ -117,21 +746,228 @@ fn extract_routes(name: &str, decorators: &[Decorator]) -> Vec<Route> {
             })
             .unwrap_or_else(|| format!("/{}", name));
 
-        routes.push(Route {
-            method: method.to_string(),
-            pattern: path,
-            handler_name: name.to_string(),
-        });
+        for method in methods {
+            routes.push(Route {
+                method,
+                pattern: path.clone(),
+                handler_name: name.to_string(),
+            });
+        }
     }
     routes
 }
 
-fn extract_server_config(decorator: &Decorator) -> ServerConfig {
-    let mut config = ServerConfig {
-        port: 8080,
-        host: "127.0.0.1".to_string(),
-        cors: CorsMode::Restrictive,
+/// Resolve the `method:` argument of `@route`. Accepts a single string or
+/// an array of strings; a missing argument means catch-all. Unknown
+/// method names are dropped rather than registered as dead routes.
+fn route_decorator_methods(decorator: &Decorator) -> Vec<String> {
+    let raw: Vec<String> = decorator
+        .args
+        .iter()
+        .find_map(|arg| match arg {
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "method" => {
+                Some(vec![s.clone()])
+            }
+            DecoratorArg::Named(key, Expr::Array(items)) if key == "method" => Some(
+                items
+                    .iter()
+                    .filter_map(|item| match item {
+                        Expr::StringLit(s) => Some(s.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .unwrap_or_else(|| vec!["ANY".to_string()]);
+
+    let mut methods: Vec<String> = Vec::new();
+    for method in raw {
+        let method = match method.to_uppercase().as_str() {
+            "*" => "ANY".to_string(),
+            upper => upper.to_string(),
+        };
+        if ROUTE_METHODS.contains(&method.as_str()) && !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+    methods
+}
+
+/// Collect middleware hooks from a function's decorators.
+///
+/// - `@before` / `@after` with no arguments register the function as a
+///   global hook.
+/// - `@before("auth")` / `@after("audit")` attach the named functions to
+///   the decorated route handler only.
+/// - `@middleware("/admin", ...)` registers the function as a before-hook
+///   for every route under the given path prefixes (or all routes when
+///   no prefix is given).
+fn extract_hooks(
+    name: &str,
+    decorators: &[Decorator],
+    before: &mut Vec<Hook>,
+    after: &mut Vec<Hook>,
+) {
+    for dec in decorators {
+        let names: Vec<String> = dec
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                DecoratorArg::Positional(Expr::StringLit(s)) => Some(s.clone()),
+                _ => None,
+            })
+            .collect();
+        let target = match dec.name.as_str() {
+            "before" => &mut *before,
+            "after" => &mut *after,
+            "middleware" => {
+                if names.is_empty() {
+                    before.push(Hook {
+                        function: name.to_string(),
+                        scope: HookScope::All,
+                    });
+                }
+                for prefix in names {
+                    before.push(Hook {
+                        function: name.to_string(),
+                        scope: HookScope::PathPrefix(prefix),
+                    });
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if names.is_empty() {
+            target.push(Hook {
+                function: name.to_string(),
+                scope: HookScope::All,
+            });
+        }
+        for function in names {
+            target.push(Hook {
+                function,
+                scope: HookScope::Handler(name.to_string()),
+            });
+        }
+    }
+}
+
+/// `@rate_limit(100, per: "minute", key: "ip")`. `per` is `"second"`,
+/// `"minute"`, `"hour"`, `"day"` or any duration (default a minute).
+/// Arguments that would leave the route unlimited are reported through
+/// `errors` rather than ignored.
+fn extract_rate_limit(
+    name: &str,
+    decorators: &[Decorator],
+    errors: &mut Vec<String>,
+) -> Option<RateLimit> {
+    let dec = decorators.iter().find(|dec| dec.name == "rate_limit")?;
+    let mut limit = None;
+    let mut per = Duration::from_secs(60);
+    let mut key = RateKey::Ip;
+    let mut fail = |problem: String| {
+        errors.push(format!("@rate_limit on '{}' {}", name, problem));
+    };
+    for arg in &dec.args {
+        match arg {
+            DecoratorArg::Positional(Expr::Int(n)) => match u32::try_from(*n) {
+                Ok(n) if n > 0 => limit = Some(n),
+                _ => fail(format!(
+                    "has a limit of {}; it must be from 1 to {}",
+                    n,
+                    u32::MAX
+                )),
+            },
+            DecoratorArg::Named(k, value) if k == "per" => match value {
+                Expr::StringLit(s) if s == "second" => per = Duration::from_secs(1),
+                Expr::StringLit(s) if s == "minute" => per = Duration::from_secs(60),
+                Expr::StringLit(s) if s == "hour" => per = Duration::from_secs(3600),
+                Expr::StringLit(s) if s == "day" => per = Duration::from_secs(86400),
+                other => match duration_arg(other) {
+                    Some(duration) => per = duration,
+                    None => fail(
+                        "needs 'per' to be \"second\", \"minute\", \"hour\", \"day\" or a duration such as \"10m\"".to_string(),
+                    ),
+                },
+            },
+            DecoratorArg::Named(k, Expr::StringLit(s)) if k == "key" => {
+                key = match s.as_str() {
+                    "ip" => RateKey::Ip,
+                    other => match other.strip_prefix("header:") {
+                        Some(header) => RateKey::Header(header.trim().to_ascii_lowercase()),
+                        None => RateKey::Function(other.to_string()),
+                    },
+                };
+            }
+            DecoratorArg::Named(k, _) if k == "key" => {
+                fail("needs 'key' to be a string".to_string())
+            }
+            DecoratorArg::Named(k, _) => fail(format!(
+                "has no '{}' argument; pass the limit first, as in @rate_limit(100, per: \"minute\")",
+                k
+            )),
+            DecoratorArg::Positional(_) => {
+                fail("needs its limit to be an Int, as in @rate_limit(100)".to_string())
+            }
+        }
+    }
+    if limit.is_none()
+        && dec
+            .args
+            .iter()
+            .all(|arg| !matches!(arg, DecoratorArg::Positional(_)))
+    {
+        fail("needs a limit, as in @rate_limit(100, per: \"minute\")".to_string());
+    }
+    Some(RateLimit {
+        handler: name.to_string(),
+        limit: limit?,
+        per,
+        key,
+    })
+}
+
+/// `on_open:` / `on_close:` on a `@ws` decorator; `None` when it names
+/// neither.
+fn extract_socket_hooks(name: &str, decorators: &[Decorator]) -> Option<SocketHooks> {
+    let dec = decorators.iter().find(|dec| dec.name == "ws")?;
+    let callback = |key: &str| {
+        dec.args.iter().find_map(|arg| match arg {
+            DecoratorArg::Named(k, Expr::StringLit(s)) if k == key => Some(s.clone()),
+            _ => None,
+        })
     };
+    let (on_open, on_close) = (callback("on_open"), callback("on_close"));
+    if on_open.is_none() && on_close.is_none() {
+        return None;
+    }
+    Some(SocketHooks {
+        handler: name.to_string(),
+        on_open,
+        on_close,
+    })
+}
+
+/// `@static("/assets", dir: "./dist")`. The prefix defaults to `/`; a
+/// decorator without `dir:` is ignored.
+fn extract_static_mount(decorator: &Decorator) -> Option<StaticMount> {
+    let mut prefix = "/".to_string();
+    let mut dir = None;
+    for arg in &decorator.args {
+        match arg {
+            DecoratorArg::Positional(Expr::StringLit(s)) => prefix = s.clone(),
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "dir" => dir = Some(s.clone()),
+            _ => {}
+        }
+    }
+    dir.map(|dir| StaticMount { prefix, dir })
+}
+
+/// `@server(...)`. Limits and timeouts it cannot use are reported through
+/// `errors` rather than left at their defaults.
+fn extract_server_config(decorator: &Decorator, errors: &mut Vec<String>) -> ServerConfig {
+    let mut config = ServerConfig::default();
     for arg in &decorator.args {
         match arg {
             DecoratorArg::Named(key, Expr::Int(n)) if key == "port" => config.port = *n as u16,
@@ -144,12 +980,137 @@ fn extract_server_config(decorator: &Decorator) -> ServerConfig {
                     _ => CorsMode::Restrictive,
                 };
             }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "static" => {
+                config.static_dir = Some(s.clone())
+            }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "spa_fallback" => {
+                config.spa_fallback = Some(s.clone())
+            }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "tls_cert" => {
+                config.tls_cert = Some(s.clone())
+            }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "tls_key" => {
+                config.tls_key = Some(s.clone())
+            }
+            DecoratorArg::Named(key, value) if key == "max_inflight" => match value {
+                Expr::Int(n) if *n > 0 => config.max_inflight = Some(*n as usize),
+                _ => errors.push("@server's max_inflight must be an Int above 0".to_string()),
+            },
+            DecoratorArg::Named(key, value) if key == "max_body" => {
+                config.max_body = server_size(key, value, errors)
+            }
+            DecoratorArg::Named(key, value) if key == "max_upload" => {
+                config.max_upload = server_size(key, value, errors)
+            }
+            DecoratorArg::Named(key, value) if key == "request_timeout" => {
+                config.request_timeout = server_duration(key, value, errors)
+            }
+            DecoratorArg::Named(key, value) if key == "keepalive" => {
+                config.keepalive = server_duration(key, value, errors)
+            }
+            DecoratorArg::Named(key, value) if key == "drain_timeout" => {
+                config.drain_timeout = server_duration(key, value, errors)
+            }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "rate_limit_store" => {
+                config.rate_limit_store = Some(s.clone())
+            }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "docs" => {
+                config.docs = Some(s.clone())
+            }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "metrics" => {
+                config.metrics = Some(s.clone())
+            }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "health" => {
+                config.health = Some(s.clone())
+            }
+            DecoratorArg::Named(key, Expr::Bool(b)) if key == "compress" => config.compress = *b,
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "session_secret" => {
+                config.session_secret = Some(s.clone())
+            }
+            DecoratorArg::Named(key, Expr::StringLit(s)) if key == "session_store" => {
+                config.session_store = Some(s.clone())
+            }
+            DecoratorArg::Named(key, value) if key == "session_max_age" => {
+                config.session_max_age = server_duration(key, value, errors)
+            }
             _ => {}
         }
     }
     config
 }
 
+/// The `@server` size option `key`, which must be above 0.
+fn server_size(key: &str, value: &Expr, errors: &mut Vec<String>) -> Option<usize> {
+    let size = size_arg(value).filter(|&n| n > 0);
+    if size.is_none() {
+        errors.push(format!(
+            "@server's {} must be a size above 0, such as 1048576 or \"10mb\"",
+            key
+        ));
+    }
+    size
+}
+
+/// The `@server` duration option `key`.
+fn server_duration(key: &str, value: &Expr, errors: &mut Vec<String>) -> Option<Duration> {
+    let duration = duration_arg(value);
+    if duration.is_none() {
+        errors.push(format!(
+            "@server's {} must be a duration above 0, such as 30 or \"500ms\"",
+            key
+        ));
+    }
+    duration
+}
+
+/// A byte count: an Int, or a string with a `b`/`kb`/`mb`/`gb` suffix
+/// (`"10mb"`). Units are powers of 1024.
+fn size_arg(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Int(n) if *n >= 0 => Some(*n as usize),
+        Expr::StringLit(s) => {
+            let s = s.trim().to_ascii_lowercase();
+            let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (digits, unit) = s.split_at(split);
+            let scale: usize = match unit.trim() {
+                "" | "b" => 1,
+                "kb" | "k" => 1 << 10,
+                "mb" | "m" => 1 << 20,
+                "gb" | "g" => 1 << 30,
+                _ => return None,
+            };
+            digits.parse::<usize>().ok()?.checked_mul(scale)
+        }
+        _ => None,
+    }
+}
+
+/// A duration: seconds as an Int or Float, or a string with an
+/// `ms`/`s`/`m`/`h` suffix (`"500ms"`, `"2m"`).
+fn duration_arg(expr: &Expr) -> Option<Duration> {
+    match expr {
+        Expr::Int(n) if *n > 0 => Some(Duration::from_secs(*n as u64)),
+        Expr::Float(f) if *f > 0.0 => Some(Duration::from_secs_f64(*f)),
+        Expr::StringLit(s) => {
+            let s = s.trim().to_ascii_lowercase();
+            let split = s
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(s.len());
+            let (number, unit) = s.split_at(split);
+            let number: f64 = number.parse().ok()?;
+            let secs = match unit.trim() {
+                "ms" => number / 1000.0,
+                "" | "s" => number,
+                "m" => number * 60.0,
+                "h" => number * 3600.0,
+                _ => return None,
+            };
+            (secs > 0.0).then(|| Duration::from_secs_f64(secs))
+        }
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -183,6 +1144,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extracts_lifecycle_hooks_in_order() {
+        let prog = parse_program(
+            "@on_start fn open_pool() { }\n\
+             @on_shutdown fn close_pool() { }\n\
+             @on_start fn register() { }\n\
+             @on_shutdown fn deregister() { }\n",
+        );
+
+        let plan = extract_runtime_plan(&prog);
+        assert_eq!(plan.on_start, vec!["open_pool", "register"]);
+        assert_eq!(plan.on_shutdown, vec!["close_pool", "deregister"]);
+        assert!(plan.server.is_none());
+    }
+
     #[test]
     fn server_route_defaults_to_function_name() {
         let prog = parse_program("@server\n@get fn list_users() { return [] }\n");
@@ -221,6 +1197,340 @@ mod tests {
         assert_eq!(plan.watches[0].body.len(), 1);
     }
 
+    #[test]
+    fn extracts_patch_head_and_options_routes() {
+        let prog = parse_program(
+            "@server\n@patch(\"/users/:id\") fn update(id, body) { return body }\n@head(\"/users\") fn probe() { return null }\n@options(\"/users\") fn opts() { return null }\n",
+        );
+
+        let plan = extract_runtime_plan(&prog);
+        let server = plan.server.expect("server plan");
+        let methods: Vec<&str> = server.routes.iter().map(|r| r.method.as_str()).collect();
+        assert_eq!(methods, vec!["PATCH", "HEAD", "OPTIONS"]);
+        assert_eq!(server.routes[0].pattern, "/users/:id");
+    }
+
+    #[test]
+    fn route_decorator_expands_method_list() {
+        let prog = parse_program(
+            "@server\n@route(\"/items\", method: [\"get\", \"POST\", \"get\", \"bogus\"]) fn items() { return [] }\n",
+        );
+
+        let plan = extract_runtime_plan(&prog);
+        let server = plan.server.expect("server plan");
+        assert_eq!(
+            server.routes,
+            vec![
+                Route {
+                    method: "GET".to_string(),
+                    pattern: "/items".to_string(),
+                    handler_name: "items".to_string(),
+                },
+                Route {
+                    method: "POST".to_string(),
+                    pattern: "/items".to_string(),
+                    handler_name: "items".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn route_decorator_without_method_is_catch_all() {
+        let prog = parse_program(
+            "@server\n@route(\"/proxy\") fn proxy() { return null }\n@route(\"/star\", method: \"*\") fn star() { return null }\n",
+        );
+
+        let plan = extract_runtime_plan(&prog);
+        let server = plan.server.expect("server plan");
+        assert_eq!(server.routes.len(), 2);
+        assert_eq!(server.routes[0].method, "ANY");
+        assert_eq!(server.routes[1].method, "ANY");
+        assert_eq!(server.routes[1].pattern, "/star");
+    }
+
+    #[test]
+    fn extracts_sse_routes() {
+        let prog = parse_program("@server\n@sse(\"/events\") fn events() { emit 1 }\n");
+
+        let server = extract_runtime_plan(&prog).server.expect("server plan");
+        assert_eq!(
+            server.routes,
+            vec![Route {
+                method: "SSE".to_string(),
+                pattern: "/events".to_string(),
+                handler_name: "events".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn extracts_websocket_lifecycle_callbacks() {
+        let prog = parse_program(
+            "@server\n\
+             @ws(\"/chat\", on_open: \"joined\", on_close: \"left\") fn chat(msg) { return msg }\n\
+             @ws(\"/echo\") fn echo(msg) { return msg }\n",
+        );
+
+        let server = extract_runtime_plan(&prog).server.expect("server plan");
+        assert_eq!(
+            server.socket_hooks_for(&server.routes[0]),
+            Some(&SocketHooks {
+                handler: "chat".to_string(),
+                on_open: Some("joined".to_string()),
+                on_close: Some("left".to_string()),
+            })
+        );
+        assert_eq!(server.socket_hooks_for(&server.routes[1]), None);
+    }
+
+    #[test]
+    fn extracts_global_prefix_and_route_hooks() {
+        let prog = parse_program(
+            "@server\n@before fn log_in(req) { return null }\n@after fn log_out(req, res) { return null }\n@middleware(\"/admin\") fn admin_only(req) { return null }\n@get(\"/admin/users\")\n@before(\"auth\")\n@after(\"audit\") fn users() { return [] }\n@get(\"/administrator\") fn other() { return [] }\n",
+        );
+
+        let plan = extract_runtime_plan(&prog);
+        let server = plan.server.expect("server plan");
+        assert_eq!(
+            server.before,
+            vec![
+                Hook {
+                    function: "log_in".to_string(),
+                    scope: HookScope::All,
+                },
+                Hook {
+                    function: "admin_only".to_string(),
+                    scope: HookScope::PathPrefix("/admin".to_string()),
+                },
+                Hook {
+                    function: "auth".to_string(),
+                    scope: HookScope::Handler("users".to_string()),
+                },
+            ]
+        );
+
+        let (before, after) = server.hooks_for(&server.routes[0]);
+        assert_eq!(before, vec!["log_in", "admin_only", "auth"]);
+        assert_eq!(after, vec!["log_out", "audit"]);
+
+        let (before, after) = server.hooks_for(&server.routes[1]);
+        assert_eq!(before, vec!["log_in"]);
+        assert_eq!(after, vec!["log_out"]);
+    }
+
+    #[test]
+    fn hooks_run_global_then_prefix_then_route_whatever_the_declaration_order() {
+        let prog = parse_program(
+            "@server\n\
+             @get(\"/admin/users\")\n@before(\"auth\")\n@after(\"audit\") fn users() { return [] }\n\
+             @middleware(\"/admin\") fn admin_only(req) { return null }\n\
+             @before fn log_in(req) { return null }\n\
+             @after fn log_out(req, res) { return null }\n\
+             @before fn trace(req) { return null }\n",
+        );
+
+        let server = extract_runtime_plan(&prog).server.expect("server plan");
+        let (before, after) = server.hooks_for(&server.routes[0]);
+        assert_eq!(before, vec!["log_in", "trace", "admin_only", "auth"]);
+        assert_eq!(after, vec!["log_out", "audit"]);
+    }
+
+    #[test]
+    fn extracts_static_directories() {
+        let prog = parse_program(
+            "@server(static: \"./public\", spa_fallback: \"index.html\")\n@static(\"/assets\", dir: \"./dist\")\n@get(\"/api\") fn api() { return [] }\n",
+        );
+
+        let server = extract_runtime_plan(&prog).server.expect("server plan");
+        assert_eq!(server.config.static_dir.as_deref(), Some("./public"));
+        assert_eq!(server.config.spa_fallback.as_deref(), Some("index.html"));
+        assert_eq!(
+            server.statics,
+            vec![StaticMount {
+                prefix: "/assets".to_string(),
+                dir: "./dist".to_string(),
+            }]
+        );
+        assert_eq!(server.routes.len(), 1);
+    }
+
+    #[test]
+    fn extracts_tls_paths() {
+        let prog =
+            parse_program("@server(port: 8443, tls_cert: \"cert.pem\", tls_key: \"key.pem\")\n");
+
+        let config = extract_runtime_plan(&prog).server.expect("server").config;
+        assert_eq!(config.tls_cert.as_deref(), Some("cert.pem"));
+        assert_eq!(config.tls_key.as_deref(), Some("key.pem"));
+    }
+
+    #[test]
+    fn extracts_metrics_and_health_paths() {
+        let prog = parse_program("@server(metrics: \"/metrics\", health: \"/healthz\")\n");
+
+        let config = extract_runtime_plan(&prog).server.expect("server").config;
+        assert_eq!(config.metrics.as_deref(), Some("/metrics"));
+        assert_eq!(config.health.as_deref(), Some("/healthz"));
+    }
+
+    #[test]
+    fn extracts_session_settings_and_csrf_routes() {
+        let prog = parse_program(
+            "@server(session_secret: \"s3cret\", session_store: \"memory\", session_max_age: \"1h\")\n\
+             @csrf\n@post(\"/form\")\nfn submit() { return 1 }\n\
+             @get(\"/form\")\nfn show() { return 1 }\n",
+        );
+
+        let server = extract_runtime_plan(&prog).server.expect("server");
+        assert_eq!(server.config.session_secret.as_deref(), Some("s3cret"));
+        assert_eq!(server.config.session_store.as_deref(), Some("memory"));
+        assert_eq!(
+            server.config.session_max_age,
+            Some(Duration::from_secs(3600))
+        );
+        assert!(server.csrf_for(&server.routes[0]));
+        assert!(!server.csrf_for(&server.routes[1]));
+    }
+
+    #[test]
+    fn extracts_compression_flag() {
+        let prog = parse_program("@server(compress: true)\n");
+        assert!(
+            extract_runtime_plan(&prog)
+                .server
+                .expect("server")
+                .config
+                .compress
+        );
+
+        let prog = parse_program("@server(port: 8080)\n");
+        assert!(
+            !extract_runtime_plan(&prog)
+                .server
+                .expect("server")
+                .config
+                .compress
+        );
+    }
+
+    #[test]
+    fn extracts_limits_and_timeouts() {
+        let prog = parse_program(
+            "@server(max_inflight: 64, max_body: \"1mb\", max_upload: \"64mb\", request_timeout: \"500ms\", keepalive: 30, drain_timeout: \"10s\")\n",
+        );
+
+        let config = extract_runtime_plan(&prog).server.expect("server").config;
+        assert_eq!(config.max_inflight, Some(64));
+        assert_eq!(config.max_body, Some(1 << 20));
+        assert_eq!(config.max_upload, Some(64 << 20));
+        assert_eq!(config.request_timeout, Some(Duration::from_millis(500)));
+        assert_eq!(config.keepalive, Some(Duration::from_secs(30)));
+        assert_eq!(config.drain_timeout, Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn bad_limits_and_timeouts_are_reported() {
+        let prog = parse_program(
+            "@server(max_inflight: 0, max_body: 0, max_upload: \"10 TB\", request_timeout: \"abc\", keepalive: 0, drain_timeout: -1, session_max_age: \"1d\")\n",
+        );
+
+        let server = extract_runtime_plan(&prog).server.expect("server");
+        assert_eq!(
+            server.errors,
+            vec![
+                "@server's max_inflight must be an Int above 0",
+                "@server's max_body must be a size above 0, such as 1048576 or \"10mb\"",
+                "@server's max_upload must be a size above 0, such as 1048576 or \"10mb\"",
+                "@server's request_timeout must be a duration above 0, such as 30 or \"500ms\"",
+                "@server's keepalive must be a duration above 0, such as 30 or \"500ms\"",
+                "@server's drain_timeout must be a duration above 0, such as 30 or \"500ms\"",
+                "@server's session_max_age must be a duration above 0, such as 30 or \"500ms\"",
+            ]
+        );
+        assert_eq!(server.config.max_body, None);
+
+        let prog = parse_program("@server(max_body: \"10 MB\")\n");
+        let server = extract_runtime_plan(&prog).server.expect("server");
+        assert!(server.errors.is_empty(), "{:?}", server.errors);
+        assert_eq!(server.config.max_body, Some(10 << 20));
+    }
+
+    #[test]
+    fn extracts_rate_limits() {
+        let prog = parse_program(
+            "@server(port: 8080, rate_limit_store: \"limits.db\")\n\
+             @get(\"/a\")\n@rate_limit(100, per: \"minute\", key: \"ip\")\nfn a() { return 1 }\n\
+             @get(\"/b\")\n@rate_limit(5, per: \"10s\", key: \"header:X-Api-Key\")\nfn b() { return 1 }\n\
+             @get(\"/c\")\n@rate_limit(2, key: \"tenant_of\")\nfn c() { return 1 }\n\
+             @get(\"/d\")\nfn d() { return 1 }\n",
+        );
+
+        let server = extract_runtime_plan(&prog).server.expect("server");
+        assert_eq!(server.config.rate_limit_store.as_deref(), Some("limits.db"));
+        let limit = |i: usize| server.rate_limit_for(&server.routes[i]).cloned();
+        assert_eq!(
+            limit(0),
+            Some(RateLimit {
+                handler: "a".to_string(),
+                limit: 100,
+                per: Duration::from_secs(60),
+                key: RateKey::Ip,
+            })
+        );
+        let b = limit(1).expect("b is limited");
+        assert_eq!(b.per, Duration::from_secs(10));
+        assert_eq!(b.key, RateKey::Header("x-api-key".to_string()));
+        let c = limit(2).expect("c is limited");
+        assert_eq!(c.per, Duration::from_secs(60));
+        assert_eq!(c.key, RateKey::Function("tenant_of".to_string()));
+        assert_eq!(limit(3), None);
+    }
+
+    #[test]
+    fn bad_rate_limits_are_reported() {
+        let prog = parse_program(
+            "@server(port: 8080)\n\
+             @get(\"/a\")\n@rate_limit(10, per: \"minutes\")\nfn a() { return 1 }\n\
+             @get(\"/b\")\n@rate_limit(requests: 10)\nfn b() { return 1 }\n\
+             @get(\"/c\")\n@rate_limit(0)\nfn c() { return 1 }\n\
+             @get(\"/d\")\n@rate_limit(4294967296, per: \"hour\")\nfn d() { return 1 }\n\
+             @get(\"/e\")\n@rate_limit(5, key: 1)\nfn e() { return 1 }\n",
+        );
+
+        let server = extract_runtime_plan(&prog).server.expect("server");
+        assert_eq!(
+            server.errors,
+            vec![
+                "@rate_limit on 'a' needs 'per' to be \"second\", \"minute\", \"hour\", \"day\" or a duration such as \"10m\"",
+                "@rate_limit on 'b' has no 'requests' argument; pass the limit first, as in @rate_limit(100, per: \"minute\")",
+                "@rate_limit on 'b' needs a limit, as in @rate_limit(100, per: \"minute\")",
+                "@rate_limit on 'c' has a limit of 0; it must be from 1 to 4294967295",
+                "@rate_limit on 'd' has a limit of 4294967296; it must be from 1 to 4294967295",
+                "@rate_limit on 'e' needs 'key' to be a string",
+            ]
+        );
+    }
+
+    #[test]
+    fn size_and_duration_args_reject_bad_units() {
+        assert_eq!(size_arg(&Expr::Int(4096)), Some(4096));
+        assert_eq!(size_arg(&Expr::StringLit("2 KB".to_string())), Some(2048));
+        assert_eq!(size_arg(&Expr::StringLit("ten".to_string())), None);
+        assert_eq!(size_arg(&Expr::StringLit("5tb".to_string())), None);
+
+        assert_eq!(
+            duration_arg(&Expr::Float(1.5)),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            duration_arg(&Expr::StringLit("2m".to_string())),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(duration_arg(&Expr::Int(0)), None);
+        assert_eq!(duration_arg(&Expr::StringLit("soon".to_string())), None);
+    }
+
     #[test]
     fn routes_without_server_do_not_create_server_plan() {
         let prog = parse_program("@get(\"/users\") fn list_users() { return [] }\n");
@@ -228,4 +1538,169 @@ mod tests {
         let plan = extract_runtime_plan(&prog);
         assert!(plan.server.is_none());
     }
+
+    /// A fresh directory holding `files`, for tests that import modules.
+    fn module_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("forge_modules_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, source) in files {
+            std::fs::write(dir.join(file), source).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn routes_from_imported_and_mounted_modules_get_their_prefixes() {
+        let dir = module_dir(
+            "prefixes",
+            &[
+                (
+                    "users.fg",
+                    "@group(\"/users\", before: [\"auth\"])\n\
+                     @get(\"/\") fn list_users() { return [] }\n\
+                     @get(\"/:id\") fn show_user(id) { return id }\n\
+                     @before fn users_log() { return null }\n",
+                ),
+                (
+                    "admin_routes.fg",
+                    "@get(\"/stats\") fn stats() { return {} }\n\
+                     @middleware(\"/reports\") fn audit() { return null }\n\
+                     @get(\"/reports/daily\") fn daily() { return {} }\n",
+                ),
+            ],
+        );
+        let prog = parse_program(
+            "@server(port: 3000)\n\
+             import \"math\"\n\
+             import \"users\"\n\
+             @mount(\"/admin\", \"admin_routes\")\n\
+             @get(\"/health\") fn health() { return \"ok\" }\n",
+        );
+
+        let server = extract_runtime_plan_in(&prog, Some(&dir))
+            .server
+            .expect("server plan");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(server.errors.is_empty(), "{:?}", server.errors);
+        let routes: Vec<(&str, &str)> = server
+            .routes
+            .iter()
+            .map(|r| (r.pattern.as_str(), r.handler_name.as_str()))
+            .collect();
+        assert_eq!(
+            routes,
+            vec![
+                ("/users", "list_users"),
+                ("/users/:id", "show_user"),
+                ("/admin/stats", "stats"),
+                ("/admin/reports/daily", "daily"),
+                ("/health", "health"),
+            ]
+        );
+        // Module middleware stays inside the module's prefix.
+        let hooks = |path: &str| {
+            server.hooks_for(&Route {
+                method: "GET".to_string(),
+                pattern: path.to_string(),
+                handler_name: String::new(),
+            })
+        };
+        assert_eq!(hooks("/users/:id").0, vec!["auth", "users_log"]);
+        assert_eq!(hooks("/admin/reports/daily").0, vec!["audit"]);
+        assert!(hooks("/health").0.is_empty());
+    }
+
+    #[test]
+    fn module_route_problems_are_reported() {
+        let dir = module_dir(
+            "conflicts",
+            &[
+                ("a.fg", "@get(\"/a\") fn list() { return 1 }\n"),
+                ("b.fg", "@get(\"/b\") fn list() { return 2 }\n"),
+            ],
+        );
+        let prog = parse_program(
+            "@server(port: 3000)\n\
+             @mount(\"/a\", \"a\")\n\
+             @mount(\"/b\", \"b\")\n\
+             @mount(\"/c\", \"missing\")\n\
+             @mount(\"/d\")\n",
+        );
+
+        let server = extract_runtime_plan_in(&prog, Some(&dir))
+            .server
+            .expect("server plan");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(server.errors.len(), 3, "{:?}", server.errors);
+        assert!(server.errors[0].starts_with("route handler 'list' is defined in both"));
+        assert_eq!(server.errors[1], "cannot mount 'missing': file not found");
+        assert!(server.errors[2].contains("needs a path prefix and a module"));
+    }
+
+    #[test]
+    fn duplicate_endpoints_are_reported() {
+        let dir = module_dir(
+            "duplicates",
+            &[
+                ("a.fg", "@get(\"/x\") fn one() { return 1 }\n"),
+                ("b.fg", "@get(\"/x\") fn two() { return 2 }\n"),
+            ],
+        );
+        let prog = parse_program(
+            "@server(port: 3000)\n\
+             @mount(\"/m\", \"a\")\n\
+             @mount(\"/m\", \"b\")\n\
+             @get(\"/a\") fn first() { return 1 }\n\
+             @route(\"/a\", method: \"GET\") fn second() { return 2 }\n\
+             @get(\"/live\") @sse(\"/live\") fn live() { return 3 }\n\
+             @route(\"/any\") fn any_a() { return 4 }\n\
+             @route(\"/any\") fn any_b() { return 5 }\n\
+             @post(\"/a\") fn third() { return 6 }\n",
+        );
+
+        let server = extract_runtime_plan_in(&prog, Some(&dir))
+            .server
+            .expect("server plan");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(
+            server.errors,
+            vec![
+                "route GET /m/x is handled by both 'one' and 'two'; a method and path can only have one handler",
+                "route GET /a is handled by both 'first' and 'second'; a method and path can only have one handler",
+                "route GET /live is declared twice on 'live'",
+                "route ANY /any is handled by both 'any_a' and 'any_b'; a method and path can only have one handler",
+            ]
+        );
+    }
+
+    #[test]
+    fn routes_must_agree_on_parameter_names() {
+        let prog = parse_program(
+            "@server(port: 3000)\n\
+             @get(\"/users/:id\") fn show() { return 1 }\n\
+             @post(\"/users/:uid\") fn update() { return 2 }\n\
+             @get(\"/users/:id/posts/:post\") fn post() { return 3 }\n\
+             @get(\"/users/:id/posts/:id2\") fn other_post() { return 4 }\n\
+             @delete(\"/users/:id\") fn remove() { return 5 }\n",
+        );
+
+        let server = extract_runtime_plan(&prog).server.expect("server plan");
+        assert_eq!(
+            server.errors,
+            vec![
+                "routes /users/:id and /users/:uid name the same path parameters differently; use the same names on both",
+                "routes /users/:id/posts/:post and /users/:id/posts/:id2 name the same path parameters differently; use the same names on both",
+            ]
+        );
+    }
+
+    #[test]
+    fn join_path_keeps_one_separator() {
+        assert_eq!(join_path("", "/users"), "/users");
+        assert_eq!(join_path("/api/v1/", "/users"), "/api/v1/users");
+        assert_eq!(join_path("api", "/"), "/api");
+        assert_eq!(join_path("/api", "items/:id"), "/api/items/:id");
+    }
 }
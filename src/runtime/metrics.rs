@@ -0,0 +1,335 @@
+//! Process-wide metrics in the Prometheus text format.
+//!
+//! One [`Registry`] is shared by everything in the process: the server's
+//! own request series (when `@server(metrics:)` is set) and the series
+//! Forge code records through the `metrics` module. Series are created on
+//! first use, keyed by metric name plus label set, and a name keeps the
+//! kind it was first used with.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::OnceLock;
+
+use parking_lot::Mutex;
+
+/// Histogram bucket bounds in seconds, the Prometheus client default.
+pub(crate) const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Content type of [`Registry::render`] output.
+pub(crate) const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Histogram => "histogram",
+        }
+    }
+}
+
+/// Label pairs, sorted by name so equal sets share a series.
+type Labels = Vec<(String, String)>;
+
+enum Series {
+    Value(f64),
+    Histogram {
+        buckets: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+struct Family {
+    kind: Kind,
+    series: BTreeMap<Labels, Series>,
+}
+
+#[derive(Default)]
+struct Families {
+    families: BTreeMap<String, Family>,
+    help: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+pub(crate) struct Registry {
+    inner: Mutex<Families>,
+}
+
+impl Registry {
+    /// The registry shared by the whole process.
+    pub fn global() -> &'static Registry {
+        static GLOBAL: OnceLock<Registry> = OnceLock::new();
+        GLOBAL.get_or_init(Registry::default)
+    }
+
+    /// Add `by` (at least zero) to a counter.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)], by: f64) -> Result<(), String> {
+        if !(by >= 0.0 && by.is_finite()) {
+            return Err(format!("counter '{}' can only increase, got {}", name, by));
+        }
+        self.update(name, Kind::Counter, labels, |series| {
+            if let Series::Value(total) = series {
+                *total += by;
+            }
+        })
+    }
+
+    /// Set a gauge to `value`.
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) -> Result<(), String> {
+        self.update(name, Kind::Gauge, labels, |series| {
+            if let Series::Value(current) = series {
+                *current = value;
+            }
+        })
+    }
+
+    /// Record one observation in a histogram with the [`BUCKETS`] bounds.
+    pub fn observe(&self, name: &str, labels: &[(&str, &str)], value: f64) -> Result<(), String> {
+        self.update(name, Kind::Histogram, labels, |series| {
+            if let Series::Histogram {
+                buckets,
+                sum,
+                count,
+            } = series
+            {
+                if let Some(i) = BUCKETS.iter().position(|bound| value <= *bound) {
+                    buckets[i] += 1;
+                }
+                *sum += value;
+                *count += 1;
+            }
+        })
+    }
+
+    /// Set the `# HELP` text for a metric, before or after its first use.
+    pub fn describe(&self, name: &str, help: &str) -> Result<(), String> {
+        check_name(name)?;
+        self.inner
+            .lock()
+            .help
+            .insert(name.to_string(), help.to_string());
+        Ok(())
+    }
+
+    fn update(
+        &self,
+        name: &str,
+        kind: Kind,
+        labels: &[(&str, &str)],
+        apply: impl FnOnce(&mut Series),
+    ) -> Result<(), String> {
+        check_name(name)?;
+        let mut key: Labels = Vec::with_capacity(labels.len());
+        for (label, value) in labels {
+            check_label(name, kind, label)?;
+            key.push((label.to_string(), value.to_string()));
+        }
+        key.sort();
+        key.dedup_by(|a, b| a.0 == b.0);
+
+        let mut inner = self.inner.lock();
+        let family = inner
+            .families
+            .entry(name.to_string())
+            .or_insert_with(|| Family {
+                kind,
+                series: BTreeMap::new(),
+            });
+        if family.kind != kind {
+            return Err(format!(
+                "metric '{}' is a {}, not a {}",
+                name,
+                family.kind.name(),
+                kind.name()
+            ));
+        }
+        let series = family.series.entry(key).or_insert_with(|| match kind {
+            Kind::Histogram => Series::Histogram {
+                buckets: vec![0; BUCKETS.len()],
+                sum: 0.0,
+                count: 0,
+            },
+            _ => Series::Value(0.0),
+        });
+        apply(series);
+        Ok(())
+    }
+
+    /// Every series in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock();
+        let mut out = String::new();
+        for (name, family) in &inner.families {
+            if let Some(help) = inner.help.get(name) {
+                let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+                let _ = writeln!(out, "# HELP {} {}", name, help);
+            }
+            let _ = writeln!(out, "# TYPE {} {}", name, family.kind.name());
+            for (labels, series) in &family.series {
+                match series {
+                    Series::Value(value) => {
+                        let _ = writeln!(
+                            out,
+                            "{}{} {}",
+                            name,
+                            label_text(labels, None),
+                            number(*value)
+                        );
+                    }
+                    Series::Histogram {
+                        buckets,
+                        sum,
+                        count,
+                    } => {
+                        let mut cumulative = 0;
+                        for (bound, n) in BUCKETS.iter().zip(buckets) {
+                            cumulative += n;
+                            let _ = writeln!(
+                                out,
+                                "{}_bucket{} {}",
+                                name,
+                                label_text(labels, Some(&number(*bound))),
+                                cumulative
+                            );
+                        }
+                        let _ = writeln!(
+                            out,
+                            "{}_bucket{} {}",
+                            name,
+                            label_text(labels, Some("+Inf")),
+                            count
+                        );
+                        let labels = label_text(labels, None);
+                        let _ = writeln!(out, "{}_sum{} {}", name, labels, number(*sum));
+                        let _ = writeln!(out, "{}_count{} {}", name, labels, count);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid metric name '{}'", name))
+    }
+}
+
+fn check_label(metric: &str, kind: Kind, label: &str) -> Result<(), String> {
+    let mut chars = label.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !label.starts_with("__")
+        && !(kind == Kind::Histogram && label == "le");
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid label '{}' on metric '{}'", label, metric))
+    }
+}
+
+/// `{a="1",b="2"}`, with the histogram `le` label appended when given.
+fn label_text(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn number(value: f64) -> String {
+    if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else if value.is_nan() {
+        "NaN".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_gauges_and_histograms() {
+        let registry = Registry::default();
+        registry
+            .describe("jobs_total", "Jobs processed.\nBy queue.")
+            .unwrap();
+        registry
+            .counter("jobs_total", &[("queue", "email")], 1.0)
+            .unwrap();
+        registry
+            .counter("jobs_total", &[("queue", "email")], 2.0)
+            .unwrap();
+        registry
+            .counter("jobs_total", &[("queue", "say \"hi\"")], 1.0)
+            .unwrap();
+        registry.gauge("workers", &[], 4.0).unwrap();
+        registry.gauge("workers", &[], 3.5).unwrap();
+        registry.observe("latency_seconds", &[], 0.015625).unwrap();
+        registry.observe("latency_seconds", &[], 7.0).unwrap();
+        registry.observe("latency_seconds", &[], 60.0).unwrap();
+
+        let text = registry.render();
+        assert!(text.contains("# HELP jobs_total Jobs processed.\\nBy queue.\n"));
+        assert!(text.contains("# TYPE jobs_total counter\n"));
+        assert!(text.contains("jobs_total{queue=\"email\"} 3\n"));
+        assert!(text.contains("jobs_total{queue=\"say \\\"hi\\\"\"} 1\n"));
+        assert!(text.contains("# TYPE workers gauge\nworkers 3.5\n"));
+        assert!(text.contains("latency_seconds_bucket{le=\"0.01\"} 0\n"));
+        assert!(text.contains("latency_seconds_bucket{le=\"0.025\"} 1\n"));
+        assert!(text.contains("latency_seconds_bucket{le=\"10\"} 2\n"));
+        assert!(text.contains("latency_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("latency_seconds_sum 67.015625\n"));
+        assert!(text.contains("latency_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn rejects_bad_names_kind_changes_and_decrements() {
+        let registry = Registry::default();
+        registry.counter("hits_total", &[], 1.0).unwrap();
+        assert_eq!(
+            registry.gauge("hits_total", &[], 1.0),
+            Err("metric 'hits_total' is a counter, not a gauge".to_string())
+        );
+        assert!(registry.counter("hits_total", &[], -1.0).is_err());
+        assert!(registry.counter("9lives", &[], 1.0).is_err());
+        assert!(registry.counter("ok", &[("bad-label", "x")], 1.0).is_err());
+        assert!(registry.observe("h", &[("le", "1")], 1.0).is_err());
+    }
+}
@@ -1,6 +1,18 @@
 pub mod client;
 pub mod embedded;
+pub mod forms;
 pub mod host;
 pub mod metadata;
+pub mod metrics;
+pub mod openapi;
+pub mod rate_limit;
+pub mod response;
 pub mod server;
+pub mod sessions;
+pub mod sockets;
+pub mod static_files;
+pub mod testing;
+pub mod tls;
 pub mod tracing_init;
+pub mod validate;
+pub mod worker;
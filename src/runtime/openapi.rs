@@ -0,0 +1,446 @@
+//! OpenAPI 3.1 documents for decorated servers.
+//!
+//! Built from the same [`ServerPlan`] the server routes with, plus the
+//! program's handler signatures and type definitions:
+//!
+//! - Path parameters come from the route pattern, typed by the handler
+//!   parameter of the same name when it has an annotation.
+//! - Other plain handler parameters are query parameters (see
+//!   `server::bind_args`); `body` / `data` is the JSON request body.
+//! - The return annotation is the `200` response schema.
+//! - `struct`/`thing` definitions become object schemas and `type`
+//!   definitions `oneOf` schemas under `components.schemas`.
+//!
+//! `forge openapi app.fg` prints the document and
+//! `@server(docs: "/openapi.json")` serves it.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::parser::ast::{FieldDef, Param, Program, Stmt, TypeAnn, Variant};
+use crate::runtime::metadata::{Route, ServerPlan};
+
+/// Handler parameters filled from something other than the query string.
+const NON_QUERY_PARAMS: &[&str] = &[
+    "body", "data", "query", "qs", "req", "request", "ctx", "res", "response",
+];
+
+/// Methods a catch-all (`@route` without `method:`) is documented under.
+const CATCH_ALL_METHODS: &[&str] = &["get", "post", "put", "patch", "delete"];
+
+/// The OpenAPI 3.1 document for `server`, whose routes are handled by
+/// functions defined in `programs` (the entry program and its route
+/// modules).
+pub fn document(programs: &[&Program], server: &ServerPlan) -> JsonValue {
+    let mut handlers: HashMap<&str, (&[Param], Option<&TypeAnn>)> = HashMap::new();
+    let mut types = TypeDefs::default();
+    for spanned in programs.iter().flat_map(|program| &program.statements) {
+        match &spanned.stmt {
+            Stmt::FnDef {
+                name,
+                params,
+                return_type,
+                ..
+            } => {
+                handlers.insert(name, (params, return_type.as_ref()));
+            }
+            Stmt::StructDef { name, fields, .. } => {
+                types.structs.push((name, fields));
+            }
+            Stmt::TypeDef { name, variants, .. } => {
+                types.enums.push((name, variants));
+            }
+            _ => {}
+        }
+    }
+
+    let mut paths = Map::new();
+    let mut operation_ids = HashSet::new();
+    for route in &server.routes {
+        let methods: Vec<String> = match route.method.as_str() {
+            "WS" => continue,
+            "SSE" => vec!["get".to_string()],
+            "ANY" => CATCH_ALL_METHODS
+                .iter()
+                .filter(|method| {
+                    !server.routes.iter().any(|other| {
+                        other.pattern == route.pattern && other.method.eq_ignore_ascii_case(method)
+                    })
+                })
+                .map(|method| method.to_string())
+                .collect(),
+            method => vec![method.to_ascii_lowercase()],
+        };
+        let (params, returns) = handlers
+            .get(route.handler_name.as_str())
+            .copied()
+            .unwrap_or((&[], None));
+        let item = paths
+            .entry(openapi_path(&route.pattern))
+            .or_insert_with(|| JsonValue::Object(Map::new()));
+        for method in methods {
+            let mut operation = operation(&types, server, route, params, returns);
+            let mut id = route.handler_name.clone();
+            if !operation_ids.insert(id.clone()) {
+                id = format!("{}_{}", route.handler_name, method);
+                operation_ids.insert(id.clone());
+            }
+            operation.insert("operationId".to_string(), json!(id));
+            item[method.as_str()] = JsonValue::Object(operation);
+        }
+    }
+
+    let scheme = if server.config.tls_cert.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let mut doc = json!({
+        "openapi": "3.1.0",
+        "info": { "title": "Forge API", "version": "1.0.0" },
+        "servers": [{
+            "url": format!("{}://{}:{}", scheme, server.config.host, server.config.port)
+        }],
+        "paths": paths,
+    });
+    let schemas = types.component_schemas();
+    if !schemas.is_empty() {
+        doc["components"] = json!({ "schemas": schemas });
+    }
+    doc
+}
+
+/// `/users/:id/*rest` → `/users/{id}/{rest}`.
+fn openapi_path(pattern: &str) -> String {
+    pattern
+        .split('/')
+        .map(|segment| match segment.strip_prefix([':', '*']) {
+            Some(name) if !name.is_empty() => format!("{{{}}}", name),
+            _ => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn path_param_names(pattern: &str) -> Vec<&str> {
+    pattern
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix([':', '*']))
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn operation(
+    types: &TypeDefs,
+    server: &ServerPlan,
+    route: &Route,
+    params: &[Param],
+    returns: Option<&TypeAnn>,
+) -> Map<String, JsonValue> {
+    let annotation = |name: &str| {
+        params
+            .iter()
+            .find(|param| param.name == name)
+            .and_then(|param| param.type_ann.as_ref())
+    };
+    let path_params = path_param_names(&route.pattern);
+
+    let mut parameters: Vec<JsonValue> = path_params
+        .iter()
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": annotation(name)
+                    .map(|ann| types.schema(ann))
+                    .unwrap_or_else(|| json!({ "type": "string" })),
+            })
+        })
+        .collect();
+    for param in params {
+        if path_params.contains(&param.name.as_str())
+            || NON_QUERY_PARAMS.contains(&param.name.as_str())
+        {
+            continue;
+        }
+        let mut parameter = json!({
+            "name": param.name,
+            "in": "query",
+            "required": false,
+            "schema": param
+                .type_ann
+                .as_ref()
+                .map(|ann| types.schema(ann))
+                .unwrap_or_else(|| json!({ "type": "string" })),
+        });
+        if param.default.is_none() && !matches!(param.type_ann, None | Some(TypeAnn::Optional(_))) {
+            parameter["required"] = json!(true);
+        }
+        parameters.push(parameter);
+    }
+
+    let mut operation = Map::new();
+    if !parameters.is_empty() {
+        operation.insert("parameters".to_string(), json!(parameters));
+    }
+    if let Some(body) = params
+        .iter()
+        .find(|param| param.name == "body" || param.name == "data")
+    {
+        let schema = body
+            .type_ann
+            .as_ref()
+            .map(|ann| types.schema(ann))
+            .unwrap_or_else(|| json!({}));
+        operation.insert(
+            "requestBody".to_string(),
+            json!({ "content": { "application/json": { "schema": schema } } }),
+        );
+    }
+
+    let content = if route.method == "SSE" {
+        json!({ "text/event-stream": { "schema": { "type": "string" } } })
+    } else {
+        let schema = returns
+            .map(|ann| types.schema(ann))
+            .unwrap_or_else(|| json!({}));
+        json!({ "application/json": { "schema": schema } })
+    };
+    let mut responses = json!({ "200": { "description": "OK", "content": content } });
+    if server.rate_limit_for(route).is_some() {
+        responses["429"] = json!({
+            "description": "Rate limit exceeded",
+            "headers": { "Retry-After": { "schema": { "type": "integer" } } },
+        });
+    }
+    operation.insert("responses".to_string(), responses);
+    operation
+}
+
+/// User-defined types in declaration order.
+#[derive(Default)]
+struct TypeDefs<'a> {
+    structs: Vec<(&'a str, &'a [FieldDef])>,
+    enums: Vec<(&'a str, &'a [Variant])>,
+}
+
+impl TypeDefs<'_> {
+    fn is_defined(&self, name: &str) -> bool {
+        self.structs.iter().any(|(n, _)| *n == name) || self.enums.iter().any(|(n, _)| *n == name)
+    }
+
+    /// JSON Schema for a type annotation. Unknown names map to `{}` (any
+    /// value) rather than failing, since annotations are optional hints.
+    fn schema(&self, ann: &TypeAnn) -> JsonValue {
+        match ann {
+            TypeAnn::Simple(name) => match name.as_str() {
+                "Int" => json!({ "type": "integer" }),
+                "Float" | "Number" => json!({ "type": "number" }),
+                "String" => json!({ "type": "string" }),
+                "Bool" => json!({ "type": "boolean" }),
+                "Null" | "Void" => json!({ "type": "null" }),
+                "Object" | "Map" => json!({ "type": "object" }),
+                "Array" | "List" => json!({ "type": "array" }),
+                name if self.is_defined(name) => schema_ref(name),
+                _ => json!({}),
+            },
+            TypeAnn::Array(inner) => json!({ "type": "array", "items": self.schema(inner) }),
+            TypeAnn::Optional(inner) => nullable(self.schema(inner)),
+            TypeAnn::Tuple(items) => json!({
+                "type": "array",
+                "prefixItems": items.iter().map(|item| self.schema(item)).collect::<Vec<_>>(),
+                "minItems": items.len(),
+                "maxItems": items.len(),
+            }),
+            TypeAnn::Generic(name, args) => match (name.as_str(), args.as_slice()) {
+                ("Array" | "List" | "Set", [item]) => {
+                    json!({ "type": "array", "items": self.schema(item) })
+                }
+                ("Map" | "Object", [.., value]) => {
+                    json!({ "type": "object", "additionalProperties": self.schema(value) })
+                }
+                ("Option" | "Optional", [inner]) => nullable(self.schema(inner)),
+                // Handlers render `Ok(v)` as `v`; errors are not 200s.
+                ("Result", [ok, ..]) => self.schema(ok),
+                (name, _) if self.is_defined(name) => schema_ref(name),
+                _ => json!({}),
+            },
+            TypeAnn::Function(..) => json!({}),
+        }
+    }
+
+    fn component_schemas(&self) -> Map<String, JsonValue> {
+        let mut schemas = Map::new();
+        for (name, fields) in &self.structs {
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            for field in fields.iter() {
+                properties.insert(field.name.clone(), self.schema(&field.type_ann));
+                if field.default.is_none() && !matches!(field.type_ann, TypeAnn::Optional(_)) {
+                    required.push(field.name.clone());
+                }
+            }
+            let mut schema = json!({ "type": "object", "properties": properties });
+            if !required.is_empty() {
+                schema["required"] = json!(required);
+            }
+            schemas.insert(name.to_string(), schema);
+        }
+        // ADT values serialize as `{"__type__", "__variant__", "_0", ...}`.
+        for (name, variants) in &self.enums {
+            let one_of: Vec<JsonValue> = variants
+                .iter()
+                .map(|variant| {
+                    let mut properties = Map::new();
+                    properties.insert("__type__".to_string(), json!({ "const": name }));
+                    properties.insert("__variant__".to_string(), json!({ "const": variant.name }));
+                    for (i, field) in variant.fields.iter().enumerate() {
+                        properties.insert(format!("_{}", i), self.schema(field));
+                    }
+                    let required: Vec<&String> = properties.keys().collect();
+                    json!({
+                        "type": "object",
+                        "title": variant.name,
+                        "required": required,
+                        "properties": properties,
+                    })
+                })
+                .collect();
+            schemas.insert(name.to_string(), json!({ "oneOf": one_of }));
+        }
+        schemas
+    }
+}
+
+fn schema_ref(name: &str) -> JsonValue {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// Allow `null` alongside `schema`, using the 3.1 type-array form when the
+/// schema is a plain `type`.
+fn nullable(schema: JsonValue) -> JsonValue {
+    match schema.get("type").and_then(JsonValue::as_str) {
+        Some(ty) => {
+            let mut schema = schema.clone();
+            schema["type"] = json!([ty, "null"]);
+            schema
+        }
+        None => json!({ "anyOf": [schema, { "type": "null" }] }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::runtime::metadata::extract_runtime_plan;
+
+    fn openapi(src: &str) -> JsonValue {
+        let tokens = Lexer::new(src).tokenize().expect("lex failed");
+        let program = Parser::new(tokens).parse_program().expect("parse failed");
+        let server = extract_runtime_plan(&program).server.expect("server");
+        document(&[&program], &server)
+    }
+
+    #[test]
+    fn documents_routes_parameters_and_schemas() {
+        let doc = openapi(
+            r#"
+            @server(port: 8080)
+
+            struct User {
+                id: Int,
+                name: String,
+                email: ?String
+            }
+
+            type Role = Admin | Member | Guest(String)
+
+            @get("/users/:id")
+            fn show(id: Int, verbose: ?Bool) -> User {
+                return { id: id, name: "ada", email: null }
+            }
+
+            @post("/users")
+            @rate_limit(10, per: "minute")
+            fn create(body: User) -> [User] {
+                return [body]
+            }
+
+            @sse("/events")
+            fn events() { emit 1 }
+
+            @ws("/chat")
+            fn chat(msg) { return msg }
+            "#,
+        );
+
+        assert_eq!(doc["openapi"], "3.1.0");
+        assert_eq!(doc["servers"][0]["url"], "http://127.0.0.1:8080");
+
+        let show = &doc["paths"]["/users/{id}"]["get"];
+        assert_eq!(show["operationId"], "show");
+        assert_eq!(show["parameters"][0]["in"], "path");
+        assert_eq!(show["parameters"][0]["schema"]["type"], "integer");
+        assert_eq!(show["parameters"][1]["name"], "verbose");
+        assert_eq!(show["parameters"][1]["required"], false);
+        assert_eq!(
+            show["parameters"][1]["schema"]["type"],
+            json!(["boolean", "null"])
+        );
+        assert_eq!(
+            show["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/User"
+        );
+
+        let create = &doc["paths"]["/users"]["post"];
+        assert_eq!(
+            create["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/User"
+        );
+        assert_eq!(
+            create["responses"]["200"]["content"]["application/json"]["schema"]["type"],
+            "array"
+        );
+        assert!(create["responses"]["429"].is_object());
+
+        assert!(
+            doc["paths"]["/events"]["get"]["responses"]["200"]["content"]["text/event-stream"]
+                .is_object()
+        );
+        assert!(doc["paths"].get("/chat").is_none());
+
+        let user = &doc["components"]["schemas"]["User"];
+        assert_eq!(
+            user["properties"]["email"]["type"],
+            json!(["string", "null"])
+        );
+        assert_eq!(user["required"], json!(["id", "name"]));
+        let role = &doc["components"]["schemas"]["Role"]["oneOf"];
+        assert_eq!(role.as_array().map(Vec::len), Some(3));
+        assert_eq!(role[2]["properties"]["_0"]["type"], "string");
+    }
+
+    #[test]
+    fn catch_all_routes_fill_unclaimed_methods() {
+        let doc = openapi(
+            r#"
+            @server(port: 8080)
+
+            @get("/files/*rest")
+            fn read(rest) { return rest }
+
+            @route("/files/*rest")
+            fn fallback(rest) { return rest }
+            "#,
+        );
+
+        let item = &doc["paths"]["/files/{rest}"];
+        assert_eq!(item["get"]["operationId"], "read");
+        assert_eq!(item["post"]["operationId"], "fallback");
+        assert_eq!(item["delete"]["operationId"], "fallback_delete");
+    }
+}
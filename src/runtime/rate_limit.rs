@@ -0,0 +1,311 @@
+//! Token buckets behind `@rate_limit`.
+//!
+//! Each (route, client key) pair owns a bucket holding up to `limit`
+//! tokens that refills continuously at `limit / per`. A request takes one
+//! token; an empty bucket answers with how long until the next token, which
+//! the server turns into `429` and `Retry-After`.
+//!
+//! Buckets live in a [`RateLimitStore`]. [`MemoryStore`] keeps them in the
+//! server process; [`SqliteStore`] (selected with
+//! `@server(rate_limit_store: "limits.db")`) keeps them in a SQLite file
+//! opened through the `db` module, so processes on one host share limits.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::interpreter::RuntimeError;
+use crate::runtime::metadata::RateLimit;
+
+/// Where buckets are kept. `take` runs on the blocking pool, so stores may
+/// do synchronous I/O.
+pub trait RateLimitStore: Send + Sync {
+    /// Take one token from `key`'s bucket at `now_ms` (Unix milliseconds).
+    /// Returns `Ok(None)` when the request may proceed, or the time until
+    /// a token is available when it may not.
+    fn take(&self, key: &str, limit: &RateLimit, now_ms: u64) -> Result<Option<Duration>, String>;
+}
+
+/// Milliseconds since the Unix epoch. Wall-clock rather than monotonic so
+/// that processes sharing a SQLite store agree on bucket ages.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Bucket {
+    tokens: f64,
+    updated_ms: u64,
+}
+
+impl Bucket {
+    fn full(limit: &RateLimit, now_ms: u64) -> Self {
+        Self {
+            tokens: f64::from(limit.limit),
+            updated_ms: now_ms,
+        }
+    }
+
+    /// Refill for the time elapsed since the last update, then try to take
+    /// a token.
+    fn take(&mut self, limit: &RateLimit, now_ms: u64) -> Option<Duration> {
+        let capacity = f64::from(limit.limit);
+        let per_ms = limit.per.as_millis().max(1) as f64;
+        // A clock that steps backwards refills nothing rather than
+        // draining the bucket.
+        let elapsed = now_ms.saturating_sub(self.updated_ms) as f64;
+        self.tokens = (self.tokens + elapsed * capacity / per_ms).min(capacity);
+        self.updated_ms = now_ms.max(self.updated_ms);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            let wait_ms = ((1.0 - self.tokens) * per_ms / capacity).ceil();
+            Some(Duration::from_millis(wait_ms as u64))
+        }
+    }
+
+    /// When the bucket will be full again, after which it is
+    /// indistinguishable from a missing one.
+    fn full_at(&self, limit: &RateLimit) -> u64 {
+        let capacity = f64::from(limit.limit);
+        let missing = (capacity - self.tokens).max(0.0);
+        self.updated_ms + (missing * limit.per.as_millis() as f64 / capacity).ceil() as u64
+    }
+}
+
+/// Size at which the in-memory store first sweeps out full buckets.
+const MEMORY_SWEEP_AT: usize = 4096;
+
+/// In-process buckets. Full buckets are swept whenever the map has doubled
+/// since the last sweep, so one-off clients do not accumulate forever.
+pub struct MemoryStore {
+    inner: Mutex<MemoryBuckets>,
+}
+
+struct MemoryBuckets {
+    buckets: HashMap<String, (Bucket, u64)>,
+    sweep_at: usize,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(MemoryBuckets {
+                buckets: HashMap::new(),
+                sweep_at: MEMORY_SWEEP_AT,
+            }),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimitStore for MemoryStore {
+    fn take(&self, key: &str, limit: &RateLimit, now_ms: u64) -> Result<Option<Duration>, String> {
+        let mut inner = self.inner.lock();
+        let (bucket, full_at) = inner
+            .buckets
+            .entry(key.to_string())
+            .or_insert_with(|| (Bucket::full(limit, now_ms), now_ms));
+        let wait = bucket.take(limit, now_ms);
+        *full_at = bucket.full_at(limit);
+
+        if inner.buckets.len() >= inner.sweep_at {
+            inner.buckets.retain(|_, (_, full_at)| *full_at > now_ms);
+            inner.sweep_at = (inner.buckets.len() * 2).max(MEMORY_SWEEP_AT);
+        }
+        Ok(wait)
+    }
+}
+
+/// How many writes a SQLite store makes between sweeps of full buckets.
+const SQLITE_SWEEP_EVERY: u64 = 256;
+
+/// Buckets in a SQLite table, updated inside an immediate transaction so
+/// concurrent processes never both spend the last token. Every
+/// [`SQLITE_SWEEP_EVERY`] writes, the same transaction also deletes the
+/// buckets that have refilled, so one-off clients do not accumulate rows.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    writes: AtomicU64,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Result<Self, RuntimeError> {
+        let fail = |e: rusqlite::Error| {
+            RuntimeError::new(&format!(
+                "failed to open rate limit store '{}': {}",
+                path, e
+            ))
+        };
+        let conn = crate::stdlib::db::open_connection(path).map_err(fail)?;
+        conn.busy_timeout(Duration::from_secs(1)).map_err(fail)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS forge_rate_limits (
+                 key TEXT PRIMARY KEY,
+                 tokens REAL NOT NULL,
+                 updated_ms INTEGER NOT NULL,
+                 full_ms INTEGER NOT NULL
+             )",
+        )
+        .map_err(fail)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            writes: AtomicU64::new(0),
+        })
+    }
+}
+
+impl RateLimitStore for SqliteStore {
+    fn take(&self, key: &str, limit: &RateLimit, now_ms: u64) -> Result<Option<Duration>, String> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+            .map_err(|e| e.to_string())?;
+        let mut bucket = tx
+            .query_row(
+                "SELECT tokens, updated_ms FROM forge_rate_limits WHERE key = ?1",
+                [key],
+                |row| {
+                    Ok(Bucket {
+                        tokens: row.get(0)?,
+                        updated_ms: row.get::<_, i64>(1)?.max(0) as u64,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .unwrap_or_else(|| Bucket::full(limit, now_ms));
+        let wait = bucket.take(limit, now_ms);
+        tx.execute(
+            "INSERT INTO forge_rate_limits (key, tokens, updated_ms, full_ms) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(key) DO UPDATE SET tokens = excluded.tokens,
+                 updated_ms = excluded.updated_ms, full_ms = excluded.full_ms",
+            rusqlite::params![
+                key,
+                bucket.tokens,
+                bucket.updated_ms as i64,
+                bucket.full_at(limit) as i64
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        if self.writes.fetch_add(1, Ordering::Relaxed) % SQLITE_SWEEP_EVERY
+            == SQLITE_SWEEP_EVERY - 1
+        {
+            tx.execute(
+                "DELETE FROM forge_rate_limits WHERE full_ms <= ?1",
+                [now_ms as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::metadata::RateKey;
+
+    fn per_second(limit: u32) -> RateLimit {
+        RateLimit {
+            handler: "h".to_string(),
+            limit,
+            per: Duration::from_secs(1),
+            key: RateKey::Ip,
+        }
+    }
+
+    #[test]
+    fn bucket_allows_bursts_then_refills_evenly() {
+        let limit = per_second(2);
+        let store = MemoryStore::new();
+        assert_eq!(store.take("k", &limit, 1_000), Ok(None));
+        assert_eq!(store.take("k", &limit, 1_000), Ok(None));
+        assert_eq!(
+            store.take("k", &limit, 1_000),
+            Ok(Some(Duration::from_millis(500)))
+        );
+        // Other clients have their own bucket.
+        assert_eq!(store.take("other", &limit, 1_000), Ok(None));
+        // Half a second refills one token.
+        assert_eq!(store.take("k", &limit, 1_500), Ok(None));
+        assert!(store.take("k", &limit, 1_500).unwrap().is_some());
+    }
+
+    #[test]
+    fn memory_store_sweeps_full_buckets() {
+        let limit = per_second(1);
+        let store = MemoryStore::new();
+        for i in 0..MEMORY_SWEEP_AT - 1 {
+            store.take(&i.to_string(), &limit, 0).unwrap();
+        }
+        // Everything above refilled long ago; only this bucket survives.
+        store.take("fresh", &limit, 60_000).unwrap();
+        assert_eq!(store.inner.lock().buckets.len(), 1);
+    }
+
+    #[test]
+    fn sqlite_store_persists_buckets() {
+        let path = std::env::temp_dir().join(format!(
+            "forge_rate_limit_{}_{}.db",
+            std::process::id(),
+            now_ms()
+        ));
+        let path = path.to_string_lossy().to_string();
+        let limit = per_second(1);
+
+        assert_eq!(
+            SqliteStore::open(&path).unwrap().take("k", &limit, 0),
+            Ok(None)
+        );
+        // A second process (here: connection) sees the spent token.
+        let again = SqliteStore::open(&path).unwrap().take("k", &limit, 100);
+        assert_eq!(again, Ok(Some(Duration::from_millis(900))));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn sqlite_store_sweeps_full_buckets() {
+        let path = std::env::temp_dir().join(format!(
+            "forge_rate_limit_sweep_{}_{}.db",
+            std::process::id(),
+            now_ms()
+        ));
+        let path = path.to_string_lossy().to_string();
+        let limit = per_second(1);
+        let store = SqliteStore::open(&path).unwrap();
+        let rows = |store: &SqliteStore| -> i64 {
+            store
+                .conn
+                .lock()
+                .query_row("SELECT COUNT(*) FROM forge_rate_limits", [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+
+        for i in 0..SQLITE_SWEEP_EVERY - 1 {
+            store.take(&i.to_string(), &limit, 0).unwrap();
+        }
+        assert_eq!(rows(&store), (SQLITE_SWEEP_EVERY - 1) as i64);
+        // Everything above refilled long ago; only this bucket survives.
+        store.take("fresh", &limit, 60_000).unwrap();
+        assert_eq!(rows(&store), 1);
+        // The surviving bucket is still spent.
+        assert!(store.take("fresh", &limit, 60_000).unwrap().is_some());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,544 @@
+//! Rendering Forge handler results into HTTP responses.
+//!
+//! Handlers return plain values (serialized as `200 OK` JSON), `Response`
+//! values built by the `response()` / `redirect()` builtins, or `Result`s.
+//! `Ok(v)` renders `v`; `Err(v)` maps onto a 4xx/5xx status so handlers
+//! can reject bad input without throwing. Runtime errors thrown by the
+//! handler stay `500` and are rendered by the caller.
+
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use indexmap::IndexMap;
+use serde_json::Value as JsonValue;
+
+use crate::interpreter::Value;
+use crate::runtime::server::{forge_to_json, json_to_forge};
+use crate::runtime::static_files::etag_matches;
+use crate::stdlib::http::{is_response, RESPONSE_TYPE};
+
+/// Body of a rendered handler response.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseBody {
+    Json(JsonValue),
+    Text(String),
+    Html(String),
+    Bytes(Vec<u8>),
+    Empty,
+}
+
+/// A fully-resolved handler response, independent of axum so it can be
+/// produced on the blocking pool and inspected in tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandlerResponse {
+    pub status: StatusCode,
+    /// Lowercase header names in insertion order.
+    pub headers: Vec<(String, String)>,
+    pub body: ResponseBody,
+}
+
+impl HandlerResponse {
+    pub fn json(status: StatusCode, body: JsonValue) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: ResponseBody::Json(body),
+        }
+    }
+
+    /// `{"error": message}` with the given status.
+    pub fn error(status: StatusCode, message: impl Into<String>) -> Self {
+        Self::json(status, serde_json::json!({ "error": message.into() }))
+    }
+
+    /// Render a handler's return value.
+    pub fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Object(obj) if is_response(obj) => Self::from_response_object(obj),
+            Value::ResultOk(inner) => Self::from_value(inner),
+            Value::ResultErr(inner) => Self::from_err(inner),
+            other => Self::json(StatusCode::OK, forge_to_json(other)),
+        }
+    }
+
+    /// `Err(v)` results. A `Response` passes through untouched; an object
+    /// with an Int `status` in 400..=599 uses that status; anything else
+    /// is a `400 Bad Request`.
+    fn from_err(inner: &Value) -> Self {
+        match inner {
+            Value::Object(obj) if is_response(obj) => Self::from_response_object(obj),
+            Value::Object(obj) => {
+                let status = match obj.get("status") {
+                    Some(Value::Int(code)) if (400..=599).contains(code) => {
+                        StatusCode::from_u16(*code as u16).unwrap_or(StatusCode::BAD_REQUEST)
+                    }
+                    _ => StatusCode::BAD_REQUEST,
+                };
+                let error = obj
+                    .get("message")
+                    .or_else(|| obj.get("error"))
+                    .map(forge_to_json)
+                    .unwrap_or_else(|| {
+                        let mut rest = obj.clone();
+                        rest.shift_remove("status");
+                        forge_to_json(&Value::Object(rest))
+                    });
+                Self::json(status, serde_json::json!({ "error": error }))
+            }
+            Value::String(message) => Self::error(StatusCode::BAD_REQUEST, message.clone()),
+            other => Self::json(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({ "error": forge_to_json(other) }),
+            ),
+        }
+    }
+
+    fn from_response_object(obj: &IndexMap<String, Value>) -> Self {
+        // `status` can be changed after `response()` checked it, so check
+        // it again before it is narrowed to a u16.
+        let status = match obj.get("status") {
+            Some(Value::Int(code)) if (100..=599).contains(code) => {
+                StatusCode::from_u16(*code as u16).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            Some(Value::Int(code)) => {
+                return Self::error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("response status {} is out of range", code),
+                )
+            }
+            _ => StatusCode::OK,
+        };
+        let headers: Vec<(String, String)> = match obj.get("headers") {
+            Some(Value::Object(map)) => map
+                .iter()
+                .map(|(k, v)| {
+                    let v = match v {
+                        Value::String(s) => s.clone(),
+                        other => format!("{}", other),
+                    };
+                    (k.to_ascii_lowercase(), v)
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let kind = match obj.get("type") {
+            Some(Value::String(kind)) => Some(kind.as_str()),
+            _ => None,
+        };
+        let body = match render_body(obj.get("body").unwrap_or(&Value::Null), kind) {
+            Ok(body) => body,
+            Err(message) => return Self::error(StatusCode::INTERNAL_SERVER_ERROR, message),
+        };
+        Self {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    /// The response as a Forge `Response` value, in the same shape
+    /// `response()` builds. Passed to `@after` hooks as `res`.
+    pub fn to_value(&self) -> Value {
+        let (body, kind) = match &self.body {
+            ResponseBody::Json(json) => (json_to_forge(json.clone()), Some("json")),
+            ResponseBody::Text(text) => (Value::String(text.clone()), Some("text")),
+            ResponseBody::Html(html) => (Value::String(html.clone()), Some("html")),
+            ResponseBody::Bytes(bytes) => (
+                Value::Array(bytes.iter().map(|b| Value::Int(*b as i64)).collect()),
+                Some("bytes"),
+            ),
+            ResponseBody::Empty => (Value::Null, None),
+        };
+        let mut obj = IndexMap::new();
+        obj.insert(
+            "__type__".to_string(),
+            Value::String(RESPONSE_TYPE.to_string()),
+        );
+        obj.insert(
+            "status".to_string(),
+            Value::Int(self.status.as_u16() as i64),
+        );
+        obj.insert(
+            "headers".to_string(),
+            Value::Object(
+                self.headers
+                    .iter()
+                    .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                    .collect(),
+            ),
+        );
+        obj.insert("body".to_string(), body);
+        if let Some(kind) = kind {
+            obj.insert("type".to_string(), Value::String(kind.to_string()));
+        }
+        Value::Object(obj)
+    }
+
+    /// Answer a conditional `GET`. A `200` without an `ETag` gets a weak
+    /// one derived from its body, and when `if_none_match` lists that tag
+    /// the response becomes a bodiless `304 Not Modified` keeping only
+    /// the validator and caching headers. JSON bodies are encoded here,
+    /// once, since the tag needs their bytes.
+    pub fn revalidate(mut self, if_none_match: Option<&str>) -> Self {
+        if self.status != StatusCode::OK {
+            return self;
+        }
+        let etag = match self.headers.iter().find(|(k, _)| k == "etag") {
+            Some((_, tag)) => tag.clone(),
+            None => {
+                if let ResponseBody::Json(json) = &self.body {
+                    if !self.has_header("content-type") {
+                        self.headers
+                            .push(("content-type".to_string(), "application/json".to_string()));
+                    }
+                    self.body = ResponseBody::Bytes(json.to_string().into_bytes());
+                }
+                let tag = match &self.body {
+                    ResponseBody::Text(text) | ResponseBody::Html(text) => {
+                        weak_etag(text.as_bytes())
+                    }
+                    ResponseBody::Bytes(bytes) => weak_etag(bytes),
+                    ResponseBody::Json(_) | ResponseBody::Empty => weak_etag(&[]),
+                };
+                self.headers.push(("etag".to_string(), tag.clone()));
+                tag
+            }
+        };
+        if if_none_match.is_some_and(|candidates| etag_matches(candidates, &etag)) {
+            self.status = StatusCode::NOT_MODIFIED;
+            self.body = ResponseBody::Empty;
+            self.headers
+                .retain(|(k, _)| NOT_MODIFIED_HEADERS.contains(&k.as_str()));
+        }
+        self
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.headers.iter().any(|(k, _)| k == name)
+    }
+}
+
+/// Headers a `304` repeats from the `200` it stands for (RFC 9110 §15.4.5).
+const NOT_MODIFIED_HEADERS: &[&str] = &[
+    "etag",
+    "cache-control",
+    "content-location",
+    "date",
+    "expires",
+    "vary",
+];
+
+/// `W/"<size>-<digest>"` in hex, the digest being the first 8 bytes of the
+/// body's SHA-256.
+fn weak_etag(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(bytes);
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("W/\"{:x}-{}\"", bytes.len(), hex)
+}
+
+/// Pick the body encoding. An explicit `type` wins; otherwise strings are
+/// text, `null` is empty and everything else is JSON. Fails only for a
+/// `bytes` array holding something other than a byte.
+fn render_body(body: &Value, kind: Option<&str>) -> Result<ResponseBody, String> {
+    Ok(match (kind, body) {
+        (_, Value::Null) if kind != Some("json") => ResponseBody::Empty,
+        (Some("html"), Value::String(s)) => ResponseBody::Html(s.clone()),
+        (Some("html"), other) => ResponseBody::Html(format!("{}", other)),
+        (Some("bytes"), Value::Array(items)) => ResponseBody::Bytes(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    match item {
+                        Value::Int(n) => u8::try_from(*n).ok(),
+                        _ => None,
+                    }
+                    .ok_or_else(|| {
+                        format!(
+                            "bytes body item {} is {}; bytes must be Ints from 0 to 255",
+                            i, item
+                        )
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        (Some("bytes"), Value::String(s)) => ResponseBody::Bytes(s.clone().into_bytes()),
+        (Some("text"), Value::String(s)) | (None, Value::String(s)) => {
+            ResponseBody::Text(s.clone())
+        }
+        (Some("text"), other) => ResponseBody::Text(format!("{}", other)),
+        (_, other) => ResponseBody::Json(forge_to_json(other)),
+    })
+}
+
+impl IntoResponse for HandlerResponse {
+    fn into_response(self) -> Response {
+        let default_content_type = match &self.body {
+            ResponseBody::Json(_) => Some("application/json"),
+            ResponseBody::Text(_) => Some("text/plain; charset=utf-8"),
+            ResponseBody::Html(_) => Some("text/html; charset=utf-8"),
+            ResponseBody::Bytes(_) => Some("application/octet-stream"),
+            ResponseBody::Empty => None,
+        };
+        let needs_content_type = !self.has_header("content-type");
+
+        let mut response = match self.body {
+            ResponseBody::Json(json) => axum::body::Body::from(json.to_string()),
+            ResponseBody::Text(text) | ResponseBody::Html(text) => axum::body::Body::from(text),
+            ResponseBody::Bytes(bytes) => axum::body::Body::from(bytes),
+            ResponseBody::Empty => axum::body::Body::empty(),
+        }
+        .into_response();
+        *response.status_mut() = self.status;
+
+        let headers = response.headers_mut();
+        if let (true, Some(ct)) = (needs_content_type, default_content_type) {
+            headers.insert(
+                axum::http::header::CONTENT_TYPE,
+                HeaderValue::from_static(ct),
+            );
+        }
+        for (name, value) in self.headers {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    headers.append(name, value);
+                }
+                _ => tracing::warn!(
+                    target: "forge.server",
+                    header = %name,
+                    "dropping invalid response header from handler"
+                ),
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::http::{build_redirect, build_response};
+
+    fn obj(pairs: &[(&str, Value)]) -> Value {
+        Value::Object(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn plain_values_render_as_ok_json() {
+        let resp = HandlerResponse::from_value(&obj(&[("a", Value::Int(1))]));
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(resp.body, ResponseBody::Json(serde_json::json!({"a": 1})));
+    }
+
+    #[test]
+    fn ok_results_unwrap() {
+        let resp = HandlerResponse::from_value(&Value::ResultOk(Box::new(Value::Int(3))));
+        assert_eq!(resp.status, StatusCode::OK);
+        assert_eq!(resp.body, ResponseBody::Json(serde_json::json!(3)));
+    }
+
+    #[test]
+    fn response_builtin_sets_status_headers_and_text_body() {
+        let value = build_response(&[
+            Value::Int(201),
+            Value::String("created".to_string()),
+            obj(&[("X-Id", Value::Int(7))]),
+        ])
+        .expect("response");
+        let resp = HandlerResponse::from_value(&value);
+
+        assert_eq!(resp.status, StatusCode::CREATED);
+        assert_eq!(resp.headers, vec![("x-id".to_string(), "7".to_string())]);
+        assert_eq!(resp.body, ResponseBody::Text("created".to_string()));
+    }
+
+    #[test]
+    fn response_options_form_honours_type() {
+        let value = build_response(&[obj(&[
+            ("status", Value::Int(200)),
+            ("body", Value::String("<h1>hi</h1>".to_string())),
+            ("type", Value::String("html".to_string())),
+        ])])
+        .expect("response");
+        let resp = HandlerResponse::from_value(&value);
+        assert_eq!(resp.body, ResponseBody::Html("<h1>hi</h1>".to_string()));
+
+        let bytes = build_response(&[obj(&[
+            ("body", Value::Array(vec![Value::Int(0), Value::Int(255)])),
+            ("type", Value::String("bytes".to_string())),
+        ])])
+        .expect("response");
+        assert_eq!(
+            HandlerResponse::from_value(&bytes).body,
+            ResponseBody::Bytes(vec![0, 255])
+        );
+    }
+
+    #[test]
+    fn bytes_bodies_reject_values_that_are_not_bytes() {
+        let render = |items: Vec<Value>| {
+            let value = build_response(&[obj(&[
+                ("body", Value::Array(items)),
+                ("type", Value::String("bytes".to_string())),
+            ])])
+            .expect("response");
+            HandlerResponse::from_value(&value)
+        };
+        for (items, bad) in [
+            (vec![Value::Int(1), Value::Int(256)], "item 1 is 256"),
+            (vec![Value::Int(-1)], "item 0 is -1"),
+            (vec![Value::Int(7), Value::Float(2.0)], "item 1 is 2"),
+        ] {
+            let resp = render(items);
+            assert_eq!(resp.status, StatusCode::INTERNAL_SERVER_ERROR);
+            let ResponseBody::Json(body) = &resp.body else {
+                panic!("expected a JSON error, got {:?}", resp.body);
+            };
+            let message = body["error"].as_str().unwrap();
+            assert!(message.contains(bad), "{}", message);
+            assert!(message.contains("from 0 to 255"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn response_builtin_rejects_bad_status_and_type() {
+        assert!(build_response(&[Value::Int(42)]).is_err());
+        assert!(build_response(&[Value::String("200".to_string())]).is_err());
+        assert!(build_response(&[obj(&[("type", Value::String("xml".to_string()))])]).is_err());
+    }
+
+    #[test]
+    fn out_of_range_status_set_after_response_is_a_500() {
+        for code in [65736, -1, 42, 600] {
+            let Value::Object(mut fields) = build_response(&[Value::Int(200)]).expect("response")
+            else {
+                panic!("response() should build an object");
+            };
+            fields.insert("status".to_string(), Value::Int(code));
+            let resp = HandlerResponse::from_value(&Value::Object(fields));
+            assert_eq!(resp.status, StatusCode::INTERNAL_SERVER_ERROR, "{}", code);
+            assert_eq!(
+                resp.body,
+                ResponseBody::Json(serde_json::json!({
+                    "error": format!("response status {} is out of range", code)
+                }))
+            );
+        }
+    }
+
+    #[test]
+    fn redirect_sets_location_and_status() {
+        let value = build_redirect(&[Value::String("/login".to_string())]).expect("redirect");
+        let resp = HandlerResponse::from_value(&value);
+        assert_eq!(resp.status, StatusCode::FOUND);
+        assert_eq!(
+            resp.headers,
+            vec![("location".to_string(), "/login".to_string())]
+        );
+        assert_eq!(resp.body, ResponseBody::Empty);
+
+        assert!(build_redirect(&[Value::String("/x".to_string()), Value::Int(200)]).is_err());
+    }
+
+    #[test]
+    fn err_results_map_to_client_errors() {
+        let plain = HandlerResponse::from_value(&Value::ResultErr(Box::new(Value::String(
+            "bad input".to_string(),
+        ))));
+        assert_eq!(plain.status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            plain.body,
+            ResponseBody::Json(serde_json::json!({"error": "bad input"}))
+        );
+
+        let not_found = HandlerResponse::from_value(&Value::ResultErr(Box::new(obj(&[
+            ("status", Value::Int(404)),
+            ("message", Value::String("no such user".to_string())),
+        ]))));
+        assert_eq!(not_found.status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            not_found.body,
+            ResponseBody::Json(serde_json::json!({"error": "no such user"}))
+        );
+
+        let out_of_range = HandlerResponse::from_value(&Value::ResultErr(Box::new(obj(&[(
+            "status",
+            Value::Int(200),
+        )]))));
+        assert_eq!(out_of_range.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn to_value_round_trips_through_from_value() {
+        let mut original = HandlerResponse::error(StatusCode::FORBIDDEN, "nope");
+        original
+            .headers
+            .push(("x-reason".to_string(), "policy".to_string()));
+        assert_eq!(HandlerResponse::from_value(&original.to_value()), original);
+
+        let html = HandlerResponse {
+            status: StatusCode::OK,
+            headers: Vec::new(),
+            body: ResponseBody::Html("<p>hi</p>".to_string()),
+        };
+        assert_eq!(HandlerResponse::from_value(&html.to_value()), html);
+    }
+
+    #[test]
+    fn explicit_content_type_is_not_overridden() {
+        let value = build_response(&[
+            Value::Int(200),
+            Value::String("a,b".to_string()),
+            obj(&[("Content-Type", Value::String("text/csv".to_string()))]),
+        ])
+        .expect("response");
+        let response = HandlerResponse::from_value(&value).into_response();
+        let types: Vec<_> = response
+            .headers()
+            .get_all(axum::http::header::CONTENT_TYPE)
+            .iter()
+            .collect();
+        assert_eq!(types, vec!["text/csv"]);
+    }
+
+    #[test]
+    fn revalidate_tags_ok_responses_and_answers_matches() {
+        let render = || HandlerResponse::from_value(&obj(&[("a", Value::Int(1))]));
+        let tagged = render().revalidate(None);
+        let etag = tagged
+            .headers
+            .iter()
+            .find(|(k, _)| k == "etag")
+            .map(|(_, v)| v.clone())
+            .unwrap();
+        assert!(etag.starts_with("W/\"7-"), "{}", etag);
+        assert_eq!(tagged.body, ResponseBody::Bytes(b"{\"a\":1}".to_vec()));
+        assert!(tagged.has_header("content-type"));
+        assert_eq!(render().revalidate(None).headers, tagged.headers);
+
+        let not_modified = render().revalidate(Some(&format!("\"x\", {}", etag)));
+        assert_eq!(not_modified.status, StatusCode::NOT_MODIFIED);
+        assert_eq!(not_modified.body, ResponseBody::Empty);
+        assert_eq!(not_modified.headers, vec![("etag".to_string(), etag)]);
+
+        // A handler's own tag is used as is, and errors are never tagged.
+        let mut own = render();
+        own.headers.push(("etag".to_string(), "\"v2\"".to_string()));
+        assert_eq!(
+            own.revalidate(Some("W/\"v2\"")).status,
+            StatusCode::NOT_MODIFIED
+        );
+        let error = HandlerResponse::error(StatusCode::NOT_FOUND, "nope").revalidate(Some("*"));
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+        assert!(!error.has_header("etag"));
+    }
+}
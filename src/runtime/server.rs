@@ -1,23 +1,28 @@
 //! Forge HTTP Server — Axum + Tokio
 //!
 //! Per-request fork architecture: each incoming request gets its own
-//! Interpreter forked from a shared, read-only [`InterpreterTemplate`].
-//! Handlers run on tokio's blocking pool via [`tokio::task::spawn_blocking`]
-//! so synchronous Forge code never blocks an async worker thread.
+//! [`Worker`] forked from the shared, read-only program being served: an
+//! interpreter for source programs, a VM for compiled ones (see
+//! [`super::worker`]). Handlers run on tokio's blocking pool via
+//! [`tokio::task::spawn_blocking`] so synchronous Forge code never blocks
+//! an async worker thread.
 //!
 //! Concurrency guarantees:
 //! - **No global lock on the hot path.** Forks share only the
-//!   [`Arc<InterpreterTemplate>`], not any mutable state.
+//!   [`Arc<Served>`], not any mutable state.
 //! - **Backpressure.** A bounded [`tokio::sync::Semaphore`] prevents the
 //!   blocking pool from queueing unboundedly; excess requests get a
-//!   503 with `Retry-After: 1`.
+//!   503 with `Retry-After: 1`. The bound, the request body cap (413)
+//!   and an optional per-request deadline (504) come from `@server`.
 //! - **Cancellation.** Each request carries an [`Arc<AtomicBool>`] that
-//!   the per-request interpreter polls at every safe point (loop / call /
+//!   the per-request worker polls at every safe point (loop / call /
 //!   statement). A `Drop` guard on the response future flips it when
 //!   axum drops the future (client disconnect, server shutdown).
 //! - **Graceful shutdown.** SIGINT/SIGTERM triggers axum's graceful
-//!   shutdown; in-flight requests get up to 30s to finish before the
-//!   process exits.
+//!   shutdown; in-flight requests get `drain_timeout` (30s by default)
+//!   to finish. Requests still open then are cancelled, and if their
+//!   handlers have not returned a second later, `host::launch`
+//!   exits the process with status 1.
 //!
 //! Behavior change vs. the previous global-mutex model:
 //! - Top-level mutations made by a handler do not persist across
@@ -31,21 +36,30 @@
 
 use indexmap::IndexMap;
 use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::{Future, IntoFuture};
 use std::io::IsTerminal;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
-    extract::{Json, Path, Query, State},
+    body::{Body, Bytes},
+    extract::{ConnectInfo, DefaultBodyLimit, FromRequest, MatchedPath, Path, Query, State},
     http,
     http::StatusCode,
-    response::{IntoResponse, Json as JsonResponse, Response},
-    routing::{delete, get, post, put},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json as JsonResponse, Response,
+    },
+    routing::{any, get, on, MethodFilter},
     Extension, Router,
 };
 use serde_json::Value as JsonValue;
 use tokio::sync::Semaphore;
+use tower::ServiceExt;
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::request_id::{
     MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer,
@@ -53,10 +67,22 @@ use tower_http::request_id::{
 use tower_http::trace::{DefaultOnResponse, TraceLayer};
 use tracing::Level;
 
-use crate::interpreter::{Interpreter, RuntimeError, Value};
-use crate::runtime::metadata::{CorsMode, ServerPlan};
+use crate::interpreter::{RuntimeError, Value};
+use crate::parser::ast::{Param, TypeAnn};
+use crate::runtime::forms::{self, UploadDir};
+use crate::runtime::metadata::{CorsMode, RateKey, RateLimit, ServerPlan};
+use crate::runtime::metrics::{self, Registry};
+use crate::runtime::rate_limit::{self, MemoryStore, RateLimitStore, SqliteStore};
+use crate::runtime::response::HandlerResponse;
+use crate::runtime::sessions::{self, Sessions};
+use crate::runtime::sockets::{self, SocketCallbacks};
+use crate::runtime::static_files::static_router;
+use crate::runtime::tls::{self, TlsListener};
 use crate::runtime::tracing_init;
-use futures_util::{SinkExt, StreamExt};
+use crate::runtime::validate::Checker;
+use crate::runtime::worker::{Served, Worker};
+use crate::stdlib::http::is_response;
+use axum::serve::ListenerExt;
 
 /// Cap on the recorded `request_id` length.
 ///
@@ -120,44 +146,54 @@ fn extract_request_id(rid: &RequestId) -> String {
 /// is better than client-perceived hangs followed by RST.
 const DEFAULT_MAX_INFLIGHT: usize = 512;
 
-/// Read-only template the server forks per request.
-///
-/// Construction-time only: once wrapped in `Arc<InterpreterTemplate>` and
-/// installed on a router, the inner [`Interpreter`] must not be mutated.
-/// All per-request work happens on the forked interpreter returned by
-/// [`Self::fork`].
-pub struct InterpreterTemplate {
-    inner: Interpreter,
-}
+/// Default cap on request bodies (axum's own default), overridable with
+/// `@server(max_body:)`. Larger bodies are rejected with 413 before any
+/// handler runs.
+const DEFAULT_MAX_BODY: usize = 2 * 1024 * 1024;
 
-impl InterpreterTemplate {
-    pub fn new(interp: Interpreter) -> Self {
-        Self { inner: interp }
-    }
+/// Default cap on a whole `multipart/form-data` request, overridable with
+/// `@server(max_upload:)`. Text fields inside it are still held to
+/// `max_body` each.
+const DEFAULT_MAX_UPLOAD: usize = 32 * 1024 * 1024;
 
-    /// Produce a fresh per-request interpreter. Cheap relative to handler
-    /// cost, expensive relative to a mutex acquire — the win is that two
-    /// concurrent requests never block on each other.
-    pub fn fork(&self) -> Interpreter {
-        self.inner.fork_for_serving()
-    }
-}
+/// Default time shutdown waits for in-flight requests, overridable with
+/// `@server(drain_timeout:)`. The same as Kubernetes' default termination
+/// grace period.
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time handlers cancelled at the end of the drain window get to notice
+/// and return before shutdown gives up on them.
+const CANCEL_GRACE: Duration = Duration::from_secs(1);
+
+/// Series the server records when `@server(metrics:)` is set.
+const REQUESTS_METRIC: &str = "forge_http_requests_total";
+const DURATION_METRIC: &str = "forge_http_request_duration_seconds";
+const INFLIGHT_METRIC: &str = "forge_http_requests_in_flight";
+const INFLIGHT_LIMIT_METRIC: &str = "forge_http_max_inflight";
+const REJECTED_METRIC: &str = "forge_http_rejected_total";
 
 /// Application state passed to every axum handler.
 #[derive(Clone)]
 pub struct AppState {
-    template: Arc<InterpreterTemplate>,
+    /// The program every request forks its [`Worker`] from. Once installed
+    /// on a router it is never mutated; forking is cheap relative to
+    /// handler cost, and two concurrent requests never block on each other.
+    template: Arc<Served>,
     permits: Arc<Semaphore>,
+    /// `@server(request_timeout:)`; `None` lets handlers run until done.
+    request_timeout: Option<Duration>,
+    /// Whether `@server(metrics:)` is set, so capacity rejections count.
+    metrics: bool,
 }
 
 /// Drop guard that signals cancellation when axum drops the response
 /// future (client disconnect, request timeout, server shutdown).
 ///
-/// The forked interpreter's [`Interpreter::cancelled`] points at the
+/// The forked worker's cancel flag (see [`Worker::set_cancelled`]) is the
 /// same `Arc<AtomicBool>`, so the long-running blocking task observes
 /// the flip at its next safe point and returns a `cancelled` error —
 /// freeing the blocking-pool thread and the fork's memory.
-struct CancelOnDrop(Arc<AtomicBool>);
+pub(crate) struct CancelOnDrop(pub(crate) Arc<AtomicBool>);
 
 impl Drop for CancelOnDrop {
     fn drop(&mut self) {
@@ -188,76 +224,614 @@ fn to_axum_path(forge_path: &str) -> String {
         .join("/")
 }
 
-fn call_handler(
-    interp: &mut Interpreter,
-    handler_name: &str,
-    path_params: &HashMap<String, String>,
-    query_params: &HashMap<String, String>,
-    body: Option<JsonValue>,
-) -> (StatusCode, JsonValue) {
-    let handler = match interp.env.get(handler_name) {
-        Some(v) => v,
-        None => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                serde_json::json!({"error": format!("handler '{}' not found", handler_name)}),
-            )
+/// Map a route method from [`crate::runtime::metadata::Route`] onto the
+/// axum filter. Only called for the concrete HTTP methods; `ANY`, `SSE`
+/// and `WS` are routed separately.
+fn method_filter(method: &str) -> MethodFilter {
+    match method {
+        "POST" => MethodFilter::POST,
+        "PUT" => MethodFilter::PUT,
+        "PATCH" => MethodFilter::PATCH,
+        "DELETE" => MethodFilter::DELETE,
+        "HEAD" => MethodFilter::HEAD,
+        "OPTIONS" => MethodFilter::OPTIONS,
+        _ => MethodFilter::GET,
+    }
+}
+
+/// Everything a handler can observe about the inbound request.
+///
+/// Built on the async side from axum extractors, then moved into the
+/// blocking task. Kept as plain Rust data (not Forge `Value`s) so the
+/// conversion cost is only paid when a handler actually asks for `req`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HandlerRequest {
+    pub method: String,
+    pub path: String,
+    pub request_id: String,
+    pub path_params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+    /// Header names are lowercased; repeated headers are joined with `", "`.
+    pub headers: IndexMap<String, String>,
+    pub remote_addr: Option<SocketAddr>,
+    /// Parsed JSON body, when the request carried one.
+    pub body: Option<JsonValue>,
+    /// Empty for `multipart/form-data`, whose parts are streamed.
+    pub raw_body: Bytes,
+}
+
+impl HandlerRequest {
+    /// Cookies from the `Cookie` header, in the order the client sent them.
+    /// Malformed pairs (no `=`) are skipped rather than rejected.
+    pub fn cookies(&self) -> IndexMap<String, String> {
+        let mut cookies = IndexMap::new();
+        if let Some(header) = self.headers.get("cookie") {
+            for pair in header.split(';') {
+                if let Some((name, value)) = pair.trim().split_once('=') {
+                    cookies.insert(name.trim().to_string(), value.trim().to_string());
+                }
+            }
         }
-    };
+        cookies
+    }
+
+    /// The body as a Forge value: parsed JSON when available, otherwise the
+    /// raw text, otherwise an empty object (the historical default for
+    /// `body`/`data` parameters on bodyless requests).
+    fn body_value(&self) -> Value {
+        match &self.body {
+            Some(json) => json_to_forge(json.clone()),
+            None if !self.raw_body.is_empty() => {
+                Value::String(String::from_utf8_lossy(&self.raw_body).into_owned())
+            }
+            None => Value::Object(IndexMap::new()),
+        }
+    }
+
+    /// The `req` object passed to handlers that declare a `req` or
+    /// `request` parameter.
+    pub fn to_value(&self) -> Value {
+        let mut obj = IndexMap::new();
+        obj.insert("method".to_string(), Value::String(self.method.clone()));
+        obj.insert("path".to_string(), Value::String(self.path.clone()));
+        obj.insert("params".to_string(), string_map_value(&self.path_params));
+        obj.insert("query".to_string(), string_map_value(&self.query));
+        obj.insert("headers".to_string(), string_map_value(&self.headers));
+        obj.insert("cookies".to_string(), string_map_value(&self.cookies()));
+        obj.insert("body".to_string(), self.body_value());
+        obj.insert(
+            "raw_body".to_string(),
+            Value::String(String::from_utf8_lossy(&self.raw_body).into_owned()),
+        );
+        obj.insert(
+            "remote_addr".to_string(),
+            self.remote_addr
+                .map(|addr| Value::String(addr.to_string()))
+                .unwrap_or(Value::Null),
+        );
+        obj.insert(
+            "request_id".to_string(),
+            Value::String(self.request_id.clone()),
+        );
+        Value::Object(obj)
+    }
+}
+
+/// The `Content-Type` of a `multipart/form-data` request, which is read
+/// as a stream rather than buffered.
+fn multipart_type(headers: &IndexMap<String, String>) -> Option<&str> {
+    headers
+        .get("content-type")
+        .map(String::as_str)
+        .filter(|ct| ct.to_ascii_lowercase().starts_with("multipart/form-data"))
+}
+
+fn string_map_value<'a>(pairs: impl IntoIterator<Item = (&'a String, &'a String)>) -> Value {
+    Value::Object(
+        pairs
+            .into_iter()
+            .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+            .collect(),
+    )
+}
+
+/// Flatten an axum `HeaderMap` into lowercase name → value pairs.
+/// Non-UTF-8 header values are decoded lossily; repeated headers are
+/// joined with `", "` per RFC 9110 §5.3.
+fn collect_headers(headers: &http::HeaderMap) -> IndexMap<String, String> {
+    let mut out: IndexMap<String, String> = IndexMap::new();
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        out.entry(name.as_str().to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    out
+}
+
+/// Decode a buffered request body. JSON content types (and untyped bodies
+/// that happen to parse as JSON) are parsed, and a JSON content type that
+/// fails to parse is a client error. Urlencoded forms become an object of
+/// strings. Anything else is left raw for `req.raw_body`.
+fn parse_body(
+    headers: &IndexMap<String, String>,
+    raw: &Bytes,
+) -> Result<Option<JsonValue>, String> {
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    let content_type = headers
+        .get("content-type")
+        .map(|ct| ct.to_ascii_lowercase())
+        .unwrap_or_default();
+    if content_type.starts_with("application/x-www-form-urlencoded") {
+        return Ok(Some(forms::parse_urlencoded(raw)));
+    }
+    let is_json = content_type.starts_with("application/json") || content_type.contains("+json");
+    match serde_json::from_slice::<JsonValue>(raw) {
+        Ok(json) if is_json || content_type.is_empty() => Ok(Some(json)),
+        Ok(_) => Ok(None),
+        Err(e) if is_json => Err(format!("invalid JSON body: {}", e)),
+        Err(_) => Ok(None),
+    }
+}
 
-    let mut args: Vec<Value> = Vec::new();
-    if let Value::Function { ref params, .. } = handler {
-        for param in params {
-            if let Some(val) = path_params.get(&param.name) {
-                args.push(Value::String(val.clone()));
+/// Middleware hooks, the rate limit and session handling that wrap one
+/// route, resolved from the [`ServerPlan`] when the router is built.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RouteHooks {
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    pub rate_limit: Option<RouteLimit>,
+    pub sessions: Option<Arc<Sessions>>,
+    /// `@csrf`: unsafe requests must carry the session's token.
+    pub csrf: bool,
+}
+
+/// A route's `@rate_limit`, bound to the server's bucket store.
+#[derive(Clone)]
+pub(crate) struct RouteLimit {
+    pub rule: RateLimit,
+    /// `"GET /path"`: keeps this route's buckets apart from other routes'.
+    pub route: String,
+    pub store: Arc<dyn RateLimitStore>,
+}
+
+impl std::fmt::Debug for RouteLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouteLimit")
+            .field("rule", &self.rule)
+            .field("route", &self.route)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Bind a handler's (or hook's) parameters by name. `ctx` holds the values
+/// attached by earlier `@before` hooks; `response` is only present for
+/// `@after` hooks.
+fn bind_args(
+    params: &[Param],
+    request: &HandlerRequest,
+    ctx: &IndexMap<String, Value>,
+    response: Option<&Value>,
+) -> Vec<Value> {
+    params
+        .iter()
+        .map(|param| {
+            if let Some(val) = request.path_params.get(&param.name) {
+                Value::String(val.clone())
             } else if param.name == "body" || param.name == "data" {
-                args.push(
-                    body.as_ref()
-                        .map(|b| json_to_forge(b.clone()))
-                        .unwrap_or(Value::Object(IndexMap::new())),
-                );
+                request.body_value()
             } else if param.name == "query" || param.name == "qs" {
-                let obj: IndexMap<String, Value> = query_params
-                    .iter()
-                    .map(|(k, v)| (k.clone(), Value::String(v.clone())))
-                    .collect();
-                args.push(Value::Object(obj));
-            } else if let Some(val) = query_params.get(&param.name) {
-                args.push(Value::String(val.clone()));
+                string_map_value(&request.query)
+            } else if param.name == "req" || param.name == "request" {
+                let mut req = request.to_value();
+                if let Value::Object(ref mut obj) = req {
+                    obj.insert("ctx".to_string(), Value::Object(ctx.clone()));
+                }
+                req
+            } else if param.name == "ctx" {
+                Value::Object(ctx.clone())
+            } else if let (Some(res), "res" | "response") = (response, param.name.as_str()) {
+                res.clone()
+            } else if let Some(val) = request.query.get(&param.name) {
+                Value::String(val.clone())
             } else {
-                args.push(Value::Null);
+                Value::Null
             }
-        }
+        })
+        .collect()
+}
+
+/// Check bound arguments against their parameter annotations: path and
+/// query params are parsed into `Int` / `Float` / `Bool`, a `body` param
+/// is validated against its struct or collection type, and missing query
+/// params take their declared default. Any violation short-circuits with a
+/// `422` listing all of them.
+fn typed_args(
+    worker: &mut Worker,
+    function: &str,
+    params: &[Param],
+    mut args: Vec<Value>,
+    request: &HandlerRequest,
+) -> Result<Vec<Value>, HandlerResponse> {
+    if params
+        .iter()
+        .all(|p| p.type_ann.is_none() && p.default.is_none())
+    {
+        return Ok(args);
+    }
+    let mut checker = Checker::new();
+    for (param, arg) in params.iter().zip(args.iter_mut()) {
+        let name = param.name.as_str();
+        let value = std::mem::replace(arg, Value::Null);
+        *arg = if request.path_params.contains_key(name) {
+            match &param.type_ann {
+                Some(ann) => checker.param(worker.declarations(), "path", ann, value, name),
+                None => value,
+            }
+        } else if matches!(name, "body" | "data") {
+            match &param.type_ann {
+                Some(ann) => {
+                    checker.value(worker.declarations(), "body", ann, value, name.to_string())
+                }
+                None => value,
+            }
+        } else if matches!(
+            name,
+            "query" | "qs" | "req" | "request" | "ctx" | "res" | "response"
+        ) {
+            value
+        } else if request.query.contains_key(name) {
+            match &param.type_ann {
+                Some(ann) => checker.param(worker.declarations(), "query", ann, value, name),
+                None => value,
+            }
+        } else if param.default.is_some() {
+            worker
+                .default_value(function, param)
+                .map_err(|e| HandlerResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        } else {
+            if matches!(&param.type_ann, Some(ann) if !matches!(ann, TypeAnn::Optional(_))) {
+                checker.missing("query", name);
+            }
+            value
+        };
+    }
+    match checker.into_response() {
+        Some(rejected) => Err(rejected),
+        None => Ok(args),
     }
+}
 
-    match interp.call_function(handler, args) {
-        Ok(value) => (StatusCode::OK, forge_to_json(&value)),
-        Err(e) => (
+/// Call a top-level Forge function with request-bound arguments. Lookup
+/// and runtime failures come back as ready-to-send 500 responses.
+fn call_bound(
+    worker: &mut Worker,
+    name: &str,
+    request: &HandlerRequest,
+    ctx: &IndexMap<String, Value>,
+    response: Option<&Value>,
+) -> Result<Value, HandlerResponse> {
+    let args = bound_args(worker, name, request, ctx, response)?;
+    worker
+        .call(name, args)
+        .map_err(|e| HandlerResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// The arguments [`call_bound`] calls `name` with.
+fn bound_args(
+    worker: &mut Worker,
+    name: &str,
+    request: &HandlerRequest,
+    ctx: &IndexMap<String, Value>,
+    response: Option<&Value>,
+) -> Result<Vec<Value>, HandlerResponse> {
+    let params = worker.params(name).ok_or_else(|| {
+        HandlerResponse::error(
             StatusCode::INTERNAL_SERVER_ERROR,
-            serde_json::json!({"error": e.message}),
-        ),
+            format!("handler '{}' not found", name),
+        )
+    })?;
+    let mut args = bind_args(&params, request, ctx, response);
+    if worker.in_session() {
+        for (param, arg) in params.iter().zip(args.iter_mut()) {
+            if param.name == "csrf_token" {
+                *arg = sessions::csrf_token(worker)
+                    .map_err(|e| HandlerResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            }
+        }
     }
+    typed_args(worker, name, &params, args, request)
 }
 
-fn call_ws_handler(interp: &mut Interpreter, handler_name: &str, text: String) -> String {
-    let handler = interp.env.get(handler_name);
-    if let Some(h) = handler {
-        match interp.call_function(h, vec![Value::String(text)]) {
-            Ok(v) => format!("{}", v),
-            Err(e) => format!("error: {}", e.message),
+/// Run one request through its route's middleware and handler.
+///
+/// Hooks share the handler's forked worker, so the whole chain is a
+/// single blocking task with a single cancellation flag. A `@before` hook
+/// continues the chain by returning `null` or `true`, attaches values for
+/// later hooks and the handler by returning a plain object (merged into
+/// `ctx`), and short-circuits with anything else: `false` is a
+/// `403 Forbidden`, while a `Response` or `Err(...)` is rendered as-is.
+/// `@after` hooks always run, see the response as `res`, and replace it
+/// by returning a non-null value.
+fn run_pipeline(
+    worker: &mut Worker,
+    hooks: &RouteHooks,
+    handler_name: &str,
+    request: &HandlerRequest,
+) -> HandlerResponse {
+    let session = match &hooks.sessions {
+        Some(sessions) => match sessions.load(request) {
+            Ok(loaded) => {
+                sessions::begin(worker, &loaded);
+                Some((sessions, loaded))
+            }
+            Err(error) => return session_failure("load", &error),
+        },
+        None => None,
+    };
+    let mut ctx = IndexMap::new();
+    let short_circuit = check_rate_limit(worker, hooks, request, None)
+        .or_else(|| check_csrf(worker, hooks, request))
+        .or_else(|| run_before_hooks(worker, &hooks.before, request, &mut ctx))
+        .or_else(|| check_rate_limit(worker, hooks, request, Some(&ctx)));
+
+    let mut response = short_circuit.unwrap_or_else(|| {
+        match call_bound(worker, handler_name, request, &ctx, None) {
+            Ok(value) => HandlerResponse::from_value(&value),
+            Err(response) => response,
+        }
+    });
+
+    for hook in &hooks.after {
+        let res = response.to_value();
+        match call_bound(worker, hook, request, &ctx, Some(&res)) {
+            Ok(Value::Null) => {}
+            Ok(value) => response = HandlerResponse::from_value(&value),
+            Err(error) => response = error,
+        }
+    }
+
+    if let Some((sessions, loaded)) = session {
+        match sessions.commit(loaded, &sessions::current(worker)) {
+            Ok(Some(cookie)) => response.headers.push(("set-cookie".to_string(), cookie)),
+            Ok(None) => {}
+            Err(error) => return session_failure("save", &error),
         }
-    } else {
-        "handler not found".to_string()
     }
+    response
+}
+
+/// A session store that cannot be read or written fails the request: a
+/// missing session would log the user out, and a lost write would drop
+/// what the handler did.
+fn session_failure(action: &str, error: &str) -> HandlerResponse {
+    tracing::error!(target: "forge.server", error = %error, "failed to {} session", action);
+    HandlerResponse::error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("failed to {} session", action),
+    )
+}
+
+/// `403` for a `@csrf` route whose request did not send the session's
+/// token back.
+fn check_csrf(
+    worker: &Worker,
+    hooks: &RouteHooks,
+    request: &HandlerRequest,
+) -> Option<HandlerResponse> {
+    (hooks.csrf && !sessions::csrf_ok(&sessions::current(worker), request))
+        .then(|| HandlerResponse::error(StatusCode::FORBIDDEN, "invalid CSRF token"))
+}
+
+/// Take a token from the route's `@rate_limit` bucket for this client, or
+/// answer `429` with `Retry-After` when it is empty. A failing store lets
+/// the request through rather than taking the route down with it.
+///
+/// Called twice per request, see [`rate_limit_client`].
+fn check_rate_limit(
+    worker: &mut Worker,
+    hooks: &RouteHooks,
+    request: &HandlerRequest,
+    ctx: Option<&IndexMap<String, Value>>,
+) -> Option<HandlerResponse> {
+    let limit = hooks.rate_limit.as_ref()?;
+    let client = match rate_limit_client(worker, &limit.rule.key, request, ctx) {
+        Ok(client) => client?,
+        Err(response) => return Some(response),
+    };
+    let bucket = format!("{}\0{}", limit.route, client);
+    match limit.store.take(&bucket, &limit.rule, rate_limit::now_ms()) {
+        Ok(None) => None,
+        Ok(Some(wait)) => {
+            let mut response =
+                HandlerResponse::error(StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded");
+            let secs = wait.as_millis().div_ceil(1000).max(1);
+            response
+                .headers
+                .push(("Retry-After".to_string(), secs.to_string()));
+            Some(response)
+        }
+        Err(e) => {
+            tracing::warn!(
+                target: "forge.server",
+                route = %limit.route,
+                error = %e,
+                "rate limit store failed; allowing request",
+            );
+            None
+        }
+    }
+}
+
+/// The identity a request is rate limited under. Each kind of key gets its
+/// own namespace so, say, a header value cannot impersonate an IP's bucket.
+/// Requests without the header or function result fall back to their IP
+/// address.
+///
+/// IP and header keys are read before any hook runs (`ctx` is `None`), so
+/// a flood never reaches the hooks. A key function runs after the
+/// before-hooks instead, with their `ctx`, so it can key on the user an
+/// auth hook verified. `None` means the key is not read in this phase.
+fn rate_limit_client(
+    worker: &mut Worker,
+    key: &RateKey,
+    request: &HandlerRequest,
+    ctx: Option<&IndexMap<String, Value>>,
+) -> Result<Option<String>, HandlerResponse> {
+    let ip = || match request.remote_addr {
+        Some(addr) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    };
+    Ok(Some(match (key, ctx) {
+        (RateKey::Ip, None) => ip(),
+        (RateKey::Header(name), None) => match request.headers.get(name) {
+            Some(value) => format!("header:{}", value),
+            None => ip(),
+        },
+        (RateKey::Function(function), Some(ctx)) => {
+            match call_bound(worker, function, request, ctx, None)? {
+                Value::Null => ip(),
+                Value::String(s) => format!("fn:{}", s),
+                other => format!("fn:{}", other),
+            }
+        }
+        _ => return Ok(None),
+    }))
+}
+
+/// Run `@before` hooks in order, merging attached values into `ctx`.
+/// Returns the response to send instead of the handler's when a hook
+/// stops the request.
+fn run_before_hooks(
+    worker: &mut Worker,
+    hooks: &[String],
+    request: &HandlerRequest,
+    ctx: &mut IndexMap<String, Value>,
+) -> Option<HandlerResponse> {
+    for hook in hooks {
+        match call_bound(worker, hook, request, ctx, None) {
+            Ok(Value::Null) | Ok(Value::Bool(true)) => {}
+            Ok(Value::Object(fields)) if !is_response(&fields) => ctx.extend(fields),
+            Ok(Value::Bool(false)) => {
+                return Some(HandlerResponse::error(StatusCode::FORBIDDEN, "forbidden"))
+            }
+            Ok(other) => return Some(HandlerResponse::from_value(&other)),
+            Err(response) => return Some(response),
+        }
+    }
+    None
+}
+
+fn describe_server_metrics() {
+    let registry = Registry::global();
+    for (name, help) in [
+        (
+            REQUESTS_METRIC,
+            "HTTP requests by method, route and status.",
+        ),
+        (DURATION_METRIC, "Time until the response head was ready."),
+        (INFLIGHT_METRIC, "Handlers running now."),
+        (INFLIGHT_LIMIT_METRIC, "The max_inflight limit."),
+        (
+            REJECTED_METRIC,
+            "Requests refused with 503 at max_inflight.",
+        ),
+    ] {
+        let _ = registry.describe(name, help);
+    }
+}
+
+/// Count and time a request for `@server(metrics:)`. Requests are
+/// labelled with the route pattern (`/users/:id`), not the raw path, so
+/// the number of series stays bounded; requests no route matched (static
+/// files, 404s) share `route="fallback"`, and methods outside the standard
+/// set share `method="other"`.
+async fn record_request(request: axum::extract::Request, next: axum::middleware::Next) -> Response {
+    let method = metric_method(request.method());
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(matched) => forge_pattern(matched.as_str()),
+        None => "fallback".to_string(),
+    };
+    let started = std::time::Instant::now();
+    let response = next.run(request).await;
+    let registry = Registry::global();
+    let status = response.status();
+    let _ = registry.counter(
+        REQUESTS_METRIC,
+        &[
+            ("method", method),
+            ("route", &route),
+            ("status", status.as_str()),
+        ],
+        1.0,
+    );
+    let _ = registry.observe(
+        DURATION_METRIC,
+        &[("method", method), ("route", &route)],
+        started.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+/// A client can send any token as the method, so only the standard ones
+/// get a series of their own.
+fn metric_method(method: &http::Method) -> &'static str {
+    match *method {
+        http::Method::GET => "GET",
+        http::Method::POST => "POST",
+        http::Method::PUT => "PUT",
+        http::Method::PATCH => "PATCH",
+        http::Method::DELETE => "DELETE",
+        http::Method::HEAD => "HEAD",
+        http::Method::OPTIONS => "OPTIONS",
+        http::Method::CONNECT => "CONNECT",
+        http::Method::TRACE => "TRACE",
+        _ => "other",
+    }
+}
+
+/// The inverse of [`to_axum_path`], for labels users will recognise.
+fn forge_pattern(axum_path: &str) -> String {
+    axum_path
+        .split('/')
+        .map(
+            |s| match s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(name) => format!(":{}", name.trim_start_matches('*')),
+                None => s.to_string(),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The `503` for a request that finds every `max_inflight` slot taken.
+fn at_capacity(state: &AppState) -> Response {
+    if state.metrics {
+        let _ = Registry::global().counter(REJECTED_METRIC, &[], 1.0);
+    }
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [("Retry-After", "1")],
+        JsonResponse(serde_json::json!({"error": "server at capacity"})),
+    )
+        .into_response()
 }
 
 /// Run a Forge handler with full per-request lifecycle:
 /// 1. Acquire a backpressure permit, or 503 if exhausted.
 /// 2. Set up the cancel-on-drop guard.
-/// 3. Fork the interpreter and ship it to the blocking pool, propagating
+/// 3. Fork a worker and ship it to the blocking pool, propagating
 ///    the tracing span across the boundary so user `log.info` events
 ///    inherit the HTTP request fields.
-/// 4. Await; capture panics into a 500 without leaking payload to the client.
+/// 4. Run the route's `@before` hooks, the handler and its `@after` hooks
+///    on that fork (see [`run_pipeline`]).
+/// 5. Await, up to the configured `request_timeout` (504 past it);
+///    capture panics into a 500 without leaking payload to the client.
 ///
 /// The `#[instrument]` attribute opens an info-level span named
 /// `forge.handler` carrying `handler = %handler_name`. Combined with
@@ -267,36 +841,28 @@ fn call_ws_handler(interp: &mut Interpreter, handler_name: &str, text: String) -
 #[tracing::instrument(
     name = "forge.handler",
     level = "info",
-    skip(state, path_params, query_params, body),
+    skip(state, hooks, request, uploads),
     fields(handler = %handler_name, request_id = tracing::field::Empty),
 )]
 async fn run_handler(
     state: AppState,
     handler_name: String,
-    request_id: String,
-    path_params: HashMap<String, String>,
-    query_params: HashMap<String, String>,
-    body: Option<JsonValue>,
+    hooks: Arc<RouteHooks>,
+    request: HandlerRequest,
+    uploads: Option<UploadDir>,
 ) -> Response {
     // Belt-and-suspenders: also record on the inner forge.handler span
     // so events emitted from this function (and via Span::current()
     // propagated into spawn_blocking) explicitly include the field.
     // The OUTER `request` span (set by TraceLayer::make_span_with in
-    // start_server) is the load-bearing place because Span::record only
+    // App::build) is the load-bearing place because Span::record only
     // affects the receiver -- the on_response event lives in the outer
     // span. This inner record is for handler-body events.
-    tracing::Span::current().record("request_id", request_id.as_str());
+    tracing::Span::current().record("request_id", request.request_id.as_str());
 
     let permit = match state.permits.clone().try_acquire_owned() {
         Ok(p) => p,
-        Err(_) => {
-            return (
-                StatusCode::SERVICE_UNAVAILABLE,
-                [("Retry-After", "1")],
-                JsonResponse(serde_json::json!({"error": "server at capacity"})),
-            )
-                .into_response();
-        }
+        Err(_) => return at_capacity(&state),
     };
 
     let cancelled = Arc::new(AtomicBool::new(false));
@@ -307,6 +873,10 @@ async fn run_handler(
 
     let template = state.template.clone();
     let cancel_for_blocking = cancelled.clone();
+    // Conditional GETs are answered from the rendered response, so the
+    // handler still runs; what a `304` saves is the body on the wire.
+    let revalidate = matches!(request.method.as_str(), "GET" | "HEAD")
+        .then(|| request.headers.get("if-none-match").cloned());
 
     // CRITICAL: capture the current tracing span on the async side,
     // re-enter it on the blocking thread. tokio::task::spawn_blocking
@@ -320,24 +890,45 @@ async fn run_handler(
     // Clone the handler name for the blocking closure; the original
     // stays available for the panic-log site below.
     let hn_for_blocking = handler_name.clone();
-    let join = tokio::task::spawn_blocking(move || {
+    let mut join = tokio::task::spawn_blocking(move || {
+        // The permit travels with the work rather than the response, so a
+        // handler that outlives its timeout still counts against
+        // `max_inflight` until it actually stops.
+        let _permit = permit;
+        // Uploaded files are deleted once the handler is done with them,
+        // not when the client stops waiting.
+        let _uploads = uploads;
         let _g = span.enter();
-        let mut interp = template.fork();
+        let mut worker = template.fork();
         // Replace the per-request token with the one the response-future
         // Drop guard owns. Now client disconnect short-circuits the
         // handler at the next loop/call/statement safe point.
-        interp.cancelled = cancel_for_blocking;
-        call_handler(
-            &mut interp,
-            &hn_for_blocking,
-            &path_params,
-            &query_params,
-            body,
-        )
+        worker.set_cancelled(cancel_for_blocking);
+        run_pipeline(&mut worker, &hooks, &hn_for_blocking, &request)
     });
 
-    let (status, json) = match join.await {
-        Ok(pair) => pair,
+    let joined = match state.request_timeout {
+        Some(limit) => match tokio::time::timeout(limit, &mut join).await {
+            Ok(joined) => joined,
+            Err(_) => {
+                // Stop the handler at its next safe point; the guard would
+                // do the same when this future ends, but be explicit.
+                cancelled.store(true, Ordering::Release);
+                tracing::warn!(
+                    target: "forge.server",
+                    handler = %handler_name,
+                    timeout_ms = limit.as_millis() as u64,
+                    "handler timed out",
+                );
+                return HandlerResponse::error(StatusCode::GATEWAY_TIMEOUT, "request timed out")
+                    .into_response();
+            }
+        },
+        None => join.await,
+    };
+
+    let response = match joined {
+        Ok(response) => response,
         Err(join_err) if join_err.is_panic() => {
             // Don't leak panic message to the client. Log it.
             let payload = join_err.into_panic();
@@ -354,326 +945,735 @@ async fn run_handler(
                 panic = %msg,
                 "handler panicked",
             );
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                serde_json::json!({"error": "internal server error"}),
-            )
+            HandlerResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
         }
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            serde_json::json!({"error": "handler join failed"}),
-        ),
+        Err(_) => HandlerResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "handler join failed"),
     };
 
-    drop(permit);
-    (status, JsonResponse(json)).into_response()
+    match revalidate {
+        Some(if_none_match) => response.revalidate(if_none_match.as_deref()),
+        None => response,
+    }
+    .into_response()
 }
 
-pub async fn start_server(
-    interpreter: Interpreter,
-    server: &ServerPlan,
-) -> Result<(), RuntimeError> {
-    // OTel must initialize BEFORE the subscriber so the OTel layer is
-    // present when init_subscriber composes the registry. No-op when
-    // the otel feature is off or OTEL_EXPORTER_OTLP_ENDPOINT is unset.
-    tracing_init::init_otel();
-    // Install the global tracing subscriber on first server boot;
-    // idempotent across multiple starts in the same process (test
-    // harness, embedder). The log stdlib module also calls this
-    // lazily so CLI-invoked scripts get the same setup.
-    tracing_init::init_subscriber();
+/// Events buffered between a running `@sse` handler and the client. A
+/// slow client applies backpressure: `emit` blocks once this fills up.
+const SSE_BUFFER: usize = 16;
 
-    let config = &server.config;
-    let routes = &server.routes;
+/// Convert an emitted value into an SSE event. Strings are sent verbatim
+/// and other values as JSON. An object with both `event` and `data` keys
+/// is a named event and may also set `id` and `retry` (milliseconds).
+///
+/// An `event` name with a line break, or an `id` with a line break or a
+/// NUL, cannot be framed; the field is dropped with a warning and the
+/// rest of the event is still sent.
+fn sse_event(value: &Value) -> Event {
+    fn data_text(value: &Value) -> String {
+        match value {
+            Value::String(s) => s.clone(),
+            other => forge_to_json(other).to_string(),
+        }
+    }
+    match value {
+        Value::Object(obj) if obj.contains_key("event") && obj.contains_key("data") => {
+            let mut event = Event::default().data(data_text(&obj["data"]));
+            if let Some(Value::String(name)) = obj.get("event") {
+                if name.contains(['\n', '\r']) {
+                    tracing::warn!(
+                        target: "forge.server",
+                        event = ?name,
+                        "sse event name contains a line break; sending the event unnamed",
+                    );
+                } else {
+                    event = event.event(name);
+                }
+            }
+            if let Some(id) = obj.get("id") {
+                let id = data_text(id);
+                if id.contains(['\n', '\r', '\0']) {
+                    tracing::warn!(
+                        target: "forge.server",
+                        id = ?id,
+                        "sse event id contains a line break or NUL; sending the event without it",
+                    );
+                } else {
+                    event = event.id(id);
+                }
+            }
+            if let Some(Value::Int(ms)) = obj.get("retry") {
+                event = event.retry(std::time::Duration::from_millis((*ms).max(0) as u64));
+            }
+            event
+        }
+        other => Event::default().data(data_text(other)),
+    }
+}
 
-    let state = AppState {
-        template: Arc::new(InterpreterTemplate::new(interpreter)),
-        permits: Arc::new(Semaphore::new(DEFAULT_MAX_INFLIGHT)),
+/// How an `@sse` request starts: either the stream opens, or a `@before`
+/// hook answered with an ordinary response.
+enum SseStart {
+    Stream,
+    Respond(HandlerResponse),
+}
+
+/// Serve an `@sse` route as `text/event-stream`.
+///
+/// The handler runs on the blocking pool like any other, with its
+/// `yield` / `emit` statements wired to the response body through a
+/// bounded channel. Values in a returned array or `Stream` are sent as
+/// events after the handler returns. The backpressure permit and the
+/// cancel-on-drop guard live inside the body stream, so a client
+/// disconnect both frees the permit and stops the handler at its next
+/// safe point (or its next `emit`, which fails once the body is gone).
+#[tracing::instrument(
+    name = "forge.sse",
+    level = "info",
+    skip(state, hooks, request, uploads),
+    fields(handler = %handler_name, request_id = tracing::field::Empty),
+)]
+async fn run_sse_handler(
+    state: AppState,
+    handler_name: String,
+    hooks: Arc<RouteHooks>,
+    request: HandlerRequest,
+    uploads: Option<UploadDir>,
+) -> Response {
+    tracing::Span::current().record("request_id", request.request_id.as_str());
+
+    let permit = match state.permits.clone().try_acquire_owned() {
+        Ok(p) => p,
+        Err(_) => return at_capacity(&state),
     };
 
-    let mut app = Router::new();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let drop_guard = CancelOnDrop(cancelled.clone());
+    let (events_tx, events_rx) = tokio::sync::mpsc::channel::<Event>(SSE_BUFFER);
+    let (start_tx, start_rx) = tokio::sync::oneshot::channel();
+    let template = state.template.clone();
+    let span = tracing::Span::current();
 
-    for route in routes {
-        let axum_path = to_axum_path(&route.pattern);
-        let hn = route.handler_name.clone();
+    tokio::task::spawn_blocking(move || {
+        let _uploads = uploads;
+        let _g = span.enter();
+        let mut worker = template.fork();
+        worker.set_cancelled(cancelled);
+
+        // Streams can read the session, but their changes are not saved:
+        // the cookie would have to go out before the handler runs.
+        if let Some(sessions) = &hooks.sessions {
+            match sessions.load(&request) {
+                Ok(loaded) => sessions::begin(&mut worker, &loaded),
+                Err(error) => {
+                    let _ = start_tx.send(SseStart::Respond(session_failure("load", &error)));
+                    return;
+                }
+            }
+        }
+        let mut ctx = IndexMap::new();
+        if let Some(response) = check_rate_limit(&mut worker, &hooks, &request, None)
+            .or_else(|| check_csrf(&worker, &hooks, &request))
+            .or_else(|| run_before_hooks(&mut worker, &hooks.before, &request, &mut ctx))
+            .or_else(|| check_rate_limit(&mut worker, &hooks, &request, Some(&ctx)))
+        {
+            let _ = start_tx.send(SseStart::Respond(response));
+            return;
+        }
+        let _ = start_tx.send(SseStart::Stream);
 
-        match route.method.as_str() {
-            "GET" => {
-                let hn = hn.clone();
-                app = app.route(
-                    &axum_path,
-                    get(move |State(state): State<AppState>,
-                              Extension(rid): Extension<RequestId>,
-                              path: Option<Path<HashMap<String, String>>>,
-                              Query(query): Query<HashMap<String, String>>| async move {
-                        let request_id = extract_request_id(&rid);
-                        let params = path.map(|Path(p)| p).unwrap_or_default();
-                        run_handler(state, hn, request_id, params, query, None).await
-                    }),
-                );
+        let send = move |event: Event| events_tx.blocking_send(event).is_ok();
+        let sink_send = send.clone();
+        worker.set_event_sink(Arc::new(move |value: Value| {
+            if sink_send(sse_event(&value)) {
+                Ok(())
+            } else {
+                Err("client disconnected".to_string())
             }
-            "POST" | "PUT" => {
-                let hn = hn.clone();
-                let method = route.method.clone();
-                let handler = move |State(state): State<AppState>,
-                                    Extension(rid): Extension<RequestId>,
-                                    path: Option<Path<HashMap<String, String>>>,
-                                    Query(query): Query<HashMap<String, String>>,
-                                    Json(body): Json<JsonValue>| async move {
-                    let request_id = extract_request_id(&rid);
-                    let params = path.map(|Path(p)| p).unwrap_or_default();
-                    run_handler(state, hn, request_id, params, query, Some(body)).await
-                };
-                if method == "POST" {
-                    app = app.route(&axum_path, post(handler));
-                } else {
-                    app = app.route(&axum_path, put(handler));
+        }));
+
+        let called = bound_args(&mut worker, &handler_name, &request, &ctx, None).map(|args| {
+            worker.call_streaming(&handler_name, args, |value| send(sse_event(&value)))
+        });
+        let failure = match called {
+            Ok(Ok(Some(Value::Array(items)))) => {
+                let _ = items.iter().all(|item| send(sse_event(item)));
+                None
+            }
+            Ok(Ok(None | Some(Value::Null))) => None,
+            Ok(Ok(Some(other))) => {
+                send(sse_event(&other));
+                None
+            }
+            Ok(Err(message)) => Some(message),
+            Err(response) => Some(match response.body {
+                crate::runtime::response::ResponseBody::Json(json) => json["error"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| json.to_string()),
+                _ => "handler failed".to_string(),
+            }),
+        };
+        if let Some(message) = failure {
+            tracing::warn!(target: "forge.server", error = %message, "sse handler failed");
+            send(Event::default().event("error").data(message));
+        }
+    });
+
+    match start_rx.await {
+        Ok(SseStart::Stream) => {}
+        Ok(SseStart::Respond(response)) => return response.into_response(),
+        Err(_) => {
+            return HandlerResponse::error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal server error",
+            )
+            .into_response()
+        }
+    }
+
+    let events = futures_util::stream::unfold(
+        (events_rx, drop_guard, permit),
+        |(mut rx, guard, permit)| async move {
+            rx.recv()
+                .await
+                .map(|event| (Ok::<_, Infallible>(event), (rx, guard, permit)))
+        },
+    );
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Turn on TCP keep-alive probes for an accepted connection when
+/// `@server(keepalive:)` is set, so dead peers on idle connections are
+/// noticed and their sockets reclaimed.
+fn set_keepalive(tcp: &tokio::net::TcpStream, idle: Option<Duration>) {
+    if let Some(idle) = idle {
+        let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+        if let Err(e) = socket2::SockRef::from(tcp).set_tcp_keepalive(&keepalive) {
+            tracing::debug!(target: "forge.server", error = %e, "failed to set TCP keep-alive");
+        }
+    }
+}
+
+/// The router for a [`ServerPlan`], with the state a listener needs to
+/// drain it. [`serve_until`] puts one behind a socket; [`App::oneshot`]
+/// dispatches to it in memory.
+pub struct App {
+    router: Router,
+    permits: Arc<Semaphore>,
+    max_inflight: usize,
+    draining: Arc<AtomicBool>,
+}
+
+impl App {
+    /// Check `server` against the program's definitions and build its
+    /// routes, middleware and built-in endpoints.
+    pub fn build(program: impl Into<Served>, server: &ServerPlan) -> Result<App, RuntimeError> {
+        let program = program.into();
+        // Per-route hooks (`@before("auth")`) name functions by string, so a
+        // typo would otherwise only surface as a 500 on the first request.
+        for hook in server.before.iter().chain(&server.after) {
+            if !program.defines(&hook.function) {
+                return Err(RuntimeError::new(&format!(
+                    "middleware '{}' is not a defined function",
+                    hook.function
+                )));
+            }
+        }
+        for hooks in &server.sockets {
+            for function in hooks.on_open.iter().chain(&hooks.on_close) {
+                if !program.defines(function) {
+                    return Err(RuntimeError::new(&format!(
+                        "websocket callback '{}' on '{}' is not a defined function",
+                        function, hooks.handler
+                    )));
                 }
             }
-            "DELETE" => {
-                let hn = hn.clone();
-                app = app.route(
-                    &axum_path,
-                    delete(move |State(state): State<AppState>,
-                                 Extension(rid): Extension<RequestId>,
-                                 path: Option<Path<HashMap<String, String>>>,
-                                 Query(query): Query<HashMap<String, String>>| async move {
-                        let request_id = extract_request_id(&rid);
-                        let params = path.map(|Path(p)| p).unwrap_or_default();
-                        run_handler(state, hn, request_id, params, query, None).await
-                    }),
-                );
+        }
+        for limit in &server.rate_limits {
+            if let RateKey::Function(function) = &limit.key {
+                if !program.defines(function) {
+                    return Err(RuntimeError::new(&format!(
+                        "rate limit key '{}' on '{}' is not a defined function",
+                        function, limit.handler
+                    )));
+                }
             }
-            "WS" => {
-                // WebSocket handlers hold session state across messages, so
-                // a per-request fork is the wrong model. Each connection
-                // gets its own forked interpreter held inside a
-                // parking_lot::Mutex. The guard is acquired only inside
-                // spawn_blocking so synchronous Forge execution never
-                // blocks the async socket task. Different connections
-                // are still fully isolated.
-                let hn = hn.clone();
-                app = app.route(
-                    &axum_path,
-                    get(
-                        move |State(state): State<AppState>,
-                              ws: axum::extract::WebSocketUpgrade| {
-                            let template = state.template.clone();
-                            let hn = hn.clone();
-                            async move {
-                                ws.on_upgrade(move |socket| async move {
-                                    use axum::extract::ws::Message;
-
-                                    let cancelled = Arc::new(AtomicBool::new(false));
-                                    let _drop_guard = CancelOnDrop(cancelled.clone());
-
-                                    let mut conn_interp = template.fork();
-                                    conn_interp.cancelled = cancelled.clone();
-                                    let interp = Arc::new(parking_lot::Mutex::new(conn_interp));
-                                    let (mut sender, mut receiver) = socket.split();
-                                    let (text_tx, mut text_rx) =
-                                        tokio::sync::mpsc::channel::<String>(1);
-
-                                    let cancel_for_receiver = cancelled.clone();
-                                    let receiver_task = tokio::spawn(async move {
-                                        // Axum 0.8/tungstenite handles Pong replies in the
-                                        // codec before yielding messages here. We only need
-                                        // to forward text and treat Close/errors as cancel.
-                                        while let Some(msg) = receiver.next().await {
-                                            match msg {
-                                                Ok(Message::Text(text)) => {
-                                                    if text_tx.try_send(text.to_string()).is_err() {
-                                                        cancel_for_receiver
-                                                            .store(true, Ordering::Release);
-                                                        break;
-                                                    }
-                                                }
-                                                Ok(Message::Close(_)) | Err(_) => {
-                                                    cancel_for_receiver
-                                                        .store(true, Ordering::Release);
-                                                    break;
-                                                }
-                                                Ok(_) => {}
-                                            }
-                                        }
-                                        cancel_for_receiver.store(true, Ordering::Release);
-                                    });
+        }
+        let config = &server.config;
+        let routes = &server.routes;
+        let limit_store: Arc<dyn RateLimitStore> = match &config.rate_limit_store {
+            Some(path) => Arc::new(SqliteStore::open(path)?),
+            None => Arc::new(MemoryStore::new()),
+        };
+        let sessions = Sessions::from_config(config)?.map(Arc::new);
+        if let (None, Some(handler)) = (&sessions, server.csrf.first()) {
+            return Err(RuntimeError::new(&format!(
+                "@csrf on '{}' needs sessions; set @server(session_secret:) or FORGE_SESSION_SECRET",
+                handler
+            )));
+        }
 
-                                    while let Some(text) = text_rx.recv().await {
-                                        if cancelled.load(Ordering::Acquire) {
-                                            break;
-                                        }
+        let max_inflight = config.max_inflight.unwrap_or(DEFAULT_MAX_INFLIGHT);
+        let permits = Arc::new(Semaphore::new(max_inflight));
+        let max_body = config.max_body.unwrap_or(DEFAULT_MAX_BODY);
+        let max_upload = config.max_upload.unwrap_or(DEFAULT_MAX_UPLOAD);
+        let state = AppState {
+            template: Arc::new(program),
+            permits: permits.clone(),
+            request_timeout: config.request_timeout,
+            metrics: config.metrics.is_some(),
+        };
 
-                                        let interp_for_blocking = interp.clone();
-                                        let hn_for_blocking = hn.clone();
-                                        let span = tracing::Span::current();
-                                        let join = tokio::task::spawn_blocking(move || {
-                                            let _g = span.enter();
-                                            let mut interp = interp_for_blocking.lock();
-                                            call_ws_handler(&mut interp, &hn_for_blocking, text)
-                                        });
-
-                                        let response = match join.await {
-                                            Ok(response) => response,
-                                            Err(join_err) if join_err.is_panic() => {
-                                                let payload = join_err.into_panic();
-                                                let msg = if let Some(s) =
-                                                    payload.downcast_ref::<&str>()
-                                                {
-                                                    (*s).to_string()
-                                                } else if let Some(s) =
-                                                    payload.downcast_ref::<String>()
-                                                {
-                                                    s.clone()
-                                                } else {
-                                                    "<non-string panic payload>".to_string()
-                                                };
-                                                tracing::error!(
-                                                    target: "forge.server",
-                                                    handler = %hn,
-                                                    panic = %msg,
-                                                    "websocket handler panicked"
-                                                );
-                                                "error: internal handler panic".to_string()
-                                            }
-                                            Err(join_err) => {
-                                                tracing::error!(
-                                                    target: "forge.server",
-                                                    handler = %hn,
-                                                    error = %join_err,
-                                                    "websocket handler task failed"
-                                                );
-                                                "error: handler task failed".to_string()
-                                            }
-                                        };
-
-                                        if cancelled.load(Ordering::Acquire) {
-                                            break;
-                                        }
+        let mut app = Router::new();
 
-                                        if sender
-                                            .send(Message::Text(response.into()))
-                                            .await
-                                            .is_err()
-                                        {
-                                            cancelled.store(true, Ordering::Release);
-                                            break;
+        for route in routes {
+            let axum_path = to_axum_path(&route.pattern);
+            let hn = route.handler_name.clone();
+
+            match route.method.as_str() {
+                "WS" => {
+                    // WebSocket handlers hold session state across messages, so
+                    // a per-request fork is the wrong model: each connection
+                    // gets its own worker for its whole life. See
+                    // [`sockets::serve_connection`].
+                    let callbacks = Arc::new(SocketCallbacks {
+                        on_open: server
+                            .socket_hooks_for(route)
+                            .and_then(|hooks| hooks.on_open.clone()),
+                        on_message: hn.clone(),
+                        on_close: server
+                            .socket_hooks_for(route)
+                            .and_then(|hooks| hooks.on_close.clone()),
+                    });
+                    app = app.route(
+                        &axum_path,
+                        get(
+                            move |State(state): State<AppState>,
+                                  Extension(rid): Extension<RequestId>,
+                                  connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+                                  path: Option<Path<HashMap<String, String>>>,
+                                  Query(query): Query<HashMap<String, String>>,
+                                  uri: http::Uri,
+                                  headers: http::HeaderMap,
+                                  ws: axum::extract::WebSocketUpgrade| {
+                                let callbacks = callbacks.clone();
+                                async move {
+                                    let request = HandlerRequest {
+                                        method: "GET".to_string(),
+                                        path: uri.path().to_string(),
+                                        request_id: extract_request_id(&rid),
+                                        path_params: path.map(|Path(p)| p).unwrap_or_default(),
+                                        query,
+                                        headers: collect_headers(&headers),
+                                        remote_addr: connect_info
+                                            .map(|Extension(ConnectInfo(addr))| addr),
+                                        ..HandlerRequest::default()
+                                    };
+                                    let worker = state.template.fork();
+                                    ws.on_upgrade(move |socket| {
+                                        sockets::serve_connection(
+                                            socket, worker, callbacks, request,
+                                        )
+                                    })
+                                }
+                            },
+                        ),
+                    );
+                }
+                method => {
+                    // One entry point for every HTTP method: the request is
+                    // captured whole so handlers can inspect headers, cookies,
+                    // the client address and non-JSON bodies via `req`.
+                    let hn = hn.clone();
+                    let (before, after) = server.hooks_for(route);
+                    let rate_limit = server.rate_limit_for(route).map(|rule| RouteLimit {
+                        rule: rule.clone(),
+                        route: format!("{} {}", route.method, route.pattern),
+                        store: limit_store.clone(),
+                    });
+                    let hooks = Arc::new(RouteHooks {
+                        before,
+                        after,
+                        rate_limit,
+                        sessions: sessions.clone(),
+                        csrf: server.csrf_for(route),
+                    });
+                    let streaming = method == "SSE";
+                    let handler =
+                        move |State(state): State<AppState>,
+                              Extension(rid): Extension<RequestId>,
+                              connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+                              path: Option<Path<HashMap<String, String>>>,
+                              Query(query): Query<HashMap<String, String>>,
+                              method: http::Method,
+                              uri: http::Uri,
+                              headers: http::HeaderMap,
+                              inbound: axum::extract::Request| async move {
+                            let headers = collect_headers(&headers);
+                            let (body, raw_body, uploads) = match multipart_type(&headers) {
+                                Some(content_type) => match forms::read_multipart(
+                                    inbound.into_body(),
+                                    content_type,
+                                    max_upload,
+                                    max_body,
+                                )
+                                .await
+                                {
+                                    Ok(form) => (Some(form.fields), Bytes::new(), form.uploads),
+                                    Err((status, message)) => {
+                                        return (
+                                            status,
+                                            JsonResponse(serde_json::json!({ "error": message })),
+                                        )
+                                            .into_response();
+                                    }
+                                },
+                                None => {
+                                    let raw_body = match Bytes::from_request(inbound, &()).await {
+                                        Ok(raw_body) => raw_body,
+                                        // Over `max_body` (413) or a broken upload.
+                                        Err(rejection) => {
+                                            return (
+                                                rejection.status(),
+                                                JsonResponse(serde_json::json!({
+                                                    "error": rejection.body_text()
+                                                })),
+                                            )
+                                                .into_response();
+                                        }
+                                    };
+                                    match parse_body(&headers, &raw_body) {
+                                        Ok(body) => (body, raw_body, None),
+                                        Err(message) => {
+                                            return (
+                                                StatusCode::BAD_REQUEST,
+                                                JsonResponse(
+                                                    serde_json::json!({ "error": message }),
+                                                ),
+                                            )
+                                                .into_response();
                                         }
                                     }
-
-                                    cancelled.store(true, Ordering::Release);
-                                    receiver_task.abort();
-                                })
+                                }
+                            };
+                            let request = HandlerRequest {
+                                method: method.to_string(),
+                                path: uri.path().to_string(),
+                                request_id: extract_request_id(&rid),
+                                path_params: path.map(|Path(p)| p).unwrap_or_default(),
+                                query,
+                                headers,
+                                remote_addr: connect_info.map(|Extension(ConnectInfo(addr))| addr),
+                                body,
+                                raw_body,
+                            };
+                            if streaming {
+                                run_sse_handler(state, hn, hooks, request, uploads).await
+                            } else {
+                                run_handler(state, hn, hooks, request, uploads).await
                             }
-                        },
-                    ),
-                );
+                        };
+                    // `ANY` is a catch-all: it answers every method not claimed
+                    // by a more specific decorator on the same path.
+                    let method_router = match method {
+                        "ANY" => any(handler),
+                        "SSE" => get(handler),
+                        method => on(method_filter(method), handler),
+                    };
+                    app = app.route(&axum_path, method_router);
+                }
             }
-            _ => {}
         }
-    }
 
-    // Apply CORS policy: restrictive by default, permissive only when explicitly requested.
-    let cors_layer = match config.cors {
-        CorsMode::Permissive => CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods(Any)
-            .allow_headers(Any),
-        CorsMode::Restrictive => CorsLayer::new(), // same-origin only
-    };
+        // Endpoints `@server` adds itself answer GET and must not shadow a
+        // route or each other.
+        let builtin = [
+            ("docs", &config.docs),
+            ("metrics", &config.metrics),
+            ("health", &config.health),
+        ];
+        for (i, (name, path)) in builtin.iter().enumerate() {
+            let Some(path) = path else { continue };
+            if routes.iter().any(|route| {
+                route.pattern == *path && matches!(route.method.as_str(), "GET" | "ANY" | "SSE")
+            }) {
+                return Err(RuntimeError::new(&format!(
+                    "{} path '{}' is already served by a route",
+                    name, path
+                )));
+            }
+            if let Some((other, _)) = builtin[..i]
+                .iter()
+                .find(|(_, other)| other.as_deref() == Some(path.as_str()))
+            {
+                return Err(RuntimeError::new(&format!(
+                    "{} path '{}' is already the {} path",
+                    name, path, other
+                )));
+            }
+        }
+
+        // `@server(docs: "/openapi.json")`: the document was generated with
+        // the plan, so serving it is a static response.
+        if let (Some(path), Some(document)) = (&config.docs, &server.openapi) {
+            let document = Bytes::from(document.to_string());
+            app = app.route(
+                &to_axum_path(path),
+                get(move || async move {
+                    ([(http::header::CONTENT_TYPE, "application/json")], document)
+                }),
+            );
+        }
 
-    // TraceLayer wraps every request in a tracing span carrying method,
-    // uri, version, AND request_id, then emits an INFO event on response
-    // with status + latency. Configured to INFO level explicitly because
-    // tower-http's defaults are DEBUG, which the default filter
-    // (forge_lang=info,tower_http=info) would silently drop.
-    //
-    // The custom make_span_with reads the RequestId that
-    // SetRequestIdLayer (stacked below) inserts into request extensions
-    // and includes it as a span field. Span::record can't propagate to
-    // parent spans, so the field MUST be added at outer-span creation
-    // for the on_response event to carry it.
-    let trace_layer = TraceLayer::new_for_http()
-        .make_span_with(|req: &http::Request<_>| {
-            let request_id = req
-                .extensions()
-                .get::<RequestId>()
-                .map(|id| request_id_for_span(id.header_value()))
-                .unwrap_or("unknown");
-            let span = tracing::info_span!(
-                "request",
-                method = %req.method(),
-                uri = %req.uri(),
-                version = ?req.version(),
-                request_id = request_id,
+        // `@server(metrics: "/metrics")`: the process-wide registry, with the
+        // in-flight gauges read from this server's permits at scrape time.
+        if let Some(path) = &config.metrics {
+            describe_server_metrics();
+            let permits = permits.clone();
+            app = app.route(
+                &to_axum_path(path),
+                get(move || async move {
+                    let registry = Registry::global();
+                    let running = max_inflight - permits.available_permits();
+                    let _ = registry.gauge(INFLIGHT_METRIC, &[], running as f64);
+                    let _ = registry.gauge(INFLIGHT_LIMIT_METRIC, &[], max_inflight as f64);
+                    (
+                        [(http::header::CONTENT_TYPE, metrics::CONTENT_TYPE)],
+                        registry.render(),
+                    )
+                }),
             );
+        }
 
-            // When OTel is wired, extract the upstream W3C traceparent
-            // header (if present) and set it as the parent context on
-            // the request span. Without this, every Forge span is a
-            // new root span -- distributed traces don't connect across
-            // services. The propagator was installed by init_otel().
-            #[cfg(feature = "otel")]
-            if tracing_init::otel_is_active() {
-                use opentelemetry::propagation::Extractor;
-                use tracing_opentelemetry::OpenTelemetrySpanExt;
-
-                struct HeaderMapExtractor<'a>(&'a http::HeaderMap);
-                impl Extractor for HeaderMapExtractor<'_> {
-                    fn get(&self, key: &str) -> Option<&str> {
-                        self.0.get(key).and_then(|v| v.to_str().ok())
+        // `@server(health: "/healthz")`: ready until shutdown starts, then
+        // `503` so load balancers stop sending new requests while we drain.
+        let draining = Arc::new(AtomicBool::new(false));
+        if let Some(path) = &config.health {
+            let draining = draining.clone();
+            app = app.route(
+                &to_axum_path(path),
+                get(move || async move {
+                    if draining.load(Ordering::Acquire) {
+                        (
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            JsonResponse(serde_json::json!({ "status": "draining" })),
+                        )
+                    } else {
+                        (
+                            StatusCode::OK,
+                            JsonResponse(serde_json::json!({ "status": "ok" })),
+                        )
                     }
-                    fn keys(&self) -> Vec<&str> {
-                        self.0.keys().map(|k| k.as_str()).collect()
-                    }
-                }
+                }),
+            );
+        }
 
-                let parent_cx = opentelemetry::global::get_text_map_propagator(|propagator| {
-                    propagator.extract(&HeaderMapExtractor(req.headers()))
-                });
-                // set_parent returns a Result that's safe to ignore --
-                // it only errors when no OTel layer is installed, which
-                // is the expected case when init_otel was a no-op.
-                let _ = span.set_parent(parent_cx);
+        // Static directories. `@static("/assets", ...)` is nested under its
+        // prefix; `@server(static: ...)` (or an `@static` at `/`) becomes the
+        // router fallback so declared routes always win.
+        let mut root_static = match &config.static_dir {
+            Some(dir) => Some(static_router(dir, config.spa_fallback.as_deref())?),
+            None => None,
+        };
+        for mount in &server.statics {
+            let prefix = mount.prefix.trim_end_matches('/');
+            let files = static_router(&mount.dir, None)?;
+            if !prefix.is_empty() {
+                app = app.nest_service(prefix, files);
+            } else if root_static.replace(files).is_some() {
+                return Err(RuntimeError::new(
+                    "only one static directory can be served at '/'",
+                ));
             }
+        }
+        if let Some(files) = root_static {
+            app = app.fallback_service(files);
+        }
+        if config.metrics.is_some() {
+            app = app.layer(axum::middleware::from_fn(record_request));
+        }
+        // gzip or deflate, as `Accept-Encoding` allows. Event streams,
+        // images, tiny bodies and already-encoded `.gz` files are left
+        // alone by the default predicate.
+        if config.compress {
+            app = app.layer(CompressionLayer::new().gzip(true).deflate(true));
+        }
+
+        // Apply CORS policy: restrictive by default, permissive only when explicitly requested.
+        let cors_layer = match config.cors {
+            CorsMode::Permissive => CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any),
+            CorsMode::Restrictive => CorsLayer::new(), // same-origin only
+        };
+
+        // TraceLayer wraps every request in a tracing span carrying method,
+        // uri, version, AND request_id, then emits an INFO event on response
+        // with status + latency. Configured to INFO level explicitly because
+        // tower-http's defaults are DEBUG, which the default filter
+        // (forge_lang=info,tower_http=info) would silently drop.
+        //
+        // The custom make_span_with reads the RequestId that
+        // SetRequestIdLayer (stacked below) inserts into request extensions
+        // and includes it as a span field. Span::record can't propagate to
+        // parent spans, so the field MUST be added at outer-span creation
+        // for the on_response event to carry it.
+        let trace_layer = TraceLayer::new_for_http()
+            .make_span_with(|req: &http::Request<_>| {
+                let request_id = req
+                    .extensions()
+                    .get::<RequestId>()
+                    .map(|id| request_id_for_span(id.header_value()))
+                    .unwrap_or("unknown");
+                let span = tracing::info_span!(
+                    "request",
+                    method = %req.method(),
+                    uri = %req.uri(),
+                    version = ?req.version(),
+                    request_id = request_id,
+                );
+
+                // When OTel is wired, extract the upstream W3C traceparent
+                // header (if present) and set it as the parent context on
+                // the request span. Without this, every Forge span is a
+                // new root span -- distributed traces don't connect across
+                // services. The propagator was installed by init_otel().
+                #[cfg(feature = "otel")]
+                if tracing_init::otel_is_active() {
+                    use opentelemetry::propagation::Extractor;
+                    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+                    struct HeaderMapExtractor<'a>(&'a http::HeaderMap);
+                    impl Extractor for HeaderMapExtractor<'_> {
+                        fn get(&self, key: &str) -> Option<&str> {
+                            self.0.get(key).and_then(|v| v.to_str().ok())
+                        }
+                        fn keys(&self) -> Vec<&str> {
+                            self.0.keys().map(|k| k.as_str()).collect()
+                        }
+                    }
 
-            span
+                    let parent_cx = opentelemetry::global::get_text_map_propagator(|propagator| {
+                        propagator.extract(&HeaderMapExtractor(req.headers()))
+                    });
+                    // set_parent returns a Result that's safe to ignore --
+                    // it only errors when no OTel layer is installed, which
+                    // is the expected case when init_otel was a no-op.
+                    let _ = span.set_parent(parent_cx);
+                }
+
+                span
+            })
+            .on_response(DefaultOnResponse::new().level(Level::INFO));
+
+        // Layer order. Axum's Router::layer is INVERTED from
+        // tower::ServiceBuilder::layer: the LAST .layer() call here is the
+        // OUTERMOST in the resulting tower stack (it wraps everything
+        // before it).
+        //
+        // We need (outside-in on the request path):
+        //   Set -> Propagate -> Trace -> Cors -> handler
+        //
+        // Why this order:
+        //   * Set runs first so it can populate the X-Request-Id header
+        //     from MakeRequestUuid (or pass through an inbound value).
+        //   * Propagate captures the (now-populated) request header into
+        //     its response future so it can copy the id back onto the
+        //     response when it returns.
+        //   * Trace's make_span_with reads the id from request extensions
+        //     (which Set also populated).
+        //
+        // To get that outside-in order in axum, we add layers innermost-first:
+        //   .layer(cors_layer)            -- innermost
+        //   .layer(trace_layer)
+        //   .layer(PropagateRequestIdLayer::...)
+        //   .layer(SetRequestIdLayer::...) -- outermost
+        let router = app
+            .layer(DefaultBodyLimit::max(max_body))
+            .layer(cors_layer)
+            .layer(trace_layer)
+            .layer(PropagateRequestIdLayer::x_request_id())
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+            .with_state(state);
+
+        Ok(App {
+            router,
+            permits,
+            max_inflight,
+            draining,
         })
-        .on_response(DefaultOnResponse::new().level(Level::INFO));
-
-    // Layer order. Axum's Router::layer is INVERTED from
-    // tower::ServiceBuilder::layer: the LAST .layer() call here is the
-    // OUTERMOST in the resulting tower stack (it wraps everything
-    // before it).
-    //
-    // We need (outside-in on the request path):
-    //   Set -> Propagate -> Trace -> Cors -> handler
-    //
-    // Why this order:
-    //   * Set runs first so it can populate the X-Request-Id header
-    //     from MakeRequestUuid (or pass through an inbound value).
-    //   * Propagate captures the (now-populated) request header into
-    //     its response future so it can copy the id back onto the
-    //     response when it returns.
-    //   * Trace's make_span_with reads the id from request extensions
-    //     (which Set also populated).
-    //
-    // To get that outside-in order in axum, we add layers innermost-first:
-    //   .layer(cors_layer)            -- innermost
-    //   .layer(trace_layer)
-    //   .layer(PropagateRequestIdLayer::...)
-    //   .layer(SetRequestIdLayer::...) -- outermost
-    let app = app
-        .layer(cors_layer)
-        .layer(trace_layer)
-        .layer(PropagateRequestIdLayer::x_request_id())
-        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
-        .with_state(state);
+    }
+
+    /// Dispatch one request without a socket. Handlers see no
+    /// `remote_addr`, and `@ws` routes cannot upgrade.
+    pub async fn oneshot(&self, request: http::Request<Body>) -> Response {
+        match self.router.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        }
+    }
+}
+
+/// How a server run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shutdown {
+    /// Every in-flight handler finished within the drain window.
+    Drained,
+    /// The drain window closed with handlers still running. They have been
+    /// cancelled but had not returned.
+    TimedOut { in_flight: usize },
+}
+
+/// Serve `server` until `shutdown` completes. Shutdown stops accepting
+/// connections and lets open requests finish for up to `drain_timeout`.
+/// Requests still open after that are cancelled, and handlers get
+/// [`CANCEL_GRACE`] to return their permits before the run is reported as
+/// [`Shutdown::TimedOut`].
+pub async fn serve_until(
+    program: impl Into<Served>,
+    server: &ServerPlan,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<Shutdown, RuntimeError> {
+    // OTel must initialize BEFORE the subscriber so the OTel layer is
+    // present when init_subscriber composes the registry. No-op when
+    // the otel feature is off or OTEL_EXPORTER_OTLP_ENDPOINT is unset.
+    tracing_init::init_otel();
+    // Install the global tracing subscriber on first server boot;
+    // idempotent across multiple starts in the same process (test
+    // harness, embedder). The log stdlib module also calls this
+    // lazily so CLI-invoked scripts get the same setup.
+    tracing_init::init_subscriber();
+
+    let App {
+        router: app,
+        permits,
+        max_inflight,
+        draining,
+    } = App::build(program, server)?;
+    let config = &server.config;
+    let routes = &server.routes;
+    let max_body = config.max_body.unwrap_or(DEFAULT_MAX_BODY);
+    let max_upload = config.max_upload.unwrap_or(DEFAULT_MAX_UPLOAD);
 
     let addr: SocketAddr = format!("{}:{}", config.host, config.port)
         .parse()
         .map_err(|e| RuntimeError::new(&format!("invalid address: {}", e)))?;
 
+    // Load certificates before printing the banner so a bad path fails
+    // fast instead of after "listening".
+    let tls_config = match tls::tls_paths(config)? {
+        Some((cert, key)) => Some(tls::load_server_config(&cert, &key)?),
+        None => None,
+    };
+    let scheme = if tls_config.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+
     // Always emit the structured startup event so log aggregators see
     // server boot regardless of TTY / format choice.
     let cors_str = match config.cors {
@@ -686,7 +1686,10 @@ pub async fn start_server(
         port = config.port,
         routes = routes.len(),
         cors = cors_str,
-        max_inflight = DEFAULT_MAX_INFLIGHT,
+        tls = tls_config.is_some(),
+        max_inflight,
+        max_body,
+        max_upload,
         "Forge server listening",
     );
 
@@ -701,12 +1704,18 @@ pub async fn start_server(
         };
         println!();
         println!("  \x1B[1;32m🔥 Forge server running\x1B[0m");
-        println!("  \x1B[1m   http://{}\x1B[0m", addr);
+        println!("  \x1B[1m   {}://{}\x1B[0m", scheme, addr);
         println!("  \x1B[90m   CORS: {}\x1B[0m", cors_label);
         println!(
             "  \x1B[90m   max in-flight: {} (excess returns 503)\x1B[0m",
-            DEFAULT_MAX_INFLIGHT
+            max_inflight
         );
+        if let Some(limit) = config.request_timeout {
+            println!(
+                "  \x1B[90m   request timeout: {:?} (slower returns 504)\x1B[0m",
+                limit
+            );
+        }
         println!();
         for route in routes {
             println!("  \x1B[36m{:>6}\x1B[0m  {}", route.method, route.pattern);
@@ -716,13 +1725,64 @@ pub async fn start_server(
         println!();
     }
 
+    let keepalive = config.keepalive;
     let listener = tokio::net::TcpListener::bind(addr)
         .await
         .map_err(|e| RuntimeError::new(&format!("bind failed: {}", e)))?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .map_err(|e| RuntimeError::new(&format!("server error: {}", e)))?;
+    let drain = config.drain_timeout.unwrap_or(DEFAULT_DRAIN_TIMEOUT);
+    let (signalled_tx, signalled) = tokio::sync::watch::channel(None);
+    let shutdown = async move {
+        shutdown.await;
+        draining.store(true, Ordering::Release);
+        tracing::info!(
+            target: "forge.server",
+            drain_secs = drain.as_secs_f64(),
+            "shutdown signal received, draining"
+        );
+        let _ = signalled_tx.send(Some(tokio::time::Instant::now() + drain));
+    };
+    let served = match tls_config {
+        Some(tls_config) => {
+            let listener = TlsListener::new(listener, tls_config)
+                .map_err(|e| RuntimeError::new(&format!("bind failed: {}", e)))?
+                // Also gets us axum's `ConnectInfo<SocketAddr>` impl,
+                // which only covers custom listeners through `TapIo`.
+                .tap_io(move |tls| {
+                    let tcp = tls.get_ref().0;
+                    let _ = tcp.set_nodelay(true);
+                    set_keepalive(tcp, keepalive);
+                });
+            let serve = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown);
+            serve_draining(serve, signalled.clone()).await
+        }
+        None => {
+            let listener = listener.tap_io(move |tcp| set_keepalive(tcp, keepalive));
+            let serve = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown);
+            serve_draining(serve, signalled.clone()).await
+        }
+    };
+    served.map_err(|e| RuntimeError::new(&format!("server error: {}", e)))?;
+
+    // Connections are closed, but handlers cancelled by a dropped
+    // connection or a request timeout may still be running on the
+    // blocking pool; each holds its permit until it returns.
+    let deadline = signalled.borrow().unwrap_or_else(tokio::time::Instant::now);
+    let outcome = wait_for_handlers(&permits, max_inflight, deadline + CANCEL_GRACE).await;
+    if let Shutdown::TimedOut { in_flight } = outcome {
+        tracing::warn!(
+            target: "forge.server",
+            in_flight,
+            "drain timeout elapsed with handlers still running"
+        );
+    }
 
     // Flush pending OpenTelemetry spans AFTER axum has finished
     // draining in-flight requests. Calling this from inside
@@ -735,15 +1795,62 @@ pub async fn start_server(
         .await
         .ok();
 
-    Ok(())
+    Ok(outcome)
+}
+
+/// Drive a gracefully-shutting-down server until it finishes on its own
+/// or the drain deadline published on `signalled` passes. Dropping the
+/// server future at the deadline closes the remaining connections, which
+/// cancels their handlers.
+async fn serve_draining<F>(
+    serve: F,
+    mut signalled: tokio::sync::watch::Receiver<Option<tokio::time::Instant>>,
+) -> std::io::Result<()>
+where
+    F: IntoFuture<Output = std::io::Result<()>>,
+{
+    let deadline = async move {
+        match signalled.wait_for(Option::is_some).await {
+            Ok(deadline) => {
+                let deadline = deadline.expect("waited for a deadline");
+                tokio::time::sleep_until(deadline).await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        served = serve.into_future() => served,
+        _ = deadline => {
+            tracing::warn!(
+                target: "forge.server",
+                "drain timeout elapsed; closing open connections"
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Wait until every handler permit is back, or `deadline`.
+async fn wait_for_handlers(
+    permits: &Semaphore,
+    max_inflight: usize,
+    deadline: tokio::time::Instant,
+) -> Shutdown {
+    let all = u32::try_from(max_inflight).unwrap_or(u32::MAX);
+    match tokio::time::timeout_at(deadline, permits.acquire_many(all)).await {
+        Ok(_) => Shutdown::Drained,
+        Err(_) => Shutdown::TimedOut {
+            in_flight: max_inflight.saturating_sub(permits.available_permits()),
+        },
+    }
 }
 
 /// Wait for SIGINT (Ctrl-C) or SIGTERM. axum will then stop accepting new
-/// connections and let in-flight requests finish (subject to client and
-/// per-request timeouts). Drop guards on dropped futures still flip the
-/// per-request cancel flag so any blocking handlers that are still
-/// running observe the cancel at their next safe point.
-async fn shutdown_signal() {
+/// connections and let in-flight requests finish (subject to client,
+/// per-request and drain timeouts). Drop guards on dropped futures still
+/// flip the per-request cancel flag so any blocking handlers that are
+/// still running observe the cancel at their next safe point.
+pub(crate) async fn shutdown_signal() {
     let ctrl_c = async {
         let _ = tokio::signal::ctrl_c().await;
     };
@@ -764,10 +1871,6 @@ async fn shutdown_signal() {
         _ = ctrl_c => {},
         _ = terminate => {},
     }
-    tracing::info!(
-        target: "forge.server",
-        "shutdown signal received, draining"
-    );
 }
 
 pub fn json_to_forge(v: JsonValue) -> Value {
@@ -825,6 +1928,7 @@ pub fn forge_to_json(v: &Value) -> JsonValue {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::interpreter::Interpreter;
 
     // ── to_axum_path ────────────────────────────────────────────────────────
 
@@ -846,6 +1950,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn metric_labels_use_forge_patterns() {
+        assert_eq!(
+            forge_pattern(&to_axum_path("/org/:org/repo/:repo")),
+            "/org/:org/repo/:repo"
+        );
+        assert_eq!(forge_pattern("/assets/{*path}"), "/assets/:path");
+        assert_eq!(metric_method(&http::Method::PATCH), "PATCH");
+        let custom = http::Method::from_bytes(b"FOO1").unwrap();
+        assert_eq!(metric_method(&custom), "other");
+    }
+
     // ── json_to_forge ────────────────────────────────────────────────────────
 
     #[test]
@@ -947,6 +2063,89 @@ mod tests {
         }
     }
 
+    // ── HandlerRequest ───────────────────────────────────────────────────────
+
+    fn request_with_headers(pairs: &[(&str, &str)]) -> HandlerRequest {
+        HandlerRequest {
+            headers: pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cookies_parse_pairs_and_skip_malformed() {
+        let req = request_with_headers(&[("cookie", "sid=abc123; theme = dark; junk; a=b=c")]);
+        let cookies = req.cookies();
+
+        assert_eq!(cookies.get("sid").map(String::as_str), Some("abc123"));
+        assert_eq!(cookies.get("theme").map(String::as_str), Some("dark"));
+        assert_eq!(cookies.get("a").map(String::as_str), Some("b=c"));
+        assert_eq!(cookies.len(), 3);
+    }
+
+    #[test]
+    fn collect_headers_lowercases_and_joins_repeats() {
+        let mut headers = http::HeaderMap::new();
+        headers.append("X-Trace", http::HeaderValue::from_static("a"));
+        headers.append("x-trace", http::HeaderValue::from_static("b"));
+        headers.insert("Accept", http::HeaderValue::from_static("text/html"));
+
+        let flat = collect_headers(&headers);
+        assert_eq!(flat.get("x-trace").map(String::as_str), Some("a, b"));
+        assert_eq!(flat.get("accept").map(String::as_str), Some("text/html"));
+    }
+
+    #[test]
+    fn parse_body_handles_json_text_and_errors() {
+        let json_ct = request_with_headers(&[("content-type", "application/json")]).headers;
+        let text_ct = request_with_headers(&[("content-type", "text/plain")]).headers;
+        let untyped = IndexMap::new();
+
+        assert_eq!(
+            parse_body(&json_ct, &Bytes::from_static(b"{\"a\":1}")).unwrap(),
+            Some(serde_json::json!({"a": 1}))
+        );
+        assert_eq!(
+            parse_body(&untyped, &Bytes::from_static(b"[1]")).unwrap(),
+            Some(serde_json::json!([1]))
+        );
+        assert_eq!(
+            parse_body(&text_ct, &Bytes::from_static(b"[1]")).unwrap(),
+            None
+        );
+        assert_eq!(parse_body(&json_ct, &Bytes::new()).unwrap(), None);
+        assert!(parse_body(&json_ct, &Bytes::from_static(b"{nope"))
+            .unwrap_err()
+            .starts_with("invalid JSON body"));
+    }
+
+    #[test]
+    fn request_value_exposes_request_fields() {
+        let mut req = request_with_headers(&[("cookie", "sid=1"), ("content-type", "text/plain")]);
+        req.method = "POST".to_string();
+        req.path = "/echo".to_string();
+        req.remote_addr = Some("127.0.0.1:9000".parse().unwrap());
+        req.raw_body = Bytes::from_static(b"hello");
+
+        let Value::Object(obj) = req.to_value() else {
+            panic!("expected object");
+        };
+        assert_eq!(obj.get("method"), Some(&Value::String("POST".to_string())));
+        assert_eq!(obj.get("path"), Some(&Value::String("/echo".to_string())));
+        assert_eq!(obj.get("body"), Some(&Value::String("hello".to_string())));
+        assert_eq!(
+            obj.get("remote_addr"),
+            Some(&Value::String("127.0.0.1:9000".to_string()))
+        );
+        let Some(Value::Object(cookies)) = obj.get("cookies") else {
+            panic!("expected cookies object");
+        };
+        assert_eq!(cookies.get("sid"), Some(&Value::String("1".to_string())));
+    }
+
     // ── Concurrency model: AppState clone is cheap and shares no locks ──
 
     /// AppState must be `Clone` (axum requires `Clone` on `with_state`).
@@ -956,8 +2155,10 @@ mod tests {
     #[test]
     fn app_state_clone_is_arc_share() {
         let state = AppState {
-            template: Arc::new(InterpreterTemplate::new(Interpreter::new())),
+            template: Arc::new(Served::from(Interpreter::new())),
             permits: Arc::new(Semaphore::new(DEFAULT_MAX_INFLIGHT)),
+            request_timeout: None,
+            metrics: false,
         };
         let other = state.clone();
         assert!(Arc::ptr_eq(&state.template, &other.template));
@@ -1032,16 +2233,211 @@ mod tests {
         assert_eq!(extracted, "b".repeat(REQUEST_ID_MAX_LEN));
     }
 
+    fn worker_with(source: &str) -> Worker {
+        let tokens = crate::lexer::Lexer::new(source)
+            .tokenize()
+            .expect("lexing should succeed");
+        let program = crate::parser::Parser::new(tokens)
+            .parse_program()
+            .expect("parsing should succeed");
+        let mut interp = Interpreter::new();
+        interp.run(&program).expect("execution should succeed");
+        Worker::Interpreted(Box::new(interp))
+    }
+
+    fn hooks(before: &[&str], after: &[&str]) -> RouteHooks {
+        RouteHooks {
+            before: before.iter().map(|s| s.to_string()).collect(),
+            after: after.iter().map(|s| s.to_string()).collect(),
+            rate_limit: None,
+            sessions: None,
+            csrf: false,
+        }
+    }
+
+    #[test]
+    fn pipeline_threads_ctx_and_lets_after_hooks_replace_response() {
+        let mut worker = worker_with(
+            r#"
+            fn load_user(req) { return { user: "ada" } }
+            fn show(ctx) { return { user: ctx.user } }
+            fn stamp(res) { return response(res.status, res.body, { "x-user": "seen" }) }
+            "#,
+        );
+        let request = HandlerRequest::default();
+
+        let plain = run_pipeline(&mut worker, &hooks(&["load_user"], &[]), "show", &request);
+        assert_eq!(
+            plain.body,
+            crate::runtime::response::ResponseBody::Json(serde_json::json!({"user": "ada"}))
+        );
+
+        let stamped = run_pipeline(
+            &mut worker,
+            &hooks(&["load_user"], &["stamp"]),
+            "show",
+            &request,
+        );
+        assert_eq!(stamped.status, StatusCode::OK);
+        assert_eq!(
+            stamped.headers,
+            vec![("x-user".to_string(), "seen".to_string())]
+        );
+    }
+
+    #[test]
+    fn pipeline_short_circuits_on_rejecting_before_hook() {
+        let mut worker = worker_with(
+            r#"
+            fn deny() { return false }
+            fn unauthorized() { return response(401, "login first") }
+            fn handler() { return undefined_function() }
+            fn keep(res) { return null }
+            "#,
+        );
+        let request = HandlerRequest::default();
+
+        let denied = run_pipeline(
+            &mut worker,
+            &hooks(&["deny"], &["keep"]),
+            "handler",
+            &request,
+        );
+        assert_eq!(denied.status, StatusCode::FORBIDDEN);
+
+        let custom = run_pipeline(
+            &mut worker,
+            &hooks(&["unauthorized"], &[]),
+            "handler",
+            &request,
+        );
+        assert_eq!(custom.status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn rate_limited_routes_answer_429_per_client() {
+        let mut worker = worker_with(
+            r#"
+            fn tenant(req) { return get(req.headers, "x-tenant", null) }
+            fn handler() { return { ok: true } }
+            "#,
+        );
+        let mut limited = hooks(&[], &[]);
+        limited.rate_limit = Some(RouteLimit {
+            rule: RateLimit {
+                handler: "handler".to_string(),
+                limit: 1,
+                per: Duration::from_secs(60),
+                key: RateKey::Function("tenant".to_string()),
+            },
+            route: "GET /".to_string(),
+            store: Arc::new(MemoryStore::new()),
+        });
+        let mut acme = HandlerRequest::default();
+        acme.headers
+            .insert("x-tenant".to_string(), "acme".to_string());
+        let anonymous = HandlerRequest::default();
+
+        let first = run_pipeline(&mut worker, &limited, "handler", &acme);
+        assert_eq!(first.status, StatusCode::OK);
+        let second = run_pipeline(&mut worker, &limited, "handler", &acme);
+        assert_eq!(second.status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(second
+            .headers
+            .iter()
+            .any(|(name, value)| name == "Retry-After" && value == "60"));
+        // A null key falls back to the (here unknown) client address.
+        let other = run_pipeline(&mut worker, &limited, "handler", &anonymous);
+        assert_eq!(other.status, StatusCode::OK);
+    }
+
+    #[test]
+    fn rate_limit_key_functions_run_after_the_auth_hook() {
+        let mut worker = worker_with(
+            r#"
+            fn auth(req) {
+                let user = get(req.headers, "x-user", "")
+                if user == "" { return response(401, { error: "log in" }) }
+                return { user: user }
+            }
+            fn by_user(ctx) { return ctx.user }
+            fn handler() { return { ok: true } }
+            "#,
+        );
+        let mut limited = hooks(&["auth"], &[]);
+        limited.rate_limit = Some(RouteLimit {
+            rule: RateLimit {
+                handler: "handler".to_string(),
+                limit: 1,
+                per: Duration::from_secs(60),
+                key: RateKey::Function("by_user".to_string()),
+            },
+            route: "GET /".to_string(),
+            store: Arc::new(MemoryStore::new()),
+        });
+        let as_user = |user: &str| {
+            let mut request = HandlerRequest::default();
+            request
+                .headers
+                .insert("x-user".to_string(), user.to_string());
+            request
+        };
+
+        let mut status = |request: &HandlerRequest| {
+            run_pipeline(&mut worker, &limited, "handler", request).status
+        };
+        assert_eq!(status(&as_user("ada")), StatusCode::OK);
+        assert_eq!(status(&as_user("ada")), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(status(&as_user("grace")), StatusCode::OK);
+        // Rejected by the hook before the key is ever read.
+        assert_eq!(status(&HandlerRequest::default()), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn draining_waits_for_permits_until_the_deadline() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let permits = Arc::new(Semaphore::new(4));
+            let soon = || tokio::time::Instant::now() + Duration::from_millis(50);
+            assert_eq!(
+                wait_for_handlers(&permits, 4, soon()).await,
+                Shutdown::Drained
+            );
+
+            // A stuck handler keeps its permit past the deadline.
+            let stuck = permits.clone().acquire_owned().await.unwrap();
+            assert_eq!(
+                wait_for_handlers(&permits, 4, soon()).await,
+                Shutdown::TimedOut { in_flight: 1 }
+            );
+
+            // One that finishes inside the window drains cleanly.
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                drop(stuck);
+            });
+            assert_eq!(
+                wait_for_handlers(&permits, 4, soon()).await,
+                Shutdown::Drained
+            );
+        });
+    }
+
     /// The template must produce independent forks. This is the
     /// integration of fork_for_serving with the server's wrapper type.
     #[test]
     fn template_forks_are_independent() {
         let mut interp = Interpreter::new();
         interp.env.define("seed".to_string(), Value::Int(7));
-        let tpl = Arc::new(InterpreterTemplate::new(interp));
+        let tpl = Arc::new(Served::from(interp));
 
-        let mut a = tpl.fork();
-        let mut b = tpl.fork();
+        let (Worker::Interpreted(mut a), Worker::Interpreted(mut b)) = (tpl.fork(), tpl.fork())
+        else {
+            panic!("an interpreter forks interpreters");
+        };
 
         a.env.define("x".to_string(), Value::Int(1));
         b.env.define("x".to_string(), Value::Int(2));
@@ -0,0 +1,601 @@
+//! Cookie sessions and CSRF tokens for decorated servers.
+//!
+//! `@server(session_secret: "...")` (or `FORGE_SESSION_SECRET`) gives each
+//! HTTP request a mutable `session` object, loaded from the
+//! `forge_session` cookie before the first `@before` hook and saved after
+//! the last `@after` hook when it changed. Cookies are signed with
+//! HMAC-SHA256 under the secret, so a tampered cookie reads as an empty
+//! session.
+//!
+//! Without `session_store`, the session's JSON travels in the cookie
+//! itself, with its expiry inside the signed part. Clients can read it but
+//! not change it. With a [`SessionStore`] ([`MemoryStore`] for
+//! `session_store: "memory"`, [`SqliteStore`] for a database path) the
+//! cookie only carries a random id and the data stays on the server.
+//!
+//! A `csrf_token` handler parameter issues a per-session token, and
+//! `@csrf` routes reject unsafe requests that do not send it back.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::Value as JsonValue;
+use sha2::Sha256;
+
+use crate::interpreter::{RuntimeError, Value};
+use crate::runtime::metadata::ServerConfig;
+use crate::runtime::rate_limit::now_ms;
+use crate::runtime::server::{forge_to_json, json_to_forge, HandlerRequest};
+use crate::runtime::worker::Worker;
+
+/// Name of the session cookie.
+pub const COOKIE: &str = "forge_session";
+
+/// How long a session lives after its last change, unless
+/// `session_max_age` says otherwise.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Browsers drop cookies larger than 4 KB.
+const MAX_COOKIE_LEN: usize = 4000;
+
+/// Session key holding the CSRF token.
+const CSRF_KEY: &str = "_csrf";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Where server-side sessions are kept. Calls run on the blocking pool, so
+/// stores may do synchronous I/O.
+pub trait SessionStore: Send + Sync {
+    /// The data saved under `id`, unless it is missing or expired at
+    /// `now_ms` (Unix milliseconds).
+    fn load(&self, id: &str, now_ms: u64) -> Result<Option<JsonValue>, String>;
+    fn save(&self, id: &str, data: &JsonValue, expires_ms: u64) -> Result<(), String>;
+    fn delete(&self, id: &str) -> Result<(), String>;
+}
+
+/// Size at which the in-memory store first sweeps out expired sessions.
+const MEMORY_SWEEP_AT: usize = 4096;
+
+/// Sessions in the server process. They are lost on restart.
+pub struct MemoryStore {
+    inner: Mutex<MemorySessions>,
+}
+
+struct MemorySessions {
+    sessions: HashMap<String, (JsonValue, u64)>,
+    sweep_at: usize,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(MemorySessions {
+                sessions: HashMap::new(),
+                sweep_at: MEMORY_SWEEP_AT,
+            }),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str, now_ms: u64) -> Result<Option<JsonValue>, String> {
+        let inner = self.inner.lock();
+        Ok(inner
+            .sessions
+            .get(id)
+            .filter(|(_, expires_ms)| *expires_ms > now_ms)
+            .map(|(data, _)| data.clone()))
+    }
+
+    fn save(&self, id: &str, data: &JsonValue, expires_ms: u64) -> Result<(), String> {
+        let mut inner = self.inner.lock();
+        inner
+            .sessions
+            .insert(id.to_string(), (data.clone(), expires_ms));
+        if inner.sessions.len() >= inner.sweep_at {
+            let now = now_ms();
+            inner
+                .sessions
+                .retain(|_, (_, expires_ms)| *expires_ms > now);
+            inner.sweep_at = (inner.sessions.len() * 2).max(MEMORY_SWEEP_AT);
+        }
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        self.inner.lock().sessions.remove(id);
+        Ok(())
+    }
+}
+
+/// Sessions in a SQLite table, opened through the `db` module, so they
+/// survive restarts and are shared by processes on one host.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Result<Self, RuntimeError> {
+        let fail = |e: rusqlite::Error| {
+            RuntimeError::new(&format!("failed to open session store '{}': {}", path, e))
+        };
+        let conn = crate::stdlib::db::open_connection(path).map_err(fail)?;
+        conn.busy_timeout(Duration::from_secs(1)).map_err(fail)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS forge_sessions (
+                 id TEXT PRIMARY KEY,
+                 data TEXT NOT NULL,
+                 expires_ms INTEGER NOT NULL
+             )",
+        )
+        .map_err(fail)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl SessionStore for SqliteStore {
+    fn load(&self, id: &str, now_ms: u64) -> Result<Option<JsonValue>, String> {
+        let data: Option<String> = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT data FROM forge_sessions WHERE id = ?1 AND expires_ms > ?2",
+                rusqlite::params![id, now_ms as i64],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        data.map(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+            .transpose()
+    }
+
+    fn save(&self, id: &str, data: &JsonValue, expires_ms: u64) -> Result<(), String> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO forge_sessions (id, data, expires_ms) VALUES (?1, ?2, ?3)
+             ON CONFLICT(id) DO UPDATE SET data = excluded.data, expires_ms = excluded.expires_ms",
+            rusqlite::params![id, data.to_string(), expires_ms as i64],
+        )
+        .map_err(|e| e.to_string())?;
+        // Expired rows are cleared out by whichever process writes next.
+        conn.execute(
+            "DELETE FROM forge_sessions WHERE expires_ms <= ?1",
+            [now_ms() as i64],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        self.conn
+            .lock()
+            .execute("DELETE FROM forge_sessions WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// A server's session settings.
+pub struct Sessions {
+    secret: Vec<u8>,
+    /// `None` keeps the data in the cookie.
+    store: Option<Arc<dyn SessionStore>>,
+    max_age: Duration,
+    /// Whether cookies get `Secure`, which is when the server speaks HTTPS.
+    secure: bool,
+}
+
+impl std::fmt::Debug for Sessions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sessions")
+            .field("stored", &self.store.is_some())
+            .field("max_age", &self.max_age)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A request's session as it was loaded.
+pub(crate) struct Loaded {
+    /// The store id from the cookie, when a server-side session exists.
+    id: Option<String>,
+    data: JsonValue,
+    /// Whether the request carried a valid session cookie.
+    had_cookie: bool,
+}
+
+impl Sessions {
+    /// The sessions `config` asks for, or `None` when no secret is set.
+    /// Decorator arguments win over `FORGE_SESSION_SECRET`.
+    pub fn from_config(config: &ServerConfig) -> Result<Option<Sessions>, RuntimeError> {
+        let Some(secret) = config
+            .session_secret
+            .clone()
+            .or_else(|| std::env::var("FORGE_SESSION_SECRET").ok())
+        else {
+            if config.session_store.is_some() {
+                return Err(RuntimeError::new(
+                    "session_store needs a session_secret (or FORGE_SESSION_SECRET)",
+                ));
+            }
+            return Ok(None);
+        };
+        if secret.len() < 32 {
+            return Err(RuntimeError::new(
+                "session_secret must be at least 32 characters",
+            ));
+        }
+        let store: Option<Arc<dyn SessionStore>> = match config.session_store.as_deref() {
+            None => None,
+            Some("memory") => Some(Arc::new(MemoryStore::new())),
+            Some(path) => Some(Arc::new(SqliteStore::open(path)?)),
+        };
+        Ok(Some(Sessions {
+            secret: secret.into_bytes(),
+            store,
+            max_age: config.session_max_age.unwrap_or(DEFAULT_MAX_AGE),
+            secure: crate::runtime::tls::tls_paths(config)?.is_some(),
+        }))
+    }
+
+    /// Read the request's session. A missing, tampered or expired cookie
+    /// gives an empty one.
+    pub(crate) fn load(&self, request: &HandlerRequest) -> Result<Loaded, String> {
+        let empty = Loaded {
+            id: None,
+            data: JsonValue::Object(Default::default()),
+            had_cookie: false,
+        };
+        let Some(payload) = request
+            .cookies()
+            .get(COOKIE)
+            .and_then(|cookie| self.verify(cookie))
+        else {
+            return Ok(empty);
+        };
+        let now = now_ms();
+        match &self.store {
+            Some(store) => match store.load(&payload, now)? {
+                Some(data) => Ok(Loaded {
+                    id: Some(payload),
+                    data,
+                    had_cookie: true,
+                }),
+                None => Ok(Loaded {
+                    had_cookie: true,
+                    ..empty
+                }),
+            },
+            None => {
+                let decoded = payload.split_once('.').and_then(|(expires, data)| {
+                    let expires = u64::from_str_radix(expires, 16).ok()?;
+                    let data = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                        .decode(data)
+                        .ok()?;
+                    (expires > now).then(|| serde_json::from_slice(&data).ok())?
+                });
+                Ok(Loaded {
+                    data: decoded.unwrap_or(empty.data),
+                    had_cookie: true,
+                    ..empty
+                })
+            }
+        }
+    }
+
+    /// Save the session if `current` differs from what was loaded, and
+    /// return the `Set-Cookie` header to send. Anything but an object
+    /// (`session = null`, say) ends the session.
+    ///
+    /// A stored session moves to a fresh id whenever its data changes and
+    /// the old row is deleted, so an id planted in a browser before login
+    /// is worthless after it.
+    pub(crate) fn commit(&self, loaded: Loaded, current: &Value) -> Result<Option<String>, String> {
+        let data = match current {
+            Value::Object(_) => forge_to_json(current),
+            _ => JsonValue::Object(Default::default()),
+        };
+        if data == loaded.data {
+            return Ok(None);
+        }
+        let is_empty = data.as_object().is_some_and(|obj| obj.is_empty());
+        if is_empty {
+            if let (Some(store), Some(id)) = (&self.store, &loaded.id) {
+                store.delete(id)?;
+            }
+            return Ok(loaded.had_cookie.then(|| self.cookie("", Duration::ZERO)));
+        }
+
+        let expires_ms = now_ms() + self.max_age.as_millis() as u64;
+        let payload = match &self.store {
+            Some(store) => {
+                let id = random_token()?;
+                store.save(&id, &data, expires_ms)?;
+                if let Some(old) = &loaded.id {
+                    store.delete(old)?;
+                }
+                id
+            }
+            None => format!(
+                "{:x}.{}",
+                expires_ms,
+                base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(data.to_string())
+            ),
+        };
+        let value = self.sign(&payload);
+        if value.len() > MAX_COOKIE_LEN {
+            return Err(format!(
+                "session is {} bytes, too large for a cookie; set @server(session_store:) to keep it on the server",
+                value.len()
+            ));
+        }
+        Ok(Some(self.cookie(&value, self.max_age)))
+    }
+
+    fn cookie(&self, value: &str, max_age: Duration) -> String {
+        format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+            COOKIE,
+            value,
+            max_age.as_secs(),
+            if self.secure { "; Secure" } else { "" }
+        )
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+
+    fn sign(&self, payload: &str) -> String {
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let tag =
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{}.{}", payload, tag)
+    }
+
+    /// The payload of a cookie signed by [`sign`](Self::sign), if the
+    /// signature holds.
+    fn verify(&self, cookie: &str) -> Option<String> {
+        let (payload, tag) = cookie.rsplit_once('.')?;
+        let tag = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(tag)
+            .ok()?;
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&tag).ok()?;
+        Some(payload.to_string())
+    }
+}
+
+/// Make `loaded` the request's `session` variable.
+pub(crate) fn begin(worker: &mut Worker, loaded: &Loaded) {
+    worker.begin_session(json_to_forge(loaded.data.clone()));
+}
+
+/// The request's `session` variable as the handler left it.
+pub(crate) fn current(worker: &Worker) -> Value {
+    worker.session()
+}
+
+/// The session's CSRF token, created on first use. Bound to handler
+/// parameters named `csrf_token`.
+pub(crate) fn csrf_token(worker: &mut Worker) -> Result<Value, String> {
+    let mut session = match current(worker) {
+        Value::Object(obj) => obj,
+        _ => IndexMap::new(),
+    };
+    if let Some(token @ Value::String(_)) = session.get(CSRF_KEY) {
+        return Ok(token.clone());
+    }
+    let token = Value::String(random_token()?);
+    session.insert(CSRF_KEY.to_string(), token.clone());
+    worker.set_session(Value::Object(session))?;
+    Ok(token)
+}
+
+/// Whether `request` may proceed on a `@csrf` route: safe methods always
+/// may, others must send the session's token in an `X-CSRF-Token` header
+/// or a `_csrf` body field.
+pub(crate) fn csrf_ok(session: &Value, request: &HandlerRequest) -> bool {
+    if matches!(request.method.as_str(), "GET" | "HEAD" | "OPTIONS") {
+        return true;
+    }
+    let Some(Value::String(expected)) = (match session {
+        Value::Object(obj) => obj.get(CSRF_KEY),
+        _ => None,
+    }) else {
+        return false;
+    };
+    let from_body = || match &request.body {
+        Some(JsonValue::Object(body)) => body.get(CSRF_KEY).and_then(JsonValue::as_str),
+        _ => None,
+    };
+    let Some(sent) = request
+        .headers
+        .get("x-csrf-token")
+        .map(String::as_str)
+        .or_else(from_body)
+    else {
+        return false;
+    };
+    // Constant-time, so response timing does not reveal how much matched.
+    sent.len() == expected.len()
+        && sent
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// 32 random bytes, URL-safe base64.
+fn random_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("no randomness for session: {}", e))?;
+    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn sessions(store: Option<Arc<dyn SessionStore>>) -> Sessions {
+        Sessions {
+            secret: SECRET.as_bytes().to_vec(),
+            store,
+            max_age: Duration::from_secs(60),
+            secure: false,
+        }
+    }
+
+    fn request_with(set_cookie: Option<&str>) -> HandlerRequest {
+        let mut request = HandlerRequest {
+            method: "POST".to_string(),
+            ..HandlerRequest::default()
+        };
+        if let Some(set_cookie) = set_cookie {
+            let pair = set_cookie.split(';').next().unwrap();
+            request
+                .headers
+                .insert("cookie".to_string(), pair.to_string());
+        }
+        request
+    }
+
+    fn user(name: &str) -> Value {
+        let mut obj = IndexMap::new();
+        obj.insert("user".to_string(), Value::String(name.to_string()));
+        Value::Object(obj)
+    }
+
+    #[test]
+    fn cookie_sessions_round_trip_and_reject_tampering() {
+        let sessions = sessions(None);
+        let fresh = sessions.load(&request_with(None)).unwrap();
+        // Nothing changed, nothing to send.
+        assert_eq!(
+            sessions.commit(fresh, &Value::Object(IndexMap::new())),
+            Ok(None)
+        );
+
+        let fresh = sessions.load(&request_with(None)).unwrap();
+        let cookie = sessions.commit(fresh, &user("ada")).unwrap().unwrap();
+        assert!(cookie.starts_with("forge_session="));
+        assert!(cookie.contains("HttpOnly; SameSite=Lax"));
+
+        let loaded = sessions.load(&request_with(Some(&cookie))).unwrap();
+        assert_eq!(loaded.data, serde_json::json!({ "user": "ada" }));
+
+        let forged = cookie.replacen("forge_session=", "forge_session=1", 1);
+        let loaded = sessions.load(&request_with(Some(&forged))).unwrap();
+        assert_eq!(loaded.data, serde_json::json!({}));
+
+        // Clearing the session expires the cookie.
+        let loaded = sessions.load(&request_with(Some(&cookie))).unwrap();
+        let cleared = sessions.commit(loaded, &Value::Null).unwrap().unwrap();
+        assert!(cleared.contains("Max-Age=0"));
+    }
+
+    #[test]
+    fn stored_sessions_keep_only_an_id_in_the_cookie() {
+        let store = Arc::new(MemoryStore::new());
+        let sessions = sessions(Some(store.clone()));
+        let fresh = sessions.load(&request_with(None)).unwrap();
+        let cookie = sessions.commit(fresh, &user("ada")).unwrap().unwrap();
+        assert!(!cookie.contains("ada"));
+
+        let loaded = sessions.load(&request_with(Some(&cookie))).unwrap();
+        assert_eq!(loaded.data, serde_json::json!({ "user": "ada" }));
+        let id = loaded.id.clone().unwrap();
+
+        sessions.commit(loaded, &Value::Null).unwrap();
+        assert_eq!(store.load(&id, 0), Ok(None));
+    }
+
+    #[test]
+    fn stored_sessions_change_id_when_their_data_changes() {
+        let store = Arc::new(MemoryStore::new());
+        let sessions = sessions(Some(store.clone()));
+        // An id an attacker got for themselves and planted in a browser.
+        let fresh = sessions.load(&request_with(None)).unwrap();
+        let planted = sessions.commit(fresh, &user("guest")).unwrap().unwrap();
+        let planted_id = sessions
+            .load(&request_with(Some(&planted)))
+            .unwrap()
+            .id
+            .unwrap();
+
+        // The victim logs in on it.
+        let loaded = sessions.load(&request_with(Some(&planted))).unwrap();
+        let login = sessions.commit(loaded, &user("ada")).unwrap().unwrap();
+        let loaded = sessions.load(&request_with(Some(&login))).unwrap();
+        assert_ne!(loaded.id.as_deref(), Some(planted_id.as_str()));
+        assert_eq!(loaded.data, serde_json::json!({ "user": "ada" }));
+        assert_eq!(store.load(&planted_id, 0), Ok(None));
+        let planted = sessions.load(&request_with(Some(&planted))).unwrap();
+        assert_eq!(planted.data, serde_json::json!({}));
+
+        // An unchanged session keeps its id and sends no cookie.
+        assert_eq!(sessions.commit(loaded, &user("ada")), Ok(None));
+    }
+
+    #[test]
+    fn sqlite_store_expires_sessions() {
+        let path = std::env::temp_dir().join(format!(
+            "forge_sessions_{}_{}.db",
+            std::process::id(),
+            now_ms()
+        ));
+        let path = path.to_string_lossy().to_string();
+        let store = SqliteStore::open(&path).unwrap();
+        let data = serde_json::json!({ "n": 1 });
+        let far = now_ms() + 60_000;
+        store.save("a", &data, far).unwrap();
+
+        let reopened = SqliteStore::open(&path).unwrap();
+        assert_eq!(reopened.load("a", 0), Ok(Some(data)));
+        assert_eq!(reopened.load("a", far), Ok(None));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn csrf_tokens_must_match_the_session() {
+        let mut worker = Worker::Interpreted(Box::new(Interpreter::new()));
+        let loaded = sessions(None).load(&request_with(None)).unwrap();
+        begin(&mut worker, &loaded);
+        let token = csrf_token(&mut worker).unwrap();
+        assert_eq!(csrf_token(&mut worker).unwrap(), token);
+        let Value::String(token) = token else {
+            panic!("token is a string")
+        };
+        let session = current(&worker);
+
+        let mut request = request_with(None);
+        assert!(!csrf_ok(&session, &request));
+        request.body = Some(serde_json::json!({ "_csrf": token }));
+        assert!(csrf_ok(&session, &request));
+        request
+            .headers
+            .insert("x-csrf-token".to_string(), "wrong".to_string());
+        assert!(!csrf_ok(&session, &request));
+
+        request.method = "GET".to_string();
+        assert!(csrf_ok(&Value::Null, &request));
+    }
+}
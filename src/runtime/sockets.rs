@@ -0,0 +1,536 @@
+//! Stateful `@ws` connections and the rooms they broadcast to.
+//!
+//! Each connection gets one worker fork for its whole life, so the
+//! `on_open`, message and `on_close` callbacks share state: a global
+//! assigned in `on_open` is still there when the next message arrives.
+//! Callbacks run one at a time on the blocking pool; a client disconnect
+//! cancels the one in progress at its next safe point.
+//!
+//! Outgoing frames for a connection go through a bounded queue drained by
+//! a writer task, so replies, `ws.broadcast` from any handler and
+//! `ws.close()` are delivered in order without holding the socket across
+//! Forge calls. The process-wide [`Hub`] maps connection ids to those
+//! queues and room names to their members.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use axum::extract::ws::{Message, WebSocket};
+use futures_util::{SinkExt, StreamExt};
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+
+use crate::interpreter::Value;
+use crate::runtime::server::{forge_to_json, json_to_forge, CancelOnDrop, HandlerRequest};
+use crate::runtime::worker::Worker;
+
+/// Frames queued for one connection before `ws.broadcast` starts skipping
+/// it as too slow.
+const OUTBOX: usize = 64;
+
+/// `__type__` of the object `ws.binary(bytes)` returns.
+const BINARY_TYPE: &str = "WsBinary";
+
+/// A frame on its way to a client.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Outbound {
+    Text(String),
+    Binary(Vec<u8>),
+    Close,
+}
+
+#[derive(Default)]
+struct HubState {
+    connections: HashMap<u64, mpsc::Sender<Outbound>>,
+    rooms: HashMap<String, BTreeSet<u64>>,
+}
+
+/// Every open `@ws` connection in the process, and the rooms they joined.
+#[derive(Default)]
+pub(crate) struct Hub {
+    next_id: AtomicU64,
+    state: Mutex<HubState>,
+}
+
+impl Hub {
+    pub fn global() -> &'static Hub {
+        static GLOBAL: OnceLock<Hub> = OnceLock::new();
+        GLOBAL.get_or_init(Hub::default)
+    }
+
+    fn register(&self, outbox: mpsc::Sender<Outbound>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.state.lock().connections.insert(id, outbox);
+        id
+    }
+
+    fn unregister(&self, id: u64) {
+        let mut state = self.state.lock();
+        state.connections.remove(&id);
+        state.rooms.retain(|_, members| {
+            members.remove(&id);
+            !members.is_empty()
+        });
+    }
+
+    pub fn join(&self, id: u64, room: &str) {
+        let mut state = self.state.lock();
+        if state.connections.contains_key(&id) {
+            state.rooms.entry(room.to_string()).or_default().insert(id);
+        }
+    }
+
+    pub fn leave(&self, id: u64, room: &str) {
+        let mut state = self.state.lock();
+        if let Some(members) = state.rooms.get_mut(room) {
+            members.remove(&id);
+            if members.is_empty() {
+                state.rooms.remove(room);
+            }
+        }
+    }
+
+    pub fn rooms_of(&self, id: u64) -> Vec<String> {
+        let state = self.state.lock();
+        let mut rooms: Vec<String> = state
+            .rooms
+            .iter()
+            .filter(|(_, members)| members.contains(&id))
+            .map(|(room, _)| room.clone())
+            .collect();
+        rooms.sort();
+        rooms
+    }
+
+    /// Queue `frame` for every member of `room` except `except`, returning
+    /// how many accepted it. A member whose queue is full misses the frame.
+    pub fn broadcast(&self, room: &str, frame: &Outbound, except: Option<u64>) -> usize {
+        let state = self.state.lock();
+        let Some(members) = state.rooms.get(room) else {
+            return 0;
+        };
+        members
+            .iter()
+            .filter(|id| Some(**id) != except)
+            .filter_map(|id| state.connections.get(id))
+            .filter(|outbox| outbox.try_send(frame.clone()).is_ok())
+            .count()
+    }
+
+    /// Queue `frame` for one connection; `false` when it is gone or full.
+    pub fn send(&self, id: u64, frame: Outbound) -> bool {
+        let state = self.state.lock();
+        state
+            .connections
+            .get(&id)
+            .is_some_and(|outbox| outbox.try_send(frame).is_ok())
+    }
+}
+
+/// The functions named on `@ws("/chat", on_open: "greet", on_close: "bye")`
+/// next to the decorated message handler.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SocketCallbacks {
+    pub on_open: Option<String>,
+    pub on_message: String,
+    pub on_close: Option<String>,
+}
+
+/// Serve one upgraded connection until either side closes it.
+pub(crate) async fn serve_connection(
+    socket: WebSocket,
+    mut worker: Worker,
+    callbacks: Arc<SocketCallbacks>,
+    request: HandlerRequest,
+) {
+    let (outbox, mut queued) = mpsc::channel::<Outbound>(OUTBOX);
+    let hub = Hub::global();
+    let id = hub.register(outbox.clone());
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _drop_guard = CancelOnDrop(cancelled.clone());
+    worker.set_cancelled(cancelled.clone());
+    worker.set_ws_connection(Some(id));
+    let conn = connection_value(id, &request);
+    let worker = Arc::new(Mutex::new(worker));
+
+    let (mut sink, mut stream) = socket.split();
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = queued.recv().await {
+            let message = match frame {
+                Outbound::Text(text) => Message::Text(text.into()),
+                Outbound::Binary(bytes) => Message::Binary(bytes.into()),
+                Outbound::Close => {
+                    let _ = sink.send(Message::Close(None)).await;
+                    break;
+                }
+            };
+            if sink.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Frames are read on their own task so a disconnect is noticed, and
+    // the running callback cancelled, even while a handler is busy.
+    let (inbound_tx, mut inbound) = mpsc::channel::<Value>(OUTBOX);
+    let cancel_on_close = cancelled.clone();
+    let reader = tokio::spawn(async move {
+        // Axum 0.8/tungstenite answers pings in the codec, so only data
+        // frames and the end of the stream reach this loop.
+        while let Some(message) = stream.next().await {
+            let payload = match message {
+                Ok(Message::Text(text)) => decode_text(text.as_str()),
+                Ok(Message::Binary(bytes)) => {
+                    Value::Array(bytes.iter().map(|b| Value::Int(*b as i64)).collect())
+                }
+                Ok(Message::Close(_)) | Err(_) => break,
+                Ok(_) => continue,
+            };
+            if inbound_tx.send(payload).await.is_err() {
+                break;
+            }
+        }
+        cancel_on_close.store(true, Ordering::Release);
+    });
+
+    let opened = match &callbacks.on_open {
+        Some(on_open) => match run_callback(&worker, on_open, &conn, &request, None).await {
+            Ok(frame) => {
+                deliver(&outbox, frame).await;
+                true
+            }
+            // A failing `on_open` refuses the connection.
+            Err(frame) => {
+                deliver(&outbox, Some(frame)).await;
+                deliver(&outbox, Some(Outbound::Close)).await;
+                false
+            }
+        },
+        None => true,
+    };
+    if opened {
+        while let Some(payload) = inbound.recv().await {
+            if cancelled.load(Ordering::Acquire) {
+                break;
+            }
+            let frame = run_callback(
+                &worker,
+                &callbacks.on_message,
+                &conn,
+                &request,
+                Some(payload),
+            )
+            .await
+            .unwrap_or_else(Some);
+            if cancelled.load(Ordering::Acquire) {
+                break;
+            }
+            deliver(&outbox, frame).await;
+        }
+    }
+
+    // The client may be gone, and with it the cancel flag set; `on_close`
+    // still gets to run to completion.
+    if let Some(on_close) = &callbacks.on_close {
+        worker
+            .lock()
+            .set_cancelled(Arc::new(AtomicBool::new(false)));
+        let _ = run_callback(&worker, on_close, &conn, &request, None).await;
+    }
+    cancelled.store(true, Ordering::Release);
+    hub.unregister(id);
+    reader.abort();
+    drop(outbox);
+    // Let queued frames (a close frame, a last reply) flush.
+    let _ = tokio::time::timeout(std::time::Duration::from_secs(1), writer).await;
+}
+
+/// Queue a callback's reply. Unlike broadcasts, replies wait for room in
+/// the queue rather than being dropped.
+async fn deliver(outbox: &mpsc::Sender<Outbound>, frame: Option<Outbound>) {
+    if let Some(frame) = frame {
+        let _ = outbox.send(frame).await;
+    }
+}
+
+/// Run one callback on the blocking pool. `Ok` carries the frame to send
+/// back, if any; `Err` carries an `error: ...` text frame describing a
+/// failure.
+async fn run_callback(
+    worker: &Arc<Mutex<Worker>>,
+    function: &str,
+    conn: &Value,
+    request: &HandlerRequest,
+    payload: Option<Value>,
+) -> Result<Option<Outbound>, Outbound> {
+    let worker = worker.clone();
+    let function_name = function.to_string();
+    let conn = conn.clone();
+    let request = request.clone();
+    let span = tracing::Span::current();
+    let joined = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut worker = worker.lock();
+        let Some(params) = worker.params(&function_name) else {
+            return Err(format!("handler '{}' not found", function_name));
+        };
+        let args = bind_socket_args(&params, &conn, &request, payload);
+        worker.call(&function_name, args)
+    })
+    .await;
+    let failure = match joined {
+        Ok(Ok(Value::Null)) => return Ok(None),
+        Ok(Ok(value)) => return Ok(Some(encode(&value))),
+        Ok(Err(message)) => message,
+        Err(join_err) if join_err.is_panic() => {
+            let payload = join_err.into_panic();
+            let panic = if let Some(s) = payload.downcast_ref::<&str>() {
+                (*s).to_string()
+            } else if let Some(s) = payload.downcast_ref::<String>() {
+                s.clone()
+            } else {
+                "<non-string panic payload>".to_string()
+            };
+            tracing::error!(
+                target: "forge.server",
+                handler = %function,
+                panic = %panic,
+                "websocket handler panicked"
+            );
+            "internal handler panic".to_string()
+        }
+        Err(join_err) => {
+            tracing::error!(
+                target: "forge.server",
+                handler = %function,
+                error = %join_err,
+                "websocket handler task failed"
+            );
+            "handler task failed".to_string()
+        }
+    };
+    tracing::warn!(target: "forge.server", handler = %function, error = %failure, "websocket handler failed");
+    Err(Outbound::Text(format!("error: {}", failure)))
+}
+
+/// Bind callback parameters by name: `conn` (or `connection`, `socket`,
+/// `req`, `request`) is the connection, `message` (or `msg`, `data`,
+/// `body`, `text`, `payload`) the frame, and path and query parameters
+/// bind as they do for HTTP handlers. A callback that names none of the
+/// message parameters gets the frame in its first otherwise unbound one,
+/// so `fn echo(m)` keeps working.
+fn bind_socket_args(
+    params: &[crate::parser::ast::Param],
+    conn: &Value,
+    request: &HandlerRequest,
+    payload: Option<Value>,
+) -> Vec<Value> {
+    const MESSAGE: &[&str] = &["message", "msg", "data", "body", "text", "payload"];
+    let named = params.iter().any(|p| MESSAGE.contains(&p.name.as_str()));
+    let mut payload = payload;
+    params
+        .iter()
+        .map(|param| {
+            let name = param.name.as_str();
+            if matches!(name, "conn" | "connection" | "socket" | "req" | "request") {
+                conn.clone()
+            } else if MESSAGE.contains(&name) {
+                payload.take().unwrap_or(Value::Null)
+            } else if let Some(value) = request.path_params.get(name) {
+                Value::String(value.clone())
+            } else if let Some(value) = request.query.get(name) {
+                Value::String(value.clone())
+            } else if !named {
+                payload.take().unwrap_or(Value::Null)
+            } else {
+                Value::Null
+            }
+        })
+        .collect()
+}
+
+/// The `conn` object: the upgrade request's fields plus the connection id.
+fn connection_value(id: u64, request: &HandlerRequest) -> Value {
+    let mut conn = IndexMap::new();
+    conn.insert("id".to_string(), Value::Int(id as i64));
+    if let Value::Object(fields) = request.to_value() {
+        for (key, value) in fields {
+            if !matches!(key.as_str(), "method" | "body" | "raw_body") {
+                conn.insert(key, value);
+            }
+        }
+    }
+    Value::Object(conn)
+}
+
+/// Text frames holding a JSON object or array arrive decoded; anything
+/// else, including other JSON, stays a string.
+fn decode_text(text: &str) -> Value {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        if let Ok(json) = serde_json::from_str(text) {
+            return json_to_forge(json);
+        }
+    }
+    Value::String(text.to_string())
+}
+
+/// Strings go out as text, `ws.binary(...)` as a binary frame and
+/// anything else as JSON text.
+fn encode(value: &Value) -> Outbound {
+    match value {
+        Value::String(text) => Outbound::Text(text.clone()),
+        Value::Object(obj) if matches!(obj.get("__type__"), Some(Value::String(t)) if t == BINARY_TYPE) =>
+        {
+            let bytes = match obj.get("bytes") {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .filter_map(|item| match item {
+                        Value::Int(n) => Some(*n as u8),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            Outbound::Binary(bytes)
+        }
+        other => Outbound::Text(forge_to_json(other).to_string()),
+    }
+}
+
+/// Server-side `ws` functions. `connection` is the `@ws` connection the
+/// calling interpreter or VM is serving, if any; `join`, `leave`, `rooms` and
+/// `close()` need one.
+pub(crate) fn call(name: &str, connection: Option<u64>, args: Vec<Value>) -> Result<Value, String> {
+    let hub = Hub::global();
+    let current = || {
+        connection.ok_or_else(|| {
+            format!(
+                "{}() can only be called while handling a @ws connection",
+                name
+            )
+        })
+    };
+    let room = |args: &[Value]| match args.first() {
+        Some(Value::String(room)) => Ok(room.clone()),
+        _ => Err(format!("{}() requires a room name", name)),
+    };
+    match name {
+        "ws.join" => {
+            hub.join(current()?, &room(&args)?);
+            Ok(Value::Null)
+        }
+        "ws.leave" => {
+            hub.leave(current()?, &room(&args)?);
+            Ok(Value::Null)
+        }
+        "ws.rooms" => Ok(Value::Array(
+            hub.rooms_of(current()?)
+                .into_iter()
+                .map(Value::String)
+                .collect(),
+        )),
+        "ws.close" => {
+            hub.send(current()?, Outbound::Close);
+            Ok(Value::Null)
+        }
+        "ws.broadcast" => {
+            let room = room(&args)?;
+            let Some(message) = args.get(1) else {
+                return Err("ws.broadcast() requires a message".to_string());
+            };
+            let except = match args.get(2) {
+                None | Some(Value::Null) => None,
+                Some(Value::Object(options)) => match options.get("except") {
+                    None | Some(Value::Null) => None,
+                    Some(Value::Int(id)) => Some(*id as u64),
+                    Some(_) => {
+                        return Err("ws.broadcast() except must be a connection id".to_string())
+                    }
+                },
+                Some(_) => return Err("ws.broadcast() options must be an object".to_string()),
+            };
+            let sent = hub.broadcast(&room, &encode(message), except);
+            Ok(Value::Int(sent as i64))
+        }
+        "ws.binary" => match args.first() {
+            Some(Value::Array(items)) => {
+                for item in items {
+                    if !matches!(item, Value::Int(0..=255)) {
+                        return Err("ws.binary() requires an array of bytes (0-255)".to_string());
+                    }
+                }
+                let mut obj = IndexMap::new();
+                obj.insert(
+                    "__type__".to_string(),
+                    Value::String(BINARY_TYPE.to_string()),
+                );
+                obj.insert("bytes".to_string(), Value::Array(items.clone()));
+                Ok(Value::Object(obj))
+            }
+            _ => Err("ws.binary() requires an array of bytes (0-255)".to_string()),
+        },
+        _ => Err(format!("unknown ws function: {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rooms_route_broadcasts_to_their_members() {
+        let hub = Hub::default();
+        let (a_tx, mut a_rx) = mpsc::channel(4);
+        let (b_tx, mut b_rx) = mpsc::channel(4);
+        let a = hub.register(a_tx);
+        let b = hub.register(b_tx);
+        hub.join(a, "lobby");
+        hub.join(b, "lobby");
+        hub.join(b, "staff");
+
+        let hello = Outbound::Text("hello".to_string());
+        assert_eq!(hub.broadcast("lobby", &hello, None), 2);
+        assert_eq!(hub.broadcast("lobby", &hello, Some(a)), 1);
+        assert_eq!(hub.broadcast("nobody", &hello, None), 0);
+        assert_eq!(hub.rooms_of(b), vec!["lobby", "staff"]);
+
+        assert_eq!(a_rx.try_recv().ok(), Some(hello.clone()));
+        assert!(a_rx.try_recv().is_err());
+        assert_eq!(b_rx.try_recv().ok(), Some(hello.clone()));
+        assert_eq!(b_rx.try_recv().ok(), Some(hello.clone()));
+
+        hub.leave(a, "lobby");
+        hub.unregister(b);
+        assert_eq!(hub.broadcast("lobby", &hello, None), 0);
+        assert!(hub.state.lock().rooms.is_empty());
+    }
+
+    #[test]
+    fn frames_decode_and_encode() {
+        assert_eq!(decode_text("plain"), Value::String("plain".to_string()));
+        assert_eq!(decode_text("42"), Value::String("42".to_string()));
+        let Value::Object(obj) = decode_text(r#"{"type":"chat","n":1}"#) else {
+            panic!("expected object");
+        };
+        assert_eq!(obj.get("n"), Some(&Value::Int(1)));
+
+        assert_eq!(
+            encode(&Value::String("hi".to_string())),
+            Outbound::Text("hi".to_string())
+        );
+        assert_eq!(encode(&Value::Int(3)), Outbound::Text("3".to_string()));
+        let binary = call(
+            "ws.binary",
+            None,
+            vec![Value::Array(vec![Value::Int(1), Value::Int(255)])],
+        )
+        .unwrap();
+        assert_eq!(encode(&binary), Outbound::Binary(vec![1, 255]));
+        assert!(call("ws.binary", None, vec![Value::Array(vec![Value::Int(256)])]).is_err());
+        assert!(call("ws.join", None, vec![Value::String("x".to_string())]).is_err());
+    }
+}
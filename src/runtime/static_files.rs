@@ -0,0 +1,161 @@
+//! Static file serving for `@server(static: ...)` and `@static(...)`.
+//!
+//! Files are served by `tower-http`'s [`ServeDir`], which already handles
+//! `Last-Modified` / `If-Modified-Since`, byte ranges and precompressed
+//! `.gz` siblings. On top of that every full (`200`) file response gets a
+//! weak ETag derived from its modification time, size and encoding, and
+//! a matching `If-None-Match` is answered with `304 Not Modified`.
+
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::interpreter::RuntimeError;
+
+/// Build a router serving `dir`. With `spa_fallback`, paths that do not
+/// name a file are answered with that file (relative to `dir`) and a
+/// `200`, so client-side routers can take over.
+pub(crate) fn static_router(dir: &str, spa_fallback: Option<&str>) -> Result<Router, RuntimeError> {
+    let root = Path::new(dir);
+    if !root.is_dir() {
+        return Err(RuntimeError::new(&format!(
+            "static directory '{}' does not exist",
+            dir
+        )));
+    }
+    let serve_dir = ServeDir::new(root).precompressed_gzip();
+    let router = match spa_fallback {
+        Some(file) => {
+            let index = root.join(file);
+            if !index.is_file() {
+                return Err(RuntimeError::new(&format!(
+                    "spa_fallback '{}' not found in '{}'",
+                    file, dir
+                )));
+            }
+            Router::new()
+                .fallback_service(serve_dir.fallback(ServeFile::new(index).precompressed_gzip()))
+        }
+        None => Router::new().fallback_service(serve_dir),
+    };
+    Ok(router.layer(middleware::from_fn(weak_etag)))
+}
+
+async fn weak_etag(request: Request, next: Next) -> Response {
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let mut response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let Some(etag) = etag_for(response.headers()) else {
+        return response;
+    };
+
+    let matched = if_none_match
+        .as_ref()
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, etag.to_str().unwrap_or_default()));
+    if matched {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        let headers = not_modified.headers_mut();
+        for name in [header::LAST_MODIFIED, header::CACHE_CONTROL, header::VARY] {
+            if let Some(value) = response.headers().get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        headers.insert(header::ETAG, etag);
+        return not_modified;
+    }
+
+    response.headers_mut().insert(header::ETAG, etag);
+    response
+}
+
+/// `W/"<size>-<mtime>[-<encoding>]"` in hex. The encoding is part of the
+/// tag because the `.gz` variant is a different representation.
+fn etag_for(headers: &HeaderMap) -> Option<HeaderValue> {
+    let modified = headers.get(header::LAST_MODIFIED)?.to_str().ok()?;
+    let modified = httpdate::parse_http_date(modified).ok()?;
+    let secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let len: u64 = headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    let tag = match headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(encoding) => format!("W/\"{:x}-{:x}-{}\"", len, secs, encoding),
+        None => format!("W/\"{:x}-{:x}\"", len, secs),
+    };
+    HeaderValue::from_str(&tag).ok()
+}
+
+/// Weak comparison (RFC 9110 §13.1.2): `*` or any listed tag whose opaque
+/// part equals ours.
+pub(crate) fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let ours = strip_weak(etag);
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || strip_weak(candidate) == ours)
+}
+
+fn strip_weak(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn etag_depends_on_size_mtime_and_encoding() {
+        let plain = headers(&[
+            (header::LAST_MODIFIED, "Thu, 01 Jan 1970 00:01:00 GMT"),
+            (header::CONTENT_LENGTH, "255"),
+        ]);
+        assert_eq!(etag_for(&plain).unwrap(), "W/\"ff-3c\"");
+
+        let gzipped = headers(&[
+            (header::LAST_MODIFIED, "Thu, 01 Jan 1970 00:01:00 GMT"),
+            (header::CONTENT_LENGTH, "255"),
+            (header::CONTENT_ENCODING, "gzip"),
+        ]);
+        assert_eq!(etag_for(&gzipped).unwrap(), "W/\"ff-3c-gzip\"");
+
+        let no_date = headers(&[(header::CONTENT_LENGTH, "1")]);
+        assert!(etag_for(&no_date).is_none());
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let etag = "W/\"ff-3c\"";
+        assert!(etag_matches("\"ff-3c\"", etag));
+        assert!(etag_matches("\"other\", W/\"ff-3c\"", etag));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches("W/\"ff-3d\"", etag));
+    }
+
+    #[test]
+    fn missing_directories_are_startup_errors() {
+        let err = static_router("./definitely-not-a-dir", None).unwrap_err();
+        assert!(err.message.contains("does not exist"));
+    }
+}
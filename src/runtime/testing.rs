@@ -0,0 +1,172 @@
+//! In-process clients for decorated servers.
+//!
+//! A [`TestClient`] builds the router `forge run` would serve for a
+//! program and dispatches requests to it with `tower::ServiceExt::oneshot`,
+//! so routes, middleware, validation and the built-in endpoints can be
+//! tested without binding a port. `schedule` and `watch` blocks are not
+//! started.
+
+use std::path::{Path, PathBuf};
+
+use axum::body::Body;
+use axum::http;
+use axum::response::Response;
+
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+use super::host;
+use super::metadata::{extract_runtime_plan_in, RuntimePlan};
+use super::server::App;
+use super::worker::Served;
+
+/// Lex, parse and run `source` as `forge run` would, returning the
+/// interpreter and the plan of what it declared. Imports resolve next to
+/// `source_file` when one is given.
+pub fn load(
+    source: &str,
+    source_file: Option<&Path>,
+) -> Result<(Interpreter, RuntimePlan), RuntimeError> {
+    let label = source_file
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "<source>".to_string());
+    let tokens = Lexer::new(source)
+        .tokenize()
+        .map_err(|e| RuntimeError::new(&format!("{}: {}", label, e)))?;
+    let program = Parser::new(tokens)
+        .parse_program()
+        .map_err(|e| RuntimeError::new(&format!("{}: {}", label, e)))?;
+
+    let mut interpreter = Interpreter::new();
+    interpreter.source = Some(source.to_string());
+    interpreter.source_file = source_file.map(Path::to_path_buf);
+    interpreter.set_defer_host_runtime(true);
+    interpreter
+        .run(&program)
+        .map_err(|e| RuntimeError::new(&format!("{}: {}", label, e.message)))?;
+
+    let base_dir = source_file.and_then(Path::parent);
+    Ok((interpreter, extract_runtime_plan_in(&program, base_dir)))
+}
+
+/// [`load`] the Forge file at `path`.
+pub fn load_file(path: &Path) -> Result<(Interpreter, RuntimePlan), RuntimeError> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| RuntimeError::new(&format!("cannot read '{}': {}", path.display(), e)))?;
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    load(&source, Some(&path))
+}
+
+/// A program's `@server`, ready to take requests in memory.
+pub struct TestClient {
+    app: App,
+}
+
+impl TestClient {
+    /// Run the plan's `@on_start` hooks and build its server.
+    pub async fn new(
+        program: impl Into<Served>,
+        plan: &RuntimePlan,
+    ) -> Result<TestClient, RuntimeError> {
+        let Some(server) = &plan.server else {
+            return Err(RuntimeError::new("program has no @server"));
+        };
+        host::check_server(plan)?;
+        let program = host::run_on_start(program.into(), plan.on_start.clone()).await?;
+        Ok(TestClient {
+            app: App::build(program, server)?,
+        })
+    }
+
+    /// Send one request through the full middleware stack.
+    pub async fn request(&self, request: http::Request<Body>) -> Response {
+        self.app.oneshot(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dispatches_without_a_socket() {
+        let (interpreter, plan) = load(
+            r#"
+            @server(port: 0)
+
+            @get("/hello/:name")
+            fn hello(name, req) -> Json {
+                return { hello: name, addr: req.remote_addr }
+            }
+            "#,
+            None,
+        )
+        .unwrap();
+        let client = TestClient::new(interpreter, &plan).await.unwrap();
+
+        let response = client
+            .request(
+                http::Request::get("/hello/ada")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert!(response.headers().contains_key("x-request-id"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({ "hello": "ada", "addr": null }));
+
+        let missing = client
+            .request(http::Request::get("/nope").body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(missing.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serves_a_compiled_program() {
+        let source = r#"
+            @server(port: 0)
+
+            let mut greeting = "hi"
+
+            @on_start
+            fn warm() { greeting = "hello" }
+
+            @get("/add/:a")
+            fn add(a: Int, b: Int = 2) -> Json {
+                return { sum: a + b, greeting: greeting }
+            }
+        "#;
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let chunk = crate::vm::compiler::compile(&program).unwrap();
+        let (interpreter, plan) = host::load_compiled(&chunk).unwrap();
+        let client = TestClient::new(interpreter, &plan).await.unwrap();
+
+        let response = client
+            .request(http::Request::get("/add/40").body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({ "sum": 42, "greeting": "hello" }));
+
+        let invalid = client
+            .request(http::Request::get("/add/x").body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(invalid.status(), http::StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn requires_a_server() {
+        let (interpreter, plan) = load("let x = 1", None).unwrap();
+        let err = TestClient::new(interpreter, &plan).await.err().unwrap();
+        assert_eq!(err.message, "program has no @server");
+    }
+}
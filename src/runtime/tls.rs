@@ -0,0 +1,188 @@
+//! HTTPS termination for the built-in server.
+//!
+//! `@server(tls_cert: ..., tls_key: ...)` (or `FORGE_TLS_CERT` /
+//! `FORGE_TLS_KEY`) swaps the plain TCP listener for [`TlsListener`].
+//! It implements axum's [`Listener`] trait, so `axum::serve` keeps
+//! handling graceful shutdown, HTTP/1.1 vs HTTP/2 (negotiated via ALPN)
+//! and `ConnectInfo` exactly as it does for plain TCP.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::serve::Listener;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::interpreter::RuntimeError;
+use crate::runtime::metadata::ServerConfig;
+
+/// Handshakes that take longer than this are dropped so a stalled client
+/// cannot hold a socket open indefinitely.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Completed handshakes waiting for axum to pick them up.
+const ACCEPT_BACKLOG: usize = 128;
+
+/// Certificate and key paths for a server, or `None` for plain HTTP.
+/// Decorator arguments win over the `FORGE_TLS_CERT` / `FORGE_TLS_KEY`
+/// environment variables; setting only one of the pair is an error.
+pub fn tls_paths(config: &ServerConfig) -> Result<Option<(String, String)>, RuntimeError> {
+    let cert = config
+        .tls_cert
+        .clone()
+        .or_else(|| std::env::var("FORGE_TLS_CERT").ok());
+    let key = config
+        .tls_key
+        .clone()
+        .or_else(|| std::env::var("FORGE_TLS_KEY").ok());
+    match (cert, key) {
+        (Some(cert), Some(key)) => Ok(Some((cert, key))),
+        (None, None) => Ok(None),
+        (Some(_), None) => Err(RuntimeError::new("tls_cert is set but tls_key is missing")),
+        (None, Some(_)) => Err(RuntimeError::new("tls_key is set but tls_cert is missing")),
+    }
+}
+
+/// Load a PEM certificate chain and private key into a rustls server
+/// config advertising `h2` and `http/1.1` over ALPN.
+pub fn load_server_config(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<Arc<rustls::ServerConfig>, RuntimeError> {
+    // Several crates in the tree enable different rustls providers, so
+    // pick ring explicitly (as the Postgres driver does).
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            RuntimeError::new(&format!(
+                "failed to read TLS certificate '{}': {}",
+                cert_path, e
+            ))
+        })?;
+    if certs.is_empty() {
+        return Err(RuntimeError::new(&format!(
+            "no certificates found in '{}'",
+            cert_path
+        )));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| RuntimeError::new(&format!("failed to read TLS key '{}': {}", key_path, e)))?;
+
+    let mut config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| RuntimeError::new(&format!("invalid TLS certificate or key: {}", e)))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// A TCP listener that yields TLS streams.
+///
+/// Handshakes run on their own tasks and completed streams are queued,
+/// so one slow client never blocks `accept` for everyone else.
+pub struct TlsListener {
+    ready: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    pub fn new(tcp: TcpListener, config: Arc<rustls::ServerConfig>) -> io::Result<Self> {
+        let local_addr = tcp.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, ready) = mpsc::channel(ACCEPT_BACKLOG);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = tokio::select! {
+                    // The server (and with it the receiver) is gone.
+                    _ = tx.closed() => break,
+                    accepted = tcp.accept() => match accepted {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            tracing::warn!(target: "forge.server", error = %e, "accept failed");
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            continue;
+                        }
+                    },
+                };
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(tls)) => {
+                            let _ = tx.send((tls, addr)).await;
+                        }
+                        Ok(Err(e)) => tracing::debug!(
+                            target: "forge.server",
+                            remote = %addr,
+                            error = %e,
+                            "TLS handshake failed"
+                        ),
+                        Err(_) => tracing::debug!(
+                            target: "forge.server",
+                            remote = %addr,
+                            "TLS handshake timed out"
+                        ),
+                    }
+                });
+            }
+        });
+
+        Ok(Self { ready, local_addr })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.ready.recv().await {
+            Some(conn) => conn,
+            // The accept task only exits once this receiver is dropped,
+            // so the channel cannot close while we are waiting on it.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(cert: Option<&str>, key: Option<&str>) -> ServerConfig {
+        ServerConfig {
+            port: 0,
+            tls_cert: cert.map(str::to_string),
+            tls_key: key.map(str::to_string),
+            ..ServerConfig::default()
+        }
+    }
+
+    #[test]
+    fn tls_requires_both_cert_and_key() {
+        assert_eq!(
+            tls_paths(&config(Some("c.pem"), Some("k.pem"))).unwrap(),
+            Some(("c.pem".to_string(), "k.pem".to_string()))
+        );
+        assert!(tls_paths(&config(Some("c.pem"), None)).is_err());
+    }
+
+    #[test]
+    fn unreadable_certificate_is_a_startup_error() {
+        let err = load_server_config("/nonexistent/cert.pem", "/nonexistent/key.pem").unwrap_err();
+        assert!(err.message.contains("failed to read TLS certificate"));
+    }
+}
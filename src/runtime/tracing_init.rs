@@ -3,7 +3,7 @@
 //! This is the one place that installs a global `tracing` subscriber.
 //! [`init_subscriber`] is idempotent (`OnceLock` + `try_init`); it is
 //! called from any path that wants its `tracing` events to reach a
-//! user — currently `start_server` (so per-request HTTP spans show up)
+//! user — currently `serve_until` (so per-request HTTP spans show up)
 //! and the `log` stdlib module (so `log.info` from a CLI-invoked
 //! script reaches the user without depending on the server path).
 //!
@@ -31,7 +31,7 @@
 //! `init_otel` MUST be called from the main tokio runtime (not from a
 //! nested runtime created by a stdlib helper), since the batch
 //! processor binds to whichever runtime constructs it. The valid call
-//! sites are `start_server` (for the HTTP path) and `main` (for CLI
+//! sites are `serve_until` (for the HTTP path) and `main` (for CLI
 //! scripts so they don't drop their last batch on exit).
 //!
 //! Honored env vars (subset of the OpenTelemetry spec):
@@ -117,7 +117,7 @@ pub fn otel_is_active() -> bool {
 /// Install the global subscriber. Idempotent and panic-safe.
 ///
 /// Called from any path that wants its `tracing` events to be visible:
-/// `start_server` (so per-request HTTP spans surface) and the `log`
+/// `serve_until` (so per-request HTTP spans surface) and the `log`
 /// stdlib module (so `log.info` from a CLI-invoked script reaches the
 /// user without depending on the server path having run).
 ///
@@ -210,7 +210,7 @@ pub fn init_subscriber() {
 ///
 /// **Must be called from the main tokio runtime**, not from a nested
 /// runtime created by a stdlib helper. The valid call sites are
-/// `start_server` (for the HTTP path) and `main` (for CLI scripts so
+/// `serve_until` (for the HTTP path) and `main` (for CLI scripts so
 /// their last batch isn't dropped on exit).
 ///
 /// Idempotent: subsequent calls are no-ops via the `OTEL_PROVIDER`
@@ -339,7 +339,7 @@ mod tests {
     use super::*;
 
     /// Calling init twice must not panic. This is the integration-test
-    /// scenario: each `start_server` call goes through `init_subscriber`,
+    /// scenario: each `serve_until` call goes through `init_subscriber`,
     /// and a single test binary may boot the server many times.
     #[test]
     fn init_is_idempotent() {
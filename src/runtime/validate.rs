@@ -0,0 +1,378 @@
+//! Type checks for annotated handler parameters.
+//!
+//! Path and query parameters arrive as strings and are parsed into the
+//! annotated `Int` / `Float` / `Bool`. JSON bodies are checked against the
+//! annotation structurally: struct types by their declared fields (filling
+//! defaults and dropping unknown keys), arrays and maps element by element.
+//! Checks never stop at the first problem, so a single `422` can list every
+//! violation in the request.
+
+use indexmap::IndexMap;
+use serde_json::json;
+
+use crate::interpreter::{Interpreter, Value};
+use crate::parser::ast::TypeAnn;
+use crate::runtime::response::HandlerResponse;
+
+/// One failed check, reported to the client as
+/// `{"in": "body", "field": "body.email", "message": "..."}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Violation {
+    pub location: &'static str,
+    pub field: String,
+    pub message: String,
+}
+
+/// Collects violations while checking the arguments of one call.
+#[derive(Default)]
+pub(crate) struct Checker {
+    pub violations: Vec<Violation>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a path or query parameter into its annotated scalar type.
+    /// Non-scalar annotations are checked against the raw string.
+    pub fn param(
+        &mut self,
+        interp: &Interpreter,
+        location: &'static str,
+        ann: &TypeAnn,
+        raw: Value,
+        field: &str,
+    ) -> Value {
+        let Value::String(text) = &raw else {
+            return self.value(interp, location, ann, raw, field.to_string());
+        };
+        let scalar = match ann {
+            TypeAnn::Optional(inner) => inner.as_ref(),
+            other => other,
+        };
+        let parsed = match scalar {
+            TypeAnn::Simple(name) if name == "Int" => text.trim().parse().ok().map(Value::Int),
+            TypeAnn::Simple(name) if name == "Float" => text.trim().parse().ok().map(Value::Float),
+            TypeAnn::Simple(name) if name == "Bool" => match text.trim() {
+                "true" | "1" => Some(Value::Bool(true)),
+                "false" | "0" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            _ => return self.value(interp, location, ann, raw, field.to_string()),
+        };
+        parsed.unwrap_or_else(|| {
+            self.fail(
+                location,
+                field.to_string(),
+                format!("expected {}, got \"{}\"", describe(scalar), text),
+            );
+            Value::Null
+        })
+    }
+
+    /// Check a value decoded from JSON against `ann`, returning it with
+    /// struct defaults filled in.
+    pub fn value(
+        &mut self,
+        interp: &Interpreter,
+        location: &'static str,
+        ann: &TypeAnn,
+        value: Value,
+        field: String,
+    ) -> Value {
+        match (ann, value) {
+            (TypeAnn::Optional(_), Value::Null) => Value::Null,
+            (TypeAnn::Optional(inner), value) => self.value(interp, location, inner, value, field),
+            (TypeAnn::Array(item), Value::Array(items)) => {
+                self.items(interp, location, item, items, &field)
+            }
+            (TypeAnn::Generic(name, args), value) => {
+                match (name.as_str(), args.as_slice(), value) {
+                    ("Array" | "List", [item], Value::Array(items)) => {
+                        self.items(interp, location, item, items, &field)
+                    }
+                    ("Map", [.., item], Value::Object(entries)) => Value::Object(
+                        entries
+                            .into_iter()
+                            .map(|(key, entry)| {
+                                let path = format!("{}.{}", field, key);
+                                (key, self.value(interp, location, item, entry, path))
+                            })
+                            .collect(),
+                    ),
+                    ("Array" | "List" | "Map", _, value) => {
+                        self.mismatch(location, ann, value, field)
+                    }
+                    (_, _, value) => value,
+                }
+            }
+            (TypeAnn::Simple(name), value) => match (name.as_str(), value) {
+                ("Int", value @ Value::Int(_))
+                | ("Float", value @ Value::Float(_))
+                | ("String", value @ Value::String(_))
+                | ("Bool", value @ Value::Bool(_))
+                | ("Array" | "List", value @ Value::Array(_))
+                | ("Object" | "Map", value @ Value::Object(_)) => value,
+                ("Float", Value::Int(n)) => Value::Float(n as f64),
+                (
+                    "Int" | "Float" | "String" | "Bool" | "Array" | "List" | "Object" | "Map",
+                    value,
+                ) => self.mismatch(location, ann, value, field),
+                (name, Value::Object(entries)) if interp.struct_fields.contains_key(name) => {
+                    self.fields(interp, location, name, entries, &field)
+                }
+                (name, value) if interp.struct_fields.contains_key(name) => {
+                    self.mismatch(location, ann, value, field)
+                }
+                // `Json`, `Any`, ADTs and unknown names accept anything.
+                (_, value) => value,
+            },
+            (TypeAnn::Array(_), value) => self.mismatch(location, ann, value, field),
+            (_, value) => value,
+        }
+    }
+
+    fn items(
+        &mut self,
+        interp: &Interpreter,
+        location: &'static str,
+        item: &TypeAnn,
+        items: Vec<Value>,
+        field: &str,
+    ) -> Value {
+        Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    self.value(interp, location, item, value, format!("{}[{}]", field, i))
+                })
+                .collect(),
+        )
+    }
+
+    /// Check an object against a struct's declared fields. Missing fields
+    /// take the struct default, or `null` when optional; keys the struct
+    /// does not declare are dropped.
+    fn fields(
+        &mut self,
+        interp: &Interpreter,
+        location: &'static str,
+        struct_name: &str,
+        mut entries: IndexMap<String, Value>,
+        field: &str,
+    ) -> Value {
+        let declared = &interp.struct_fields[struct_name];
+        let defaults = interp.struct_defaults.get(struct_name);
+        let mut checked = IndexMap::new();
+        for (name, ann) in declared {
+            let path = format!("{}.{}", field, name);
+            let value = match entries.shift_remove(name) {
+                Some(value) => self.value(interp, location, ann, value, path),
+                None => match defaults.and_then(|defaults| defaults.get(name)) {
+                    Some(default) => default.clone(),
+                    None if matches!(ann, TypeAnn::Optional(_)) => Value::Null,
+                    None => {
+                        self.fail(location, path, "is required".to_string());
+                        Value::Null
+                    }
+                },
+            };
+            checked.insert(name.clone(), value);
+        }
+        Value::Object(checked)
+    }
+
+    /// Record that a required parameter is absent.
+    pub fn missing(&mut self, location: &'static str, field: &str) {
+        self.fail(location, field.to_string(), "is required".to_string());
+    }
+
+    fn mismatch(
+        &mut self,
+        location: &'static str,
+        ann: &TypeAnn,
+        value: Value,
+        field: String,
+    ) -> Value {
+        let got = match &value {
+            Value::Null => "null".to_string(),
+            Value::Object(_) => "Object".to_string(),
+            other => other.type_name().to_string(),
+        };
+        self.fail(
+            location,
+            field,
+            format!("expected {}, got {}", describe(ann), got),
+        );
+        value
+    }
+
+    fn fail(&mut self, location: &'static str, field: String, message: String) {
+        self.violations.push(Violation {
+            location,
+            field,
+            message,
+        });
+    }
+
+    /// `422 Unprocessable Entity` listing every violation, or `None` when
+    /// everything checked out.
+    pub fn into_response(self) -> Option<HandlerResponse> {
+        if self.violations.is_empty() {
+            return None;
+        }
+        let violations: Vec<_> = self
+            .violations
+            .iter()
+            .map(|v| json!({ "in": v.location, "field": v.field, "message": v.message }))
+            .collect();
+        Some(HandlerResponse::json(
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            json!({ "error": "validation failed", "violations": violations }),
+        ))
+    }
+}
+
+/// Forge spelling of a type annotation, for messages.
+fn describe(ann: &TypeAnn) -> String {
+    match ann {
+        TypeAnn::Simple(name) => name.clone(),
+        TypeAnn::Array(item) => format!("[{}]", describe(item)),
+        TypeAnn::Optional(inner) => format!("?{}", describe(inner)),
+        TypeAnn::Generic(name, args) => format!(
+            "{}<{}>",
+            name,
+            args.iter().map(describe).collect::<Vec<_>>().join(", ")
+        ),
+        TypeAnn::Tuple(items) => format!(
+            "({})",
+            items.iter().map(describe).collect::<Vec<_>>().join(", ")
+        ),
+        TypeAnn::Function(..) => "Function".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn interp_with(source: &str) -> Interpreter {
+        let tokens = Lexer::new(source).tokenize().expect("lex");
+        let program = Parser::new(tokens).parse_program().expect("parse");
+        let mut interp = Interpreter::new();
+        interp.run(&program).expect("run");
+        interp
+    }
+
+    fn simple(name: &str) -> TypeAnn {
+        TypeAnn::Simple(name.to_string())
+    }
+
+    fn object(pairs: &[(&str, Value)]) -> Value {
+        Value::Object(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn struct_bodies_report_every_violation_and_fill_defaults() {
+        let interp = interp_with(
+            r#"
+            struct Address { city: String, zip: String }
+            struct NewUser {
+                name: String,
+                age: Int,
+                admin: Bool = false,
+                nickname: ?String,
+                tags: [String],
+                address: Address
+            }
+            "#,
+        );
+        let body = object(&[
+            ("name", Value::Int(7)),
+            (
+                "tags",
+                Value::Array(vec![Value::String("a".into()), Value::Int(1)]),
+            ),
+            ("address", object(&[("city", Value::String("Oslo".into()))])),
+            ("extra", Value::Bool(true)),
+        ]);
+
+        let mut checker = Checker::new();
+        let checked = checker.value(&interp, "body", &simple("NewUser"), body, "body".into());
+        let fields: Vec<(&str, &str)> = checker
+            .violations
+            .iter()
+            .map(|v| (v.field.as_str(), v.message.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("body.name", "expected String, got Int"),
+                ("body.age", "is required"),
+                ("body.tags[1]", "expected String, got Int"),
+                ("body.address.zip", "is required"),
+            ]
+        );
+        let Value::Object(checked) = checked else {
+            panic!("expected object");
+        };
+        assert_eq!(checked.get("admin"), Some(&Value::Bool(false)));
+        assert_eq!(checked.get("nickname"), Some(&Value::Null));
+        assert!(!checked.contains_key("extra"));
+    }
+
+    #[test]
+    fn params_are_parsed_into_annotated_scalars() {
+        let interp = Interpreter::new();
+        let mut checker = Checker::new();
+        let raw = |s: &str| Value::String(s.to_string());
+
+        assert_eq!(
+            checker.param(&interp, "path", &simple("Int"), raw("42"), "id"),
+            Value::Int(42)
+        );
+        assert_eq!(
+            checker.param(&interp, "query", &simple("Float"), raw("0.5"), "ratio"),
+            Value::Float(0.5)
+        );
+        assert_eq!(
+            checker.param(
+                &interp,
+                "query",
+                &TypeAnn::Optional(Box::new(simple("Bool"))),
+                raw("true"),
+                "verbose"
+            ),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            checker.param(&interp, "query", &simple("String"), raw("x"), "q"),
+            raw("x")
+        );
+        assert!(checker.violations.is_empty());
+
+        checker.param(&interp, "path", &simple("Int"), raw("abc"), "id");
+        assert_eq!(
+            checker.violations,
+            vec![Violation {
+                location: "path",
+                field: "id".to_string(),
+                message: "expected Int, got \"abc\"".to_string(),
+            }]
+        );
+        let response = checker.into_response().expect("422");
+        assert_eq!(
+            response.status,
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//! What the server runs a program's handlers on.
+//!
+//! A program loaded from source is served by a tree-walking
+//! [`Interpreter`], forked for every request. A compiled program never
+//! builds one per request: each request gets a [`VmWorker`] started from
+//! the state its main chunk left behind, and the handler signatures and
+//! struct definitions the server binds and validates arguments with are
+//! read from a single shared copy of the chunk's declarations.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::interpreter::{EventSink, Interpreter, RuntimeError, Value};
+use crate::parser::ast::{Param, Program, SpannedStmt, Stmt};
+use crate::vm::template::{VmTemplate, VmWorker};
+
+/// A program ready to serve.
+pub enum Served {
+    /// Run from source; requests fork the interpreter.
+    Interpreted(Interpreter),
+    /// Run from bytecode; requests start VMs from its template.
+    Compiled(Compiled),
+}
+
+impl From<Interpreter> for Served {
+    fn from(interpreter: Interpreter) -> Self {
+        Served::Interpreted(interpreter)
+    }
+}
+
+impl From<Compiled> for Served {
+    fn from(compiled: Compiled) -> Self {
+        Served::Compiled(compiled)
+    }
+}
+
+/// A compiled program whose main chunk has run.
+pub struct Compiled {
+    worker: VmWorker,
+    declarations: Arc<Declarations>,
+}
+
+/// What the server needs to know about a compiled program's functions
+/// and structs, which the VM does not keep.
+struct Declarations {
+    /// Has run the program's struct definitions and nothing else, so
+    /// request bodies validate against them.
+    structs: Interpreter,
+    /// Each top-level function's parameters, with the value of each
+    /// declared default (`null` where there is none).
+    functions: HashMap<String, (Vec<Param>, Vec<Value>)>,
+}
+
+impl Compiled {
+    /// Serve `template`, the state left by a chunk compiled with
+    /// `declarations`.
+    pub fn new(template: VmTemplate, declarations: &[SpannedStmt]) -> Result<Self, RuntimeError> {
+        let mut structs = Interpreter::new();
+        structs.run(&Program {
+            statements: declarations
+                .iter()
+                .filter(|s| matches!(s.stmt, Stmt::StructDef { .. }))
+                .cloned()
+                .collect(),
+        })?;
+        let mut functions = HashMap::new();
+        for spanned in declarations {
+            if let Stmt::FnDef { name, params, .. } = &spanned.stmt {
+                // The compiler only lets literal defaults through.
+                let defaults = params
+                    .iter()
+                    .map(|param| match &param.default {
+                        Some(default) => structs.eval_expr(default),
+                        None => Ok(Value::Null),
+                    })
+                    .collect::<Result<_, _>>()?;
+                functions.insert(name.clone(), (params.clone(), defaults));
+            }
+        }
+        Ok(Compiled {
+            worker: VmWorker::new(Arc::new(template)),
+            declarations: Arc::new(Declarations { structs, functions }),
+        })
+    }
+}
+
+impl Served {
+    /// Whether the program defines a function called `name`.
+    pub(crate) fn defines(&self, name: &str) -> bool {
+        match self {
+            Served::Interpreted(interp) => {
+                matches!(interp.env.get(name), Some(Value::Function { .. }))
+            }
+            Served::Compiled(compiled) => compiled.worker.defines(name),
+        }
+    }
+
+    /// A worker for one request, starting from the program's state.
+    pub(crate) fn fork(&self) -> Worker {
+        match self {
+            Served::Interpreted(interp) => Worker::Interpreted(Box::new(interp.fork_for_serving())),
+            Served::Compiled(compiled) => Worker::Compiled(Box::new(VmRequest {
+                worker: compiled.worker.fork(),
+                declarations: Arc::clone(&compiled.declarations),
+                cancelled: Arc::new(AtomicBool::new(false)),
+                session: None,
+            })),
+        }
+    }
+
+    /// A copy to run background work on, such as `@on_shutdown` hooks,
+    /// without touching the state requests fork from.
+    pub(crate) fn fork_for_background(&self) -> Served {
+        match self {
+            Served::Interpreted(interp) => {
+                Served::Interpreted(interp.fork_for_background_runtime())
+            }
+            Served::Compiled(compiled) => Served::Compiled(Compiled {
+                worker: compiled.worker.fork(),
+                declarations: Arc::clone(&compiled.declarations),
+            }),
+        }
+    }
+
+    /// Call the lifecycle hook `name` with no arguments. What it changes
+    /// stays on this program.
+    pub(crate) fn call_hook(&mut self, name: &str) -> Result<Value, RuntimeError> {
+        match self {
+            Served::Interpreted(interp) => {
+                let function = interp
+                    .env
+                    .get(name)
+                    .ok_or_else(|| RuntimeError::new(&format!("'{}' is not defined", name)))?;
+                interp.call_function(function, Vec::new())
+            }
+            Served::Compiled(compiled) => {
+                let args = compiled
+                    .declarations
+                    .functions
+                    .get(name)
+                    .map(|(_, defaults)| defaults.clone())
+                    .unwrap_or_default();
+                compiled
+                    .worker
+                    .call(name, &args, &Arc::new(AtomicBool::new(false)))
+                    .map_err(|e| RuntimeError::new(&e.message))
+            }
+        }
+    }
+
+    /// Make what hooks changed so far the state requests fork from.
+    pub(crate) fn settle(&mut self) -> Result<(), RuntimeError> {
+        match self {
+            Served::Interpreted(_) => Ok(()),
+            Served::Compiled(compiled) => compiled
+                .worker
+                .settle()
+                .map_err(|e| RuntimeError::new(&e.message)),
+        }
+    }
+}
+
+/// Where one request's hooks and handler run, or one WebSocket
+/// connection's callbacks.
+pub(crate) enum Worker {
+    Interpreted(Box<Interpreter>),
+    Compiled(Box<VmRequest>),
+}
+
+pub(crate) struct VmRequest {
+    worker: VmWorker,
+    declarations: Arc<Declarations>,
+    cancelled: Arc<AtomicBool>,
+    /// The request's `session`. The VM keeps its own globals, so it is
+    /// copied in before each call and read back after.
+    session: Option<Value>,
+}
+
+impl Worker {
+    /// Stop at the next safe point once `cancelled` is set.
+    pub(crate) fn set_cancelled(&mut self, cancelled: Arc<AtomicBool>) {
+        match self {
+            Worker::Interpreted(interp) => interp.cancelled = cancelled,
+            Worker::Compiled(vm) => vm.cancelled = cancelled,
+        }
+    }
+
+    /// The `@ws` connection the calls that follow are handling.
+    pub(crate) fn set_ws_connection(&mut self, connection: Option<u64>) {
+        match self {
+            Worker::Interpreted(interp) => interp.ws_connection = connection,
+            Worker::Compiled(vm) => vm.worker.set_ws_connection(connection),
+        }
+    }
+
+    /// Where `yield` / `emit` send values from the calls that follow.
+    pub(crate) fn set_event_sink(&mut self, sink: EventSink) {
+        match self {
+            Worker::Interpreted(interp) => interp.event_sink = Some(sink),
+            Worker::Compiled(vm) => vm.worker.set_event_sink(Some(sink)),
+        }
+    }
+
+    /// The parameters of the function `name`, or `None` if the program
+    /// does not define it.
+    pub(crate) fn params(&self, name: &str) -> Option<Vec<Param>> {
+        match self {
+            Worker::Interpreted(interp) => match interp.env.get(name)? {
+                Value::Function { params, .. } => Some(params),
+                _ => Some(Vec::new()),
+            },
+            Worker::Compiled(vm) if vm.worker.defines(name) => Some(
+                vm.declarations
+                    .functions
+                    .get(name)
+                    .map(|(params, _)| params.clone())
+                    .unwrap_or_default(),
+            ),
+            Worker::Compiled(_) => None,
+        }
+    }
+
+    /// The declared default of `param` on the function `name`.
+    pub(crate) fn default_value(&mut self, name: &str, param: &Param) -> Result<Value, String> {
+        match self {
+            Worker::Interpreted(interp) => match &param.default {
+                Some(default) => interp.eval_expr(default).map_err(|e| e.message),
+                None => Ok(Value::Null),
+            },
+            Worker::Compiled(vm) => Ok(vm
+                .declarations
+                .functions
+                .get(name)
+                .and_then(|(params, defaults)| {
+                    let i = params.iter().position(|p| p.name == param.name)?;
+                    defaults.get(i).cloned()
+                })
+                .unwrap_or(Value::Null)),
+        }
+    }
+
+    /// The struct definitions request bodies are validated against.
+    pub(crate) fn declarations(&self) -> &Interpreter {
+        match self {
+            Worker::Interpreted(interp) => interp,
+            Worker::Compiled(vm) => &vm.declarations.structs,
+        }
+    }
+
+    /// Call the function `name`.
+    pub(crate) fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        match self {
+            Worker::Interpreted(interp) => call_interpreted(interp, name, args),
+            Worker::Compiled(vm) => {
+                vm.with_session(|worker, cancelled| worker.call(name, &args, cancelled))
+            }
+        }
+    }
+
+    /// [`call`](Self::call) a function that may return a stream, passing
+    /// its items to `each` until that returns `false`. Results other than
+    /// a stream come back as `Some`.
+    pub(crate) fn call_streaming(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+        mut each: impl FnMut(Value) -> bool,
+    ) -> Result<Option<Value>, String> {
+        match self {
+            Worker::Interpreted(interp) => match call_interpreted(interp, name, args)? {
+                Value::Stream(cell) => loop {
+                    match interp.stream_next(&cell) {
+                        Ok(Some(value)) => {
+                            if !each(value) {
+                                return Ok(None);
+                            }
+                        }
+                        Ok(None) => return Ok(None),
+                        Err(e) => return Err(e.message),
+                    }
+                },
+                other => Ok(Some(other)),
+            },
+            Worker::Compiled(vm) => vm.with_session(|worker, cancelled| {
+                worker.call_streaming(name, &args, cancelled, each)
+            }),
+        }
+    }
+
+    /// Make `session` the request's `session` variable.
+    pub(crate) fn begin_session(&mut self, session: Value) {
+        match self {
+            Worker::Interpreted(interp) => {
+                interp
+                    .env
+                    .define_with_mutability("session".to_string(), session, true);
+                interp.in_session = true;
+            }
+            Worker::Compiled(vm) => vm.session = Some(session),
+        }
+    }
+
+    /// Whether this request has a `session` variable.
+    pub(crate) fn in_session(&self) -> bool {
+        match self {
+            Worker::Interpreted(interp) => interp.in_session,
+            Worker::Compiled(vm) => vm.session.is_some(),
+        }
+    }
+
+    /// The request's `session` variable as the handler left it.
+    pub(crate) fn session(&self) -> Value {
+        match self {
+            Worker::Interpreted(interp) => interp.env.get("session").unwrap_or(Value::Null),
+            Worker::Compiled(vm) => vm.session.clone().unwrap_or(Value::Null),
+        }
+    }
+
+    pub(crate) fn set_session(&mut self, session: Value) -> Result<(), String> {
+        match self {
+            Worker::Interpreted(interp) => {
+                interp.env.set("session", session).map_err(|e| e.message)
+            }
+            Worker::Compiled(vm) => {
+                vm.session = Some(session);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn call_interpreted(
+    interp: &mut Interpreter,
+    name: &str,
+    args: Vec<Value>,
+) -> Result<Value, String> {
+    let function = interp
+        .env
+        .get(name)
+        .ok_or_else(|| format!("handler '{}' not found", name))?;
+    interp.call_function(function, args).map_err(|e| e.message)
+}
+
+impl VmRequest {
+    fn with_session<T>(
+        &mut self,
+        call: impl FnOnce(&mut VmWorker, &Arc<AtomicBool>) -> Result<T, crate::vm::machine::VMError>,
+    ) -> Result<T, String> {
+        if let Some(session) = &self.session {
+            self.worker
+                .set_global("session", session)
+                .map_err(|e| e.message)?;
+        }
+        let result = call(&mut self.worker, &self.cancelled).map_err(|e| e.message);
+        if self.session.is_some() {
+            if let Some(session) = self.worker.global("session") {
+                self.session = Some(session);
+            }
+        }
+        result
+    }
+}
@@ -40,6 +40,16 @@ fn get_conn() -> Result<Arc<Mutex<Connection>>, String> {
     })
 }
 
+/// Open a SQLite database by path, or a private in-memory one for
+/// `":memory:"`. Shared with runtime features that keep state in SQLite.
+pub fn open_connection(path: &str) -> rusqlite::Result<Connection> {
+    if path == ":memory:" {
+        Connection::open_in_memory()
+    } else {
+        Connection::open(path)
+    }
+}
+
 /// Convert a Vec<Value> into rusqlite params
 fn value_to_sql_params(params: &[Value]) -> Vec<Box<dyn rusqlite::types::ToSql>> {
     params
@@ -106,23 +116,16 @@ fn query_rows(
 pub fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
     match name {
         "db.open" => match args.first() {
-            Some(Value::String(path)) => {
-                let conn = if path == ":memory:" {
-                    Connection::open_in_memory()
-                } else {
-                    Connection::open(path)
-                };
-                match conn {
-                    Ok(c) => {
-                        let arc = Arc::new(Mutex::new(c));
-                        DB_CONN.with(|cell| {
-                            *cell.borrow_mut() = Some(arc);
-                        });
-                        Ok(Value::Bool(true))
-                    }
-                    Err(e) => Err(format!("db.open error: {}", e)),
+            Some(Value::String(path)) => match open_connection(path) {
+                Ok(c) => {
+                    let arc = Arc::new(Mutex::new(c));
+                    DB_CONN.with(|cell| {
+                        *cell.borrow_mut() = Some(arc);
+                    });
+                    Ok(Value::Bool(true))
                 }
-            }
+                Err(e) => Err(format!("db.open error: {}", e)),
+            },
             _ => Err("db.open() requires a path string (use ':memory:' for in-memory)".to_string()),
         },
 
@@ -1,5 +1,6 @@
 use crate::interpreter::Value;
 use indexmap::IndexMap;
+use std::collections::HashMap;
 use std::time::Instant;
 
 pub fn create_module() -> Value {
@@ -94,6 +95,123 @@ pub fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
     }
 }
 
+/// Marker stored under `__type__` on values built by `response()` and
+/// `redirect()`. The server recognizes it when rendering handler results.
+pub const RESPONSE_TYPE: &str = "Response";
+
+/// Body encodings accepted by the `type` option of `response()`.
+const RESPONSE_BODY_TYPES: &[&str] = &["json", "text", "html", "bytes"];
+
+/// `response(status, body?, headers?)` or
+/// `response({ status, body, headers, type })`.
+///
+/// Builds a response description for server handlers. `type` picks the
+/// body encoding (`json`, `text`, `html` or `bytes`); when omitted the
+/// server infers it from the body value.
+pub fn build_response(args: &[Value]) -> Result<Value, String> {
+    let (status, body, headers, body_type) = match args {
+        [Value::Object(opts)] if !is_response(opts) => (
+            opts.get("status").cloned().unwrap_or(Value::Int(200)),
+            opts.get("body").cloned().unwrap_or(Value::Null),
+            opts.get("headers").cloned(),
+            opts.get("type").cloned(),
+        ),
+        [status, rest @ ..] if rest.len() <= 2 => (
+            status.clone(),
+            rest.first().cloned().unwrap_or(Value::Null),
+            rest.get(1).cloned(),
+            None,
+        ),
+        _ => {
+            return Err(
+                "response() expects (status, body?, headers?) or an options object".to_string(),
+            )
+        }
+    };
+
+    let status = match status {
+        Value::Int(code) if (100..=599).contains(&code) => code,
+        Value::Int(code) => return Err(format!("response() status {} is out of range", code)),
+        other => {
+            return Err(format!(
+                "response() status must be an Int, got {}",
+                other.type_name()
+            ))
+        }
+    };
+
+    let headers = match headers {
+        None | Some(Value::Null) => IndexMap::new(),
+        Some(Value::Object(map)) => {
+            let mut out = IndexMap::new();
+            for (name, value) in map {
+                let value = match value {
+                    Value::String(s) => s,
+                    other => format!("{}", other),
+                };
+                out.insert(name.to_ascii_lowercase(), Value::String(value));
+            }
+            out
+        }
+        Some(other) => {
+            return Err(format!(
+                "response() headers must be an object, got {}",
+                other.type_name()
+            ))
+        }
+    };
+
+    let mut obj = IndexMap::new();
+    obj.insert(
+        "__type__".to_string(),
+        Value::String(RESPONSE_TYPE.to_string()),
+    );
+    obj.insert("status".to_string(), Value::Int(status));
+    obj.insert("headers".to_string(), Value::Object(headers));
+    obj.insert("body".to_string(), body);
+    match body_type {
+        None | Some(Value::Null) => {}
+        Some(Value::String(kind)) if RESPONSE_BODY_TYPES.contains(&kind.as_str()) => {
+            obj.insert("type".to_string(), Value::String(kind));
+        }
+        Some(other) => {
+            return Err(format!(
+                "response() type must be one of {}, got {}",
+                RESPONSE_BODY_TYPES.join(", "),
+                other
+            ))
+        }
+    }
+    Ok(Value::Object(obj))
+}
+
+/// `redirect(url, status?)` — a response with a `Location` header.
+/// Defaults to `302 Found`; only 3xx statuses are accepted.
+pub fn build_redirect(args: &[Value]) -> Result<Value, String> {
+    let url = match args.first() {
+        Some(Value::String(url)) => url.clone(),
+        _ => return Err("redirect() requires a URL string".to_string()),
+    };
+    let status = match args.get(1) {
+        None => 302,
+        Some(Value::Int(code)) if (300..=399).contains(code) => *code,
+        Some(other) => {
+            return Err(format!(
+                "redirect() status must be a 3xx Int, got {}",
+                other
+            ))
+        }
+    };
+    let mut headers = IndexMap::new();
+    headers.insert("location".to_string(), Value::String(url));
+    build_response(&[Value::Int(status), Value::Null, Value::Object(headers)])
+}
+
+/// True when `obj` was produced by [`build_response`].
+pub fn is_response(obj: &IndexMap<String, Value>) -> bool {
+    matches!(obj.get("__type__"), Some(Value::String(t)) if t == RESPONSE_TYPE)
+}
+
 fn do_request(method: &str, args: &[Value]) -> Result<Value, String> {
     let url = match args.first() {
         Some(Value::String(s)) => s.clone(),
@@ -107,92 +225,15 @@ fn do_request(method: &str, args: &[Value]) -> Result<Value, String> {
 
     let opts = args.get(1);
 
-    let mut headers_map = std::collections::HashMap::new();
-    let mut body_str = None;
+    let (final_url, headers_map, body_str) = match opts {
+        Some(Value::Object(opt_map)) => request_parts(&url, opt_map),
+        _ => (url.clone(), HashMap::new(), None),
+    };
     let mut timeout_secs: Option<u64> = None;
     let mut max_redirects: Option<usize> = None;
     let mut max_bytes: Option<u64> = None;
-    let mut final_url = url.clone();
 
     if let Some(Value::Object(opt_map)) = opts {
-        if let Some(Value::Object(hdrs)) = opt_map.get("headers") {
-            for (k, v) in hdrs {
-                headers_map.insert(k.clone(), format!("{}", v));
-            }
-        }
-        if let Some(Value::String(auth)) = opt_map.get("auth") {
-            headers_map.insert("Authorization".to_string(), format!("Bearer {}", auth));
-        }
-        // Basic auth: { basic_auth: { user: "x", pass: "y" } }
-        if let Some(Value::Object(basic)) = opt_map.get("basic_auth") {
-            let user = basic
-                .get("user")
-                .map(|v| format!("{}", v))
-                .unwrap_or_default();
-            let pass = basic
-                .get("pass")
-                .map(|v| format!("{}", v))
-                .unwrap_or_default();
-            use base64::Engine;
-            let encoded =
-                base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, pass));
-            headers_map.insert("Authorization".to_string(), format!("Basic {}", encoded));
-        }
-        // Query params: { params: { key: "val" } } — appended to URL
-        if let Some(Value::Object(params)) = opt_map.get("params") {
-            let separator = if final_url.contains('?') { "&" } else { "?" };
-            let query: Vec<String> = params
-                .iter()
-                .map(|(k, v)| {
-                    let val = match v {
-                        Value::String(s) => s.clone(),
-                        other => format!("{}", other),
-                    };
-                    format!("{}={}", percent_encode(k), percent_encode(&val))
-                })
-                .collect();
-            final_url = format!("{}{}{}", final_url, separator, query.join("&"));
-        }
-        // Form data: { form: { key: "val" } } — url-encoded form body
-        if let Some(Value::Object(form)) = opt_map.get("form") {
-            let pairs: Vec<String> = form
-                .iter()
-                .map(|(k, v)| {
-                    let val = match v {
-                        Value::String(s) => s.clone(),
-                        other => format!("{}", other),
-                    };
-                    format!("{}={}", percent_encode(k), percent_encode(&val))
-                })
-                .collect();
-            body_str = Some(pairs.join("&"));
-            if !headers_map.contains_key("Content-Type") {
-                headers_map.insert(
-                    "Content-Type".to_string(),
-                    "application/x-www-form-urlencoded".to_string(),
-                );
-            }
-        }
-        // Cookies: { cookies: { key: "val" } } — sent as Cookie header
-        if let Some(Value::Object(cookies)) = opt_map.get("cookies") {
-            let cookie_str: Vec<String> = cookies
-                .iter()
-                .map(|(k, v)| {
-                    let val = match v {
-                        Value::String(s) => s.clone(),
-                        other => format!("{}", other),
-                    };
-                    format!("{}={}", k, val)
-                })
-                .collect();
-            headers_map.insert("Cookie".to_string(), cookie_str.join("; "));
-        }
-        if let Some(body_val) = opt_map.get("body") {
-            body_str = Some(body_val.to_json_string());
-            if !headers_map.contains_key("Content-Type") {
-                headers_map.insert("Content-Type".to_string(), "application/json".to_string());
-            }
-        }
         if let Some(Value::Int(t)) = opt_map.get("timeout") {
             timeout_secs = Some(*t as u64);
         }
@@ -239,6 +280,97 @@ fn do_request(method: &str, args: &[Value]) -> Result<Value, String> {
     }
 }
 
+/// The URL, headers and body described by a request options object:
+/// `headers`, `auth`, `basic_auth`, `params`, `form`, `cookies` and
+/// `body`.
+pub(crate) fn request_parts(
+    url: &str,
+    opt_map: &IndexMap<String, Value>,
+) -> (String, HashMap<String, String>, Option<String>) {
+    let mut headers_map = HashMap::new();
+    let mut body_str = None;
+    let mut final_url = url.to_string();
+    if let Some(Value::Object(hdrs)) = opt_map.get("headers") {
+        for (k, v) in hdrs {
+            headers_map.insert(k.clone(), format!("{}", v));
+        }
+    }
+    if let Some(Value::String(auth)) = opt_map.get("auth") {
+        headers_map.insert("Authorization".to_string(), format!("Bearer {}", auth));
+    }
+    // Basic auth: { basic_auth: { user: "x", pass: "y" } }
+    if let Some(Value::Object(basic)) = opt_map.get("basic_auth") {
+        let user = basic
+            .get("user")
+            .map(|v| format!("{}", v))
+            .unwrap_or_default();
+        let pass = basic
+            .get("pass")
+            .map(|v| format!("{}", v))
+            .unwrap_or_default();
+        use base64::Engine;
+        let encoded =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, pass));
+        headers_map.insert("Authorization".to_string(), format!("Basic {}", encoded));
+    }
+    // Query params: { params: { key: "val" } } — appended to URL
+    if let Some(Value::Object(params)) = opt_map.get("params") {
+        let separator = if final_url.contains('?') { "&" } else { "?" };
+        let query: Vec<String> = params
+            .iter()
+            .map(|(k, v)| {
+                let val = match v {
+                    Value::String(s) => s.clone(),
+                    other => format!("{}", other),
+                };
+                format!("{}={}", percent_encode(k), percent_encode(&val))
+            })
+            .collect();
+        final_url = format!("{}{}{}", final_url, separator, query.join("&"));
+    }
+    // Form data: { form: { key: "val" } } — url-encoded form body
+    if let Some(Value::Object(form)) = opt_map.get("form") {
+        let pairs: Vec<String> = form
+            .iter()
+            .map(|(k, v)| {
+                let val = match v {
+                    Value::String(s) => s.clone(),
+                    other => format!("{}", other),
+                };
+                format!("{}={}", percent_encode(k), percent_encode(&val))
+            })
+            .collect();
+        body_str = Some(pairs.join("&"));
+        if !headers_map.contains_key("Content-Type") {
+            headers_map.insert(
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string(),
+            );
+        }
+    }
+    // Cookies: { cookies: { key: "val" } } — sent as Cookie header
+    if let Some(Value::Object(cookies)) = opt_map.get("cookies") {
+        let cookie_str: Vec<String> = cookies
+            .iter()
+            .map(|(k, v)| {
+                let val = match v {
+                    Value::String(s) => s.clone(),
+                    other => format!("{}", other),
+                };
+                format!("{}={}", k, val)
+            })
+            .collect();
+        headers_map.insert("Cookie".to_string(), cookie_str.join("; "));
+    }
+    if let Some(body_val) = opt_map.get("body") {
+        body_str = Some(body_val.to_json_string());
+        if !headers_map.contains_key("Content-Type") {
+            headers_map.insert("Content-Type".to_string(), "application/json".to_string());
+        }
+    }
+    (final_url, headers_map, body_str)
+}
+
 /// Extract `timeout`, `max_redirects`, and `max_bytes` from an options object.
 /// Returns `(timeout_secs, max_redirects, max_bytes)` with `None` for any
 /// field that wasn't present or wasn't a valid integer.
@@ -36,7 +36,7 @@ pub fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
 
     // Ensure a tracing subscriber is installed. Idempotent -- the server
     // path also calls this on boot. Without this call, log.info from a
-    // CLI-invoked script (where start_server never ran) would silently
+    // CLI-invoked script (where serve_until never ran) would silently
     // drop because no subscriber is registered.
     crate::runtime::tracing_init::init_subscriber();
 
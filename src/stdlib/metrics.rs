@@ -0,0 +1,103 @@
+use crate::interpreter::Value;
+use crate::runtime::metrics::Registry;
+use indexmap::IndexMap;
+
+pub fn create_module() -> Value {
+    let mut m = IndexMap::new();
+    for name in ["counter", "gauge", "histogram", "describe", "render"] {
+        m.insert(
+            name.to_string(),
+            Value::BuiltIn(format!("metrics.{}", name)),
+        );
+    }
+    Value::Object(m)
+}
+
+pub fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
+    let registry = Registry::global();
+    match name {
+        // metrics.counter(name, labels?, by?) — `by` may also come second.
+        "metrics.counter" => {
+            let metric = metric_name(name, &args)?;
+            let (labels, by) = match args.get(1) {
+                Some(Value::Int(_) | Value::Float(_)) => (None, args.get(1)),
+                labels => (labels, args.get(2)),
+            };
+            let by = by.map(|v| number(name, v)).transpose()?;
+            let labels = labels_arg(name, labels)?;
+            registry.counter(metric, &borrowed(&labels), by.unwrap_or(1.0))?;
+            Ok(Value::Null)
+        }
+        "metrics.gauge" | "metrics.histogram" => {
+            let metric = metric_name(name, &args)?;
+            let value = match args.get(1) {
+                Some(value) => number(name, value)?,
+                None => return Err(format!("{}() requires a value", name)),
+            };
+            let labels = labels_arg(name, args.get(2))?;
+            if name == "metrics.gauge" {
+                registry.gauge(metric, &borrowed(&labels), value)?;
+            } else {
+                registry.observe(metric, &borrowed(&labels), value)?;
+            }
+            Ok(Value::Null)
+        }
+        "metrics.describe" => match (args.first(), args.get(1)) {
+            (Some(Value::String(metric)), Some(Value::String(help))) => {
+                registry.describe(metric, help)?;
+                Ok(Value::Null)
+            }
+            _ => Err("metrics.describe() requires a metric name and help text".to_string()),
+        },
+        "metrics.render" => Ok(Value::String(registry.render())),
+        _ => Err(format!("unknown metrics function: {}", name)),
+    }
+}
+
+fn metric_name<'a>(function: &str, args: &'a [Value]) -> Result<&'a str, String> {
+    match args.first() {
+        Some(Value::String(name)) => Ok(name),
+        _ => Err(format!("{}() requires a metric name", function)),
+    }
+}
+
+fn number(function: &str, value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Int(n) => Ok(*n as f64),
+        Value::Float(n) => Ok(*n),
+        other => Err(format!(
+            "{}() expects a number, got {}",
+            function,
+            other.type_name()
+        )),
+    }
+}
+
+/// Label values may be any scalar; they are recorded as their string form.
+fn labels_arg(function: &str, labels: Option<&Value>) -> Result<Vec<(String, String)>, String> {
+    match labels {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Object(map)) => Ok(map
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => format!("{}", other),
+                };
+                (name.clone(), value)
+            })
+            .collect()),
+        Some(other) => Err(format!(
+            "{}() labels must be an object, got {}",
+            function,
+            other.type_name()
+        )),
+    }
+}
+
+fn borrowed(labels: &[(String, String)]) -> Vec<(&str, &str)> {
+    labels
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect()
+}
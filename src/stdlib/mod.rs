@@ -10,6 +10,7 @@ pub mod json_module;
 pub mod jwt;
 pub mod log;
 pub mod math;
+pub mod metrics;
 #[cfg(feature = "mysql")]
 pub mod mysql;
 pub mod npc;
@@ -19,6 +20,7 @@ pub mod path_module;
 pub mod pg;
 pub mod regex_module;
 pub mod term;
+pub mod testing;
 pub mod time;
 pub mod toml_module;
 pub mod url_module;
@@ -29,6 +31,12 @@ use crate::interpreter::Value;
 pub fn create_math_module() -> Value {
     math::create_module()
 }
+pub fn create_metrics_module() -> Value {
+    metrics::create_module()
+}
+pub fn create_testing_module() -> Value {
+    testing::create_module()
+}
 pub fn create_fs_module() -> Value {
     fs::create_module()
 }
@@ -0,0 +1,182 @@
+use crate::interpreter::Value;
+use crate::runtime::server::json_to_forge;
+use crate::runtime::testing::{load_file, TestClient};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+pub fn create_module() -> Value {
+    let mut m = IndexMap::new();
+    for name in [
+        "client", "request", "get", "post", "put", "patch", "delete", "head",
+    ] {
+        m.insert(
+            name.to_string(),
+            Value::BuiltIn(format!("testing.{}", name)),
+        );
+    }
+    Value::Object(m)
+}
+
+/// Clients by id. They live until the process exits, like a server would.
+fn clients() -> &'static parking_lot::Mutex<HashMap<i64, Arc<TestClient>>> {
+    static CLIENTS: OnceLock<parking_lot::Mutex<HashMap<i64, Arc<TestClient>>>> = OnceLock::new();
+    CLIENTS.get_or_init(Default::default)
+}
+
+pub fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
+    match name {
+        "testing.client" => match args.first() {
+            Some(Value::String(path)) => open_client(path),
+            _ => Err("testing.client() requires a path to a Forge file".to_string()),
+        },
+        "testing.request" => {
+            let method = match args.get(1) {
+                Some(Value::String(method)) => method.to_uppercase(),
+                _ => return Err("testing.request() requires a method string".to_string()),
+            };
+            let mut rest = args;
+            rest.remove(1);
+            send(name, &method, &rest)
+        }
+        "testing.get" => send(name, "GET", &args),
+        "testing.post" => send(name, "POST", &args),
+        "testing.put" => send(name, "PUT", &args),
+        "testing.patch" => send(name, "PATCH", &args),
+        "testing.delete" => send(name, "DELETE", &args),
+        "testing.head" => send(name, "HEAD", &args),
+        _ => Err(format!("unknown testing function: {}", name)),
+    }
+}
+
+fn open_client(path: &str) -> Result<Value, String> {
+    let (interpreter, plan) = load_file(std::path::Path::new(path))
+        .map_err(|e| format!("testing.client: {}", e.message))?;
+    let client = run_testing(async move {
+        TestClient::new(interpreter, &plan)
+            .await
+            .map_err(|e| format!("testing.client: {}", e.message))
+    })?;
+
+    static NEXT_ID: std::sync::atomic::AtomicI64 = std::sync::atomic::AtomicI64::new(1);
+    let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    clients().lock().insert(id, Arc::new(client));
+
+    let mut obj = IndexMap::new();
+    obj.insert(
+        "__type__".to_string(),
+        Value::String("TestClient".to_string()),
+    );
+    obj.insert("id".to_string(), Value::Int(id));
+    obj.insert("file".to_string(), Value::String(path.to_string()));
+    Ok(Value::Object(obj))
+}
+
+/// `testing.get(client, path, opts?)` and friends. `opts` takes the same
+/// `headers`, `auth`, `params`, `form`, `cookies` and `body` keys as the
+/// `http` module.
+fn send(name: &str, method: &str, args: &[Value]) -> Result<Value, String> {
+    let client = match args.first() {
+        Some(Value::Object(obj)) => match obj.get("id") {
+            Some(Value::Int(id)) => clients().lock().get(id).cloned(),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(client) = client else {
+        return Err(format!(
+            "{}() requires a client from testing.client()",
+            name
+        ));
+    };
+    let path = match args.get(1) {
+        Some(Value::String(path)) if path.starts_with('/') => path.clone(),
+        _ => return Err(format!("{}() requires a path starting with '/'", name)),
+    };
+    let (uri, headers, body) = match args.get(2) {
+        Some(Value::Object(opts)) => crate::stdlib::http::request_parts(&path, opts),
+        None | Some(Value::Null) => (path, HashMap::new(), None),
+        Some(_) => return Err(format!("{}() options must be an object", name)),
+    };
+
+    let mut builder = axum::http::Request::builder().method(method).uri(&uri);
+    for (key, value) in &headers {
+        builder = builder.header(key.as_str(), value.as_str());
+    }
+    let request = builder
+        .body(axum::body::Body::from(body.unwrap_or_default()))
+        .map_err(|e| format!("{}(): invalid request: {}", name, e))?;
+
+    run_testing(async move {
+        let response = client.request(request).await;
+        let status = response.status();
+        let headers: IndexMap<String, Value> = response
+            .headers()
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.to_string(),
+                    Value::String(v.to_str().unwrap_or("").to_string()),
+                )
+            })
+            .collect();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .map_err(|e| format!("failed to read response body: {}", e))?;
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+
+        let mut result = IndexMap::new();
+        result.insert("status".to_string(), Value::Int(status.as_u16() as i64));
+        result.insert("ok".to_string(), Value::Bool(status.is_success()));
+        result.insert("headers".to_string(), Value::Object(headers));
+        let json = serde_json::from_str(&text)
+            .map(json_to_forge)
+            .unwrap_or(Value::Null);
+        result.insert("body".to_string(), Value::String(text));
+        result.insert("json".to_string(), json);
+        Ok(Value::Object(result))
+    })
+}
+
+fn run_testing<F, T>(future: F) -> Result<T, String>
+where
+    F: std::future::Future<Output = Result<T, String>>,
+{
+    // Handlers run on the blocking pool of whichever runtime drives the
+    // request, so outside of one a runtime is kept per thread rather than
+    // built per call.
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        tokio::task::block_in_place(|| handle.block_on(future))
+    } else {
+        thread_local! {
+            static RT: tokio::runtime::Runtime = tokio::runtime::Runtime::new()
+                .expect("BUG: failed to create fallback tokio runtime");
+        }
+        RT.with(|rt| rt.block_on(future))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_calls_without_a_client() {
+        let err = call(
+            "testing.get",
+            vec![
+                Value::String("nope".to_string()),
+                Value::String("/".to_string()),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(err, "testing.get() requires a client from testing.client()");
+        assert!(call("testing.client", vec![]).is_err());
+        assert!(call(
+            "testing.client",
+            vec![Value::String("/no/such/app.fg".to_string())]
+        )
+        .unwrap_err()
+        .contains("cannot read"));
+    }
+}
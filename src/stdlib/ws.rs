@@ -16,6 +16,10 @@ pub fn create_module() -> Value {
         Value::BuiltIn("ws.receive".to_string()),
     );
     m.insert("close".to_string(), Value::BuiltIn("ws.close".to_string()));
+    // Server side: rooms for `@ws` connections.
+    for name in ["join", "leave", "rooms", "broadcast", "binary"] {
+        m.insert(name.to_string(), Value::BuiltIn(format!("ws.{}", name)));
+    }
     Value::Object(m)
 }
 
@@ -47,6 +51,9 @@ fn ws_counter() -> &'static std::sync::Mutex<u64> {
 
 pub fn call(name: &str, args: Vec<Value>) -> Result<Value, String> {
     match name {
+        "ws.join" | "ws.leave" | "ws.rooms" | "ws.broadcast" | "ws.binary" => {
+            crate::runtime::sockets::call(name, None, args)
+        }
         "ws.connect" => {
             let url = match args.first() {
                 Some(Value::String(s)) => s.clone(),
@@ -522,7 +522,7 @@ impl TypeChecker {
                     },
                 );
             }
-            Stmt::TypeDef { name, variants } => {
+            Stmt::TypeDef { name, variants, .. } => {
                 let variant_names: Vec<String> = variants.iter().map(|v| v.name.clone()).collect();
                 self.type_defs.insert(name.clone(), variant_names.clone());
 
@@ -781,27 +781,31 @@ impl TypeChecker {
 
     /// Check if a match expression covers all variants of a known type.
     fn check_match_exhaustiveness(&mut self, subject_type: &InferredType, arms: &[MatchArm]) {
-        // Check if any arm is a wildcard/catch-all (makes match exhaustive)
-        for arm in arms {
-            match &arm.pattern {
-                Pattern::Wildcard => return,
-                Pattern::Binding(_) => return, // any binding is a catch-all
-                _ => {}
-            }
+        // A guarded arm may decline its value, so only unguarded arms count,
+        // and an or-pattern counts as each of its alternatives.
+        let covering: Vec<&Pattern> = arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .flat_map(|arm| arm.pattern.alternatives())
+            .collect();
+        if covering.iter().any(|p| p.is_irrefutable()) {
+            return;
         }
+        // `Some(v)` covers every Some, `Some(1)` does not.
+        let covers_variant = |variant: &str| {
+            covering.iter().any(|p| {
+                matches!(p, Pattern::Constructor { name, fields }
+                    if name == variant && fields.iter().all(Pattern::is_irrefutable))
+            })
+        };
 
         // Determine required variants and check coverage
         match subject_type {
             InferredType::Option(_) => {
-                let mut has_some = false;
-                let mut has_none = false;
-                for arm in arms {
-                    match &arm.pattern {
-                        Pattern::Constructor { name, .. } if name == "Some" => has_some = true,
-                        Pattern::Literal(expr) if is_null_expr(expr) => has_none = true,
-                        _ => {}
-                    }
-                }
+                let has_some = covers_variant("Some");
+                let has_none = covering
+                    .iter()
+                    .any(|p| matches!(p, Pattern::Literal(expr) if is_null_expr(expr)));
                 let mut missing = Vec::new();
                 if !has_some {
                     missing.push("Some");
@@ -818,22 +822,11 @@ impl TypeChecker {
                 }
             }
             InferredType::Result(_, _) => {
-                let mut has_ok = false;
-                let mut has_err = false;
-                for arm in arms {
-                    if let Pattern::Constructor { name, .. } = &arm.pattern {
-                        match name.as_str() {
-                            "Ok" => has_ok = true,
-                            "Err" => has_err = true,
-                            _ => {}
-                        }
-                    }
-                }
                 let mut missing = Vec::new();
-                if !has_ok {
+                if !covers_variant("Ok") {
                     missing.push("Ok");
                 }
-                if !has_err {
+                if !covers_variant("Err") {
                     missing.push("Err");
                 }
                 if !missing.is_empty() {
@@ -847,8 +840,8 @@ impl TypeChecker {
             InferredType::Bool => {
                 let mut has_true = false;
                 let mut has_false = false;
-                for arm in arms {
-                    if let Pattern::Literal(Expr::Bool(v)) = &arm.pattern {
+                for pattern in &covering {
+                    if let Pattern::Literal(Expr::Bool(v)) = pattern {
                         if *v {
                             has_true = true;
                         } else {
@@ -870,6 +863,61 @@ impl TypeChecker {
                     ));
                 }
             }
+            InferredType::Array(_) => {
+                // Only array patterns whose items all match anything say
+                // something about which lengths are covered.
+                let mut exact = Vec::new();
+                let mut at_least: Option<usize> = None;
+                let mut any_array_pattern = false;
+                for pattern in &covering {
+                    if let Pattern::Array { items, rest } = pattern {
+                        any_array_pattern = true;
+                        if !items.iter().all(Pattern::is_irrefutable) {
+                            continue;
+                        }
+                        match rest {
+                            Some(_) => {
+                                at_least =
+                                    Some(at_least.map_or(items.len(), |n| n.min(items.len())))
+                            }
+                            None => exact.push(items.len()),
+                        }
+                    }
+                }
+                if !any_array_pattern {
+                    return;
+                }
+                let shape = |len: usize, rest: bool| {
+                    let mut parts = vec!["_"; len];
+                    if rest {
+                        parts.push("...");
+                    }
+                    format!("[{}]", parts.join(", "))
+                };
+                let missing: Vec<String> = match at_least {
+                    Some(min) => (0..min)
+                        .filter(|len| !exact.contains(len))
+                        .map(|len| shape(len, false))
+                        .collect(),
+                    None => {
+                        let mut missing: Vec<String> =
+                            (0..=exact.iter().copied().max().unwrap_or(0))
+                                .filter(|len| !exact.contains(len))
+                                .map(|len| shape(len, false))
+                                .collect();
+                        let longest = exact.iter().copied().max().map_or(0, |n| n + 1);
+                        missing.push(shape(longest, true));
+                        missing
+                    }
+                };
+                if !missing.is_empty() {
+                    self.emit(format!(
+                        "non-exhaustive match on {} — missing: {}",
+                        subject_type,
+                        missing.join(", ")
+                    ));
+                }
+            }
             _ => {} // Unknown, Int, String, etc. — cannot check exhaustiveness
         }
     }
@@ -1064,6 +1112,9 @@ impl TypeChecker {
                         }
                     }
 
+                    if let Some(guard) = &arm.guard {
+                        self.infer_expr(guard);
+                    }
                     for s in &arm.body {
                         self.current_line = s.line;
                         self.check_stmt(&s.stmt);
@@ -1223,7 +1274,18 @@ impl TypeChecker {
                             InferredType::Unknown
                         }
                     }
-                    BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+                    BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::Shl | BinOp::Shr => {
+                        InferredType::Int
+                    }
+                    // A negative exponent makes Int ** Int a Float.
+                    BinOp::Pow => {
+                        if lt == InferredType::Float || rt == InferredType::Float {
+                            InferredType::Float
+                        } else {
+                            InferredType::Unknown
+                        }
+                    }
+                    BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod | BinOp::FloorDiv => {
                         if lt == InferredType::Float || rt == InferredType::Float {
                             InferredType::Float
                         } else if lt == InferredType::Int && rt == InferredType::Int {
@@ -1412,12 +1474,26 @@ impl TypeChecker {
             Expr::Index { object, index } => {
                 let obj_type = self.infer_expr(object);
                 self.infer_expr(index);
+                if matches!(index.as_ref(), Expr::Range { .. }) {
+                    // A slice keeps the type of what it slices.
+                    return match obj_type {
+                        InferredType::Array(_) | InferredType::String => obj_type,
+                        _ => InferredType::Unknown,
+                    };
+                }
                 if let InferredType::Array(inner) = obj_type {
                     return *inner;
                 }
                 InferredType::Unknown
             }
 
+            Expr::Range { start, end, .. } => {
+                for bound in [start, end].into_iter().flatten() {
+                    self.infer_expr(bound);
+                }
+                InferredType::Unknown
+            }
+
             Expr::Pipeline { value, function } => {
                 self.infer_expr(value);
                 self.infer_expr(function);
@@ -2143,6 +2219,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn exhaustive_guarded_arm_does_not_cover() {
+        let w = warnings_for(
+            "fn f(x: Result<Int, String>) {\n  match x {\n    Ok(v) => { say v }\n    Err(e) if e == \"x\" => { say e }\n  }\n}",
+        );
+        assert_eq!(w.len(), 1, "guarded Err should not count");
+        assert!(w[0].message.contains("Err"), "{}", w[0].message);
+    }
+
+    #[test]
+    fn exhaustive_or_pattern_and_refutable_fields() {
+        let w = warnings_for(
+            "fn f(x: Result<Int, Int>) {\n  match x {\n    Ok(v) | Err(v) => { say v }\n  }\n}",
+        );
+        assert!(w.is_empty(), "or-pattern covers both: {:?}", w);
+
+        let w = warnings_for(
+            "fn f(x: Result<Int, String>) {\n  match x {\n    Ok(1) => { say 1 }\n    Err(e) => { say e }\n  }\n}",
+        );
+        assert_eq!(w.len(), 1, "Ok(1) does not cover every Ok");
+        assert!(w[0].message.contains("Ok"), "{}", w[0].message);
+    }
+
+    #[test]
+    fn exhaustive_array_lengths() {
+        let w = warnings_for(
+            "fn f(xs: [Int]) {\n  match xs {\n    [] => { say 0 }\n    [first, ...rest] => { say first }\n  }\n}",
+        );
+        assert!(w.is_empty(), "empty plus head/rest is exhaustive: {:?}", w);
+
+        let w = warnings_for(
+            "fn f(xs: [Int]) {\n  match xs {\n    [a, b, ...rest] => { say a }\n    [one] => { say one }\n  }\n}",
+        );
+        assert_eq!(w.len(), 1);
+        assert!(w[0].message.ends_with("missing: []"), "{}", w[0].message);
+
+        let w = warnings_for(
+            "fn f(xs: [Int]) {\n  match xs {\n    [] => { say 0 }\n    [a] => { say a }\n  }\n}",
+        );
+        assert_eq!(w.len(), 1);
+        assert!(
+            w[0].message.ends_with("missing: [_, _, ...]"),
+            "{}",
+            w[0].message
+        );
+    }
+
     // ========== 8B.2: Generic Type Resolution ==========
 
     #[test]
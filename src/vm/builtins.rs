@@ -8,9 +8,21 @@ use std::sync::Arc;
 
 use super::machine::{VMError, VM};
 use super::value::*;
+use crate::interpreter::{modules, range, IntRange};
 
 impl VM {
-    pub(super) fn call_native(&mut self, name: &str, args: Vec<Value>) -> Result<Value, VMError> {
+    pub(super) fn call_native(
+        &mut self,
+        name: &str,
+        mut args: Vec<Value>,
+    ) -> Result<Value, VMError> {
+        if range::COLLECTING_BUILTINS.contains(&name) {
+            if let Some(&first) = args.first() {
+                if let Some(items) = self.collect_range(first)? {
+                    args[0] = Value::obj(self.gc.alloc(ObjKind::Array(items)));
+                }
+            }
+        }
         match name {
             "__forge_register_struct" => {
                 if args.len() != 3 {
@@ -178,6 +190,137 @@ impl VM {
 
                 Ok(Value::bool_val(true))
             }
+            "__forge_match_variant" => {
+                if args.len() != 2 {
+                    return Err(VMError::new(
+                        "__forge_match_variant() requires (value, variant_name)",
+                    ));
+                }
+                let name = self.get_string_arg(&args, 1)?;
+                let matched = match args[0].as_obj().and_then(|r| self.gc.get(r)) {
+                    Some(obj) => match &obj.kind {
+                        ObjKind::ResultOk(_) => name == "Ok",
+                        ObjKind::ResultErr(_) => name == "Err",
+                        ObjKind::Object(map) => ["__type__", "__variant__"].iter().any(|key| {
+                            map.get(*key)
+                                .and_then(|v| self.get_string(v))
+                                .is_some_and(|v| v == name)
+                        }),
+                        _ => false,
+                    },
+                    None => false,
+                };
+                Ok(Value::bool_val(matched))
+            }
+            "__forge_match_field" => {
+                if args.len() != 2 {
+                    return Err(VMError::new(
+                        "__forge_match_field() requires (value, index)",
+                    ));
+                }
+                let index = args[1].as_int(&self.gc).unwrap_or(0);
+                let field = match args[0].as_obj().and_then(|r| self.gc.get(r)) {
+                    Some(obj) => match &obj.kind {
+                        ObjKind::ResultOk(inner) | ObjKind::ResultErr(inner) if index == 0 => {
+                            Some(*inner)
+                        }
+                        ObjKind::Object(map) => map.get(&format!("_{}", index)).cloned(),
+                        _ => None,
+                    },
+                    None => None,
+                };
+                Ok(field.unwrap_or(Value::null()))
+            }
+            "__forge_match_range" => {
+                if args.len() != 4 {
+                    return Err(VMError::new(
+                        "__forge_match_range() requires (value, start, end, inclusive)",
+                    ));
+                }
+                // A null bound is an open end of the pattern.
+                let inclusive = args[3].as_bool().unwrap_or(false);
+                let above_start = args[1].is_null()
+                    || self
+                        .pattern_ordering(&args[0], &args[1])
+                        .is_some_and(|o| o.is_ge());
+                let below_end = args[2].is_null()
+                    || self.pattern_ordering(&args[0], &args[2]).is_some_and(|o| {
+                        if inclusive {
+                            o.is_le()
+                        } else {
+                            o.is_lt()
+                        }
+                    });
+                Ok(Value::bool_val(above_start && below_end))
+            }
+            "__forge_make_range" => {
+                if args.len() != 3 {
+                    return Err(VMError::new(
+                        "__forge_make_range() requires (start, end, inclusive)",
+                    ));
+                }
+                let (Some(start), Some(end)) = (args[0].as_int(&self.gc), args[1].as_int(&self.gc))
+                else {
+                    return Err(VMError::new(&range::bounds_error(
+                        args[0].type_name(&self.gc),
+                        args[1].type_name(&self.gc),
+                    )));
+                };
+                let inclusive = args[2].as_bool().unwrap_or(false);
+                let r = self
+                    .gc
+                    .alloc(ObjKind::Range(IntRange::new(start, end, inclusive)));
+                Ok(Value::obj(r))
+            }
+            "__forge_match_object" => {
+                if args.len() < 2 {
+                    return Err(VMError::new(
+                        "__forge_match_object() requires (value, type_name, ...keys)",
+                    ));
+                }
+                let Some(fields) = self.get_object_fields(&args[0]) else {
+                    return Ok(Value::bool_val(false));
+                };
+                if let Some(type_name) = self.get_string(&args[1]) {
+                    let actual = fields.get("__type__").and_then(|v| self.get_string(v));
+                    if actual.as_deref() != Some(type_name.as_str()) {
+                        return Ok(Value::bool_val(false));
+                    }
+                }
+                let has_keys = args[2..].iter().all(|key| {
+                    self.get_string(key)
+                        .is_some_and(|key| fields.contains_key(&key))
+                });
+                Ok(Value::bool_val(has_keys))
+            }
+            "__forge_match_array" => {
+                if args.len() != 3 {
+                    return Err(VMError::new(
+                        "__forge_match_array() requires (value, length, has_rest)",
+                    ));
+                }
+                let want = args[1].as_int(&self.gc).unwrap_or(0).max(0) as usize;
+                let has_rest = args[2].as_bool().unwrap_or(false);
+                let matched = match args[0].as_obj().and_then(|r| self.gc.get(r)) {
+                    Some(obj) => match &obj.kind {
+                        ObjKind::Array(items) if has_rest => items.len() >= want,
+                        ObjKind::Array(items) => items.len() == want,
+                        _ => false,
+                    },
+                    None => false,
+                };
+                Ok(Value::bool_val(matched))
+            }
+            "__forge_match_rest" => {
+                if args.len() != 2 {
+                    return Err(VMError::new("__forge_match_rest() requires (array, start)"));
+                }
+                let start = args[1].as_int(&self.gc).unwrap_or(0).max(0) as usize;
+                let items = self.array_items(&args[0], "__forge_match_rest() requires an array")?;
+                let rest = items.get(start..).unwrap_or_default().to_vec();
+                let r = self.gc.alloc(ObjKind::Array(rest));
+                Ok(Value::obj(r))
+            }
             "__forge_retry_count" => {
                 if args.len() != 1 {
                     return Err(VMError::new("__forge_retry_count() requires (count)"));
@@ -307,6 +450,17 @@ impl VM {
                 };
                 Err(VMError::new(&message))
             }
+            "__forge_emit" => {
+                // `yield` / `emit`: send the value down the `@sse` stream
+                // this VM is serving.
+                if let Some(sink) = self.event_sink.clone() {
+                    let value =
+                        self.convert_to_interp_val(&args.first().copied().unwrap_or(Value::null()));
+                    self.check_stream_boundary()?;
+                    sink(value).map_err(|e| VMError::new(&e))?;
+                }
+                Ok(Value::null())
+            }
             "__forge_import_module" => {
                 if args.is_empty() || args.len() > 2 {
                     return Err(VMError::new(
@@ -342,48 +496,69 @@ impl VM {
                 let path = self.get_string_arg(&args, 0)?;
                 let file_path = crate::package::resolve_import(&path)
                     .unwrap_or_else(|| std::path::PathBuf::from(&path));
-                let source = std::fs::read_to_string(&file_path)
-                    .map_err(|e| VMError::new(&format!("cannot import '{}': {}", path, e)))?;
 
-                let mut lexer = crate::lexer::Lexer::new(&source);
-                let tokens = lexer.tokenize().map_err(|e| {
-                    VMError::new(&format!("import '{}' lex error: {}", path, e.message))
-                })?;
-                let mut parser = crate::parser::Parser::new(tokens);
-                let program = parser.parse_program().map_err(|e| {
-                    VMError::new(&format!("import '{}' parse error: {}", path, e.message))
-                })?;
+                // A module runs once per VM. Its exports object is kept in a
+                // hidden global, which also keeps it alive across GCs and
+                // carries it into forks of this VM.
+                let key = modules::canonical(&file_path);
+                let cache_key = format!("__forge_module__:{}", key.display());
+                let exports = match self.globals.get(&cache_key).copied() {
+                    Some(exports) => exports,
+                    None => {
+                        let chain = modules::enter_import(
+                            &self.module_chain,
+                            self.source_file.as_deref(),
+                            &key,
+                        )
+                        .map_err(|e| VMError::new(&e))?;
+                        let program = modules::parse_module(&path, &file_path)
+                            .map_err(|e| VMError::new(&e))?;
+                        let module = key.display().to_string();
+                        let chunk = crate::vm::compiler::compile_module(&program, &module)
+                            .map_err(|e| {
+                                VMError::new(&format!(
+                                    "import '{}' compile error: {}",
+                                    path, e.message
+                                ))
+                            })?;
+                        let outer = std::mem::replace(&mut self.module_chain, chain);
+                        let result = self.execute_module(&chunk);
+                        self.module_chain = outer;
+                        // A cycle error already names every file involved.
+                        result.map_err(|e| {
+                            if e.message.starts_with("import cycle:") {
+                                e
+                            } else {
+                                VMError::new(&format!("import '{}' runtime error: {}", path, e))
+                            }
+                        })?;
 
-                let export_names = if requested_names.is_empty() {
-                    program
-                        .statements
-                        .iter()
-                        .filter_map(|spanned| match &spanned.stmt {
-                            crate::parser::ast::Stmt::FnDef { name, .. }
-                            | crate::parser::ast::Stmt::Let { name, .. } => Some(name.clone()),
-                            _ => None,
-                        })
-                        .collect::<Vec<_>>()
-                } else {
-                    requested_names
+                        // The module's globals are scoped to it; only its
+                        // exports are handed to the importer.
+                        let mut exports = IndexMap::new();
+                        for name in crate::vm::compiler::import_export_names(&program) {
+                            let global = crate::vm::compiler::module_global(&module, &name);
+                            if let Some(value) = self.globals.get(&global) {
+                                exports.insert(name, *value);
+                            }
+                        }
+                        let exports = Value::obj(self.gc.alloc(ObjKind::Object(exports)));
+                        self.globals.insert(cache_key, exports);
+                        exports
+                    }
                 };
 
-                let chunk = crate::vm::compiler::compile_module(&program).map_err(|e| {
-                    VMError::new(&format!("import '{}' compile error: {}", path, e.message))
-                })?;
-                self.execute_module(&chunk).map_err(|e| {
-                    VMError::new(&format!("import '{}' runtime error: {}", path, e))
-                })?;
-
-                let mut exports = IndexMap::new();
-                for name in export_names {
-                    let value = self.globals.get(&name).cloned().ok_or_else(|| {
-                        VMError::new(&format!("import '{}' does not export '{}'", path, name))
-                    })?;
-                    exports.insert(name, value);
+                if let Some(missing) = requested_names.iter().find(|name| {
+                    !exports.as_obj().and_then(|r| self.gc.get(r)).is_some_and(
+                        |obj| matches!(&obj.kind, ObjKind::Object(map) if map.contains_key(*name)),
+                    )
+                }) {
+                    return Err(VMError::new(&format!(
+                        "import '{}' does not export '{}'",
+                        path, missing
+                    )));
                 }
-                let exports_ref = self.gc.alloc(ObjKind::Object(exports));
-                Ok(Value::obj(exports_ref))
+                Ok(exports)
             }
             "println" | "say" => {
                 let text: Vec<String> = args.iter().map(|v| v.display(&self.gc)).collect();
@@ -421,12 +596,13 @@ impl VM {
                             }
                             ObjKind::Object(o) => o.len() as i64,
                             ObjKind::Map(pairs) => pairs.len() as i64,
+                            ObjKind::Range(range) => range.int_len(),
                             _ => 0,
                         })
                     } else {
                         0
                     };
-                    Ok(Value::small_int(len))
+                    Ok(Value::int(len, &mut self.gc))
                 }
                 None => Err(VMError::new("len() requires an argument")),
             },
@@ -1109,6 +1285,10 @@ impl VM {
                                     .any(|v| v.display(&self.gc) == val.display(&self.gc));
                                 return Ok(Value::bool_val(found));
                             }
+                            ObjKind::Range(range) => {
+                                let found = val.as_int(&self.gc).is_some_and(|n| range.contains(n));
+                                return Ok(Value::bool_val(found));
+                            }
                             _ => {}
                         }
                     }
@@ -1266,6 +1446,16 @@ impl VM {
                 let id = uuid::Uuid::new_v4().to_string();
                 Ok(self.alloc_string(&id))
             }
+            "response" | "redirect" => {
+                let interp_args = self.args_to_interp(&args)?;
+                let result = if name == "response" {
+                    crate::stdlib::http::build_response(&interp_args)
+                } else {
+                    crate::stdlib::http::build_redirect(&interp_args)
+                }
+                .map_err(|e| VMError::new(&e))?;
+                self.from_interp_checked(&result)
+            }
             "json" => {
                 if let Some(v) = args.first() {
                     let s = v.to_json_string(&self.gc);
@@ -1713,6 +1903,18 @@ impl VM {
                     crate::stdlib::jwt::call(n, interp_args).map_err(|e| VMError::new(&e))?;
                 self.from_interp_checked(&result)
             }
+            n if n.starts_with("metrics.") => {
+                let interp_args = self.args_to_interp(&args)?;
+                let result =
+                    crate::stdlib::metrics::call(n, interp_args).map_err(|e| VMError::new(&e))?;
+                self.from_interp_checked(&result)
+            }
+            n if n.starts_with("testing.") => {
+                let interp_args = self.args_to_interp(&args)?;
+                let result =
+                    crate::stdlib::testing::call(n, interp_args).map_err(|e| VMError::new(&e))?;
+                self.from_interp_checked(&result)
+            }
             #[cfg(feature = "mysql")]
             n if n.starts_with("mysql.") => {
                 let interp_args = self.args_to_interp(&args)?;
@@ -1738,6 +1940,17 @@ impl VM {
                     .map_err(|e| VMError::new(&e))?;
                 self.from_interp_checked(&result)
             }
+            "ws.join" | "ws.leave" | "ws.rooms" | "ws.broadcast" | "ws.binary" => {
+                let interp_args = self.args_to_interp(&args)?;
+                let result = crate::runtime::sockets::call(name, self.ws_connection, interp_args)
+                    .map_err(|e| VMError::new(&e))?;
+                self.from_interp_checked(&result)
+            }
+            "ws.close" if args.is_empty() => {
+                let result = crate::runtime::sockets::call(name, self.ws_connection, Vec::new())
+                    .map_err(|e| VMError::new(&e))?;
+                self.from_interp_checked(&result)
+            }
             n if n.starts_with("ws.") => {
                 let interp_args = self.args_to_interp(&args)?;
                 let result =
@@ -3499,6 +3712,26 @@ impl VM {
         })
     }
 
+    /// Order a value against a range-pattern bound. Ints and floats compare
+    /// numerically and strings lexically; anything else never matches.
+    fn pattern_ordering(&self, value: &Value, bound: &Value) -> Option<std::cmp::Ordering> {
+        let number = |v: &Value| {
+            v.as_int(&self.gc)
+                .map(|n| n as f64)
+                .or_else(|| v.as_float())
+        };
+        if let (Some(a), Some(b)) = (value.as_int(&self.gc), bound.as_int(&self.gc)) {
+            return Some(a.cmp(&b));
+        }
+        if let (Some(a), Some(b)) = (number(value), number(bound)) {
+            return a.partial_cmp(&b);
+        }
+        match (self.get_string(value), self.get_string(bound)) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => None,
+        }
+    }
+
     fn value_variant_name(&self, value: &Value) -> Option<String> {
         self.get_object_fields(value).and_then(|fields| {
             fields
@@ -3862,6 +4095,12 @@ impl VM {
             }
         }
 
+        if method_name == "to_array" && extra_args.is_empty() {
+            if let Some(items) = self.collect_range(receiver)? {
+                return Ok(Value::obj(self.gc.alloc(ObjKind::Array(items))));
+            }
+        }
+
         if Self::is_builtin_method_name(method_name) {
             if let Some(func) = self.globals.get(method_name).cloned() {
                 let mut full_args = Vec::with_capacity(extra_args.len() + 1);
@@ -3878,6 +4117,22 @@ impl VM {
         )))
     }
 
+    /// The elements of `v` if it is a range, for builtins and methods that
+    /// need an array. Ranges too large to collect are an error.
+    fn collect_range(&mut self, v: Value) -> Result<Option<Vec<Value>>, VMError> {
+        let range = match v.as_obj().and_then(|r| self.gc.get(r)).map(|obj| &obj.kind) {
+            Some(ObjKind::Range(range)) => *range,
+            _ => return Ok(None),
+        };
+        let items = range::collect(&range).map_err(|e| VMError::new(&e))?;
+        Ok(Some(
+            items
+                .into_iter()
+                .map(|n| Value::int(n, &mut self.gc))
+                .collect(),
+        ))
+    }
+
     /// Build a `StreamKind` source wrapping a collection value. Peels
     /// `Frozen(inner)` one level so frozen collections still stream.
     fn make_stream_kind_from(&self, v: Value) -> Option<StreamKind> {
@@ -3904,6 +4159,10 @@ impl VM {
                 chars: s.chars().collect(),
                 idx: 0,
             }),
+            ObjKind::Range(range) => Some(StreamKind::RangeIter {
+                range: *range,
+                idx: 0,
+            }),
             ObjKind::Frozen(inner) => {
                 let ir = inner.as_obj()?;
                 let iobj = self.gc.get(ir)?;
@@ -4015,6 +4274,7 @@ impl VM {
                 Yield(Value),
                 YieldTuple(Value, Value),
                 YieldChar(char),
+                YieldInt(i64),
                 Done,
                 PullFilter { upstream: GcRef, pred: Value },
                 PullMap { upstream: GcRef, fn_val: Value },
@@ -4069,6 +4329,13 @@ impl VM {
                             Step::Done
                         }
                     }
+                    StreamKind::RangeIter { range, idx } => match range.get(*idx) {
+                        Some(n) => {
+                            *idx += 1;
+                            Step::YieldInt(n)
+                        }
+                        None => Step::Done,
+                    },
                     StreamKind::Filter { upstream, pred } => {
                         let up_r = upstream
                             .as_obj()
@@ -4173,6 +4440,7 @@ impl VM {
                     let sr = self.gc.alloc(ObjKind::String(c.to_string()));
                     return Ok(Some(Value::obj(sr)));
                 }
+                Step::YieldInt(n) => return Ok(Some(Value::int(n, &mut self.gc))),
                 Step::Done => return Ok(None),
                 Step::PullFilter { upstream, pred } => loop {
                     match self.stream_next_vm(upstream)? {
@@ -1,3 +1,5 @@
+use crate::parser::ast::SpannedStmt;
+
 /// Bytecode opcodes for the Forge register-based VM.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
@@ -64,11 +66,19 @@ pub enum OpCode {
     IterGet, // A=dst, B=obj_reg, C=idx_reg — like GetIndex but allows Set (for for-loop iteration)
     SquadBegin, // A=dst (push squad context for collecting spawn handles)
     SquadEnd, // A=dst (join all collected handles, produce result array)
+    Pow,     // A=dst, B=left, C=right
+    FloorDiv,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    RangeCheck, // A=start_reg, B=end_reg (error unless both are Int; starts a counted for loop)
 }
 
 // Compile-time guard: if a new variant is added to OpCode, this assertion
 // will fail, reminding you to update the TryFrom impl below.
-const _: () = assert!(OpCode::SquadEnd as u8 + 1 == 61);
+const _: () = assert!(OpCode::RangeCheck as u8 + 1 == 69);
 
 impl TryFrom<u8> for OpCode {
     type Error = u8;
@@ -136,6 +146,14 @@ impl TryFrom<u8> for OpCode {
             58 => Ok(OpCode::IterGet),
             59 => Ok(OpCode::SquadBegin),
             60 => Ok(OpCode::SquadEnd),
+            61 => Ok(OpCode::Pow),
+            62 => Ok(OpCode::FloorDiv),
+            63 => Ok(OpCode::BitAnd),
+            64 => Ok(OpCode::BitOr),
+            65 => Ok(OpCode::BitXor),
+            66 => Ok(OpCode::Shl),
+            67 => Ok(OpCode::Shr),
+            68 => Ok(OpCode::RangeCheck),
             _ => Err(value),
         }
     }
@@ -172,6 +190,11 @@ pub struct Chunk {
     pub upvalue_count: u8,
     pub arity: u8,
     pub upvalue_sources: Vec<UpvalueSource>,
+    /// Top-level decorators, function signatures and struct fields of a
+    /// program that uses decorator-driven runtime features, in source
+    /// order and with function bodies dropped. The host runtime plans its
+    /// server from these. Only set on the main chunk.
+    pub declarations: Vec<SpannedStmt>,
 }
 
 impl Chunk {
@@ -187,6 +210,7 @@ impl Chunk {
             upvalue_count: 0,
             arity: 0,
             upvalue_sources: Vec::new(),
+            declarations: Vec::new(),
         }
     }
 
@@ -295,11 +319,14 @@ mod tests {
         assert_eq!(OpCode::try_from(56u8), Ok(OpCode::Freeze));
         assert_eq!(OpCode::try_from(57u8), Ok(OpCode::NewTuple));
         assert_eq!(OpCode::try_from(58u8), Ok(OpCode::IterGet));
+        assert_eq!(OpCode::try_from(61u8), Ok(OpCode::Pow));
+        assert_eq!(OpCode::try_from(67u8), Ok(OpCode::Shr));
+        assert_eq!(OpCode::try_from(68u8), Ok(OpCode::RangeCheck));
     }
 
     #[test]
     fn try_from_invalid_opcode() {
-        assert_eq!(OpCode::try_from(61u8), Err(61));
+        assert_eq!(OpCode::try_from(69u8), Err(69));
         assert_eq!(OpCode::try_from(255u8), Err(255));
     }
 }
@@ -1,4 +1,5 @@
 use super::bytecode::*;
+use crate::interpreter::modules;
 use crate::parser::ast::*;
 
 struct Local {
@@ -24,6 +25,13 @@ struct CleanupContext {
     loop_depth: usize,
 }
 
+/// The top-level names of an imported module, which are kept under
+/// [`module_global`] keys so they never become globals of the importer.
+struct ModuleScope {
+    module: String,
+    names: std::collections::HashSet<String>,
+}
+
 #[derive(Clone)]
 struct UpvalueEntry {
     name: String,
@@ -42,6 +50,11 @@ pub struct Compiler {
     parent_locals: Vec<(String, u8)>,
     parent_upvalues: Vec<(String, u8)>,
     module_mode: bool,
+    /// Set while compiling an imported module, including its functions.
+    module_scope: Option<std::sync::Arc<ModuleScope>>,
+    /// Names bound by file imports, with the resolved file and the path
+    /// as written.
+    imports: std::collections::HashMap<String, (String, String)>,
     /// Source line currently being compiled. Top-level loops update this
     /// from `SpannedStmt` before each statement so any `emit` that passes
     /// `0` for the line picks up a real source span instead.
@@ -76,6 +89,8 @@ impl Compiler {
             parent_locals: Vec::new(),
             parent_upvalues: Vec::new(),
             module_mode: false,
+            module_scope: None,
+            imports: std::collections::HashMap::new(),
             current_line: 0,
             current_col: 0,
         }
@@ -197,6 +212,60 @@ impl Compiler {
         None
     }
 
+    /// Bind `name` from the import of `path`, which resolved to `file`,
+    /// rejecting names that are already defined in this file or imported
+    /// from another one.
+    fn add_import(&mut self, name: &str, file: &str, path: &str) -> Result<u8, CompileError> {
+        match self.imports.get(name) {
+            Some((prev_file, prev_path)) if prev_file != file => {
+                return Err(CompileError::new(&modules::collision_error(
+                    path,
+                    name,
+                    Some(prev_path),
+                )));
+            }
+            None if self.resolve_local(name).is_some() => {
+                return Err(CompileError::new(&modules::collision_error(
+                    path, name, None,
+                )));
+            }
+            _ => {}
+        }
+        self.imports
+            .insert(name.to_string(), (file.to_string(), path.to_string()));
+        self.add_local(name, false)
+    }
+
+    /// The global `name` is read and written under: module-scoped for an
+    /// imported module's own top-level names, as written otherwise.
+    fn global_key(&self, name: &str) -> String {
+        match &self.module_scope {
+            Some(scope) if scope.names.contains(name) => module_global(&scope.module, name),
+            _ => name.to_string(),
+        }
+    }
+
+    /// Publish a top-level import binding as a global too, so functions
+    /// compiled before it, pattern checks and the host runtime can find
+    /// it by name the way they find top-level functions.
+    fn emit_import_global(&mut self, name: &str, reg: u8) {
+        if self.scope_depth == 1 {
+            let name_idx = self.const_str(&self.global_key(name));
+            self.emit(encode_abx(OpCode::SetGlobal, reg, name_idx), 0);
+        }
+    }
+
+    /// Top-level declarations may not redefine a name an import bound.
+    fn check_not_imported(&self, name: &str) -> Result<(), CompileError> {
+        if self.scope_depth != 1 {
+            return Ok(());
+        }
+        match self.imports.get(name) {
+            Some((_, path)) => Err(CompileError::new(&modules::redefinition_error(name, path))),
+            None => Ok(()),
+        }
+    }
+
     fn resolve_upvalue(&self, name: &str) -> Option<u8> {
         for (i, uv) in self.upvalues.iter().enumerate() {
             if uv.name == name {
@@ -256,8 +325,14 @@ impl Compiler {
 
 pub fn compile(program: &Program) -> Result<Chunk, CompileError> {
     let mut c = Compiler::new("<main>");
+    c.chunk.declarations = runtime_declarations(program)?;
     c.begin_scope();
     for spanned in &program.statements {
+        // Top-level decorators only configure the host runtime, which reads
+        // them from `declarations`; there is nothing to execute.
+        if matches!(spanned.stmt, Stmt::DecoratorStmt(_)) {
+            continue;
+        }
         c.set_span(spanned);
         compile_stmt(&mut c, &spanned.stmt)?;
     }
@@ -266,9 +341,15 @@ pub fn compile(program: &Program) -> Result<Chunk, CompileError> {
     Ok(c.chunk)
 }
 
-pub fn compile_module(program: &Program) -> Result<Chunk, CompileError> {
+/// Compile an imported file. `module` names it (the VM uses its canonical
+/// path) and scopes its top-level globals, see [`module_global`].
+pub fn compile_module(program: &Program, module: &str) -> Result<Chunk, CompileError> {
     let mut c = Compiler::new("<module>");
     c.module_mode = true;
+    c.module_scope = Some(std::sync::Arc::new(ModuleScope {
+        module: module.to_string(),
+        names: module_top_level_names(program, module)?,
+    }));
     c.begin_scope();
     for spanned in &program.statements {
         c.set_span(spanned);
@@ -401,7 +482,7 @@ fn compile_set_global_expr(c: &mut Compiler, name: &str, expr: Expr) -> Result<(
     let saved = c.next_register;
     let reg = c.alloc_reg()?;
     compile_expr(c, &expr, reg)?;
-    let name_idx = c.const_str(name);
+    let name_idx = c.const_str(&c.global_key(name));
     c.emit(encode_abx(OpCode::SetGlobal, reg, name_idx), 0);
     c.free_to(saved);
     Ok(())
@@ -439,6 +520,115 @@ fn type_ann_name(type_ann: &TypeAnn) -> String {
     }
 }
 
+/// Function decorators the test runner reads; they need no host runtime.
+const TEST_DECORATORS: [&str; 4] = ["test", "skip", "before", "after"];
+
+/// The declaration skeleton stored in [`Chunk::declarations`]: every
+/// top-level decorator, function signature and struct definition, in
+/// order, for programs with standalone decorators or runtime function
+/// decorators such as `@get`. Decorator arguments, parameter defaults and
+/// field defaults must be literals, since the skeleton is rebuilt without
+/// the rest of the program.
+fn runtime_declarations(program: &Program) -> Result<Vec<SpannedStmt>, CompileError> {
+    let uses_runtime = program.statements.iter().any(|s| match &s.stmt {
+        Stmt::DecoratorStmt(_) => true,
+        Stmt::FnDef { decorators, .. } => decorators
+            .iter()
+            .any(|d| !TEST_DECORATORS.contains(&d.name.as_str())),
+        _ => false,
+    });
+    if !uses_runtime {
+        return Ok(Vec::new());
+    }
+
+    let mut declarations = Vec::new();
+    for spanned in &program.statements {
+        let stmt = match &spanned.stmt {
+            Stmt::DecoratorStmt(decorator) => {
+                check_literal_decorator(decorator)?;
+                Stmt::DecoratorStmt(decorator.clone())
+            }
+            Stmt::FnDef {
+                name,
+                params,
+                decorators,
+                is_async,
+                ..
+            } => {
+                for decorator in decorators {
+                    check_literal_decorator(decorator)?;
+                }
+                for param in params {
+                    if param.default.as_ref().is_some_and(|d| !is_literal(d)) {
+                        return Err(CompileError::new(&format!(
+                            "default for parameter '{}' of '{}' must be a literal in a compiled server",
+                            param.name, name
+                        )));
+                    }
+                }
+                Stmt::FnDef {
+                    name: name.clone(),
+                    type_params: Vec::new(),
+                    params: params.clone(),
+                    return_type: None,
+                    body: Vec::new(),
+                    decorators: decorators.clone(),
+                    is_async: *is_async,
+                    is_pub: false,
+                }
+            }
+            Stmt::StructDef { name, fields, .. } => {
+                if let Some(field) = fields
+                    .iter()
+                    .find(|f| f.default.as_ref().is_some_and(|d| !is_literal(d)))
+                {
+                    return Err(CompileError::new(&format!(
+                        "default for field '{}' of '{}' must be a literal in a compiled server",
+                        field.name, name
+                    )));
+                }
+                Stmt::StructDef {
+                    name: name.clone(),
+                    type_params: Vec::new(),
+                    fields: fields.clone(),
+                    is_pub: false,
+                }
+            }
+            _ => continue,
+        };
+        declarations.push(SpannedStmt::new(stmt, spanned.line, spanned.col));
+    }
+    Ok(declarations)
+}
+
+fn check_literal_decorator(decorator: &Decorator) -> Result<(), CompileError> {
+    let literal = decorator.args.iter().all(|arg| match arg {
+        DecoratorArg::Positional(expr) | DecoratorArg::Named(_, expr) => is_literal(expr),
+    });
+    if literal {
+        Ok(())
+    } else {
+        Err(CompileError::new(&format!(
+            "arguments to '@{}' must be literals in a compiled program",
+            decorator.name
+        )))
+    }
+}
+
+/// Numbers, strings, booleans, `null`, bare names and collections of them.
+fn is_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Int(_) | Expr::Float(_) | Expr::StringLit(_) | Expr::Bool(_) | Expr::Ident(_) => true,
+        Expr::UnaryOp {
+            op: UnaryOp::Neg,
+            operand,
+        } => matches!(**operand, Expr::Int(_) | Expr::Float(_)),
+        Expr::Array(items) | Expr::Tuple(items) => items.iter().all(is_literal),
+        Expr::Object(fields) => fields.iter().all(|(_, value)| is_literal(value)),
+        _ => false,
+    }
+}
+
 fn resolve_import_path(path: &str) -> Result<std::path::PathBuf, CompileError> {
     crate::package::resolve_import(path).ok_or_else(|| {
         CompileError::new(&format!(
@@ -448,29 +638,140 @@ fn resolve_import_path(path: &str) -> Result<std::path::PathBuf, CompileError> {
     })
 }
 
-fn parse_import_program(path: &str) -> Result<(String, Program), CompileError> {
+fn parse_import_program(path: &str) -> Result<(String, std::sync::Arc<Program>), CompileError> {
     let resolved = resolve_import_path(path)?;
-    let source = std::fs::read_to_string(&resolved)
-        .map_err(|e| CompileError::new(&format!("cannot import '{}': {}", path, e)))?;
-    let mut lexer = crate::lexer::Lexer::new(&source);
-    let tokens = lexer
-        .tokenize()
-        .map_err(|e| CompileError::new(&format!("import '{}' lex error: {}", path, e.message)))?;
-    let mut parser = crate::parser::Parser::new(tokens);
-    let program = parser
-        .parse_program()
-        .map_err(|e| CompileError::new(&format!("import '{}' parse error: {}", path, e.message)))?;
+    let program = modules::parse_module(path, &resolved).map_err(|e| CompileError::new(&e))?;
     Ok((resolved.display().to_string(), program))
 }
 
-fn import_export_names(program: &Program) -> Vec<String> {
-    program
+/// Module names `import` resolves to the standard library, not a file.
+const BUILTIN_IMPORTS: [&str; 25] = [
+    "math", "fs", "io", "crypto", "db", "pg", "env", "json", "regex", "log", "term", "http", "csv",
+    "exec", "time", "url", "toml", "npc", "ws", "jwt", "mysql", "os", "path", "metrics", "testing",
+];
+
+/// The global an imported module keeps its top-level `name` under.
+pub(crate) fn module_global(module: &str, name: &str) -> String {
+    format!("{}::{}", module, name)
+}
+
+/// Names a module binds at its top level: its functions, bindings and
+/// variant constructors, and the names its own imports bind.
+fn module_top_level_names(
+    program: &Program,
+    module: &str,
+) -> Result<std::collections::HashSet<String>, CompileError> {
+    let mut names = std::collections::HashSet::new();
+    for spanned in &program.statements {
+        match &spanned.stmt {
+            Stmt::FnDef { name, .. } | Stmt::Let { name, .. } => {
+                names.insert(name.clone());
+            }
+            Stmt::TypeDef { variants, .. } => {
+                names.extend(variants.iter().map(|v| v.name.clone()));
+            }
+            Stmt::Import { path, .. } if BUILTIN_IMPORTS.contains(&path.as_str()) => {}
+            Stmt::Import {
+                alias: Some(alias), ..
+            } => {
+                names.insert(alias.clone());
+            }
+            Stmt::Import {
+                names: Some(list), ..
+            } => names.extend(list.iter().cloned()),
+            Stmt::Import { path, .. } => {
+                let (_, imported) = parse_import_program(path)?;
+                names.extend(import_export_names(&imported));
+                for (_, _, nested) in nested_whole_imports(&imported, Some(module))? {
+                    names.extend(import_export_names(&nested));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(names)
+}
+
+/// The files `program` imports whole, and the files those import whole,
+/// each once, as (path as written, resolved path, program). `importer` is
+/// the canonical path of the file doing the import, which an import cycle
+/// would otherwise lead back to.
+fn nested_whole_imports(
+    program: &Program,
+    importer: Option<&str>,
+) -> Result<Vec<(String, String, std::sync::Arc<Program>)>, CompileError> {
+    fn walk(
+        program: &Program,
+        seen: &mut Vec<String>,
+        out: &mut Vec<(String, String, std::sync::Arc<Program>)>,
+    ) -> Result<(), CompileError> {
+        for spanned in &program.statements {
+            let Stmt::Import {
+                path,
+                names: None,
+                alias: None,
+            } = &spanned.stmt
+            else {
+                continue;
+            };
+            if BUILTIN_IMPORTS.contains(&path.as_str()) {
+                continue;
+            }
+            let (resolved, nested) = parse_import_program(path)?;
+            let key = modules::canonical(std::path::Path::new(&resolved))
+                .display()
+                .to_string();
+            if seen.contains(&key) {
+                continue;
+            }
+            seen.push(key);
+            out.push((path.clone(), resolved, std::sync::Arc::clone(&nested)));
+            walk(&nested, seen, out)?;
+        }
+        Ok(())
+    }
+    let mut out = Vec::new();
+    let mut seen = importer.map(str::to_string).into_iter().collect();
+    walk(program, &mut seen, &mut out)?;
+    Ok(out)
+}
+
+/// Bind `names` from the module object in `module_reg`, imported from
+/// `path`, which resolved to `file`.
+fn bind_module_exports(
+    c: &mut Compiler,
+    module_reg: u8,
+    names: &[String],
+    file: &str,
+    path: &str,
+) -> Result<(), CompileError> {
+    for name in names {
+        let local_reg = c.add_import(name, file, path)?;
+        let field_idx = c.const_str(name);
+        c.emit(
+            encode_abc(OpCode::GetField, local_reg, module_reg, field_idx as u8),
+            0,
+        );
+        c.emit_import_global(name, local_reg);
+    }
+    Ok(())
+}
+
+/// Exported names an import binds as values. Structs are registered with
+/// the VM globally when the module runs, so they have no value to bind.
+pub(crate) fn import_export_names(program: &Program) -> Vec<String> {
+    let structs: Vec<&str> = program
         .statements
         .iter()
         .filter_map(|spanned| match &spanned.stmt {
-            Stmt::FnDef { name, .. } | Stmt::Let { name, .. } => Some(name.clone()),
+            Stmt::StructDef { name, .. } => Some(name.as_str()),
             _ => None,
         })
+        .collect();
+    program
+        .export_names()
+        .into_iter()
+        .filter(|name| !structs.contains(&name.as_str()))
         .collect()
 }
 
@@ -556,6 +857,230 @@ fn variant_object_expr(type_name: &str, variant_name: &str, field_params: &[Stri
     Expr::Object(fields)
 }
 
+/// Emit the tests for one match pattern against `value`, pushing a jump to
+/// `fails` for every way it can miss. Bindings are written into the locals
+/// in `bindings` as they are reached; on a miss the arm's scope is dropped
+/// anyway, so partially bound locals are never observed.
+fn compile_pattern(
+    c: &mut Compiler,
+    pattern: &Pattern,
+    value: u8,
+    bindings: &[(String, u8)],
+    fails: &mut Vec<usize>,
+) -> Result<(), CompileError> {
+    let saved = c.next_register;
+    match pattern {
+        Pattern::Wildcard => {}
+        Pattern::Binding(name) => {
+            // A name bound to a unit variant matches only that variant.
+            let fn_reg = c.alloc_reg()?;
+            let fn_idx = c.const_str("__forge_binding_matches");
+            c.emit(encode_abx(OpCode::GetGlobal, fn_reg, fn_idx), 0);
+            let name_reg = c.alloc_reg()?;
+            let name_idx = c.const_str(&c.global_key(name));
+            c.emit(encode_abx(OpCode::LoadConst, name_reg, name_idx), 0);
+            let value_reg = c.alloc_reg()?;
+            c.emit(encode_abc(OpCode::Move, value_reg, value, 0), 0);
+            let check = c.alloc_reg()?;
+            c.emit(encode_abc(OpCode::Call, fn_reg, 2, check), 0);
+            fails.push(c.emit_jump(OpCode::JumpIfFalse, check, 0));
+            if let Some((_, reg)) = bindings.iter().find(|(bound, _)| bound == name) {
+                c.emit(encode_abc(OpCode::Move, *reg, value, 0), 0);
+            }
+        }
+        Pattern::Literal(lit) => {
+            let lr = c.alloc_reg()?;
+            compile_expr(c, lit, lr)?;
+            let cr = c.alloc_reg()?;
+            c.emit(encode_abc(OpCode::Eq, cr, value, lr), 0);
+            fails.push(c.emit_jump(OpCode::JumpIfFalse, cr, 0));
+        }
+        Pattern::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            let inclusive = Expr::Bool(*inclusive);
+            let open = Expr::Ident("null".to_string());
+            let start = start.as_ref().unwrap_or(&open);
+            let end = end.as_ref().unwrap_or(&open);
+            let check =
+                emit_match_call(c, "__forge_match_range", &[start, end, &inclusive], value)?;
+            fails.push(c.emit_jump(OpCode::JumpIfFalse, check, 0));
+        }
+        Pattern::Or(alternatives) => {
+            let mut matched = Vec::new();
+            for (i, alternative) in alternatives.iter().enumerate() {
+                if i + 1 == alternatives.len() {
+                    compile_pattern(c, alternative, value, bindings, fails)?;
+                    break;
+                }
+                let mut next = Vec::new();
+                compile_pattern(c, alternative, value, bindings, &mut next)?;
+                matched.push(c.emit_jump(OpCode::Jump, 0, 0));
+                for skip in next {
+                    c.patch_jump(skip);
+                }
+            }
+            for jump in matched {
+                c.patch_jump(jump);
+            }
+        }
+        Pattern::Constructor { name, fields } => {
+            let name_arg = Expr::StringLit(name.clone());
+            let check = emit_match_call(c, "__forge_match_variant", &[&name_arg], value)?;
+            fails.push(c.emit_jump(OpCode::JumpIfFalse, check, 0));
+            for (i, field) in fields.iter().enumerate() {
+                if matches!(field, Pattern::Wildcard) {
+                    continue;
+                }
+                let index = Expr::Int(i as i64);
+                let field_reg = emit_match_call(c, "__forge_match_field", &[&index], value)?;
+                compile_pattern(c, field, field_reg, bindings, fails)?;
+            }
+        }
+        Pattern::Object { type_name, fields } => {
+            let type_arg = match type_name {
+                Some(name) => Expr::StringLit(name.clone()),
+                None => Expr::Ident("null".to_string()),
+            };
+            let keys: Vec<Expr> = fields
+                .iter()
+                .map(|(key, _)| Expr::StringLit(key.clone()))
+                .collect();
+            let mut args = vec![&type_arg];
+            args.extend(keys.iter());
+            let check = emit_match_call(c, "__forge_match_object", &args, value)?;
+            fails.push(c.emit_jump(OpCode::JumpIfFalse, check, 0));
+            for (key, field) in fields {
+                let key_reg = c.alloc_reg()?;
+                compile_expr(c, &Expr::StringLit(key.clone()), key_reg)?;
+                let field_reg = c.alloc_reg()?;
+                c.emit(encode_abc(OpCode::GetIndex, field_reg, value, key_reg), 0);
+                compile_pattern(c, field, field_reg, bindings, fails)?;
+            }
+        }
+        Pattern::Array { items, rest } => {
+            let len = Expr::Int(items.len() as i64);
+            let open = Expr::Bool(rest.is_some());
+            let check = emit_match_call(c, "__forge_match_array", &[&len, &open], value)?;
+            fails.push(c.emit_jump(OpCode::JumpIfFalse, check, 0));
+            for (i, item) in items.iter().enumerate() {
+                let index_reg = c.alloc_reg()?;
+                compile_expr(c, &Expr::Int(i as i64), index_reg)?;
+                let item_reg = c.alloc_reg()?;
+                c.emit(encode_abc(OpCode::GetIndex, item_reg, value, index_reg), 0);
+                compile_pattern(c, item, item_reg, bindings, fails)?;
+            }
+            if let Some(rest) = rest {
+                if !matches!(**rest, Pattern::Wildcard) {
+                    let rest_reg = emit_match_call(c, "__forge_match_rest", &[&len], value)?;
+                    compile_pattern(c, rest, rest_reg, bindings, fails)?;
+                }
+            }
+        }
+    }
+    c.free_to(saved);
+    Ok(())
+}
+
+/// Call the pattern native `name` with `value` followed by `args`, returning
+/// the register holding its result.
+fn emit_match_call(
+    c: &mut Compiler,
+    name: &str,
+    args: &[&Expr],
+    value: u8,
+) -> Result<u8, CompileError> {
+    let fn_reg = c.alloc_reg()?;
+    let fn_idx = c.const_str(name);
+    c.emit(encode_abx(OpCode::GetGlobal, fn_reg, fn_idx), 0);
+    let value_reg = c.alloc_reg()?;
+    c.emit(encode_abc(OpCode::Move, value_reg, value, 0), 0);
+    for arg in args {
+        let arg_reg = c.alloc_reg()?;
+        compile_expr(c, arg, arg_reg)?;
+    }
+    let dst = c.alloc_reg()?;
+    c.emit(
+        encode_abc(OpCode::Call, fn_reg, (args.len() + 1) as u8, dst),
+        0,
+    );
+    Ok(dst)
+}
+
+/// `for i in a..b` without building the range: a counter register steps
+/// from `a` to `b`, so the loop allocates nothing and the JIT can keep the
+/// counter in a native register. `b` is evaluated once, before the loop.
+fn compile_counted_for(
+    c: &mut Compiler,
+    var: &str,
+    start: &Expr,
+    end: &Expr,
+    inclusive: bool,
+    body: &[SpannedStmt],
+) -> Result<(), CompileError> {
+    let saved = c.next_register;
+    let counter = c.alloc_reg()?;
+    compile_expr(c, start, counter)?;
+    let end_reg = c.alloc_reg()?;
+    compile_expr(c, end, end_reg)?;
+    c.emit(encode_abc(OpCode::RangeCheck, counter, end_reg, 0), 0);
+    // Whether the loop runs again. An inclusive loop stops after the body
+    // runs with `counter == end` rather than stepping past `end`, which
+    // may be Int.max.
+    let cond_reg = c.alloc_reg()?;
+    if inclusive {
+        c.emit(encode_abc(OpCode::LtEq, cond_reg, counter, end_reg), 0);
+    }
+
+    let loop_start = c.chunk.code_len();
+    c.loops.push(LoopContext {
+        start: loop_start,
+        break_jumps: Vec::new(),
+    });
+    if !inclusive {
+        c.emit(encode_abc(OpCode::Lt, cond_reg, counter, end_reg), 0);
+    }
+    let exit = c.emit_jump(OpCode::JumpIfFalse, cond_reg, 0);
+
+    c.begin_scope();
+    let var_reg = c.add_local(var, false)?;
+    c.emit(encode_abc(OpCode::Move, var_reg, counter, 0), 0);
+    // Step before the body: `continue` jumps straight back to the test.
+    let last = if inclusive {
+        c.emit(encode_abc(OpCode::Lt, cond_reg, counter, end_reg), 0);
+        Some(c.emit_jump(OpCode::JumpIfFalse, cond_reg, 0))
+    } else {
+        None
+    };
+    let one = c.const_int(1);
+    let one_reg = c.alloc_reg()?;
+    c.emit(encode_abx(OpCode::LoadConst, one_reg, one), 0);
+    c.emit(encode_abc(OpCode::Add, counter, counter, one_reg), 0);
+    c.free_to(one_reg);
+    if let Some(last) = last {
+        c.patch_jump(last);
+    }
+    for s in body {
+        c.set_span(s);
+        compile_stmt(c, &s.stmt)?;
+    }
+    c.end_scope();
+    c.emit_loop(loop_start, 0);
+    c.patch_jump(exit);
+
+    let ctx = c
+        .loops
+        .pop()
+        .ok_or_else(|| CompileError::new("internal: loop stack underflow in for"))?;
+    for bj in ctx.break_jumps {
+        c.patch_jump(bj);
+    }
+    c.free_to(saved);
+    Ok(())
+}
+
 /// Compile a spawn/squad body: if the last statement is an expression,
 /// compile it as a return so the task returns its value (not null).
 fn compile_spawn_body(sc: &mut Compiler, body: &[SpannedStmt]) -> Result<(), CompileError> {
@@ -599,10 +1124,11 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             value,
             ..
         } => {
+            c.check_not_imported(name)?;
             let reg = c.add_local(name, *mutable)?;
             compile_expr(c, value, reg)?;
             if c.module_mode && c.scope_depth == 1 {
-                let name_idx = c.const_str(name);
+                let name_idx = c.const_str(&c.global_key(name));
                 c.emit(encode_abx(OpCode::SetGlobal, reg, name_idx), 0);
             }
             Ok(())
@@ -646,7 +1172,7 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
                     } else {
                         let tmp = c.alloc_reg()?;
                         compile_expr(c, value, tmp)?;
-                        let name_idx = c.const_str(name);
+                        let name_idx = c.const_str(&c.global_key(name));
                         c.emit(encode_abx(OpCode::SetGlobal, tmp, name_idx), 0);
                         c.free_to(tmp);
                     }
@@ -683,12 +1209,14 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
         Stmt::FnDef {
             name, params, body, ..
         } => {
+            c.check_not_imported(name)?;
             let parent_locals = c.snapshot_locals();
             let parent_upvalues = c.snapshot_upvalues();
 
             let mut fc = Compiler::new(name);
             fc.parent_locals = parent_locals;
             fc.parent_upvalues = parent_upvalues;
+            fc.module_scope = c.module_scope.clone();
             fc.current_line = c.current_line;
             fc.current_col = c.current_col;
             fc.begin_scope();
@@ -715,7 +1243,7 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             c.emit(encode_abx(OpCode::Closure, fn_reg, proto_idx), 0);
 
             // Also register as global for recursion and cross-scope access
-            let name_idx = c.const_str(name);
+            let name_idx = c.const_str(&c.global_key(name));
             c.emit(encode_abx(OpCode::SetGlobal, fn_reg, name_idx), 0);
             Ok(())
         }
@@ -826,6 +1354,19 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             Ok(())
         }
 
+        Stmt::For {
+            var,
+            var2: None,
+            iterable:
+                Expr::Range {
+                    start: Some(start),
+                    end: Some(end),
+                    inclusive,
+                },
+            body,
+            ..
+        } => compile_counted_for(c, var, start, end, *inclusive, body),
+
         Stmt::For {
             var,
             var2,
@@ -933,95 +1474,38 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             let mut end_jumps = Vec::new();
 
             for arm in arms {
-                match &arm.pattern {
-                    Pattern::Wildcard => {
-                        c.begin_scope();
-                        for s in &arm.body {
-                            c.set_span(s);
-                            compile_stmt(c, &s.stmt)?;
-                        }
-                        c.end_scope();
-                        break;
-                    }
-                    Pattern::Binding(name) => {
-                        let saved = c.next_register;
-                        let fn_reg = c.alloc_reg()?;
-                        let fn_idx = c.const_str("__forge_binding_matches");
-                        c.emit(encode_abx(OpCode::GetGlobal, fn_reg, fn_idx), 0);
-
-                        let name_reg = c.alloc_reg()?;
-                        let name_idx = c.const_str(name);
-                        c.emit(encode_abx(OpCode::LoadConst, name_reg, name_idx), 0);
-
-                        let value_reg = c.alloc_reg()?;
-                        c.emit(encode_abc(OpCode::Move, value_reg, subj, 0), 0);
-
-                        let check_reg = c.alloc_reg()?;
-                        c.emit(encode_abc(OpCode::Call, fn_reg, 2, check_reg), 0);
-                        let skip = c.emit_jump(OpCode::JumpIfFalse, check_reg, 0);
-                        c.free_to(saved);
-
-                        c.begin_scope();
-                        let vr = c.add_local(name, false)?;
-                        c.emit(encode_abc(OpCode::Move, vr, subj, 0), 0);
-                        for s in &arm.body {
-                            c.set_span(s);
-                            compile_stmt(c, &s.stmt)?;
-                        }
-                        c.end_scope();
-
-                        let ej = c.emit_jump(OpCode::Jump, 0, 0);
-                        end_jumps.push(ej);
-                        c.patch_jump(skip);
-                    }
-                    Pattern::Literal(lit) => {
-                        let lr = c.alloc_reg()?;
-                        compile_expr(c, lit, lr)?;
-                        let cr = c.alloc_reg()?;
-                        c.emit(encode_abc(OpCode::Eq, cr, subj, lr), 0);
-                        let skip = c.emit_jump(OpCode::JumpIfFalse, cr, 0);
-                        c.free_to(lr);
-
-                        c.begin_scope();
-                        for s in &arm.body {
-                            c.set_span(s);
-                            compile_stmt(c, &s.stmt)?;
-                        }
-                        c.end_scope();
-
-                        let ej = c.emit_jump(OpCode::Jump, 0, 0);
-                        end_jumps.push(ej);
-                        c.patch_jump(skip);
-                    }
-                    Pattern::Constructor { name, fields } => {
-                        let variant_idx = c.const_str(name);
-                        let field_name = c.const_str("__variant__");
-                        let vr = c.alloc_reg()?;
-                        c.emit(encode_abc(OpCode::GetField, vr, subj, field_name as u8), 0);
-                        let nr = c.alloc_reg()?;
-                        c.emit(encode_abx(OpCode::LoadConst, nr, variant_idx), 0);
-                        let cr = c.alloc_reg()?;
-                        c.emit(encode_abc(OpCode::Eq, cr, vr, nr), 0);
-                        let skip = c.emit_jump(OpCode::JumpIfFalse, cr, 0);
-                        c.free_to(vr);
-
-                        c.begin_scope();
-                        for (i, fp) in fields.iter().enumerate() {
-                            if let Pattern::Binding(bname) = fp {
-                                let fr = c.add_local(bname, false)?;
-                                c.emit(encode_abc(OpCode::ExtractField, fr, subj, i as u8), 0);
-                            }
-                        }
-                        for s in &arm.body {
-                            c.set_span(s);
-                            compile_stmt(c, &s.stmt)?;
-                        }
-                        c.end_scope();
+                let arm_base = c.next_register;
+                c.begin_scope();
+                // Bindings get their locals up front so every alternative of
+                // an or-pattern writes the same registers.
+                let mut bindings = Vec::new();
+                for name in arm.pattern.bindings() {
+                    let reg = c.add_local(&name, false)?;
+                    bindings.push((name, reg));
+                }
+                let mut fails = Vec::new();
+                compile_pattern(c, &arm.pattern, subj, &bindings, &mut fails)?;
+                if let Some(guard) = &arm.guard {
+                    let gr = c.alloc_reg()?;
+                    compile_expr(c, guard, gr)?;
+                    fails.push(c.emit_jump(OpCode::JumpIfFalse, gr, 0));
+                    c.free_to(gr);
+                }
+                for s in &arm.body {
+                    c.set_span(s);
+                    compile_stmt(c, &s.stmt)?;
+                }
+                c.end_scope();
+                c.free_to(arm_base);
 
-                        let ej = c.emit_jump(OpCode::Jump, 0, 0);
-                        end_jumps.push(ej);
-                        c.patch_jump(skip);
-                    }
+                if fails.is_empty() {
+                    // Nothing can fall past an unconditional arm.
+                    break;
+                }
+                let ej = c.emit_jump(OpCode::Jump, 0, 0);
+                end_jumps.push(ej);
+                for skip in fails {
+                    c.patch_jump(skip);
                 }
             }
             for ej in end_jumps {
@@ -1039,7 +1523,10 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             Ok(())
         }
 
-        Stmt::TypeDef { name, variants } => {
+        Stmt::TypeDef { name, variants, .. } => {
+            for variant in variants {
+                c.check_not_imported(&variant.name)?;
+            }
             for variant in variants {
                 if variant.fields.is_empty() {
                     compile_set_global_expr(
@@ -1081,7 +1568,7 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
         }
 
         Stmt::DecoratorStmt(decorator) => Err(CompileError::new(&format!(
-            "VM does not support standalone decorator '@{}' — use --interpreter for decorator-driven runtime features",
+            "VM only supports standalone decorator '@{}' at the top level of a program — use --interpreter elsewhere",
             decorator.name
         ))),
 
@@ -1221,7 +1708,7 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             }
             Ok(())
         }
-        Stmt::YieldStmt(_) => Ok(()),
+        Stmt::YieldStmt(expr) => compile_hidden_stmt(c, "__forge_emit", vec![expr.clone()]),
 
         Stmt::When { subject, arms } => {
             let subj_reg = c.alloc_reg()?;
@@ -1433,6 +1920,7 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             let mut sc = Compiler::new("<schedule>");
             sc.parent_locals = parent_locals;
             sc.parent_upvalues = parent_upvalues;
+            sc.module_scope = c.module_scope.clone();
             sc.current_line = c.current_line;
             sc.current_col = c.current_col;
             sc.begin_scope();
@@ -1473,6 +1961,7 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             let mut sc = Compiler::new("<watch>");
             sc.parent_locals = parent_locals;
             sc.parent_upvalues = parent_upvalues;
+            sc.module_scope = c.module_scope.clone();
             sc.current_line = c.current_line;
             sc.current_col = c.current_col;
             sc.begin_scope();
@@ -1593,53 +2082,69 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             Ok(())
         }
 
-        Stmt::Import { path, names } => {
-            let builtin_modules = [
-                "math", "fs", "io", "crypto", "db", "pg", "env", "json", "regex", "log", "term",
-                "http", "csv", "exec", "time", "url", "toml", "npc", "ws", "jwt", "mysql", "os",
-                "path",
-            ];
-            if builtin_modules.contains(&path.as_str()) {
+        Stmt::Import { path, names, alias } => {
+            if BUILTIN_IMPORTS.contains(&path.as_str()) {
                 return Ok(());
             }
 
-            let (resolved_path, export_names) = match names {
-                Some(name_list) => (
-                    resolve_import_path(path)?.display().to_string(),
-                    name_list.clone(),
-                ),
-                None => {
-                    let (resolved_path, program) = parse_import_program(path)?;
-                    (resolved_path, import_export_names(&program))
+            let (resolved_path, program) = parse_import_program(path)?;
+            let exported = program.export_names();
+            let bindable = import_export_names(&program);
+            let export_names = match names {
+                Some(name_list) => {
+                    if let Some(missing) = name_list.iter().find(|n| !exported.contains(n)) {
+                        return Err(CompileError::new(&format!(
+                            "import '{}' does not export '{}'",
+                            path, missing
+                        )));
+                    }
+                    name_list
+                        .iter()
+                        .filter(|n| bindable.contains(n))
+                        .cloned()
+                        .collect()
                 }
+                None => bindable,
             };
 
-            if export_names.is_empty() {
-                return Ok(());
-            }
-
             let import_args = match names {
-                Some(name_list) => vec![
-                    Expr::StringLit(resolved_path),
+                Some(_) => vec![
+                    Expr::StringLit(resolved_path.clone()),
                     Expr::Array(
-                        name_list
+                        export_names
                             .iter()
                             .map(|name| Expr::StringLit(name.clone()))
                             .collect(),
                     ),
                 ],
-                None => vec![Expr::StringLit(resolved_path)],
+                None => vec![Expr::StringLit(resolved_path.clone())],
             };
 
+            if let Some(alias) = alias {
+                let module_reg = c.add_import(alias, &resolved_path, path)?;
+                compile_hidden_call(c, "__forge_import_module", import_args, module_reg)?;
+                c.emit_import_global(alias, module_reg);
+                return Ok(());
+            }
+
             let module_reg = c.alloc_reg()?;
             compile_hidden_call(c, "__forge_import_module", import_args, module_reg)?;
-            for name in export_names {
-                let local_reg = c.add_local(&name, false)?;
-                let field_idx = c.const_str(&name);
-                c.emit(
-                    encode_abc(OpCode::GetField, local_reg, module_reg, field_idx as u8),
-                    0,
-                );
+            bind_module_exports(c, module_reg, &export_names, &resolved_path, path)?;
+            // Like the interpreter, a whole-file import also binds the
+            // exports of the modules that file imported whole or mounted.
+            if names.is_none() {
+                let importer = c.module_scope.as_ref().map(|scope| scope.module.as_str());
+                for (nested_path, nested_file, nested) in nested_whole_imports(&program, importer)?
+                {
+                    compile_hidden_call(
+                        c,
+                        "__forge_import_module",
+                        vec![Expr::StringLit(nested_file.clone())],
+                        module_reg,
+                    )?;
+                    let exports = import_export_names(&nested);
+                    bind_module_exports(c, module_reg, &exports, &nested_file, &nested_path)?;
+                }
             }
             Ok(())
         }
@@ -1651,6 +2156,7 @@ fn compile_stmt(c: &mut Compiler, stmt: &Stmt) -> Result<(), CompileError> {
             let mut sc = Compiler::new("<spawn>");
             sc.parent_locals = parent_locals;
             sc.parent_upvalues = parent_upvalues;
+            sc.module_scope = c.module_scope.clone();
             sc.current_line = c.current_line;
             sc.current_col = c.current_col;
             sc.begin_scope();
@@ -1712,7 +2218,7 @@ fn compile_expr(c: &mut Compiler, expr: &Expr, dst: u8) -> Result<(), CompileErr
                 let uv_idx = c.add_upvalue(name, UpvalueSource::Upvalue(parent_upvalue));
                 c.emit(encode_abc(OpCode::GetUpvalue, dst, uv_idx, 0), 0);
             } else {
-                let idx = c.const_str(name);
+                let idx = c.const_str(&c.global_key(name));
                 c.emit(encode_abx(OpCode::GetGlobal, dst, idx), 0);
             }
         }
@@ -1747,6 +2253,13 @@ fn compile_expr(c: &mut Compiler, expr: &Expr, dst: u8) -> Result<(), CompileErr
                 BinOp::Mul => OpCode::Mul,
                 BinOp::Div => OpCode::Div,
                 BinOp::Mod => OpCode::Mod,
+                BinOp::Pow => OpCode::Pow,
+                BinOp::FloorDiv => OpCode::FloorDiv,
+                BinOp::BitAnd => OpCode::BitAnd,
+                BinOp::BitOr => OpCode::BitOr,
+                BinOp::BitXor => OpCode::BitXor,
+                BinOp::Shl => OpCode::Shl,
+                BinOp::Shr => OpCode::Shr,
                 BinOp::Eq => OpCode::Eq,
                 BinOp::NotEq => OpCode::NotEq,
                 BinOp::Lt => OpCode::Lt,
@@ -1814,6 +2327,25 @@ fn compile_expr(c: &mut Compiler, expr: &Expr, dst: u8) -> Result<(), CompileErr
             c.emit(encode_abc(OpCode::GetIndex, dst, or, ir), 0);
             c.free_to(saved);
         }
+        Expr::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            // Open bounds only appear in slices; fill them in the way
+            // `IntRange::open` does so `xs[..]` covers the whole sequence.
+            let start = start.as_deref().cloned().unwrap_or(Expr::Int(0));
+            let (end, inclusive) = match end {
+                Some(end) => (end.as_ref().clone(), *inclusive),
+                None => (Expr::Int(i64::MAX), true),
+            };
+            compile_hidden_call(
+                c,
+                "__forge_make_range",
+                vec![start, end, Expr::Bool(inclusive)],
+                dst,
+            )?;
+        }
         Expr::Array(items) => {
             let start = c.next_register;
             for item in items {
@@ -1885,6 +2417,7 @@ fn compile_expr(c: &mut Compiler, expr: &Expr, dst: u8) -> Result<(), CompileErr
             let mut lc = Compiler::new("<lambda>");
             lc.parent_locals = parent_locals;
             lc.parent_upvalues = parent_upvalues;
+            lc.module_scope = c.module_scope.clone();
             lc.current_line = c.current_line;
             lc.current_col = c.current_col;
             lc.begin_scope();
@@ -1956,6 +2489,7 @@ fn compile_expr(c: &mut Compiler, expr: &Expr, dst: u8) -> Result<(), CompileErr
             let mut sc = Compiler::new("<spawn>");
             sc.parent_locals = parent_locals;
             sc.parent_upvalues = parent_upvalues;
+            sc.module_scope = c.module_scope.clone();
             sc.current_line = c.current_line;
             sc.current_col = c.current_col;
             sc.begin_scope();
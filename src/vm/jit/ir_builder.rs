@@ -378,7 +378,12 @@ pub fn build_function<M: Module>(
                     b.def_var(regs[a], v);
                     b.ins().jump(next, &[]);
                 }
-                OpCode::Add | OpCode::Sub | OpCode::Mul | OpCode::Div | OpCode::Mod => {
+                OpCode::Add
+                | OpCode::Sub
+                | OpCode::Mul
+                | OpCode::Div
+                | OpCode::Mod
+                | OpCode::FloorDiv => {
                     let l_raw = b.use_var(regs[bb]);
                     let r_raw = b.use_var(regs[cc]);
                     let l_is_float = reg_type(bb) == RegType::Float;
@@ -407,6 +412,10 @@ pub fn build_function<M: Module>(
                                 let prod = b.ins().fmul(trunc, r);
                                 b.ins().fsub(l, prod)
                             }
+                            OpCode::FloorDiv => {
+                                let div = b.ins().fdiv(l, r);
+                                b.ins().floor(div)
+                            }
                             _ => unreachable!(),
                         };
                         // Bitcast F64 → I64 to store in I64 register
@@ -418,12 +427,43 @@ pub fn build_function<M: Module>(
                             OpCode::Mul => b.ins().imul(l_raw, r_raw),
                             OpCode::Div => b.ins().sdiv(l_raw, r_raw),
                             OpCode::Mod => b.ins().srem(l_raw, r_raw),
+                            OpCode::FloorDiv => {
+                                // sdiv truncates; step down when the remainder
+                                // is nonzero and its sign differs from the divisor's.
+                                let quot = b.ins().sdiv(l_raw, r_raw);
+                                let rem = b.ins().srem(l_raw, r_raw);
+                                let signs = b.ins().bxor(rem, r_raw);
+                                let opposite = b.ins().icmp_imm(IntCC::SignedLessThan, signs, 0);
+                                let inexact = b.ins().icmp_imm(IntCC::NotEqual, rem, 0);
+                                let adjust = b.ins().band(opposite, inexact);
+                                let adjust = b.ins().uextend(I64, adjust);
+                                b.ins().isub(quot, adjust)
+                            }
                             _ => unreachable!(),
                         }
                     };
                     b.def_var(regs[a], result);
                     b.ins().jump(next, &[]);
                 }
+                OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor | OpCode::Shl | OpCode::Shr => {
+                    // Type analysis only admits Int operands and in-range
+                    // constant shift amounts.
+                    let l = b.use_var(regs[bb]);
+                    let r = b.use_var(regs[cc]);
+                    let result = match opcode {
+                        OpCode::BitAnd => b.ins().band(l, r),
+                        OpCode::BitOr => b.ins().bor(l, r),
+                        OpCode::BitXor => b.ins().bxor(l, r),
+                        OpCode::Shl => b.ins().ishl(l, r),
+                        _ => b.ins().sshr(l, r),
+                    };
+                    b.def_var(regs[a], result);
+                    b.ins().jump(next, &[]);
+                }
+                // Type analysis only admits loops whose bounds are Ints.
+                OpCode::RangeCheck => {
+                    b.ins().jump(next, &[]);
+                }
                 OpCode::Neg => {
                     let v = b.use_var(regs[bb]);
                     let mf = cranelift_codegen::ir::MemFlags::new();
@@ -347,6 +347,12 @@ pub extern "C" fn rt_array_get(vm_ptr: *mut VM, arr_ref: i64, idx: i64) -> i64 {
                 return encode_value(val, &vm.gc) as i64;
             }
         }
+        if let ObjKind::Range(range) = &obj.kind {
+            if let Some(n) = range.get(idx as usize) {
+                let val = Value::int(n, &mut vm.gc);
+                return encode_value(&val, &vm.gc) as i64;
+            }
+        }
     }
     encode_null() as i64
 }
@@ -375,6 +381,7 @@ pub extern "C" fn rt_obj_len(vm_ptr: *mut VM, obj_ref: i64) -> i64 {
             ObjKind::String(s) => s.chars().count() as i64,
             ObjKind::Array(a) | ObjKind::Tuple(a) | ObjKind::Set(a) => a.len() as i64,
             ObjKind::Object(o) => o.len() as i64,
+            ObjKind::Range(range) => range.int_len(),
             _ => 0,
         },
         None => 0,
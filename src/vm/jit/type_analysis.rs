@@ -49,6 +49,20 @@ impl ConstValue {
             ConstValue::Bool(true) => false,
         }
     }
+
+    /// A `~/` divisor native code can use as is: not zero, and not the -1
+    /// that makes `Int.min ~/ -1` overflow into a Float.
+    fn is_plain_divisor(self) -> bool {
+        match self {
+            ConstValue::Int(n) => n != 0 && n != -1,
+            ConstValue::Float(n) => n != 0.0,
+            ConstValue::Bool(_) => false,
+        }
+    }
+}
+
+fn counted_loop_bounds_are_ints(types: &[RegType], start: usize, end: usize) -> bool {
+    types.get(start) == Some(&RegType::Int) && types.get(end) == Some(&RegType::Int)
 }
 
 /// Pre-pass: analyze bytecode to determine register types.
@@ -62,6 +76,7 @@ pub fn analyze(chunk: &Chunk) -> TypeInfo {
     let mut has_string_ops = false;
     let mut has_collection_ops = false;
     let mut has_global_ops = false;
+    let mut has_bitwise_ops = false;
     let mut return_type = RegType::Int;
 
     for i in 0..chunk.arity as usize {
@@ -149,11 +164,18 @@ pub fn analyze(chunk: &Chunk) -> TypeInfo {
                     constants[a] = None;
                 }
             }
-            OpCode::Div | OpCode::Mod => {
+            OpCode::Div | OpCode::Mod | OpCode::FloorDiv => {
                 if a < types.len() && bb < types.len() && cc < types.len() {
                     if constants[cc].is_some_and(ConstValue::is_zero) {
                         has_unsupported = true;
                     }
+                    // Native `~/` has neither the zero check nor the
+                    // overflow promotion, so the divisor must not need them.
+                    if matches!(opcode, OpCode::FloorDiv)
+                        && !constants[cc].is_some_and(ConstValue::is_plain_divisor)
+                    {
+                        has_unsupported = true;
+                    }
                     if types[bb] == RegType::Float || types[cc] == RegType::Float {
                         types[a] = RegType::Float;
                         has_float = true;
@@ -163,6 +185,34 @@ pub fn analyze(chunk: &Chunk) -> TypeInfo {
                     constants[a] = None;
                 }
             }
+            OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor | OpCode::Shl | OpCode::Shr => {
+                has_bitwise_ops = true;
+                if a < types.len() && bb < types.len() && cc < types.len() {
+                    if types[bb] == RegType::Float || types[cc] == RegType::Float {
+                        has_unsupported = true;
+                    }
+                    // Cranelift masks shift amounts instead of rejecting them,
+                    // so only shifts by an in-range constant are compiled.
+                    if matches!(opcode, OpCode::Shl | OpCode::Shr)
+                        && !matches!(constants[cc], Some(ConstValue::Int(0..=63)))
+                    {
+                        has_unsupported = true;
+                    }
+                    types[a] = RegType::Int;
+                    constants[a] = None;
+                }
+            }
+            OpCode::Pow => {
+                has_unsupported = true;
+                if a < constants.len() {
+                    constants[a] = None;
+                }
+            }
+            // The compiled loop skips the check, so its bounds must be known
+            // to be Ints; anything else stays in the VM to raise the error.
+            OpCode::RangeCheck if !counted_loop_bounds_are_ints(&types, a, bb) => {
+                has_unsupported = true;
+            }
             OpCode::Neg => {
                 if a < types.len() && bb < types.len() {
                     types[a] = types[bb];
@@ -273,6 +323,13 @@ pub fn analyze(chunk: &Chunk) -> TypeInfo {
 
             OpCode::GetGlobal => {
                 has_global_ops = true;
+                // Match patterns inspect the runtime kind of their subject,
+                // which integer-mode registers do not carry.
+                if let Some(Constant::Str(name)) = chunk.constants.get(bx as usize) {
+                    if name.starts_with("__forge_match_") {
+                        has_unsupported = true;
+                    }
+                }
                 if a < types.len() {
                     types[a] = RegType::Unknown;
                     constants[a] = None;
@@ -293,6 +350,11 @@ pub fn analyze(chunk: &Chunk) -> TypeInfo {
         }
     }
 
+    // Float promotion below would turn bitwise operands into floats.
+    if has_float && has_bitwise_ops {
+        has_unsupported = true;
+    }
+
     // I64-everywhere ABI allows mixing floats with string/collection/global ops.
     // Float values are stored as IEEE 754 bit patterns via bitcast.
 
@@ -374,7 +436,7 @@ pub fn analyze(chunk: &Chunk) -> TypeInfo {
                         constants[a] = None;
                     }
                 }
-                OpCode::Div | OpCode::Mod => {
+                OpCode::Div | OpCode::Mod | OpCode::FloorDiv => {
                     if a < types.len() && bb < types.len() && cc < types.len() {
                         if types[bb] == RegType::Float || types[cc] == RegType::Float {
                             types[a] = RegType::Float;
@@ -454,6 +516,9 @@ pub fn analyze(chunk: &Chunk) -> TypeInfo {
                         constants[dst] = None;
                     }
                 }
+                OpCode::RangeCheck if !counted_loop_bounds_are_ints(&types, a, bb) => {
+                    has_unsupported = true;
+                }
                 _ => {}
             }
         }
@@ -729,6 +794,20 @@ mod tests {
         assert_eq!(info.reg_types[1], RegType::Unknown);
     }
 
+    #[test]
+    fn analyze_match_pattern_natives_are_unsupported() {
+        let mut chunk = Chunk::new("match_array");
+        chunk.arity = 1;
+        chunk.max_registers = 2;
+        let name_idx = chunk.add_constant(Constant::Str("__forge_match_array".to_string()));
+        chunk.emit(encode_abx(OpCode::GetGlobal, 1, name_idx), 1);
+        chunk.emit(encode_abc(OpCode::Return, 0, 0, 0), 2);
+
+        let info = analyze(&chunk);
+        assert!(info.has_global_ops);
+        assert!(info.has_unsupported_ops);
+    }
+
     #[test]
     fn analyze_set_global_sets_flag() {
         let mut chunk = Chunk::new("write_global");
@@ -775,4 +854,68 @@ mod tests {
         assert!(!info.has_global_ops);
         assert_eq!(info.reg_types[2], RegType::Int);
     }
+
+    #[test]
+    fn analyze_shifts_need_a_constant_in_range_amount() {
+        let shift_by = |amount: Option<i64>| {
+            let mut chunk = Chunk::new("shift");
+            chunk.arity = 2;
+            chunk.max_registers = 4;
+            if let Some(n) = amount {
+                let idx = chunk.add_constant(Constant::Int(n));
+                chunk.emit(encode_abx(OpCode::LoadConst, 1, idx), 1);
+            }
+            chunk.emit(encode_abc(OpCode::Shl, 2, 0, 1), 2);
+            chunk.emit(encode_abc(OpCode::BitAnd, 3, 2, 0), 3);
+            chunk.emit(encode_abc(OpCode::Return, 3, 0, 0), 4);
+            analyze(&chunk)
+        };
+
+        let info = shift_by(Some(3));
+        assert!(!info.has_unsupported_ops);
+        assert_eq!(info.reg_types[3], RegType::Int);
+        assert!(shift_by(Some(64)).has_unsupported_ops);
+        assert!(shift_by(None).has_unsupported_ops);
+    }
+
+    #[test]
+    fn analyze_floor_division_needs_a_plain_constant_divisor() {
+        let floor_div_by = |divisor: Option<i64>| {
+            let mut chunk = Chunk::new("fd");
+            chunk.arity = 2;
+            chunk.max_registers = 3;
+            if let Some(n) = divisor {
+                let idx = chunk.add_constant(Constant::Int(n));
+                chunk.emit(encode_abx(OpCode::LoadConst, 1, idx), 1);
+            }
+            chunk.emit(encode_abc(OpCode::FloorDiv, 2, 0, 1), 2);
+            chunk.emit(encode_abc(OpCode::Return, 2, 0, 0), 3);
+            analyze(&chunk)
+        };
+
+        assert!(!floor_div_by(Some(2)).has_unsupported_ops);
+        assert!(!floor_div_by(Some(-3)).has_unsupported_ops);
+        assert!(floor_div_by(Some(0)).has_unsupported_ops);
+        assert!(floor_div_by(Some(-1)).has_unsupported_ops);
+        assert!(floor_div_by(None).has_unsupported_ops);
+    }
+
+    #[test]
+    fn analyze_counted_loops_need_int_bounds() {
+        let count_to = |end: Constant| {
+            let mut chunk = Chunk::new("count");
+            chunk.arity = 1;
+            chunk.max_registers = 3;
+            let idx = chunk.add_constant(end);
+            chunk.emit(encode_abx(OpCode::LoadConst, 1, idx), 1);
+            chunk.emit(encode_abc(OpCode::RangeCheck, 0, 1, 0), 1);
+            chunk.emit(encode_abc(OpCode::Lt, 2, 0, 1), 1);
+            chunk.emit(encode_abc(OpCode::Return, 0, 0, 0), 2);
+            analyze(&chunk)
+        };
+
+        assert!(!count_to(Constant::Int(10)).has_unsupported_ops);
+        assert!(count_to(Constant::Float(10.0)).has_unsupported_ops);
+        assert!(count_to(Constant::Str("10".to_string())).has_unsupported_ops);
+    }
 }
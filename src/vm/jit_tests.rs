@@ -554,18 +554,33 @@ fn vm_error_display_includes_trace() {
 }
 
 #[test]
-fn vm_compiler_rejects_standalone_decorator() {
-    let err = compile_source_result("@server(port: 8080)\n")
-        .expect_err("standalone decorators must not silently compile");
+fn vm_compiler_rejects_nested_standalone_decorator() {
+    let err = compile_source_result("fn f() {\n@server(port: 8080)\n}\n")
+        .expect_err("nested standalone decorators must not silently compile");
 
     assert!(
         err.message
-            .contains("VM does not support standalone decorator"),
+            .contains("VM only supports standalone decorator '@server' at the top level"),
         "unexpected error: {}",
         err.message
     );
 }
 
+#[test]
+fn vm_compiler_records_server_declarations() {
+    let chunk = compile_source_result(
+        "@server(port: 8080)\n@get(\"/a/:id\")\nfn a(id: Int, q = 2) { return id }\n",
+    )
+    .expect("top-level server decorators should compile");
+    assert_eq!(chunk.declarations.len(), 2);
+
+    let chunk = compile_source_result("fn plain() { return 1 }\n").unwrap();
+    assert!(chunk.declarations.is_empty());
+
+    let err = compile_source_result("@server(port: 1 + 1)\n").unwrap_err();
+    assert!(err.message.contains("must be literals"), "{}", err.message);
+}
+
 #[test]
 fn vm_compiler_accepts_metadata_function_decorator() {
     let result = compile_source_result("@test\nfn sample() { return 1 }\n");
@@ -8,6 +8,7 @@ use super::frame::*;
 use super::gc::Gc;
 use super::profiler::Profiler;
 use super::value::*;
+use crate::interpreter::{int_ops, range};
 
 /// Wrapper for sending a VM to another thread.
 /// SAFETY: fork_for_spawn() asserts jit_cache/jit_modules are empty (no raw
@@ -196,12 +197,17 @@ pub struct VM {
     #[cfg(feature = "jit")]
     /// Keeps JIT-compiled code pages alive. Must never be shrunk while
     /// `jit_cache` holds pointers into these modules.
-    jit_modules: Vec<super::jit::jit_module::JitCompiler>,
+    pub(crate) jit_modules: Vec<super::jit::jit_module::JitCompiler>,
     #[cfg(feature = "jit")]
     /// GcRef roots for string constants baked into JIT native code.
     /// These must survive GC so that bridge calls using the baked indices
     /// continue to resolve valid objects.
     pub jit_roots: Vec<GcRef>,
+    #[cfg(feature = "jit")]
+    /// Whether hot functions are compiled on the fly. Off for a VM that may
+    /// later move to another thread, which must own no JIT code (see
+    /// `SendableVM`).
+    pub(crate) auto_jit: bool,
     pub profiler: Profiler,
     skip_timeout_check_once: bool,
     /// Set by the Stream arms of `convert_to_interp_val` / `convert_interp_value`
@@ -221,6 +227,18 @@ pub struct VM {
     )>,
     /// Cooperative cancellation flag — shared with squad parent, checked at safe points.
     cancelled: Arc<std::sync::atomic::AtomicBool>,
+    /// Canonical paths of the modules whose imports are still running,
+    /// outermost first; importing one of them again is a cycle.
+    pub(super) module_chain: Vec<std::path::PathBuf>,
+    /// The file the program was loaded from, which starts every import
+    /// chain.
+    pub source_file: Option<std::path::PathBuf>,
+    /// The `@ws` connection this VM is handling a callback for, which
+    /// `ws.join`, `ws.leave`, `ws.rooms` and `ws.close()` act on.
+    pub(crate) ws_connection: Option<u64>,
+    /// Destination for `yield` / `emit` in an `@sse` handler; they are
+    /// no-ops when unset.
+    pub(crate) event_sink: Option<crate::interpreter::EventSink>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -312,11 +330,17 @@ impl VM {
             jit_modules: Vec::new(),
             #[cfg(feature = "jit")]
             jit_roots: Vec::new(),
+            #[cfg(feature = "jit")]
+            auto_jit: true,
             profiler: Profiler::new(false),
             skip_timeout_check_once: false,
             stream_boundary_error: std::cell::Cell::new(false),
             squad_stack: Vec::new(),
             cancelled: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            module_chain: Vec::new(),
+            source_file: None,
+            ws_connection: None,
+            event_sink: None,
         };
         vm.register_builtins();
         vm
@@ -339,16 +363,29 @@ impl VM {
             jit_modules: Vec::new(),
             #[cfg(feature = "jit")]
             jit_roots: Vec::new(),
+            #[cfg(feature = "jit")]
+            auto_jit: true,
             profiler: Profiler::new(true),
             skip_timeout_check_once: false,
             stream_boundary_error: std::cell::Cell::new(false),
             squad_stack: Vec::new(),
             cancelled: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            module_chain: Vec::new(),
+            source_file: None,
+            ws_connection: None,
+            event_sink: None,
         };
         vm.register_builtins();
         vm
     }
 
+    /// Share a cancellation flag with the VM; it stops with a
+    /// `task cancelled` error at the next call or loop back-edge once the
+    /// flag is set.
+    pub fn set_cancelled(&mut self, flag: Arc<std::sync::atomic::AtomicBool>) {
+        self.cancelled = flag;
+    }
+
     fn register_builtins(&mut self) {
         let builtins = [
             "print",
@@ -384,6 +421,8 @@ impl VM {
             "unwrap_or",
             "json",
             "fetch",
+            "response",
+            "redirect",
             "uuid",
             "exit",
             "run_command",
@@ -434,6 +473,13 @@ impl VM {
             "__forge_validate_impl",
             "__forge_call_method",
             "__forge_binding_matches",
+            "__forge_match_variant",
+            "__forge_match_field",
+            "__forge_match_range",
+            "__forge_make_range",
+            "__forge_match_object",
+            "__forge_match_array",
+            "__forge_match_rest",
             "__forge_retry_count",
             "__forge_retry_wait",
             "__forge_retry_failed",
@@ -444,6 +490,7 @@ impl VM {
             "__forge_register_agent",
             "__forge_raise_error",
             "__forge_import_module",
+            "__forge_emit",
             // Collections
             "first",
             "last",
@@ -759,6 +806,56 @@ impl VM {
         let jwt_ref = self.gc.alloc(ObjKind::Object(jwt_map));
         self.globals.insert("jwt".to_string(), Value::obj(jwt_ref));
 
+        // ws module
+        let mut ws_map = IndexMap::new();
+        for name in &[
+            "connect",
+            "send",
+            "receive",
+            "close",
+            "join",
+            "leave",
+            "rooms",
+            "broadcast",
+            "binary",
+        ] {
+            let full = format!("ws.{}", name);
+            let nr = self
+                .gc
+                .alloc(ObjKind::NativeFunction(NativeFn { name: full }));
+            ws_map.insert(name.to_string(), Value::obj(nr));
+        }
+        let ws_ref = self.gc.alloc(ObjKind::Object(ws_map));
+        self.globals.insert("ws".to_string(), Value::obj(ws_ref));
+
+        // metrics module
+        let mut metrics_map = IndexMap::new();
+        for name in &["counter", "gauge", "histogram", "describe", "render"] {
+            let full = format!("metrics.{}", name);
+            let nr = self
+                .gc
+                .alloc(ObjKind::NativeFunction(NativeFn { name: full }));
+            metrics_map.insert(name.to_string(), Value::obj(nr));
+        }
+        let metrics_ref = self.gc.alloc(ObjKind::Object(metrics_map));
+        self.globals
+            .insert("metrics".to_string(), Value::obj(metrics_ref));
+
+        // testing module
+        let mut testing_map = IndexMap::new();
+        for name in &[
+            "client", "request", "get", "post", "put", "patch", "delete", "head",
+        ] {
+            let full = format!("testing.{}", name);
+            let nr = self
+                .gc
+                .alloc(ObjKind::NativeFunction(NativeFn { name: full }));
+            testing_map.insert(name.to_string(), Value::obj(nr));
+        }
+        let testing_ref = self.gc.alloc(ObjKind::Object(testing_map));
+        self.globals
+            .insert("testing".to_string(), Value::obj(testing_ref));
+
         // mysql module
         #[cfg(feature = "mysql")]
         {
@@ -1216,6 +1313,27 @@ impl VM {
                         self.registers[base + a as usize] =
                             self.arith_op(&left, &right, OpCode::Mod)?;
                     }
+                    OpCode::Pow | OpCode::FloorDiv => {
+                        let left = self.registers[base + b as usize];
+                        let right = self.registers[base + c as usize];
+                        self.registers[base + a as usize] = self.arith_op(&left, &right, opcode)?;
+                    }
+                    OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor | OpCode::Shl | OpCode::Shr => {
+                        let left = self.registers[base + b as usize];
+                        let right = self.registers[base + c as usize];
+                        self.registers[base + a as usize] =
+                            self.bitwise_op(&left, &right, opcode)?;
+                    }
+                    OpCode::RangeCheck => {
+                        let start = self.registers[base + a as usize];
+                        let end = self.registers[base + b as usize];
+                        if start.as_int(&self.gc).is_none() || end.as_int(&self.gc).is_none() {
+                            return Err(VMError::new(&range::bounds_error(
+                                start.type_name(&self.gc),
+                                end.type_name(&self.gc),
+                            )));
+                        }
+                    }
                     OpCode::Neg => {
                         let src = self.registers[base + b as usize];
                         self.registers[base + a as usize] = match src.classify(&self.gc) {
@@ -1612,6 +1730,11 @@ impl VM {
                                             }
                                         }
                                     }
+                                    ObjKind::Range(range) => {
+                                        let range = *range;
+                                        direct_result = Some(self.range_field(range, field)?);
+                                        needs_alloc = None;
+                                    }
                                     _ => {
                                         return Err(VMError::new(&format!(
                                             "cannot access field '{}' on {}",
@@ -1659,7 +1782,9 @@ impl VM {
                         let obj = self.registers[base + b as usize];
                         let idx = self.registers[base + c as usize];
                         let result = if let Some(r) = obj.as_obj() {
-                            if let Some(i) = idx.as_int(&self.gc) {
+                            if let Some(v) = self.range_index(r, idx)? {
+                                v
+                            } else if let Some(i) = idx.as_int(&self.gc) {
                                 if let Some(o) = self.gc.get(r) {
                                     if let ObjKind::Array(items) | ObjKind::Tuple(items) = &o.kind {
                                         items
@@ -1706,6 +1831,7 @@ impl VM {
                                 // we can drop the gc borrow before allocating.
                                 enum IterSrc {
                                     Item(Value),
+                                    Int(i64),
                                     Pair(Value, Value),
                                     ObjPair(String, Value),
                                 }
@@ -1719,6 +1845,9 @@ impl VM {
                                         ObjKind::Map(pairs) => pairs
                                             .get(i as usize)
                                             .map(|(k, v)| IterSrc::Pair(*k, *v)),
+                                        ObjKind::Range(range) => {
+                                            range.get(i as usize).map(IterSrc::Int)
+                                        }
                                         ObjKind::Object(map) => map
                                             .iter()
                                             .nth(i as usize)
@@ -1734,6 +1863,7 @@ impl VM {
                                 };
                                 match src {
                                     Some(IterSrc::Item(v)) => v,
+                                    Some(IterSrc::Int(n)) => Value::int(n, &mut self.gc),
                                     Some(IterSrc::Pair(k, v)) => {
                                         let tr = self.gc.alloc(ObjKind::Tuple(vec![k, v]));
                                         Value::obj(tr)
@@ -1800,6 +1930,7 @@ impl VM {
                                     }
                                     ObjKind::Object(o) => o.len() as i64,
                                     ObjKind::Map(p) => p.len() as i64,
+                                    ObjKind::Range(range) => range.int_len(),
                                     _ => 0,
                                 }
                             } else {
@@ -1808,7 +1939,7 @@ impl VM {
                         } else {
                             0
                         };
-                        self.registers[base + a as usize] = Value::small_int(len);
+                        self.registers[base + a as usize] = Value::int(len, &mut self.gc);
                     }
                     OpCode::Concat => {
                         let left = self.registers[base + b as usize].display(&self.gc);
@@ -2322,7 +2453,11 @@ impl VM {
 
                         // Auto-JIT: compile hot functions on the fly
                         #[cfg(feature = "jit")]
-                        if jit_eligible && !already_jit && self.profiler.is_hot(&func_name) {
+                        if self.auto_jit
+                            && jit_eligible
+                            && !already_jit
+                            && self.profiler.is_hot(&func_name)
+                        {
                             let type_info = super::jit::type_analysis::analyze(&chunk);
                             let needs_vm_ptr = type_info.has_string_ops
                                 || type_info.has_collection_ops
@@ -2593,6 +2728,52 @@ impl VM {
         }
     }
 
+    fn range_field(&mut self, range: range::IntRange, field: &str) -> Result<Value, VMError> {
+        let n = match field {
+            "start" => range.start,
+            "end" => range.end,
+            "len" => range.int_len(),
+            "inclusive" => return Ok(Value::bool_val(range.inclusive)),
+            _ => return Err(VMError::new(&format!("no field '{}' on Range", field))),
+        };
+        Ok(Value::int(n, &mut self.gc))
+    }
+
+    /// The `GetIndex` cases that involve a range: `r[i]` on a range, or
+    /// `xs[a..b]` on an array, tuple or string. `None` for anything else.
+    fn range_index(&mut self, obj: GcRef, idx: Value) -> Result<Option<Value>, VMError> {
+        let slice = idx
+            .as_obj()
+            .and_then(|r| match self.gc.get(r).map(|o| &o.kind) {
+                Some(ObjKind::Range(range)) => Some(*range),
+                _ => None,
+            });
+        let kind = match (self.gc.get(obj).map(|o| &o.kind), slice) {
+            (Some(ObjKind::Range(range)), None) => {
+                let range = *range;
+                let Some(i) = idx.as_int(&self.gc) else {
+                    return Ok(None);
+                };
+                let n = range::index(&range, i).map_err(|e| VMError::new(&e))?;
+                return Ok(Some(Value::int(n, &mut self.gc)));
+            }
+            (Some(ObjKind::Array(items)), Some(range)) => {
+                let (from, to) = range.slice_bounds(items.len());
+                ObjKind::Array(items[from..to].to_vec())
+            }
+            (Some(ObjKind::Tuple(items)), Some(range)) => {
+                let (from, to) = range.slice_bounds(items.len());
+                ObjKind::Tuple(items[from..to].to_vec())
+            }
+            (Some(ObjKind::String(s)), Some(range)) => {
+                let (from, to) = range.slice_bounds(s.chars().count());
+                ObjKind::String(s.chars().skip(from).take(to - from).collect())
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(Value::obj(self.gc.alloc(kind))))
+    }
+
     pub(super) fn convert_to_interp_val(&self, v: &Value) -> crate::interpreter::Value {
         match v.classify(&self.gc) {
             ValueKind::Int(n) => crate::interpreter::Value::Int(n),
@@ -2650,6 +2831,7 @@ impl VM {
                             crate::interpreter::Value::Map(converted)
                         }
                         ObjKind::Frozen(inner) => self.convert_to_interp_val(inner),
+                        ObjKind::Range(range) => crate::interpreter::Value::Range(*range),
                         ObjKind::Stream(_) => {
                             // Streams cannot cross the VM/interpreter boundary.
                             // Set the flag so callers can surface a VMError.
@@ -2708,6 +2890,9 @@ impl VM {
                 let r = self.gc.alloc(ObjKind::Map(vm_pairs));
                 Value::obj(r)
             }
+            crate::interpreter::Value::Range(range) => {
+                Value::obj(self.gc.alloc(ObjKind::Range(*range)))
+            }
             crate::interpreter::Value::Stream(_) => {
                 // Streams cannot cross the interpreter/VM boundary.
                 // See `stream_boundary_error` docs on the VM struct.
@@ -2801,6 +2986,13 @@ impl VM {
                     }
                     Ok(Value::int(a % b, &mut self.gc))
                 }
+                OpCode::FloorDiv => {
+                    if b == 0 {
+                        return Err(VMError::new("division by zero"));
+                    }
+                    Ok(self.int_or_float(int_ops::floor_div(a, b)))
+                }
+                OpCode::Pow => Ok(self.int_or_float(int_ops::pow(a, b))),
                 _ => Err(VMError::new("invalid operation")),
             },
             (ValueKind::Float(a), ValueKind::Float(b)) => match op {
@@ -2809,6 +3001,8 @@ impl VM {
                 OpCode::Mul => Ok(Value::float(a * b)),
                 OpCode::Div => Ok(Value::float(a / b)),
                 OpCode::Mod => Ok(Value::float(a % b)),
+                OpCode::FloorDiv => Ok(Value::float((a / b).floor())),
+                OpCode::Pow => Ok(Value::float(a.powf(b))),
                 _ => Err(VMError::new("invalid operation")),
             },
             (ValueKind::Int(a), ValueKind::Float(_b)) => {
@@ -2833,6 +3027,41 @@ impl VM {
         }
     }
 
+    fn int_or_float(&mut self, n: int_ops::IntOrFloat) -> Value {
+        match n {
+            int_ops::IntOrFloat::Int(n) => Value::int(n, &mut self.gc),
+            int_ops::IntOrFloat::Float(n) => Value::float(n),
+        }
+    }
+
+    /// `&`, `|`, `^`, `<<` and `>>`, which only accept Int operands.
+    fn bitwise_op(&mut self, left: &Value, right: &Value, op: OpCode) -> Result<Value, VMError> {
+        let (ValueKind::Int(a), ValueKind::Int(b)) =
+            (left.classify(&self.gc), right.classify(&self.gc))
+        else {
+            let symbol = match op {
+                OpCode::BitAnd => "&",
+                OpCode::BitOr => "|",
+                OpCode::BitXor => "^",
+                OpCode::Shl => "<<",
+                _ => ">>",
+            };
+            return Err(VMError::new(&format!(
+                "'{}' requires Int operands, got {} and {}",
+                symbol,
+                left.type_name(&self.gc),
+                right.type_name(&self.gc)
+            )));
+        };
+        let result = match op {
+            OpCode::BitAnd => a & b,
+            OpCode::BitOr => a | b,
+            OpCode::BitXor => a ^ b,
+            _ => int_ops::shift(a, b, op == OpCode::Shl).map_err(|e| VMError::new(&e))?,
+        };
+        Ok(Value::int(result, &mut self.gc))
+    }
+
     fn compare_op(&self, left: &Value, right: &Value, op: OpCode) -> Result<Value, VMError> {
         match (left.classify(&self.gc), right.classify(&self.gc)) {
             (ValueKind::Int(a), ValueKind::Int(b)) => {
@@ -10,12 +10,14 @@ pub mod machine;
 pub mod nanbox;
 pub mod profiler;
 pub mod serialize;
+pub mod template;
 pub mod value;
 
 use crate::parser::ast::Program;
 use machine::{VMError, VM};
 
 /// Compile and execute a Forge program using the bytecode VM.
+#[allow(dead_code)]
 pub fn run(program: &Program) -> Result<(), VMError> {
     let chunk = compiler::compile(program).map_err(|e| VMError::new(&e.message))?;
     let mut vm = VM::new();
@@ -24,6 +26,7 @@ pub fn run(program: &Program) -> Result<(), VMError> {
 }
 
 /// Compile and execute with profiling enabled. Prints a report after execution.
+#[allow(dead_code)]
 pub fn run_with_profiling(program: &Program) -> Result<(), VMError> {
     let chunk = compiler::compile(program).map_err(|e| VMError::new(&e.message))?;
     let mut vm = VM::with_profiling();
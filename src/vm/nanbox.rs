@@ -238,6 +238,7 @@ impl NanBoxedValue {
                 super::value::ObjKind::Object(o) => !o.is_empty(),
                 super::value::ObjKind::Tuple(a) | super::value::ObjKind::Set(a) => !a.is_empty(),
                 super::value::ObjKind::Map(pairs) => !pairs.is_empty(),
+                super::value::ObjKind::Range(range) => !range.is_empty(),
                 super::value::ObjKind::ResultOk(_) => true,
                 super::value::ObjKind::ResultErr(_) => false,
                 _ => true,
@@ -620,6 +620,100 @@ fn cross_backend_parity_timeout_block_expires() {
     );
 }
 
+#[test]
+fn cross_backend_parity_integer_operator_edges() {
+    assert_cross_backend_value(
+        "[7 ~/ -2, -7.5 ~/ 2, 2 ** 64 > 0, -2 ** 2, 1 << 63 < 0, -1 >> 63]",
+        "[-4, -4, true, -4, true, -1]",
+    );
+}
+
+#[test]
+fn cross_backend_parity_bitwise_operators_reject_non_ints() {
+    assert_cross_backend_error_contains("1 & 1.5", "'&' requires Int operands");
+    assert_cross_backend_error_contains("true ^ false", "'^' requires Int operands");
+    assert_cross_backend_error_contains("1 << 64", "shift amount 64 is out of range");
+    assert_cross_backend_error_contains("8 >> -1", "shift amount -1 is out of range");
+    assert_cross_backend_error_contains("1 ~/ 0", "division by zero");
+}
+
+#[test]
+fn cross_backend_parity_floor_division_by_a_runtime_divisor() {
+    let fd = "fn fd(a, b) { return a ~/ b }\n";
+    assert_cross_backend_error_contains(&format!("{}fd(7, 0)", fd), "division by zero");
+    assert_cross_backend_value(
+        &format!("{}fd(-9223372036854775807 - 1, -1)", fd),
+        "9223372036854776000",
+    );
+    assert_cross_backend_value(&format!("{}fd(-7, 2) + fd(7, -2)", fd), "-8");
+}
+
+#[test]
+fn cross_backend_parity_range_edges() {
+    assert_cross_backend_value(
+        "[len(5..2), len(0..=-1), (3..3).to_array(), [1, 2, 3][1..99], \"abc\"[-2..], 1..=3]",
+        "[0, 0, [], [2, 3], bc, 1..=3]",
+    );
+    assert_cross_backend_value(
+        "let r = 0..=9223372036854775807\n[len(r), r.len, len(-9223372036854775807 - 1..0)]",
+        "[9223372036854775807, 9223372036854775807, 9223372036854775807]",
+    );
+}
+
+#[test]
+fn cross_backend_parity_huge_ranges_refuse_to_collect() {
+    for src in [
+        "filter(0..10**10, fn(x) { return x < 3 })",
+        "map(0..10**10, fn(x) { return x })",
+        "sum(0..=9223372036854775807)",
+        "(0..10**10).to_array()",
+    ] {
+        assert_cross_backend_error_contains(src, "too large to collect");
+    }
+    assert_cross_backend_value(
+        "(0..10**10).stream().filter(fn(x) { return x % 2 == 1 }).take(3).collect()",
+        "[1, 3, 5]",
+    );
+}
+
+#[test]
+fn cross_backend_parity_inclusive_loops_stop_at_int_max() {
+    assert_cross_backend_value(
+        r#"
+        fn count(a, b) {
+            let mut n = 0
+            for i in a..=b {
+                n = n + 1
+            }
+            return n
+        }
+        fn odd_sum(a, b) {
+            let mut total = 0
+            for i in a..=b {
+                if i % 2 == 0 {
+                    continue
+                }
+                total = total + i
+            }
+            return total
+        }
+        let mut last = 0
+        for i in 9223372036854775806..=9223372036854775807 {
+            last = i
+        }
+        [count(9223372036854775806, 9223372036854775807), count(3, 3), count(3, 1), odd_sum(1, 5), last]
+        "#,
+        "[2, 1, 0, 9, 9223372036854775807]",
+    );
+}
+
+#[test]
+fn cross_backend_parity_ranges_reject_non_int_bounds() {
+    assert_cross_backend_error_contains("for i in 0..\"x\" { }", "range bounds must be Int");
+    assert_cross_backend_error_contains("let r = 1.5..3", "range bounds must be Int");
+    assert_cross_backend_error_contains("(0..3)[3]", "index out of bounds");
+}
+
 #[test]
 fn cross_backend_parity_where_filter_syntax() {
     assert_cross_backend_value(
@@ -695,6 +789,19 @@ fn cross_backend_parity_type_builtin_statement_start() {
     assert_cross_backend_value("type(42)", "Int");
 }
 
+#[test]
+fn cross_backend_parity_response_builtins() {
+    assert_cross_backend_value(
+        r#"
+        let created = response(201, "ok", { "X-Id": 7 })
+        let moved = redirect("/login")
+        [created.status, created.headers["x-id"], moved.status, moved.headers.location]
+        "#,
+        "[201, 7, 302, /login]",
+    );
+    assert_cross_backend_error_contains("response(42)", "out of range");
+}
+
 #[test]
 fn cross_backend_parity_file_import() {
     assert_cross_backend_value(
@@ -717,6 +824,193 @@ fn cross_backend_parity_named_file_import() {
     );
 }
 
+#[test]
+fn cross_backend_parity_namespace_import_reaches_private_helpers() {
+    assert_cross_backend_value(
+        r#"
+        import "tests/parity/modules/visibility_helper.fg" as helper
+        helper.quadruple(helper.unit)
+        "#,
+        "40",
+    );
+}
+
+#[test]
+fn cross_backend_parity_imports_bind_only_exported_names() {
+    let helper = "tests/parity/modules/visibility_helper.fg";
+    for import in [
+        format!("import \"{helper}\""),
+        format!("import {{ quadruple }} from \"{helper}\""),
+        format!("import \"{helper}\" as helper"),
+    ] {
+        assert_cross_backend_error_contains(&format!("{import}\ndouble(1)"), "undefined variable");
+    }
+    assert_cross_backend_error_contains(
+        &format!("import {{ quadruple }} from \"{helper}\"\nunit"),
+        "undefined variable",
+    );
+    assert_cross_backend_error_contains(
+        &format!("import \"{helper}\" as helper\nquadruple(1)"),
+        "undefined variable",
+    );
+}
+
+#[test]
+fn cross_backend_parity_nested_whole_file_imports() {
+    let dir = std::env::temp_dir().join(format!("forge_parity_nested_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let inner = dir.join("inner.fg").display().to_string();
+    let lib = dir.join("lib.fg").display().to_string();
+    std::fs::write(
+        &inner,
+        "fn inner_helper() { return 0 }\npub fn inner_open() { return 1 }",
+    )
+    .unwrap();
+    std::fs::write(
+        &lib,
+        format!("import \"{inner}\"\nfn zz_private() {{ return 2 }}\npub fn open() {{ return inner_open() }}"),
+    )
+    .unwrap();
+
+    assert_cross_backend_value(&format!("import \"{lib}\"\nopen() + inner_open()"), "2");
+    for name in ["zz_private", "inner_helper"] {
+        assert_cross_backend_error_contains(
+            &format!("import \"{lib}\"\n{name}()"),
+            "undefined variable",
+        );
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn vm_rejects_private_and_colliding_imports() {
+    let compile_err = |source: &str| {
+        compiler::compile(&parse_program(source))
+            .expect_err("compile should fail")
+            .message
+    };
+    assert!(
+        compile_err(r#"import { double } from "tests/parity/modules/visibility_helper.fg""#)
+            .contains("does not export 'double'")
+    );
+    assert!(
+        compile_err("import \"tests/parity/modules/visibility_helper.fg\"\nlet unit = 1")
+            .contains("'unit' is already imported")
+    );
+    assert!(compile_err(
+        "fn quadruple(x) { return x }\nimport \"tests/parity/modules/visibility_helper.fg\""
+    )
+    .contains("already defined in this file"));
+}
+
+#[test]
+fn vm_diamond_imports_share_one_module_instance() {
+    let dir = std::env::temp_dir().join(format!("forge_vm_diamond_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let counter = dir.join("counter.fg").display().to_string();
+    std::fs::write(
+        &counter,
+        "println(\"loaded\")\nlet mut hits = 0\npub fn bump() {\n    hits = hits + 1\n    return hits\n}",
+    )
+    .unwrap();
+    for side in ["left", "right"] {
+        std::fs::write(
+            dir.join(format!("{side}.fg")),
+            format!("import \"{counter}\" as c\npub fn {side}() {{ return c.bump() }}"),
+        )
+        .unwrap();
+    }
+    let output = run_on_vm(&format!(
+        "import \"{}\"\nimport \"{}\"\nprintln(left())\nprintln(right())",
+        dir.join("left.fg").display(),
+        dir.join("right.fg").display()
+    ));
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(output, vec!["loaded", "1", "2"]);
+}
+
+#[test]
+fn vm_import_cycles_report_the_chain() {
+    let dir = std::env::temp_dir().join(format!("forge_vm_cycle_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let a = dir.join("a.fg").display().to_string();
+    let b = dir.join("b.fg").display().to_string();
+    std::fs::write(&a, format!("import \"{b}\"\nlet from_a = 1")).unwrap();
+    std::fs::write(&b, format!("import \"{a}\"\nlet from_b = 2")).unwrap();
+
+    let chunk = compiler::compile(&parse_program(&format!("import \"{a}\""))).unwrap();
+    let err = VM::new().execute(&chunk).expect_err("cycle should fail");
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(
+        err.message.starts_with("import cycle:") && err.message.contains("a.fg -> "),
+        "{}",
+        err.message
+    );
+}
+
+/// The error each engine reports for running `entry`.
+fn import_errors_on_both_engines(entry: &std::path::Path) -> (String, String) {
+    let program = parse_program(&std::fs::read_to_string(entry).unwrap());
+
+    let mut interpreter = Interpreter::new();
+    interpreter.source_file = Some(entry.to_path_buf());
+    let interp_err = interpreter
+        .run(&program)
+        .expect_err("interpreter should fail")
+        .message;
+
+    let vm_err = match compiler::compile(&program) {
+        Ok(chunk) => {
+            let mut vm = VM::new();
+            vm.source_file = Some(entry.to_path_buf());
+            vm.execute(&chunk).expect_err("vm should fail").message
+        }
+        Err(e) => e.message,
+    };
+    (interp_err, vm_err)
+}
+
+#[test]
+fn cross_backend_parity_import_cycle_error() {
+    let dir = std::env::temp_dir().join(format!("forge_parity_cycle_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = std::fs::canonicalize(&dir).unwrap();
+    let entry = dir.join("cyc.fg");
+    let c1 = dir.join("c1.fg").display().to_string();
+    let c2 = dir.join("c2.fg").display().to_string();
+    std::fs::write(&entry, format!("import \"{c1}\"")).unwrap();
+    std::fs::write(&c1, format!("import \"{c2}\"\npub let a = 1")).unwrap();
+    std::fs::write(&c2, format!("import \"{c1}\"\npub let b = 2")).unwrap();
+
+    let (interp_err, vm_err) = import_errors_on_both_engines(&entry);
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(
+        interp_err,
+        format!("import cycle: {} -> {c1} -> {c2} -> {c1}", entry.display())
+    );
+    assert_eq!(vm_err, interp_err);
+}
+
+#[test]
+fn cross_backend_parity_import_collision_error() {
+    let dir = std::env::temp_dir().join(format!("forge_parity_collide_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let util = dir.join("util.fg").display().to_string();
+    let other = dir.join("other.fg").display().to_string();
+    std::fs::write(&util, "pub fn helper() { return 1 }").unwrap();
+    std::fs::write(&other, "pub fn helper() { return 2 }").unwrap();
+    let entry = dir.join("main.fg");
+    std::fs::write(&entry, format!("import \"{other}\"\nimport \"{util}\"")).unwrap();
+
+    let (interp_err, vm_err) = import_errors_on_both_engines(&entry);
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(
+        interp_err,
+        format!("import '{util}' collides with 'helper' already imported from '{other}'")
+    );
+    assert_eq!(vm_err, interp_err);
+}
+
 #[test]
 fn cross_backend_parity_mutable_closure_counter() {
     assert_cross_backend_value(
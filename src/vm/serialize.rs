@@ -1,9 +1,12 @@
 use super::bytecode::{Chunk, Constant, UpvalueSource};
+use crate::parser::ast::{
+    Decorator, DecoratorArg, Expr, FieldDef, Param, SpannedStmt, Stmt, TypeAnn, UnaryOp,
+};
 use std::io::{self, Read, Write};
 
 const MAGIC: &[u8; 4] = b"FGC\0";
 const VERSION_MAJOR: u8 = 1;
-const VERSION_MINOR: u8 = 2;
+const VERSION_MINOR: u8 = 5;
 
 #[derive(Debug)]
 pub struct SerializeError {
@@ -45,7 +48,8 @@ fn write_chunk(w: &mut Vec<u8>, chunk: &Chunk) -> Result<(), SerializeError> {
     w.write_all(MAGIC)?;
     w.push(VERSION_MAJOR);
     w.push(VERSION_MINOR);
-    write_chunk_inner(w, chunk)
+    write_chunk_inner(w, chunk)?;
+    write_declarations(w, &chunk.declarations)
 }
 
 fn write_chunk_inner(w: &mut Vec<u8>, chunk: &Chunk) -> Result<(), SerializeError> {
@@ -169,7 +173,11 @@ fn read_chunk_root<R: Read>(r: &mut R) -> Result<Chunk, SerializeError> {
         )));
     }
 
-    read_chunk_inner(r, version[1])
+    let mut chunk = read_chunk_inner(r, version[1])?;
+    if version[1] >= 3 {
+        chunk.declarations = read_declarations(r)?;
+    }
+    Ok(chunk)
 }
 
 fn read_chunk_inner<R: Read>(r: &mut R, minor_version: u8) -> Result<Chunk, SerializeError> {
@@ -274,9 +282,382 @@ fn read_chunk_inner<R: Read>(r: &mut R, minor_version: u8) -> Result<Chunk, Seri
         upvalue_count,
         arity,
         upvalue_sources,
+        declarations: Vec::new(),
     })
 }
 
+// Declarations (v1.3+) keep only what `Chunk::declarations` can hold:
+// decorators, body-less functions and structs, with literal expressions.
+
+/// Deepest expression or type nesting accepted when reading.
+const MAX_DEPTH: usize = 64;
+
+fn write_declarations(w: &mut Vec<u8>, declarations: &[SpannedStmt]) -> Result<(), SerializeError> {
+    write_u32(w, declarations.len() as u32)?;
+    for spanned in declarations {
+        write_u32(w, spanned.line as u32)?;
+        write_u32(w, spanned.col as u32)?;
+        match &spanned.stmt {
+            Stmt::DecoratorStmt(decorator) => {
+                w.push(0x01);
+                write_decorator(w, decorator)?;
+            }
+            Stmt::FnDef {
+                name,
+                params,
+                decorators,
+                is_async,
+                ..
+            } => {
+                w.push(0x02);
+                write_string(w, name)?;
+                w.push(u8::from(*is_async));
+                write_u16(w, params.len() as u16)?;
+                for param in params {
+                    write_string(w, &param.name)?;
+                    write_optional(w, param.type_ann.as_ref(), write_type)?;
+                    write_optional(w, param.default.as_ref(), write_expr)?;
+                }
+                write_u16(w, decorators.len() as u16)?;
+                for decorator in decorators {
+                    write_decorator(w, decorator)?;
+                }
+            }
+            Stmt::StructDef { name, fields, .. } => {
+                w.push(0x03);
+                write_string(w, name)?;
+                write_u16(w, fields.len() as u16)?;
+                for field in fields {
+                    write_string(w, &field.name)?;
+                    write_type(w, &field.type_ann)?;
+                    write_optional(w, field.default.as_ref(), write_expr)?;
+                    w.push(u8::from(field.embedded));
+                }
+            }
+            _ => return Err(SerializeError::new("unsupported declaration")),
+        }
+    }
+    Ok(())
+}
+
+fn write_optional<T>(
+    w: &mut Vec<u8>,
+    value: Option<&T>,
+    write: fn(&mut Vec<u8>, &T) -> Result<(), SerializeError>,
+) -> Result<(), SerializeError> {
+    match value {
+        Some(value) => {
+            w.push(1);
+            write(w, value)
+        }
+        None => {
+            w.push(0);
+            Ok(())
+        }
+    }
+}
+
+fn write_decorator(w: &mut Vec<u8>, decorator: &Decorator) -> Result<(), SerializeError> {
+    write_string(w, &decorator.name)?;
+    write_u16(w, decorator.args.len() as u16)?;
+    for arg in &decorator.args {
+        match arg {
+            DecoratorArg::Positional(expr) => {
+                w.push(0x01);
+                write_expr(w, expr)?;
+            }
+            DecoratorArg::Named(key, expr) => {
+                w.push(0x02);
+                write_string(w, key)?;
+                write_expr(w, expr)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_type(w: &mut Vec<u8>, ann: &TypeAnn) -> Result<(), SerializeError> {
+    match ann {
+        TypeAnn::Simple(name) => {
+            w.push(0x01);
+            write_string(w, name)?;
+        }
+        TypeAnn::Array(inner) => {
+            w.push(0x02);
+            write_type(w, inner)?;
+        }
+        TypeAnn::Generic(name, args) => {
+            w.push(0x03);
+            write_string(w, name)?;
+            write_types(w, args)?;
+        }
+        TypeAnn::Function(params, ret) => {
+            w.push(0x04);
+            write_types(w, params)?;
+            write_type(w, ret)?;
+        }
+        TypeAnn::Optional(inner) => {
+            w.push(0x05);
+            write_type(w, inner)?;
+        }
+        TypeAnn::Tuple(items) => {
+            w.push(0x06);
+            write_types(w, items)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_types(w: &mut Vec<u8>, anns: &[TypeAnn]) -> Result<(), SerializeError> {
+    write_u16(w, anns.len() as u16)?;
+    for ann in anns {
+        write_type(w, ann)?;
+    }
+    Ok(())
+}
+
+fn write_expr(w: &mut Vec<u8>, expr: &Expr) -> Result<(), SerializeError> {
+    match expr {
+        Expr::Int(n) => {
+            w.push(0x01);
+            write_i64(w, *n)?;
+        }
+        Expr::Float(n) => {
+            w.push(0x02);
+            write_f64(w, *n)?;
+        }
+        Expr::StringLit(s) => {
+            w.push(0x03);
+            write_string(w, s)?;
+        }
+        Expr::Bool(b) => {
+            w.push(0x04);
+            w.push(u8::from(*b));
+        }
+        Expr::Ident(name) => {
+            w.push(0x05);
+            write_string(w, name)?;
+        }
+        Expr::Array(items) => {
+            w.push(0x06);
+            write_exprs(w, items)?;
+        }
+        Expr::Tuple(items) => {
+            w.push(0x07);
+            write_exprs(w, items)?;
+        }
+        Expr::Object(fields) => {
+            w.push(0x08);
+            write_u16(w, fields.len() as u16)?;
+            for (key, value) in fields {
+                write_string(w, key)?;
+                write_expr(w, value)?;
+            }
+        }
+        Expr::UnaryOp {
+            op: UnaryOp::Neg,
+            operand,
+        } => {
+            w.push(0x09);
+            write_expr(w, operand)?;
+        }
+        _ => {
+            return Err(SerializeError::new(
+                "only literal expressions can be stored in declarations",
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn write_exprs(w: &mut Vec<u8>, exprs: &[Expr]) -> Result<(), SerializeError> {
+    write_u16(w, exprs.len() as u16)?;
+    for expr in exprs {
+        write_expr(w, expr)?;
+    }
+    Ok(())
+}
+
+fn read_declarations<R: Read>(r: &mut R) -> Result<Vec<SpannedStmt>, SerializeError> {
+    let count = read_u32(r)? as usize;
+    if count > 65536 {
+        return Err(SerializeError::new("too many declarations"));
+    }
+    let mut declarations = Vec::with_capacity(count);
+    for _ in 0..count {
+        let line = read_u32(r)? as usize;
+        let col = read_u32(r)? as usize;
+        let stmt = match read_u8(r)? {
+            0x01 => Stmt::DecoratorStmt(read_decorator(r)?),
+            0x02 => {
+                let name = read_string(r)?;
+                let is_async = read_u8(r)? != 0;
+                let param_count = read_u16(r)?;
+                let mut params = Vec::with_capacity(param_count as usize);
+                for _ in 0..param_count {
+                    params.push(Param {
+                        name: read_string(r)?,
+                        type_ann: read_optional(r, read_type)?,
+                        default: read_optional(r, read_expr)?,
+                    });
+                }
+                let decorator_count = read_u16(r)?;
+                let mut decorators = Vec::with_capacity(decorator_count as usize);
+                for _ in 0..decorator_count {
+                    decorators.push(read_decorator(r)?);
+                }
+                Stmt::FnDef {
+                    name,
+                    type_params: Vec::new(),
+                    params,
+                    return_type: None,
+                    body: Vec::new(),
+                    decorators,
+                    is_async,
+                    is_pub: false,
+                }
+            }
+            0x03 => {
+                let name = read_string(r)?;
+                let field_count = read_u16(r)?;
+                let mut fields = Vec::with_capacity(field_count as usize);
+                for _ in 0..field_count {
+                    fields.push(FieldDef {
+                        name: read_string(r)?,
+                        type_ann: read_type(r, 0)?,
+                        default: read_optional(r, read_expr)?,
+                        embedded: read_u8(r)? != 0,
+                    });
+                }
+                Stmt::StructDef {
+                    name,
+                    type_params: Vec::new(),
+                    fields,
+                    is_pub: false,
+                }
+            }
+            other => {
+                return Err(SerializeError::new(&format!(
+                    "unknown declaration tag: 0x{:02x}",
+                    other
+                )))
+            }
+        };
+        declarations.push(SpannedStmt::new(stmt, line, col));
+    }
+    Ok(declarations)
+}
+
+fn read_optional<R: Read, T>(
+    r: &mut R,
+    read: fn(&mut R, usize) -> Result<T, SerializeError>,
+) -> Result<Option<T>, SerializeError> {
+    match read_u8(r)? {
+        0 => Ok(None),
+        _ => read(r, 0).map(Some),
+    }
+}
+
+fn read_decorator<R: Read>(r: &mut R) -> Result<Decorator, SerializeError> {
+    let name = read_string(r)?;
+    let count = read_u16(r)?;
+    let mut args = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        args.push(match read_u8(r)? {
+            0x01 => DecoratorArg::Positional(read_expr(r, 0)?),
+            0x02 => {
+                let key = read_string(r)?;
+                DecoratorArg::Named(key, read_expr(r, 0)?)
+            }
+            other => {
+                return Err(SerializeError::new(&format!(
+                    "unknown decorator argument tag: 0x{:02x}",
+                    other
+                )))
+            }
+        });
+    }
+    Ok(Decorator { name, args })
+}
+
+fn read_type<R: Read>(r: &mut R, depth: usize) -> Result<TypeAnn, SerializeError> {
+    if depth > MAX_DEPTH {
+        return Err(SerializeError::new("type annotation nested too deeply"));
+    }
+    Ok(match read_u8(r)? {
+        0x01 => TypeAnn::Simple(read_string(r)?),
+        0x02 => TypeAnn::Array(Box::new(read_type(r, depth + 1)?)),
+        0x03 => {
+            let name = read_string(r)?;
+            TypeAnn::Generic(name, read_types(r, depth + 1)?)
+        }
+        0x04 => {
+            let params = read_types(r, depth + 1)?;
+            TypeAnn::Function(params, Box::new(read_type(r, depth + 1)?))
+        }
+        0x05 => TypeAnn::Optional(Box::new(read_type(r, depth + 1)?)),
+        0x06 => TypeAnn::Tuple(read_types(r, depth + 1)?),
+        other => {
+            return Err(SerializeError::new(&format!(
+                "unknown type annotation tag: 0x{:02x}",
+                other
+            )))
+        }
+    })
+}
+
+fn read_types<R: Read>(r: &mut R, depth: usize) -> Result<Vec<TypeAnn>, SerializeError> {
+    let count = read_u16(r)?;
+    let mut anns = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        anns.push(read_type(r, depth)?);
+    }
+    Ok(anns)
+}
+
+fn read_expr<R: Read>(r: &mut R, depth: usize) -> Result<Expr, SerializeError> {
+    if depth > MAX_DEPTH {
+        return Err(SerializeError::new("expression nested too deeply"));
+    }
+    Ok(match read_u8(r)? {
+        0x01 => Expr::Int(read_i64(r)?),
+        0x02 => Expr::Float(read_f64(r)?),
+        0x03 => Expr::StringLit(read_string(r)?),
+        0x04 => Expr::Bool(read_u8(r)? != 0),
+        0x05 => Expr::Ident(read_string(r)?),
+        0x06 => Expr::Array(read_exprs(r, depth + 1)?),
+        0x07 => Expr::Tuple(read_exprs(r, depth + 1)?),
+        0x08 => {
+            let count = read_u16(r)?;
+            let mut fields = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let key = read_string(r)?;
+                fields.push((key, read_expr(r, depth + 1)?));
+            }
+            Expr::Object(fields)
+        }
+        0x09 => Expr::UnaryOp {
+            op: UnaryOp::Neg,
+            operand: Box::new(read_expr(r, depth + 1)?),
+        },
+        other => {
+            return Err(SerializeError::new(&format!(
+                "unknown expression tag: 0x{:02x}",
+                other
+            )))
+        }
+    })
+}
+
+fn read_exprs<R: Read>(r: &mut R, depth: usize) -> Result<Vec<Expr>, SerializeError> {
+    let count = read_u16(r)?;
+    let mut exprs = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        exprs.push(read_expr(r, depth)?);
+    }
+    Ok(exprs)
+}
+
 fn read_constant<R: Read>(r: &mut R) -> Result<Constant, SerializeError> {
     let mut tag = [0u8; 1];
     r.read_exact(&mut tag)?;
@@ -307,6 +688,12 @@ fn read_string<R: Read>(r: &mut R) -> Result<String, SerializeError> {
     String::from_utf8(buf).map_err(|_| SerializeError::new("invalid UTF-8 in string constant"))
 }
 
+fn read_u8<R: Read>(r: &mut R) -> Result<u8, SerializeError> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
 fn read_u16<R: Read>(r: &mut R) -> Result<u16, SerializeError> {
     let mut buf = [0u8; 2];
     r.read_exact(&mut buf)?;
@@ -783,6 +1170,53 @@ println(result)
         assert_eq!(orig_fib.arity, rest_fib.arity);
     }
 
+    #[test]
+    fn round_trip_server_declarations() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+        use crate::vm::compiler;
+
+        let source = r#"
+@server(port: 8080, cors: true)
+
+struct User { name: String, age: Int = 0 }
+
+@get("/users/:id")
+fn show(id: Int, verbose: Bool = false) -> Json {
+    return { id: id }
+}
+
+@post("/users")
+fn create(body: User) {
+    return body
+}
+"#;
+
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let chunk = compiler::compile(&program).unwrap();
+        assert_eq!(chunk.declarations.len(), 4);
+
+        let bytes = serialize_chunk(&chunk).unwrap();
+        let restored = deserialize_chunk(&bytes).unwrap();
+        assert_eq!(
+            format!("{:?}", chunk.declarations),
+            format!("{:?}", restored.declarations)
+        );
+
+        // A v1.2 file carries no declarations and still loads.
+        let mut old = bytes.clone();
+        old[5] = 2;
+        old.truncate(bytes.len() - declarations_len(&chunk));
+        assert!(deserialize_chunk(&old).unwrap().declarations.is_empty());
+    }
+
+    fn declarations_len(chunk: &Chunk) -> usize {
+        let mut buf = Vec::new();
+        write_declarations(&mut buf, &chunk.declarations).unwrap();
+        buf.len()
+    }
+
     #[test]
     fn round_trip_loop_program() {
         use crate::lexer::Lexer;
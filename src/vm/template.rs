@@ -0,0 +1,633 @@
+//! Program state that fresh VMs can be started from.
+//!
+//! A decorated server runs every request on a VM of its own. Instead of
+//! re-running the program for each one, the server captures a
+//! [`VmTemplate`] once, after the main chunk (and any `@on_start` hooks)
+//! have run: data is copied out of the GC into plain values, and functions
+//! keep their compiled prototypes behind the same `Arc<Chunk>`, so forking
+//! never recompiles anything. The template is `Send + Sync`, so requests
+//! start their VMs from it concurrently without a lock.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use indexmap::IndexMap;
+
+use super::bytecode::Chunk;
+use super::gc::Gc;
+use super::machine::{VMError, VM};
+use super::value::*;
+use crate::interpreter::IntRange;
+
+/// A value copied out of a VM's heap.
+enum Saved {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+    String(String),
+    Array(Vec<Saved>),
+    Object(IndexMap<String, Saved>),
+    Tuple(Vec<Saved>),
+    Set(Vec<Saved>),
+    Map(Vec<(Saved, Saved)>),
+    Range(IntRange),
+    ResultOk(Box<Saved>),
+    ResultErr(Box<Saved>),
+    Frozen(Box<Saved>),
+    Channel(Arc<VmChannelInner>),
+    Native(String),
+    Function {
+        name: String,
+        chunk: Arc<Chunk>,
+    },
+    /// Upvalues are indices into [`VmTemplate::upvalues`], so closures
+    /// that shared an upvalue still share it after a fork.
+    Closure {
+        name: String,
+        chunk: Arc<Chunk>,
+        upvalues: Vec<usize>,
+    },
+}
+
+pub struct VmTemplate {
+    globals: Vec<(String, Saved)>,
+    upvalues: Vec<Saved>,
+    method_tables: Vec<(String, IndexMap<String, Saved>)>,
+    static_methods: Vec<(String, IndexMap<String, Saved>)>,
+    struct_defaults: Vec<(String, IndexMap<String, Saved>)>,
+    embedded_fields: HashMap<String, Vec<(String, String)>>,
+    functions: HashSet<String>,
+}
+
+impl VmTemplate {
+    /// Copy the state `vm` has built up. Globals still holding the builtin
+    /// `VM::new` registered are left out, since every fork registers its
+    /// own. Fails if any state holds a stream, which is single-use and
+    /// cannot be handed to more than one request.
+    pub fn capture(vm: &VM) -> Result<VmTemplate, VMError> {
+        let fresh = VM::new();
+        let mut capture = Capture {
+            gc: &vm.gc,
+            upvalue_slots: HashMap::new(),
+            upvalues: Vec::new(),
+            stream: false,
+        };
+
+        let mut names: Vec<&String> = vm.globals.keys().collect();
+        names.sort();
+        let mut globals = Vec::new();
+        let mut functions = HashSet::new();
+        for name in names {
+            let value = vm.globals[name];
+            if fresh
+                .globals
+                .get(name)
+                .is_some_and(|builtin| is_same_builtin(&fresh.gc, builtin, &vm.gc, &value))
+            {
+                continue;
+            }
+            let saved = capture.save(&value);
+            if matches!(saved, Saved::Function { .. } | Saved::Closure { .. }) {
+                functions.insert(name.clone());
+            }
+            globals.push((name.clone(), saved));
+        }
+
+        let method_tables = capture.save_tables(&vm.method_tables);
+        let static_methods = capture.save_tables(&vm.static_methods);
+        let struct_defaults = capture.save_tables(&vm.struct_defaults);
+
+        if capture.stream {
+            return Err(VMError::new(
+                "a stream cannot be shared between requests; call .collect() first to materialize it",
+            ));
+        }
+        Ok(VmTemplate {
+            globals,
+            upvalues: capture.upvalues,
+            method_tables,
+            static_methods,
+            struct_defaults,
+            embedded_fields: vm.embedded_fields.clone(),
+            functions,
+        })
+    }
+
+    /// Whether the program defined a global function called `name`.
+    pub fn defines(&self, name: &str) -> bool {
+        self.functions.contains(name)
+    }
+
+    /// A new VM holding a private copy of the captured state.
+    pub fn instantiate(&self) -> VM {
+        let mut vm = VM::new();
+        // A worker's VM can change threads between requests.
+        #[cfg(feature = "jit")]
+        {
+            vm.auto_jit = false;
+        }
+        let gc = &mut vm.gc;
+        // Allocate every upvalue before filling any, since an upvalue can
+        // hold a closure over itself or another upvalue.
+        let upvalues: Vec<GcRef> = self
+            .upvalues
+            .iter()
+            .map(|_| {
+                gc.alloc(ObjKind::Upvalue(ObjUpvalue {
+                    value: Value::null(),
+                }))
+            })
+            .collect();
+        for (saved, r) in self.upvalues.iter().zip(&upvalues) {
+            let value = restore(gc, saved, &upvalues);
+            if let Some(obj) = gc.get_mut(*r) {
+                obj.kind = ObjKind::Upvalue(ObjUpvalue { value });
+            }
+        }
+        for (name, saved) in &self.globals {
+            let value = restore(gc, saved, &upvalues);
+            vm.globals.insert(name.clone(), value);
+        }
+        let mut tables = |saved: &[(String, IndexMap<String, Saved>)]| {
+            saved
+                .iter()
+                .map(|(name, entries)| {
+                    let entries = entries
+                        .iter()
+                        .map(|(key, saved)| (key.clone(), restore(gc, saved, &upvalues)))
+                        .collect();
+                    (name.clone(), entries)
+                })
+                .collect::<HashMap<_, _>>()
+        };
+        let method_tables = tables(&self.method_tables);
+        let static_methods = tables(&self.static_methods);
+        let struct_defaults = tables(&self.struct_defaults);
+        vm.method_tables = method_tables;
+        vm.static_methods = static_methods;
+        vm.struct_defaults = struct_defaults;
+        vm.embedded_fields = self.embedded_fields.clone();
+        vm
+    }
+}
+
+/// Whether `value` (on `gc`) is still what `VM::new` registered as
+/// `builtin` (on `fresh`): the native function of the same name, or a
+/// module object holding the same members. Compared by content, since a
+/// rebound builtin's heap slot can be reused by something else.
+fn is_same_builtin(fresh: &Gc, builtin: &Value, gc: &Gc, value: &Value) -> bool {
+    let (Some(a), Some(b)) = (builtin.as_obj(), value.as_obj()) else {
+        return builtin.0.to_bits() == value.0.to_bits();
+    };
+    match (fresh.get(a).map(|o| &o.kind), gc.get(b).map(|o| &o.kind)) {
+        (Some(ObjKind::NativeFunction(a)), Some(ObjKind::NativeFunction(b))) => a.name == b.name,
+        (Some(ObjKind::String(a)), Some(ObjKind::String(b))) => a == b,
+        (Some(ObjKind::Object(a)), Some(ObjKind::Object(b))) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|((ka, va), (kb, vb))| ka == kb && is_same_builtin(fresh, va, gc, vb))
+        }
+        _ => false,
+    }
+}
+
+struct Capture<'a> {
+    gc: &'a Gc,
+    upvalue_slots: HashMap<GcRef, usize>,
+    upvalues: Vec<Saved>,
+    stream: bool,
+}
+
+impl Capture<'_> {
+    fn save_tables(
+        &mut self,
+        tables: &HashMap<String, IndexMap<String, Value>>,
+    ) -> Vec<(String, IndexMap<String, Saved>)> {
+        tables
+            .iter()
+            .map(|(name, entries)| {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.save(value)))
+                    .collect();
+                (name.clone(), entries)
+            })
+            .collect()
+    }
+
+    fn save(&mut self, value: &Value) -> Saved {
+        let r = match value.classify(self.gc) {
+            ValueKind::Int(n) => return Saved::Int(n),
+            ValueKind::Float(n) => return Saved::Float(n),
+            ValueKind::Bool(b) => return Saved::Bool(b),
+            ValueKind::Null => return Saved::Null,
+            ValueKind::Obj(r) => r,
+        };
+        let Some(obj) = self.gc.get(r) else {
+            return Saved::Null;
+        };
+        match &obj.kind {
+            ObjKind::String(s) => Saved::String(s.clone()),
+            ObjKind::BoxedInt(n) => Saved::Int(*n),
+            ObjKind::Range(range) => Saved::Range(*range),
+            ObjKind::Array(items) => Saved::Array(items.iter().map(|v| self.save(v)).collect()),
+            ObjKind::Tuple(items) => Saved::Tuple(items.iter().map(|v| self.save(v)).collect()),
+            ObjKind::Set(items) => Saved::Set(items.iter().map(|v| self.save(v)).collect()),
+            ObjKind::Object(map) => {
+                Saved::Object(map.iter().map(|(k, v)| (k.clone(), self.save(v))).collect())
+            }
+            ObjKind::Map(pairs) => Saved::Map(
+                pairs
+                    .iter()
+                    .map(|(k, v)| (self.save(k), self.save(v)))
+                    .collect(),
+            ),
+            ObjKind::ResultOk(v) => Saved::ResultOk(Box::new(self.save(v))),
+            ObjKind::ResultErr(v) => Saved::ResultErr(Box::new(self.save(v))),
+            ObjKind::Frozen(v) => Saved::Frozen(Box::new(self.save(v))),
+            ObjKind::Channel(ch) => Saved::Channel(ch.clone()),
+            ObjKind::NativeFunction(native) => Saved::Native(native.name.clone()),
+            ObjKind::Function(f) => Saved::Function {
+                name: f.name.clone(),
+                chunk: Arc::clone(&f.chunk),
+            },
+            ObjKind::Closure(c) => {
+                let upvalues = c.upvalues.iter().map(|uv| self.upvalue(*uv)).collect();
+                Saved::Closure {
+                    name: c.function.name.clone(),
+                    chunk: Arc::clone(&c.function.chunk),
+                    upvalues,
+                }
+            }
+            ObjKind::Stream(_) => {
+                self.stream = true;
+                Saved::Null
+            }
+            // Task handles belong to the VM that spawned them, and bare
+            // upvalues are only reachable through their closures.
+            ObjKind::TaskHandle(_) | ObjKind::Upvalue(_) => Saved::Null,
+        }
+    }
+
+    fn upvalue(&mut self, r: GcRef) -> usize {
+        if let Some(&slot) = self.upvalue_slots.get(&r) {
+            return slot;
+        }
+        // Reserve the slot first: a closure stored in its own upvalue
+        // (direct recursion through a local) refers back to it.
+        let slot = self.upvalues.len();
+        self.upvalue_slots.insert(r, slot);
+        self.upvalues.push(Saved::Null);
+        let value = match self.gc.get(r).map(|obj| &obj.kind) {
+            Some(ObjKind::Upvalue(uv)) => uv.value,
+            _ => Value::null(),
+        };
+        self.upvalues[slot] = self.save(&value);
+        slot
+    }
+}
+
+fn restore(gc: &mut Gc, saved: &Saved, upvalues: &[GcRef]) -> Value {
+    let kind = match saved {
+        Saved::Int(n) => return Value::int(*n, gc),
+        Saved::Float(n) => return Value::float(*n),
+        Saved::Bool(b) => return Value::bool_val(*b),
+        Saved::Null => return Value::null(),
+        Saved::String(s) => ObjKind::String(s.clone()),
+        Saved::Array(items) => ObjKind::Array(restore_all(gc, items, upvalues)),
+        Saved::Tuple(items) => ObjKind::Tuple(restore_all(gc, items, upvalues)),
+        Saved::Set(items) => ObjKind::Set(restore_all(gc, items, upvalues)),
+        Saved::Object(map) => ObjKind::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), restore(gc, v, upvalues)))
+                .collect(),
+        ),
+        Saved::Map(pairs) => ObjKind::Map(
+            pairs
+                .iter()
+                .map(|(k, v)| (restore(gc, k, upvalues), restore(gc, v, upvalues)))
+                .collect(),
+        ),
+        Saved::Range(range) => ObjKind::Range(*range),
+        Saved::ResultOk(v) => ObjKind::ResultOk(restore(gc, v, upvalues)),
+        Saved::ResultErr(v) => ObjKind::ResultErr(restore(gc, v, upvalues)),
+        Saved::Frozen(v) => ObjKind::Frozen(restore(gc, v, upvalues)),
+        Saved::Channel(ch) => ObjKind::Channel(ch.clone()),
+        Saved::Native(name) => ObjKind::NativeFunction(NativeFn { name: name.clone() }),
+        Saved::Function { name, chunk } => ObjKind::Function(ObjFunction {
+            name: name.clone(),
+            chunk: Arc::clone(chunk),
+        }),
+        Saved::Closure {
+            name,
+            chunk,
+            upvalues: slots,
+        } => ObjKind::Closure(ObjClosure {
+            function: ObjFunction {
+                name: name.clone(),
+                chunk: Arc::clone(chunk),
+            },
+            upvalues: slots
+                .iter()
+                .filter_map(|slot| upvalues.get(*slot).copied())
+                .collect(),
+        }),
+    };
+    Value::obj(gc.alloc(kind))
+}
+
+fn restore_all(gc: &mut Gc, items: &[Saved], upvalues: &[GcRef]) -> Vec<Value> {
+    items.iter().map(|v| restore(gc, v, upvalues)).collect()
+}
+
+/// The global a stream being drained by [`VmWorker::call_streaming`] is
+/// kept in.
+const STREAM_ROOT: &str = "__forge_stream__";
+
+/// A VM started from a [`VmTemplate`] on first use, serving one request,
+/// hook or WebSocket connection of a compiled program.
+pub struct VmWorker {
+    template: Arc<VmTemplate>,
+    /// Only reached through `&mut self` with `Mutex::get_mut`, so the lock
+    /// is never taken. It is there so that a shared `&VmWorker`, as held by
+    /// an interpreter template, cannot reach the VM at all.
+    vm: Mutex<Option<WorkerVm>>,
+}
+
+struct WorkerVm(VM);
+
+// SAFETY: `instantiate` turns `auto_jit` off, so like the VM in
+// `SendableVM` this one never holds JIT code; `vm_mut` asserts as much,
+// in release builds too, before every use. Its other fields are owned or
+// `Arc`-wrapped. The `Mutex` in `VmWorker` keeps it to one thread at a
+// time.
+unsafe impl Send for WorkerVm {}
+
+impl VmWorker {
+    pub fn new(template: Arc<VmTemplate>) -> Self {
+        Self {
+            template,
+            vm: Mutex::new(None),
+        }
+    }
+
+    /// A worker on the same template whose VM has not started yet.
+    pub fn fork(&self) -> Self {
+        Self::new(Arc::clone(&self.template))
+    }
+
+    pub fn defines(&self, name: &str) -> bool {
+        self.template.defines(name)
+    }
+
+    fn vm_mut(&mut self) -> &mut VM {
+        let template = &self.template;
+        let vm = self.vm.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let vm = &mut vm.get_or_insert_with(|| WorkerVm(template.instantiate())).0;
+        #[cfg(feature = "jit")]
+        assert!(
+            vm.jit_cache.is_empty() && vm.jit_modules.is_empty(),
+            "BUG: a worker VM must have empty jit_cache/jit_modules to be safely Send"
+        );
+        vm
+    }
+
+    /// Call the global function `name`, stopping at the VM's next safe
+    /// point once `cancelled` is set.
+    pub fn call(
+        &mut self,
+        name: &str,
+        args: &[crate::interpreter::Value],
+        cancelled: &Arc<AtomicBool>,
+    ) -> Result<crate::interpreter::Value, VMError> {
+        let result = self.invoke(name, args, cancelled)?;
+        let vm = self.vm_mut();
+        let result = vm.convert_to_interp_val(&result);
+        vm.check_stream_boundary()?;
+        Ok(result)
+    }
+
+    /// [`call`](Self::call) for a function that may return a stream: its
+    /// items are passed to `each` until it returns `false` or the stream
+    /// ends, and `None` comes back. Any other result is returned as is.
+    pub fn call_streaming(
+        &mut self,
+        name: &str,
+        args: &[crate::interpreter::Value],
+        cancelled: &Arc<AtomicBool>,
+        mut each: impl FnMut(crate::interpreter::Value) -> bool,
+    ) -> Result<Option<crate::interpreter::Value>, VMError> {
+        let result = self.invoke(name, args, cancelled)?;
+        let vm = self.vm_mut();
+        let stream = result
+            .as_obj()
+            .filter(|r| matches!(vm.gc.get(*r).map(|obj| &obj.kind), Some(ObjKind::Stream(_))));
+        let Some(stream) = stream else {
+            let result = vm.convert_to_interp_val(&result);
+            vm.check_stream_boundary()?;
+            return Ok(Some(result));
+        };
+        // Pulling items can run closures and with them the collector, which
+        // only sees what globals and registers hold.
+        vm.globals.insert(STREAM_ROOT.to_string(), result);
+        let drained = loop {
+            match vm.stream_next_vm(stream) {
+                Ok(Some(item)) => {
+                    let item = vm.convert_to_interp_val(&item);
+                    if let Err(e) = vm.check_stream_boundary() {
+                        break Err(e);
+                    }
+                    if !each(item) {
+                        break Ok(None);
+                    }
+                }
+                Ok(None) => break Ok(None),
+                Err(e) => break Err(e),
+            }
+        };
+        vm.globals.remove(STREAM_ROOT);
+        drained
+    }
+
+    fn invoke(
+        &mut self,
+        name: &str,
+        args: &[crate::interpreter::Value],
+        cancelled: &Arc<AtomicBool>,
+    ) -> Result<Value, VMError> {
+        let vm = self.vm_mut();
+        vm.set_cancelled(Arc::clone(cancelled));
+        let function = vm
+            .globals
+            .get(name)
+            .copied()
+            .ok_or_else(|| VMError::new(&format!("'{}' is not defined", name)))?;
+        let mut vm_args = Vec::with_capacity(args.len());
+        for arg in args {
+            vm_args.push(vm.from_interp_checked(arg)?);
+        }
+        vm.call_value(function, vm_args)
+    }
+
+    /// Where `yield` / `emit` send values from the calls that follow.
+    pub fn set_event_sink(&mut self, sink: Option<crate::interpreter::EventSink>) {
+        self.vm_mut().event_sink = sink;
+    }
+
+    /// The `@ws` connection the calls that follow are handling, if any.
+    pub fn set_ws_connection(&mut self, connection: Option<u64>) {
+        self.vm_mut().ws_connection = connection;
+    }
+
+    /// Set a global on this worker's VM, starting it if needed.
+    pub fn set_global(
+        &mut self,
+        name: &str,
+        value: &crate::interpreter::Value,
+    ) -> Result<(), VMError> {
+        let vm = self.vm_mut();
+        let value = vm.from_interp_checked(value)?;
+        vm.globals.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// A global of the running VM, if it has started and defines `name`.
+    pub fn global(&mut self, name: &str) -> Option<crate::interpreter::Value> {
+        let vm = &self
+            .vm
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()?
+            .0;
+        vm.globals
+            .get(name)
+            .map(|value| vm.convert_to_interp_val(value))
+    }
+
+    /// Fold what the running VM changed back into the template, so later
+    /// forks start from it. A no-op until the VM has started.
+    pub fn settle(&mut self) -> Result<(), VMError> {
+        let vm = self.vm.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if let Some(WorkerVm(vm)) = vm.take() {
+            self.template = Arc::new(VmTemplate::capture(&vm)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Value as IValue;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn run(source: &str) -> VM {
+        let tokens = Lexer::new(source).tokenize().expect("lex");
+        let program = Parser::new(tokens).parse_program().expect("parse");
+        let chunk = super::super::compiler::compile(&program).expect("compile");
+        let mut vm = VM::new();
+        vm.execute(&chunk).expect("run");
+        vm
+    }
+
+    fn cancel() -> Arc<AtomicBool> {
+        Arc::new(AtomicBool::new(false))
+    }
+
+    #[test]
+    fn forks_share_prototypes_but_not_state() {
+        let vm = run(r#"
+            let mut hits = 0
+            let greeting = "hello"
+            fn hit() {
+                hits = hits + 1
+                return hits
+            }
+            fn greet(name) { return "{greeting}, {name}: {math.sqrt(16)}" }
+        "#);
+        let template = Arc::new(VmTemplate::capture(&vm).unwrap());
+        assert!(template.defines("hit") && template.defines("greet"));
+        assert!(!template.defines("math") && !template.defines("hits"));
+
+        let mut a = VmWorker::new(Arc::clone(&template));
+        let mut b = a.fork();
+        assert_eq!(a.call("hit", &[], &cancel()).unwrap(), IValue::Int(1));
+        assert_eq!(a.call("hit", &[], &cancel()).unwrap(), IValue::Int(2));
+        assert_eq!(b.call("hit", &[], &cancel()).unwrap(), IValue::Int(1));
+        assert_eq!(
+            b.call("greet", &[IValue::String("ada".into())], &cancel())
+                .unwrap(),
+            IValue::String("hello, ada: 4".into())
+        );
+
+        a.settle().unwrap();
+        assert_eq!(
+            a.fork().call("hit", &[], &cancel()).unwrap(),
+            IValue::Int(3)
+        );
+    }
+
+    #[test]
+    fn rejects_streams_and_stops_when_cancelled() {
+        let vm = run("let s = [1, 2, 3].stream()\nfn items() { return s }\n");
+        assert!(VmTemplate::capture(&vm)
+            .err()
+            .unwrap()
+            .message
+            .contains("stream"));
+
+        let vm = run("fn spin() { while true { } }\n");
+        let mut worker = VmWorker::new(Arc::new(VmTemplate::capture(&vm).unwrap()));
+        let err = worker
+            .call("spin", &[], &Arc::new(AtomicBool::new(true)))
+            .unwrap_err();
+        assert_eq!(err.message, "task cancelled");
+    }
+
+    #[test]
+    fn rebound_builtins_are_captured_by_content_not_slot() {
+        let vm = run("let str = len\nfn size(x) { return str(x) }\n");
+        let template = Arc::new(VmTemplate::capture(&vm).unwrap());
+        let captured = |template: &VmTemplate, name: &str| {
+            template.globals.iter().any(|(global, _)| global == name)
+        };
+        assert!(!captured(&template, "len") && !captured(&template, "str"));
+        let mut worker = VmWorker::new(template);
+        assert_eq!(
+            worker
+                .call("size", &[IValue::String("abcd".into())], &cancel())
+                .unwrap(),
+            IValue::Int(4)
+        );
+
+        // A builtin's slot reused by another native after a rebinding.
+        let mut vm = run("");
+        let slot = vm.globals["len"].as_obj().unwrap();
+        vm.gc.get_mut(slot).unwrap().kind = ObjKind::NativeFunction(NativeFn {
+            name: "print".to_string(),
+        });
+        let template = VmTemplate::capture(&vm).unwrap();
+        assert!(captured(&template, "len"));
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn worker_vms_never_hold_jit_code() {
+        let source = "fn sq(n) { return n * n }\n\
+                      fn total() { let mut t = 0\n for i in 0..300 { t += sq(i) }\n return t }\n";
+        let mut vm = run(source);
+        let template = Arc::new(VmTemplate::capture(&vm).unwrap());
+        let total = vm.globals["total"];
+        vm.call_value(total, vec![]).unwrap();
+        assert!(vm.jit_cache.contains_key("sq"), "a plain VM compiles sq");
+
+        let mut worker = VmWorker::new(template);
+        let sum = worker.call("total", &[], &cancel()).unwrap();
+        assert_eq!(sum, IValue::Int(8955050));
+        assert!(worker.vm_mut().jit_cache.is_empty());
+    }
+}
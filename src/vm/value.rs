@@ -1,6 +1,7 @@
 use super::bytecode::Chunk;
 use super::gc::Gc;
 use super::nanbox::NanBoxedValue;
+use crate::interpreter::IntRange;
 use indexmap::IndexMap;
 use std::fmt;
 use std::sync::mpsc::{Receiver, Sender, SyncSender};
@@ -57,6 +58,7 @@ pub enum SharedValue {
     Tuple(Vec<SharedValue>),
     Set(Vec<SharedValue>),
     Map(Vec<(SharedValue, SharedValue)>),
+    Range(IntRange),
 }
 
 thread_local! {
@@ -115,6 +117,7 @@ pub fn value_to_shared(gc: &Gc, val: &Value) -> SharedValue {
                         .collect(),
                 ),
                 ObjKind::BoxedInt(n) => SharedValue::Int(*n),
+                ObjKind::Range(range) => SharedValue::Range(*range),
                 ObjKind::Stream(_) => {
                     // Streams cannot cross the VM↔interpreter / thread
                     // boundary. Set the thread-local flag so callers can
@@ -186,6 +189,7 @@ pub fn shared_to_value(gc: &mut Gc, sv: &SharedValue) -> Value {
             let r = gc.alloc(ObjKind::Map(vals));
             Value::obj(r)
         }
+        SharedValue::Range(range) => Value::obj(gc.alloc(ObjKind::Range(*range))),
     }
 }
 
@@ -468,6 +472,7 @@ impl GcObject {
             }
             ObjKind::Stream(sb) => format!("Stream({})", sb.kind.short_name()),
             ObjKind::BoxedInt(n) => n.to_string(),
+            ObjKind::Range(range) => range.to_string(),
         }
     }
 
@@ -523,6 +528,7 @@ impl GcObject {
             ObjKind::Map(_) => "Map",
             ObjKind::Stream(_) => "Stream",
             ObjKind::BoxedInt(_) => "Int",
+            ObjKind::Range(_) => "Range",
         }
     }
 
@@ -555,6 +561,7 @@ impl GcObject {
                     })
             }
             (ObjKind::BoxedInt(a), ObjKind::BoxedInt(b)) => a == b,
+            (ObjKind::Range(a), ObjKind::Range(b)) => a == b,
             _ => false,
         }
     }
@@ -623,7 +630,7 @@ impl GcObject {
                             push(v, worklist);
                         }
                     }
-                    StreamKind::StringIter { .. } => {}
+                    StreamKind::StringIter { .. } | StreamKind::RangeIter { .. } => {}
                     StreamKind::Filter { upstream, pred } => {
                         push(upstream, worklist);
                         push(pred, worklist);
@@ -680,6 +687,8 @@ pub enum ObjKind {
     /// Heap-boxed i64 for values exceeding 48-bit NaN-box inline range.
     /// Used when NaN-boxed Value is active; transparent to user code.
     BoxedInt(i64),
+    /// `a..b` or `a..=b`; elements are computed on demand.
+    Range(IntRange),
 }
 
 /// VM-side stream state. Mirrors the interpreter's `StreamKind` with
@@ -708,6 +717,10 @@ pub enum StreamKind {
         chars: Vec<char>,
         idx: usize,
     },
+    RangeIter {
+        range: IntRange,
+        idx: usize,
+    },
     Filter {
         upstream: Value,
         pred: Value,
@@ -756,6 +769,7 @@ impl StreamKind {
             StreamKind::SetIter { .. } => "SetIter",
             StreamKind::MapIter { .. } => "MapIter",
             StreamKind::StringIter { .. } => "StringIter",
+            StreamKind::RangeIter { .. } => "RangeIter",
             StreamKind::Filter { .. } => "Filter",
             StreamKind::Map { .. } => "Map",
             StreamKind::Take { .. } => "Take",
@@ -18,8 +18,10 @@
 use forge_lang::interpreter::Interpreter;
 use forge_lang::lexer::Lexer;
 use forge_lang::parser::Parser;
-use forge_lang::runtime::metadata::extract_runtime_plan;
-use forge_lang::runtime::server::start_server;
+use forge_lang::runtime::host::launch_until;
+use forge_lang::runtime::metadata::extract_runtime_plan_in;
+use forge_lang::runtime::server::{serve_until, Shutdown};
+use forge_lang::runtime::worker::Served;
 use futures_util::SinkExt;
 
 use std::net::TcpListener;
@@ -42,8 +44,19 @@ fn pick_port() -> u16 {
 /// and is dropped when the runtime is dropped at test exit.
 fn spawn_test_server(source: &str) -> u16 {
     let port = pick_port();
-    let src = source.replace("__PORT__", &port.to_string());
+    start_server_thread(source.replace("__PORT__", &port.to_string()), None);
 
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .build()
+        .expect("client");
+    wait_until_ready(&client, &format!("http://127.0.0.1:{}/ping", port));
+    port
+}
+
+/// Run the program's `@server` on a background tokio runtime. Imports
+/// resolve next to `source_file` when one is given.
+fn start_server_thread(src: String, source_file: Option<PathBuf>) {
     std::thread::spawn(move || {
         let rt = tokio::runtime::Builder::new_multi_thread()
             .worker_threads(2)
@@ -58,32 +71,34 @@ fn spawn_test_server(source: &str) -> u16 {
             let program = parser.parse_program().expect("parse");
 
             let mut interp = Interpreter::new();
+            interp.source_file = source_file.clone();
             interp.run(&program).expect("run");
 
-            let plan = extract_runtime_plan(&program);
+            let base_dir = source_file.as_deref().and_then(Path::parent);
+            let plan = extract_runtime_plan_in(&program, base_dir);
             let server = plan.server.expect("program has @server decorator");
-            start_server(interp, &server).await.expect("server start");
+            // Runs until the test process exits and drops the runtime.
+            serve_until(interp, &server, std::future::pending())
+                .await
+                .expect("server start");
         });
     });
+}
 
-    // Poll for readiness up to 5s.
-    let client = reqwest::blocking::Client::builder()
-        .timeout(Duration::from_millis(500))
-        .build()
-        .expect("client");
-    let url = format!("http://127.0.0.1:{}/ping", port);
+/// Poll `url` until it answers with a success status, for up to 5s.
+fn wait_until_ready(client: &reqwest::blocking::Client, url: &str) {
     for _ in 0..50 {
         if client
-            .get(&url)
+            .get(url)
             .send()
             .map(|r| r.status().is_success())
             .unwrap_or(false)
         {
-            return port;
+            return;
         }
         std::thread::sleep(Duration::from_millis(100));
     }
-    panic!("server failed to start on port {} within 5s", port);
+    panic!("server at {} failed to start within 5s", url);
 }
 
 fn unique_temp_file(name: &str) -> PathBuf {
@@ -534,3 +549,1908 @@ fn request_id_is_generated_and_propagated() {
         "two server-generated request_ids should differ"
     );
 }
+
+#[test]
+fn extended_methods_and_catch_all_routes_dispatch() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @patch("/items/:id")
+        fn patch_item(id, body) {
+            return { id: id, name: body.name }
+        }
+
+        @options("/items/:id")
+        fn item_options(id) {
+            return { id: id }
+        }
+
+        @route("/proxy", method: ["GET", "PUT"])
+        fn proxy() {
+            return { proxied: true }
+        }
+
+        @route("/anything")
+        fn anything() {
+            return { matched: true }
+        }
+        "#,
+    );
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+
+    let patched: serde_json::Value = client
+        .patch(format!("{}/items/7", base))
+        .json(&serde_json::json!({"name": "widget"}))
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    assert_eq!(patched, serde_json::json!({"id": "7", "name": "widget"}));
+
+    let options = client
+        .request(reqwest::Method::OPTIONS, format!("{}/items/7", base))
+        .send()
+        .expect("send");
+    assert!(options.status().is_success());
+
+    let proxied = client
+        .put(format!("{}/proxy", base))
+        .json(&serde_json::json!({}))
+        .send()
+        .expect("send");
+    assert!(proxied.status().is_success());
+
+    // Path exists but the method isn't registered: 405 with an Allow header
+    // listing the registered methods.
+    let rejected = client
+        .delete(format!("{}/proxy", base))
+        .send()
+        .expect("send");
+    assert_eq!(rejected.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+    let allow = rejected
+        .headers()
+        .get("allow")
+        .expect("405 response missing Allow header")
+        .to_str()
+        .expect("Allow header not UTF-8")
+        .to_string();
+    assert!(
+        allow.contains("GET"),
+        "Allow header {:?} missing GET",
+        allow
+    );
+    assert!(
+        allow.contains("PUT"),
+        "Allow header {:?} missing PUT",
+        allow
+    );
+
+    for method in [reqwest::Method::GET, reqwest::Method::DELETE] {
+        let resp = client
+            .request(method.clone(), format!("{}/anything", base))
+            .send()
+            .expect("send");
+        assert!(
+            resp.status().is_success(),
+            "catch-all route rejected {}: {}",
+            method,
+            resp.status()
+        );
+    }
+}
+
+#[test]
+fn handlers_receive_full_request_object() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @post("/echo/:id")
+        fn echo(id, req) {
+            return {
+                id: id,
+                method: req.method,
+                path: req.path,
+                agent: req.headers["x-client"],
+                session: req.cookies.sid,
+                raw: req.raw_body,
+                body: req.body,
+                addr: req.remote_addr
+            }
+        }
+        "#,
+    );
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+    let body: serde_json::Value = client
+        .post(format!("http://127.0.0.1:{}/echo/9", port))
+        .header("X-Client", "forge-test")
+        .header("Cookie", "sid=s3cr3t; theme=dark")
+        .header("Content-Type", "text/plain")
+        .body("plain text payload")
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "id": "9",
+            "method": "POST",
+            "path": "/echo/9",
+            "agent": "forge-test",
+            "session": "s3cr3t",
+            "raw": "plain text payload",
+            "body": "plain text payload",
+            "addr": body["addr"].clone()
+        })
+    );
+    assert!(
+        body["addr"]
+            .as_str()
+            .is_some_and(|addr| addr.starts_with("127.0.0.1:")),
+        "remote_addr should be the loopback peer; got {}",
+        body["addr"]
+    );
+
+    let bad_json = client
+        .post(format!("http://127.0.0.1:{}/echo/9", port))
+        .header("Content-Type", "application/json")
+        .body("{not json")
+        .send()
+        .expect("send");
+    assert_eq!(bad_json.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn handlers_control_status_headers_and_body() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @post("/things")
+        fn create(body) {
+            return response(201, { created: body.name }, { "X-Thing": "yes" })
+        }
+
+        @get("/page")
+        fn page() {
+            return response({ status: 200, body: "<h1>Forge</h1>", type: "html" })
+        }
+
+        @get("/old")
+        fn old() {
+            return redirect("/new", 301)
+        }
+
+        @get("/users/:id")
+        fn user(id) {
+            if id == "0" {
+                return Err({ status: 404, message: "no such user" })
+            }
+            return Ok({ id: id })
+        }
+
+        @get("/validate")
+        fn validate() {
+            return Err("name is required")
+        }
+        "#,
+    );
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("client");
+
+    let created = client
+        .post(format!("{}/things", base))
+        .json(&serde_json::json!({"name": "widget"}))
+        .send()
+        .expect("send");
+    assert_eq!(created.status(), reqwest::StatusCode::CREATED);
+    assert_eq!(created.headers()["x-thing"], "yes");
+    assert_eq!(
+        created.json::<serde_json::Value>().expect("json"),
+        serde_json::json!({"created": "widget"})
+    );
+
+    let page = client.get(format!("{}/page", base)).send().expect("send");
+    assert_eq!(page.headers()["content-type"], "text/html; charset=utf-8");
+    assert_eq!(page.text().expect("text"), "<h1>Forge</h1>");
+
+    let old = client.get(format!("{}/old", base)).send().expect("send");
+    assert_eq!(old.status(), reqwest::StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(old.headers()["location"], "/new");
+
+    let found: serde_json::Value = client
+        .get(format!("{}/users/5", base))
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    assert_eq!(found, serde_json::json!({"id": "5"}));
+
+    let missing = client
+        .get(format!("{}/users/0", base))
+        .send()
+        .expect("send");
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(
+        missing.json::<serde_json::Value>().expect("json"),
+        serde_json::json!({"error": "no such user"})
+    );
+
+    let invalid = client
+        .get(format!("{}/validate", base))
+        .send()
+        .expect("send");
+    assert_eq!(invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn middleware_hooks_wrap_handlers() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__)
+
+        @before
+        fn tag_request(req) {
+            return { seen_path: req.path }
+        }
+
+        @after
+        fn stamp(res) {
+            return response(res.status, res.body, { "X-Stamped": "yes" })
+        }
+
+        @middleware("/admin")
+        fn require_token(req) {
+            if get(req.headers, "authorization", "") == "Bearer secret" {
+                return { role: "admin" }
+            }
+            return response(401, { error: "unauthorized" })
+        }
+
+        fn only_beta(query) {
+            return get(query, "beta", "0") == "1"
+        }
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @get("/admin/stats")
+        fn stats(ctx) {
+            return { role: ctx.role, path: ctx.seen_path }
+        }
+
+        @get("/beta")
+        @before("only_beta")
+        fn beta(req) {
+            return { path: req.ctx.seen_path }
+        }
+        "#,
+    );
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+
+    let denied = client
+        .get(format!("{}/admin/stats", base))
+        .send()
+        .expect("send");
+    assert_eq!(denied.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(denied.headers()["x-stamped"], "yes");
+
+    let allowed = client
+        .get(format!("{}/admin/stats", base))
+        .header("Authorization", "Bearer secret")
+        .send()
+        .expect("send");
+    assert_eq!(allowed.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        allowed.json::<serde_json::Value>().expect("json"),
+        serde_json::json!({"role": "admin", "path": "/admin/stats"})
+    );
+
+    let forbidden = client.get(format!("{}/beta", base)).send().expect("send");
+    assert_eq!(forbidden.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let beta = client
+        .get(format!("{}/beta?beta=1", base))
+        .send()
+        .expect("send");
+    assert_eq!(beta.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        beta.json::<serde_json::Value>().expect("json"),
+        serde_json::json!({"path": "/beta"})
+    );
+}
+
+#[test]
+fn static_directories_and_spa_fallback_are_served() {
+    let root = unique_temp_file("static_root").with_extension("");
+    let assets = root.join("assets");
+    std::fs::create_dir_all(&assets).expect("create static dirs");
+    std::fs::write(root.join("index.html"), "<div id=app></div>").expect("write index");
+    std::fs::write(assets.join("app.js"), "console.log('forge')").expect("write js");
+    std::fs::write(assets.join("app.js.gz"), b"\x1f\x8bfake-gzip").expect("write gz");
+
+    let port = spawn_test_server(&format!(
+        r#"
+        @server(port: __PORT__, static: "{root}", spa_fallback: "index.html")
+        @static("/assets", dir: "{assets}")
+
+        @get("/ping")
+        fn ping() -> Json {{
+            return {{ ok: true }}
+        }}
+        "#,
+        root = forge_string_literal_path(&root),
+        assets = forge_string_literal_path(&assets),
+    ));
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+
+    let js = client
+        .get(format!("{}/assets/app.js", base))
+        .send()
+        .expect("send");
+    assert_eq!(js.status(), reqwest::StatusCode::OK);
+    assert!(js.headers().contains_key("last-modified"));
+    let etag = js.headers()["etag"].to_str().expect("etag").to_string();
+    assert!(etag.starts_with("W/\""), "weak etag, got {}", etag);
+    assert_eq!(js.text().expect("text"), "console.log('forge')");
+
+    let cached = client
+        .get(format!("{}/assets/app.js", base))
+        .header("If-None-Match", &etag)
+        .send()
+        .expect("send");
+    assert_eq!(cached.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+    let partial = client
+        .get(format!("{}/assets/app.js", base))
+        .header("Range", "bytes=0-6")
+        .send()
+        .expect("send");
+    assert_eq!(partial.status(), reqwest::StatusCode::PARTIAL_CONTENT);
+    assert_eq!(partial.text().expect("text"), "console");
+
+    let gz = client
+        .get(format!("{}/assets/app.js", base))
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .expect("send");
+    assert_eq!(gz.headers()["content-encoding"], "gzip");
+    assert_ne!(gz.headers()["etag"].to_str().expect("etag"), etag);
+
+    let spa = client
+        .get(format!("{}/dashboard/settings", base))
+        .send()
+        .expect("send");
+    assert_eq!(spa.status(), reqwest::StatusCode::OK);
+    assert_eq!(spa.text().expect("text"), "<div id=app></div>");
+
+    let api: serde_json::Value = client
+        .get(format!("{}/ping", base))
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    assert_eq!(api, serde_json::json!({"ok": true}));
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn tls_server_serves_https_and_negotiates_http2() {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+        .expect("generate self-signed cert");
+    let cert_pem = certified.cert.pem();
+    let cert_path = unique_temp_file("tls_cert").with_extension("pem");
+    let key_path = unique_temp_file("tls_key").with_extension("pem");
+    std::fs::write(&cert_path, &cert_pem).expect("write cert");
+    std::fs::write(&key_path, certified.key_pair.serialize_pem()).expect("write key");
+
+    let port = pick_port();
+    start_server_thread(
+        format!(
+            r#"
+        @server(port: {port}, tls_cert: "{cert}", tls_key: "{key}")
+
+        @get("/ping")
+        fn ping(req) {{
+            return {{ ok: true, addr: req.remote_addr }}
+        }}
+        "#,
+            port = port,
+            cert = forge_string_literal_path(&cert_path),
+            key = forge_string_literal_path(&key_path),
+        ),
+        None,
+    );
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .add_root_certificate(
+            reqwest::Certificate::from_pem(cert_pem.as_bytes()).expect("parse cert"),
+        )
+        .build()
+        .expect("client");
+    let url = format!("https://localhost:{}/ping", port);
+    wait_until_ready(&client, &url);
+
+    let body: serde_json::Value = client.get(&url).send().expect("send").json().expect("json");
+    assert_eq!(body["ok"], serde_json::json!(true));
+    assert!(body["addr"]
+        .as_str()
+        .is_some_and(|addr| addr.starts_with("127.0.0.1:")));
+
+    // Plain HTTP against the TLS port must not be served.
+    assert!(
+        reqwest::blocking::get(format!("http://127.0.0.1:{}/ping", port))
+            .map(|r| !r.status().is_success())
+            .unwrap_or(true)
+    );
+
+    // ALPN offers h2 first, so an h2-capable client gets HTTP/2.
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let negotiated = rt.block_on(async {
+        let mut roots = rustls::RootCertStore::empty();
+        roots
+            .add(certified.cert.der().clone())
+            .expect("trust self-signed cert");
+        let mut config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+        let tcp = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .expect("connect");
+        let name = rustls::pki_types::ServerName::try_from("localhost").expect("server name");
+        let tls = connector.connect(name, tcp).await.expect("handshake");
+        tls.get_ref().1.alpn_protocol().map(|p| p.to_vec())
+    });
+    assert_eq!(negotiated.as_deref(), Some(&b"h2"[..]));
+
+    let _ = std::fs::remove_file(&cert_path);
+    let _ = std::fs::remove_file(&key_path);
+}
+
+#[test]
+fn sse_handlers_stream_emitted_and_returned_events() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @sse("/events")
+        fn events() {
+            emit "hello"
+            yield { n: 1 }
+            emit { event: "tick", data: { n: 2 }, id: 7 }
+            return [3, 4]
+        }
+        "#,
+    );
+
+    let response = reqwest::blocking::Client::new()
+        .get(format!("http://127.0.0.1:{}/events", port))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .expect("send");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let body = response.text().expect("body");
+    assert_eq!(
+        body,
+        "data: hello\n\ndata: {\"n\":1}\n\ndata: {\"n\":2}\nevent: tick\nid: 7\n\ndata: 3\n\ndata: 4\n\n"
+    );
+}
+
+#[test]
+fn sse_events_with_unframeable_names_or_ids_drop_those_fields() {
+    // Forge strings have no `\0` escape, so the NUL goes into the source.
+    let source = r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @sse("/events")
+        fn events() {
+            emit { event: "a\nb", data: 1 }
+            emit { event: "ok", data: 2, id: "x__NUL__y" }
+            emit { event: "ok", data: 3, id: "line\rbreak" }
+            emit "after"
+        }
+        "#
+    .replace("__NUL__", "\0");
+    let port = spawn_test_server(&source);
+
+    let response = reqwest::blocking::Client::new()
+        .get(format!("http://127.0.0.1:{}/events", port))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .expect("send");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.text().expect("body"),
+        "data: 1\n\ndata: 2\nevent: ok\n\ndata: 3\nevent: ok\n\ndata: after\n\n"
+    );
+}
+
+#[test]
+fn sse_handler_cancelled_on_client_disconnect() {
+    use std::io::{Read, Write};
+
+    let progress = unique_temp_file("sse_cancel_progress");
+    let finished = unique_temp_file("sse_cancel_finished");
+    for path in [&progress, &finished] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let source = r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @sse("/forever")
+        fn forever() {
+            let mut i = 0
+            repeat 1000000 times {
+                i = i + 1
+                emit i
+                fs.write("__PROGRESS__", str(i))
+            }
+            fs.write("__FINISHED__", "done")
+        }
+        "#
+    .replace("__PROGRESS__", &forge_string_literal_path(&progress))
+    .replace("__FINISHED__", &forge_string_literal_path(&finished));
+    let port = spawn_test_server(&source);
+
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).expect("connect");
+    write!(
+        stream,
+        "GET /forever HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\n\r\n"
+    )
+    .expect("send request");
+    let mut buf = [0u8; 256];
+    let n = stream.read(&mut buf).expect("read first chunk");
+    assert!(String::from_utf8_lossy(&buf[..n]).contains("text/event-stream"));
+    assert!(
+        wait_for_path(&progress, Duration::from_secs(5)),
+        "sse handler never started"
+    );
+    drop(stream);
+
+    // Once the client is gone the next emit fails and the handler stops.
+    std::thread::sleep(Duration::from_millis(500));
+    let settled = std::fs::read_to_string(&progress).unwrap_or_default();
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(
+        std::fs::read_to_string(&progress).unwrap_or_default(),
+        settled
+    );
+    assert!(
+        !finished.exists(),
+        "handler ran to completion after disconnect"
+    );
+
+    for path in [&progress, &finished] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[test]
+fn server_limits_map_to_413_503_and_504() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__, max_inflight: 1, max_body: 64, request_timeout: "300ms", keepalive: 30)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @post("/echo")
+        fn echo(body) {
+            return body
+        }
+
+        @get("/slow")
+        fn slow() -> Json {
+            wait(2)
+            return { ok: true }
+        }
+        "#,
+    );
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+
+    let small = client
+        .post(format!("{}/echo", base))
+        .json(&serde_json::json!({ "n": 1 }))
+        .send()
+        .expect("send");
+    assert_eq!(small.status(), reqwest::StatusCode::OK);
+
+    let large = client
+        .post(format!("{}/echo", base))
+        .json(&serde_json::json!({ "pad": "x".repeat(200) }))
+        .send()
+        .expect("send");
+    assert_eq!(large.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+    let body: serde_json::Value = large.json().expect("json error body");
+    assert!(body["error"].is_string());
+
+    let started = Instant::now();
+    let timed_out = client.get(format!("{}/slow", base)).send().expect("send");
+    assert_eq!(timed_out.status(), reqwest::StatusCode::GATEWAY_TIMEOUT);
+    assert!(started.elapsed() < Duration::from_millis(1500));
+
+    // Let the cancelled handler release its slot, then occupy it again.
+    std::thread::sleep(Duration::from_millis(300));
+    let hold = {
+        let client = client.clone();
+        let url = format!("{}/slow", base);
+        std::thread::spawn(move || client.get(url).send().map(|r| r.status()))
+    };
+    std::thread::sleep(Duration::from_millis(100));
+    let rejected = client.get(format!("{}/ping", base)).send().expect("send");
+    assert_eq!(rejected.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(rejected.headers()["retry-after"], "1");
+    assert_eq!(
+        hold.join().expect("join").expect("send"),
+        reqwest::StatusCode::GATEWAY_TIMEOUT
+    );
+}
+
+#[test]
+fn rate_limited_routes_return_429_with_retry_after() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @get("/search")
+        @rate_limit(2, per: "minute")
+        fn search() -> Json {
+            return { ok: true }
+        }
+
+        @get("/reports")
+        @rate_limit(1, per: "hour", key: "header:X-Api-Key")
+        fn reports() -> Json {
+            return { ok: true }
+        }
+        "#,
+    );
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+    let get = |path: &str, api_key: Option<&str>| {
+        let mut request = client.get(format!("{}{}", base, path));
+        if let Some(key) = api_key {
+            request = request.header("X-Api-Key", key);
+        }
+        request.send().expect("send")
+    };
+
+    assert_eq!(get("/search", None).status(), reqwest::StatusCode::OK);
+    assert_eq!(get("/search", None).status(), reqwest::StatusCode::OK);
+    let limited = get("/search", None);
+    assert_eq!(limited.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = limited.headers()["retry-after"]
+        .to_str()
+        .expect("ascii")
+        .parse()
+        .expect("seconds");
+    assert!(
+        (1..=30).contains(&retry_after),
+        "retry-after {}",
+        retry_after
+    );
+    let body: serde_json::Value = limited.json().expect("json error body");
+    assert_eq!(body["error"], "rate limit exceeded");
+
+    // Unlimited routes and other keys are unaffected.
+    assert_eq!(get("/ping", None).status(), reqwest::StatusCode::OK);
+    assert_eq!(get("/reports", Some("a")).status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        get("/reports", Some("a")).status(),
+        reqwest::StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(get("/reports", Some("b")).status(), reqwest::StatusCode::OK);
+}
+
+#[test]
+fn docs_endpoint_serves_generated_openapi() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__, docs: "/openapi.json")
+
+        struct Todo {
+            id: Int,
+            title: String
+        }
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @get("/todos/:id")
+        fn show(id: Int) -> Todo {
+            return Todo { id: id, title: "write docs" }
+        }
+        "#,
+    );
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+    let response = client
+        .get(format!("http://127.0.0.1:{}/openapi.json", port))
+        .send()
+        .expect("send");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let doc: serde_json::Value = response.json().expect("json document");
+    assert_eq!(doc["openapi"], "3.1.0");
+    assert_eq!(
+        doc["paths"]["/todos/{id}"]["get"]["parameters"][0]["schema"]["type"],
+        "integer"
+    );
+    assert_eq!(
+        doc["components"]["schemas"]["Todo"]["required"],
+        serde_json::json!(["id", "title"])
+    );
+    // The docs route itself is not part of the API it describes.
+    assert!(doc["paths"].get("/openapi.json").is_none());
+}
+
+#[test]
+fn typed_params_and_bodies_are_validated_before_the_handler() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__)
+
+        struct LineItem {
+            sku: String,
+            qty: Int = 1
+        }
+
+        struct Order {
+            customer: String,
+            items: [LineItem],
+            note: ?String
+        }
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @get("/items/:id")
+        fn show(id: Int, page: Int = 1, verbose: ?Bool) -> Json {
+            return { id: id + 1, page: page * 2, verbose: verbose }
+        }
+
+        @post("/orders")
+        fn create(body: Order) -> Json {
+            return body
+        }
+        "#,
+    );
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+    let url = |path: &str| format!("http://127.0.0.1:{}{}", port, path);
+
+    let ok: serde_json::Value = client
+        .get(url("/items/41?verbose=true"))
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    assert_eq!(
+        ok,
+        serde_json::json!({ "id": 42, "page": 2, "verbose": true })
+    );
+
+    let bad = client.get(url("/items/abc?page=two")).send().expect("send");
+    assert_eq!(bad.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let bad: serde_json::Value = bad.json().expect("json");
+    assert_eq!(bad["error"], "validation failed");
+    assert_eq!(
+        bad["violations"],
+        serde_json::json!([
+            { "in": "path", "field": "id", "message": "expected Int, got \"abc\"" },
+            { "in": "query", "field": "page", "message": "expected Int, got \"two\"" }
+        ])
+    );
+
+    let rejected = client
+        .post(url("/orders"))
+        .json(&serde_json::json!({ "items": [{ "sku": 7 }, { "qty": "2" }] }))
+        .send()
+        .expect("send");
+    assert_eq!(rejected.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let rejected: serde_json::Value = rejected.json().expect("json");
+    let fields: Vec<&str> = rejected["violations"]
+        .as_array()
+        .expect("violations")
+        .iter()
+        .map(|v| v["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            "body.customer",
+            "body.items[0].sku",
+            "body.items[1].sku",
+            "body.items[1].qty"
+        ]
+    );
+
+    let created: serde_json::Value = client
+        .post(url("/orders"))
+        .json(&serde_json::json!({
+            "customer": "ada",
+            "items": [{ "sku": "A-1" }],
+            "coupon": "ignored"
+        }))
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    assert_eq!(
+        created,
+        serde_json::json!({
+            "customer": "ada",
+            "items": [{ "sku": "A-1", "qty": 1 }],
+            "note": null
+        })
+    );
+}
+
+#[test]
+fn form_and_multipart_bodies_reach_handlers() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__, max_upload: "1kb")
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @post("/signup")
+        fn signup(body) -> Json {
+            return body
+        }
+
+        @post("/upload")
+        fn upload(body) -> Json {
+            let doc = body.document
+            return {
+                title: body.title,
+                filename: doc.filename,
+                content_type: doc.content_type,
+                size: doc.size,
+                contents: fs.read(doc.path),
+                path: doc.path
+            }
+        }
+        "#,
+    );
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+    let url = |path: &str| format!("http://127.0.0.1:{}{}", port, path);
+
+    let form: serde_json::Value = client
+        .post(url("/signup"))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("name=Ada+Lovelace&role=admin&role=dev")
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    assert_eq!(
+        form,
+        serde_json::json!({ "name": "Ada Lovelace", "role": ["admin", "dev"] })
+    );
+
+    let multipart = |contents: &str| {
+        format!(
+            "--b0undary\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+             notes\r\n\
+             --b0undary\r\n\
+             Content-Disposition: form-data; name=\"document\"; filename=\"notes.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             {}\r\n\
+             --b0undary--\r\n",
+            contents
+        )
+    };
+    let mut uploaded: serde_json::Value = client
+        .post(url("/upload"))
+        .header("content-type", "multipart/form-data; boundary=b0undary")
+        .body(multipart("hello from a file"))
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    // The handler has finished, so its upload directory is already gone.
+    let stored = std::path::PathBuf::from(uploaded["path"].as_str().expect("path"));
+    assert!(
+        !stored.exists(),
+        "upload left behind at {}",
+        stored.display()
+    );
+    uploaded.as_object_mut().expect("object").remove("path");
+    assert_eq!(
+        uploaded,
+        serde_json::json!({
+            "title": "notes",
+            "filename": "notes.txt",
+            "content_type": "text/plain",
+            "size": 17,
+            "contents": "hello from a file"
+        })
+    );
+
+    let too_big = client
+        .post(url("/upload"))
+        .header("content-type", "multipart/form-data; boundary=b0undary")
+        .body(multipart(&"x".repeat(2048)))
+        .send()
+        .expect("send");
+    assert_eq!(too_big.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[test]
+fn grouped_and_mounted_route_modules_are_served() {
+    let dir = unique_temp_file("route_modules").with_extension("");
+    std::fs::create_dir_all(dir.join("routes")).expect("module dir");
+    std::fs::write(
+        dir.join("routes").join("users.fg"),
+        r#"
+        @group("/users")
+
+        fn label(id) {
+            return "user " + id
+        }
+
+        @get("/:id")
+        fn show_user(id: Int) -> Json {
+            return { id: id, label: label(str(id)) }
+        }
+        "#,
+    )
+    .expect("write users module");
+    std::fs::write(
+        dir.join("admin_routes.fg"),
+        r#"
+        import "routes/users"
+
+        @before
+        fn require_admin(req) {
+            if get(req.headers, "x-admin", "") != "yes" {
+                return response(401, { error: "admins only" })
+            }
+            return null
+        }
+
+        @get("/stats")
+        fn stats() -> Json {
+            return { users: 1 }
+        }
+        "#,
+    )
+    .expect("write admin module");
+
+    let port = pick_port();
+    let main = dir.join("main.fg");
+    let source = format!(
+        r#"
+        @server(port: {})
+        @mount("/api/v1", "routes/users")
+        @mount("/admin", "admin_routes")
+
+        @get("/ping")
+        fn ping() -> Json {{
+            return {{ ok: true }}
+        }}
+        "#,
+        port
+    );
+    std::fs::write(&main, &source).expect("write main");
+    start_server_thread(source, Some(main));
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+    let url = |path: &str| format!("http://127.0.0.1:{}{}", port, path);
+    wait_until_ready(&client, &url("/ping"));
+
+    let user: serde_json::Value = client
+        .get(url("/api/v1/users/7"))
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    assert_eq!(user, serde_json::json!({ "id": 7, "label": "user 7" }));
+
+    // The admin module's middleware covers its own routes, including the
+    // users module it imports, but not the same module mounted elsewhere.
+    let denied = client.get(url("/admin/stats")).send().expect("send");
+    assert_eq!(denied.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let nested = client
+        .get(url("/admin/users/7"))
+        .header("x-admin", "yes")
+        .send()
+        .expect("send");
+    assert_eq!(nested.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        client
+            .get(url("/admin/users/7"))
+            .send()
+            .expect("send")
+            .status(),
+        reqwest::StatusCode::UNAUTHORIZED
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn lifecycle_hooks_run_around_a_draining_server() {
+    let marker = unique_temp_file("on_shutdown");
+    let port = pick_port();
+    let source = format!(
+        r#"
+        @server(port: {port}, drain_timeout: "5s")
+
+        let mut pool = null
+
+        @on_start
+        fn open_pool() {{
+            pool = {{ open: true }}
+        }}
+
+        @on_shutdown
+        fn close_pool() {{
+            fs.write("{marker}", "closed")
+        }}
+
+        @get("/ping")
+        fn ping() -> Json {{
+            return {{ pool: pool }}
+        }}
+
+        @get("/slow")
+        fn slow() -> Json {{
+            wait(0.5)
+            return {{ finished: true }}
+        }}
+        "#,
+        port = port,
+        marker = forge_string_literal_path(&marker),
+    );
+
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .expect("build tokio runtime");
+        rt.block_on(async move {
+            let tokens = Lexer::new(&source).tokenize().expect("lex");
+            let program = Parser::new(tokens).parse_program().expect("parse");
+            let mut interp = Interpreter::new();
+            interp.run(&program).expect("run");
+            let plan = extract_runtime_plan_in(&program, None);
+            let shutdown = async {
+                let _ = stop_rx.await;
+            };
+            let outcome = launch_until(interp, &plan, shutdown).await;
+            let _ = done_tx.send(outcome.map_err(|e| e.message));
+        });
+    });
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+    let url = format!("http://127.0.0.1:{}", port);
+    wait_until_ready(&client, &format!("{}/ping", url));
+
+    // @on_start ran on the interpreter handlers fork from.
+    let ping: serde_json::Value = client
+        .get(format!("{}/ping", url))
+        .send()
+        .expect("send")
+        .json()
+        .expect("json");
+    assert_eq!(ping, serde_json::json!({ "pool": { "open": true } }));
+
+    let slow = std::thread::spawn({
+        let client = client.clone();
+        let url = url.clone();
+        move || client.get(format!("{}/slow", url)).send()
+    });
+    std::thread::sleep(Duration::from_millis(150));
+    stop_tx.send(()).expect("signal shutdown");
+
+    // The in-flight request finishes during the drain...
+    let slow = slow.join().expect("join").expect("slow request");
+    assert_eq!(slow.status(), reqwest::StatusCode::OK);
+    // ...and the server then stops cleanly and runs @on_shutdown.
+    let outcome = done_rx
+        .recv_timeout(Duration::from_secs(10))
+        .expect("server stopped");
+    assert_eq!(outcome, Ok(Shutdown::Drained));
+    assert_eq!(std::fs::read_to_string(&marker).expect("marker"), "closed");
+    let _ = std::fs::remove_file(&marker);
+}
+
+#[test]
+fn metrics_and_health_endpoints_are_served() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__, metrics: "/metrics", health: "/healthz")
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        @get("/books/:id")
+        fn show_book(id: Int) -> Json {
+            metrics.counter("bookshop_views_total", { shelf: "fiction" })
+            metrics.histogram("bookshop_price", 12.5)
+            return { id: id }
+        }
+        "#,
+    );
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("client");
+    let url = |path: &str| format!("http://127.0.0.1:{}{}", port, path);
+    for id in [1, 2] {
+        let response = client.get(url(&format!("/books/{}", id))).send();
+        assert_eq!(response.expect("send").status(), reqwest::StatusCode::OK);
+    }
+    let missing = client.get(url("/nowhere")).send().expect("send");
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    for method in ["FOO1", "FOO2"] {
+        let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap();
+        let odd = client
+            .request(method, url("/nowhere"))
+            .send()
+            .expect("send");
+        assert_eq!(odd.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    let health = client.get(url("/healthz")).send().expect("send");
+    assert_eq!(health.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        health.json::<serde_json::Value>().expect("json"),
+        serde_json::json!({ "status": "ok" })
+    );
+
+    let response = client.get(url("/metrics")).send().expect("send");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain; version=0.0.4"));
+    let text = response.text().expect("text");
+    for line in [
+        "# TYPE forge_http_requests_total counter",
+        "forge_http_requests_total{method=\"GET\",route=\"/books/:id\",status=\"200\"} 2",
+        "forge_http_requests_total{method=\"GET\",route=\"fallback\",status=\"404\"} 1",
+        "forge_http_requests_total{method=\"other\",route=\"fallback\",status=\"404\"} 2",
+        "forge_http_request_duration_seconds_count{method=\"GET\",route=\"/books/:id\"} 2",
+        "forge_http_requests_in_flight 0",
+        "forge_http_max_inflight 512",
+        "bookshop_views_total{shelf=\"fiction\"} 2",
+        "bookshop_price_bucket{le=\"+Inf\"} 2",
+        "bookshop_price_sum 25",
+    ] {
+        assert!(
+            text.lines().any(|l| l == line),
+            "missing `{}` in:\n{}",
+            line,
+            text
+        );
+    }
+}
+
+#[test]
+fn websocket_callbacks_share_state_and_broadcast_to_rooms() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json {
+            return { ok: true }
+        }
+
+        let mut name = "anon"
+
+        fn joined(conn) {
+            name = conn.query.name
+            ws.join(conn.params.room)
+            ws.broadcast(conn.params.room, { joined: name }, { except: conn.id })
+            return { welcome: name, rooms: ws.rooms() }
+        }
+
+        fn left(conn) {
+            ws.broadcast(conn.params.room, { left: name })
+        }
+
+        @ws("/rooms/:room", on_open: "joined", on_close: "left")
+        fn chat(conn, msg) {
+            if typeof(msg) == "Array" {
+                return ws.binary(reverse(msg))
+            }
+            if msg.type == "say" {
+                ws.broadcast(conn.params.room, { user: name, text: msg.text })
+                return null
+            }
+            if msg.type == "bye" {
+                ws.close()
+                return null
+            }
+            return msg
+        }
+
+        @post("/announce/:room")
+        fn announce(room, body) -> Json {
+            return { delivered: ws.broadcast(room, body) }
+        }
+        "#,
+    );
+
+    let rt = tokio::runtime::Runtime::new().expect("test runtime");
+    rt.block_on(async {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::Message;
+
+        async fn next_json<S>(ws: &mut S) -> serde_json::Value
+        where
+            S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
+                + Unpin,
+        {
+            let message = tokio::time::timeout(Duration::from_secs(5), ws.next())
+                .await
+                .expect("frame in time")
+                .expect("stream open")
+                .expect("frame");
+            serde_json::from_str(message.to_text().expect("text frame")).expect("json frame")
+        }
+
+        let url = |name: &str| format!("ws://127.0.0.1:{}/rooms/lobby?name={}", port, name);
+        let (mut ada, _) = tokio_tungstenite::connect_async(url("ada"))
+            .await
+            .expect("connect ada");
+        assert_eq!(
+            next_json(&mut ada).await,
+            serde_json::json!({ "welcome": "ada", "rooms": ["lobby"] })
+        );
+
+        let (mut bob, _) = tokio_tungstenite::connect_async(url("bob"))
+            .await
+            .expect("connect bob");
+        assert_eq!(
+            next_json(&mut bob).await,
+            serde_json::json!({ "welcome": "bob", "rooms": ["lobby"] })
+        );
+        assert_eq!(
+            next_json(&mut ada).await,
+            serde_json::json!({ "joined": "bob" })
+        );
+
+        // Each connection keeps the name its own `on_open` stored.
+        ada.send(Message::Text(r#"{"type":"say","text":"hi"}"#.into()))
+            .await
+            .expect("send");
+        for ws in [&mut ada, &mut bob] {
+            assert_eq!(
+                next_json(ws).await,
+                serde_json::json!({ "user": "ada", "text": "hi" })
+            );
+        }
+
+        bob.send(Message::Text(r#"{"type":"ping","n":1}"#.into()))
+            .await
+            .expect("send");
+        assert_eq!(
+            next_json(&mut bob).await,
+            serde_json::json!({ "type": "ping", "n": 1 })
+        );
+
+        bob.send(Message::Binary(vec![1u8, 2, 3].into()))
+            .await
+            .expect("send");
+        let reply = tokio::time::timeout(Duration::from_secs(5), bob.next())
+            .await
+            .expect("frame in time")
+            .expect("stream open")
+            .expect("frame");
+        assert_eq!(reply, Message::Binary(vec![3u8, 2, 1].into()));
+
+        let delivered = reqwest::Client::new()
+            .post(format!("http://127.0.0.1:{}/announce/lobby", port))
+            .json(&serde_json::json!({ "notice": "maintenance" }))
+            .send()
+            .await
+            .expect("announce")
+            .json::<serde_json::Value>()
+            .await
+            .expect("json");
+        assert_eq!(delivered, serde_json::json!({ "delivered": 2 }));
+        for ws in [&mut ada, &mut bob] {
+            assert_eq!(
+                next_json(ws).await,
+                serde_json::json!({ "notice": "maintenance" })
+            );
+        }
+
+        bob.send(Message::Text(r#"{"type":"bye"}"#.into()))
+            .await
+            .expect("send");
+        let closing = tokio::time::timeout(Duration::from_secs(5), bob.next())
+            .await
+            .expect("close in time");
+        assert!(matches!(closing, Some(Ok(Message::Close(_))) | None));
+        drop(bob);
+
+        assert_eq!(
+            next_json(&mut ada).await,
+            serde_json::json!({ "left": "bob" })
+        );
+        let _ = ada.send(Message::Close(None)).await;
+    });
+}
+
+#[test]
+fn test_client_dispatches_in_memory() {
+    use axum::body::Body;
+    use axum::http;
+    use forge_lang::runtime::testing::{load, TestClient};
+
+    let (interpreter, plan) = load(
+        r#"
+        @server(port: 1, health: "/healthz")
+
+        struct NewBook {
+            title: String
+        }
+
+        @before
+        fn require_token(req) {
+            if req.headers["x-token"] != "secret" {
+                return response(401, { error: "unauthorized" })
+            }
+            return null
+        }
+
+        @post("/books")
+        fn create_book(body: NewBook) -> Json {
+            return response(201, { title: body.title })
+        }
+        "#,
+        None,
+    )
+    .expect("load");
+
+    let rt = tokio::runtime::Runtime::new().expect("test runtime");
+    rt.block_on(async {
+        let client = TestClient::new(interpreter, &plan).await.expect("client");
+        let post = |token: &str, body: &str| {
+            http::Request::post("/books")
+                .header("content-type", "application/json")
+                .header("x-token", token)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let json = |response: axum::response::Response| async move {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            serde_json::from_slice::<serde_json::Value>(&bytes).expect("json")
+        };
+
+        let denied = client.request(post("wrong", r#"{"title":"Dune"}"#)).await;
+        assert_eq!(denied.status(), http::StatusCode::UNAUTHORIZED);
+
+        let created = client.request(post("secret", r#"{"title":"Dune"}"#)).await;
+        assert_eq!(created.status(), http::StatusCode::CREATED);
+        assert_eq!(json(created).await, serde_json::json!({ "title": "Dune" }));
+
+        let invalid = client.request(post("secret", "{}")).await;
+        assert_eq!(invalid.status(), http::StatusCode::UNPROCESSABLE_ENTITY);
+
+        let health = client
+            .request(http::Request::get("/healthz").body(Body::empty()).unwrap())
+            .await;
+        assert_eq!(json(health).await, serde_json::json!({ "status": "ok" }));
+    });
+}
+
+#[test]
+fn compiled_bytecode_server_serves_decorated_routes() {
+    use forge_lang::vm::{compiler, serialize};
+
+    let port = pick_port();
+    let src = r#"
+        @server(port: __PORT__)
+
+        let prefix = "item"
+        fn label(id) { return "{prefix}-{id}" }
+
+        struct Item { name: String, qty: Int = 1 }
+
+        @get("/ping")
+        fn ping() -> Json { return { ok: true } }
+
+        @get("/items/:id")
+        fn show(id: Int) -> Json { return { id: id, label: label(id) } }
+
+        @post("/items")
+        fn create(body: Item) -> Json { return { name: body.name, qty: body.qty } }
+    "#
+    .replace("__PORT__", &port.to_string());
+
+    // Round-trip through the .fgc format, as `forge build` then
+    // `forge run app.fgc` would.
+    let tokens = Lexer::new(&src).tokenize().expect("lex");
+    let program = Parser::new(tokens).parse_program().expect("parse");
+    let chunk = compiler::compile(&program).expect("compile");
+    let bytes = serialize::serialize_chunk(&chunk).expect("serialize");
+    let chunk = serialize::deserialize_chunk(&bytes).expect("deserialize");
+
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().expect("build tokio runtime");
+        rt.block_on(async move {
+            let (interpreter, plan) =
+                forge_lang::runtime::host::load_compiled(&chunk).expect("load");
+            launch_until(interpreter, &plan, std::future::pending())
+                .await
+                .expect("server start");
+        });
+    });
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .expect("client");
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_until_ready(&client, &format!("{}/ping", base));
+
+    let shown: serde_json::Value = client
+        .get(format!("{}/items/7", base))
+        .send()
+        .expect("get")
+        .json()
+        .expect("json");
+    assert_eq!(shown, serde_json::json!({ "id": 7, "label": "item-7" }));
+
+    let invalid = client.get(format!("{}/items/x", base)).send().expect("get");
+    assert_eq!(invalid.status().as_u16(), 422);
+
+    let created: serde_json::Value = client
+        .post(format!("{}/items", base))
+        .json(&serde_json::json!({ "name": "bolt" }))
+        .send()
+        .expect("post")
+        .json()
+        .expect("json");
+    assert_eq!(created, serde_json::json!({ "name": "bolt", "qty": 1 }));
+}
+
+#[test]
+fn compiled_websocket_callbacks_act_on_their_own_connection() {
+    use forge_lang::vm::compiler;
+
+    let port = pick_port();
+    let src = r#"
+        @server(port: __PORT__)
+
+        @get("/ping")
+        fn ping() -> Json { return { ok: true } }
+
+        fn joined(conn) {
+            ws.join(conn.params.room)
+            return { rooms: ws.rooms() }
+        }
+
+        @ws("/rooms/:room", on_open: "joined")
+        fn chat(conn, msg) {
+            if msg.type == "leave" {
+                ws.leave(conn.params.room)
+                return { rooms: ws.rooms() }
+            }
+            ws.close()
+            return null
+        }
+    "#
+    .replace("__PORT__", &port.to_string());
+
+    let tokens = Lexer::new(&src).tokenize().expect("lex");
+    let program = Parser::new(tokens).parse_program().expect("parse");
+    let chunk = compiler::compile(&program).expect("compile");
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().expect("build tokio runtime");
+        rt.block_on(async move {
+            let (interpreter, plan) =
+                forge_lang::runtime::host::load_compiled(&chunk).expect("load");
+            launch_until(interpreter, &plan, std::future::pending())
+                .await
+                .expect("server start");
+        });
+    });
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .expect("client");
+    wait_until_ready(&client, &format!("http://127.0.0.1:{}/ping", port));
+
+    let rt = tokio::runtime::Runtime::new().expect("test runtime");
+    rt.block_on(async {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::Message;
+
+        let url = format!("ws://127.0.0.1:{}/rooms/lobby", port);
+        let (mut ws, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .expect("connect");
+        let frame = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("frame in time")
+            .expect("stream open")
+            .expect("frame");
+        assert_eq!(frame.to_text().unwrap(), r#"{"rooms":["lobby"]}"#);
+
+        ws.send(Message::Text(r#"{"type":"leave"}"#.into()))
+            .await
+            .expect("send");
+        let frame = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("frame in time")
+            .expect("stream open")
+            .expect("frame");
+        assert_eq!(frame.to_text().unwrap(), r#"{"rooms":[]}"#);
+
+        ws.send(Message::Text(r#"{"type":"bye"}"#.into()))
+            .await
+            .expect("send");
+        let closing = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("close in time");
+        assert!(matches!(closing, Some(Ok(Message::Close(_))) | None));
+    });
+}
+
+#[test]
+fn compresses_and_revalidates_json_responses() {
+    let port = spawn_test_server(
+        r#"
+        @server(port: __PORT__, compress: true)
+
+        @get("/ping")
+        fn ping() -> Json { return { ok: true } }
+
+        @get("/items")
+        fn items() -> Json {
+            return range(0, 200).map(fn(i) { return { id: i, name: "item" } })
+        }
+        "#,
+    );
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .expect("client");
+    let url = format!("http://127.0.0.1:{}/items", port);
+
+    let plain = client.get(&url).send().expect("get");
+    assert_eq!(plain.status().as_u16(), 200);
+    assert!(plain.headers().get("content-encoding").is_none());
+    let etag = plain.headers()["etag"].to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/\""), "{}", etag);
+    let plain_len = plain.bytes().expect("body").len();
+
+    let gzipped = client
+        .get(&url)
+        .header("accept-encoding", "gzip")
+        .send()
+        .expect("get");
+    assert_eq!(gzipped.headers()["content-encoding"], "gzip");
+    assert_eq!(gzipped.headers()["etag"].to_str().unwrap(), etag);
+    assert!(gzipped.bytes().expect("body").len() < plain_len / 4);
+
+    let cached = client
+        .get(&url)
+        .header("if-none-match", &etag)
+        .send()
+        .expect("get");
+    assert_eq!(cached.status().as_u16(), 304);
+    assert!(cached.bytes().expect("body").is_empty());
+}
+
+#[test]
+fn sessions_persist_across_requests_and_guard_forms() {
+    use axum::body::Body;
+    use axum::http;
+    use forge_lang::runtime::testing::TestClient;
+    use forge_lang::vm::compiler;
+
+    let src = r#"
+        @server(port: 0, session_secret: "test-secret-test-secret-test-secret")
+
+        @post("/login/:name")
+        fn login(name) {
+            session.user = name
+            return { ok: true }
+        }
+
+        @get("/me")
+        fn me() -> Json { return { user: session.get("user") } }
+
+        @get("/form")
+        fn form(csrf_token) -> Json { return { token: csrf_token } }
+
+        @csrf
+        @post("/form")
+        fn submit() -> Json { return { saved: session.user } }
+
+        @post("/logout")
+        fn logout() {
+            session = {}
+            return { ok: true }
+        }
+    "#;
+    let tokens = Lexer::new(src).tokenize().expect("lex");
+    let program = Parser::new(tokens).parse_program().expect("parse");
+    let chunk = compiler::compile(&program).expect("compile");
+
+    let rt = tokio::runtime::Runtime::new().expect("test runtime");
+    // The same program, interpreted and compiled.
+    let (interpreter, interpreted_plan) =
+        forge_lang::runtime::testing::load(src, None).expect("load");
+    for (program, plan) in [
+        (Served::from(interpreter), interpreted_plan),
+        forge_lang::runtime::host::load_compiled(&chunk).expect("load compiled"),
+    ] {
+        rt.block_on(async {
+            let client = TestClient::new(program, &plan).await.expect("client");
+            let send = |method: &str, path: &str, cookie: &str, token: Option<&str>| {
+                let mut request = http::Request::builder()
+                    .method(method)
+                    .uri(path)
+                    .header("cookie", cookie);
+                if let Some(token) = token {
+                    request = request.header("x-csrf-token", token);
+                }
+                request.body(Body::empty()).unwrap()
+            };
+            let cookie_of = |response: &axum::response::Response| {
+                let set = response.headers()["set-cookie"].to_str().unwrap();
+                set.split(';').next().unwrap().to_string()
+            };
+            let json = |response: axum::response::Response| async move {
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .expect("body");
+                serde_json::from_slice::<serde_json::Value>(&bytes).expect("json")
+            };
+
+            let anonymous = client.request(send("GET", "/me", "", None)).await;
+            assert!(anonymous.headers().get("set-cookie").is_none());
+            assert_eq!(json(anonymous).await, serde_json::json!({ "user": null }));
+
+            let login = client.request(send("POST", "/login/ada", "", None)).await;
+            let cookie = cookie_of(&login);
+            let me = client.request(send("GET", "/me", &cookie, None)).await;
+            assert_eq!(json(me).await, serde_json::json!({ "user": "ada" }));
+
+            let forged = cookie.replace("forge_session=", "forge_session=x");
+            let me = client.request(send("GET", "/me", &forged, None)).await;
+            assert_eq!(json(me).await, serde_json::json!({ "user": null }));
+
+            let form = client.request(send("GET", "/form", &cookie, None)).await;
+            let cookie = cookie_of(&form);
+            let token = json(form).await["token"].as_str().unwrap().to_string();
+
+            let denied = client.request(send("POST", "/form", &cookie, None)).await;
+            assert_eq!(denied.status(), http::StatusCode::FORBIDDEN);
+            let saved = client
+                .request(send("POST", "/form", &cookie, Some(&token)))
+                .await;
+            assert_eq!(json(saved).await, serde_json::json!({ "saved": "ada" }));
+
+            let logout = client.request(send("POST", "/logout", &cookie, None)).await;
+            assert!(logout.headers()["set-cookie"]
+                .to_str()
+                .unwrap()
+                .contains("Max-Age=0"));
+        });
+    }
+}
+
+#[test]
+fn compiled_sse_routes_stream_on_the_vm() {
+    use axum::body::Body;
+    use axum::http;
+    use forge_lang::runtime::testing::TestClient;
+    use forge_lang::vm::compiler;
+
+    let src = r#"
+        @server(port: 8080)
+
+        let mut greeting = "hi"
+
+        @on_start
+        fn warm() { greeting = "hello" }
+
+        @sse("/events")
+        fn events(n: Int = 2) {
+            emit greeting
+            yield { n: n }
+            emit { event: "tick", data: { n: n + 1 }, id: 7 }
+            return [3, 4]
+        }
+
+        @sse("/count")
+        fn count() { return (1..4).stream().map(fn(n) { return n * 10 }) }
+    "#;
+    let tokens = Lexer::new(src).tokenize().expect("lex");
+    let program = Parser::new(tokens).parse_program().expect("parse");
+    let chunk = compiler::compile(&program).expect("compile");
+    let (program, plan) = forge_lang::runtime::host::load_compiled(&chunk).expect("load");
+
+    let rt = tokio::runtime::Runtime::new().expect("test runtime");
+    rt.block_on(async {
+        let client = TestClient::new(program, &plan).await.expect("client");
+        let body = |path: &'static str| {
+            let client = &client;
+            async move {
+                let response = client
+                    .request(http::Request::get(path).body(Body::empty()).unwrap())
+                    .await;
+                assert_eq!(response.headers()["content-type"], "text/event-stream");
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .expect("body");
+                String::from_utf8(bytes.to_vec()).expect("utf8")
+            }
+        };
+        assert_eq!(
+            body("/events").await,
+            "data: hello\n\ndata: {\"n\":2}\n\ndata: {\"n\":3}\nevent: tick\nid: 7\n\ndata: 3\n\ndata: 4\n\n"
+        );
+        assert_eq!(
+            body("/count").await,
+            "data: 10\n\ndata: 20\n\ndata: 30\n\n"
+        );
+    });
+}
+
+#[test]
+fn servers_importing_a_helper_serve_the_same_compiled() {
+    use axum::body::Body;
+    use axum::http;
+    use forge_lang::runtime::testing::TestClient;
+    use forge_lang::vm::compiler;
+
+    // Imports resolve from the working directory, the crate root here.
+    let src = r#"
+        @server(port: 8080)
+        import "tests/parity/modules/import_helper.fg"
+
+        @get("/answer")
+        fn show_answer() -> Json { return { answer: helper() } }
+    "#;
+    let tokens = Lexer::new(src).tokenize().expect("lex");
+    let program = Parser::new(tokens).parse_program().expect("parse");
+    let chunk = compiler::compile(&program).expect("compile");
+    let (interpreter, interpreted_plan) =
+        forge_lang::runtime::testing::load(src, None).expect("load");
+
+    let rt = tokio::runtime::Runtime::new().expect("test runtime");
+    for (program, plan) in [
+        (Served::from(interpreter), interpreted_plan),
+        forge_lang::runtime::host::load_compiled(&chunk).expect("load compiled"),
+    ] {
+        rt.block_on(async {
+            let client = TestClient::new(program, &plan).await.expect("client");
+            let response = client
+                .request(http::Request::get("/answer").body(Body::empty()).unwrap())
+                .await;
+            assert_eq!(response.status(), http::StatusCode::OK);
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            let body: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+            assert_eq!(body, serde_json::json!({ "answer": 42 }));
+        });
+    }
+}